@@ -0,0 +1,60 @@
+#!/usr/bin/env python3
+"""
+Fabric speaker diarization sidecar.
+
+Runs pyannote's speaker-diarization pipeline on an audio file and prints the speaker turns
+as JSON: [{"start": 0.5, "end": 4.2, "speaker": "SPEAKER_00"}, ...]
+
+Requires `pip install pyannote.audio` and a Hugging Face token (HF_TOKEN) that has accepted
+the model's terms at https://huggingface.co/pyannote/speaker-diarization-3.1
+"""
+
+import argparse
+import json
+import os
+import sys
+
+MODEL = "pyannote/speaker-diarization-3.1"
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--audio", required=True)
+    parser.add_argument("--speakers", type=int, default=None, help="Exact number of speakers, if known")
+    args = parser.parse_args()
+
+    try:
+        from pyannote.audio import Pipeline
+    except ImportError:
+        print("Diarization requires pyannote.audio: pip install pyannote.audio", file=sys.stderr)
+        sys.exit(2)
+
+    token = os.environ.get("HF_TOKEN")
+    if not token:
+        print("Diarization needs a Hugging Face token; add one under the huggingface API key.", file=sys.stderr)
+        sys.exit(2)
+
+    pipeline = Pipeline.from_pretrained(MODEL, use_auth_token=token)
+    if pipeline is None:
+        print(f"Could not load {MODEL}; accept its terms on Hugging Face first.", file=sys.stderr)
+        sys.exit(2)
+
+    try:
+        import torch
+
+        if torch.cuda.is_available():
+            pipeline.to(torch.device("cuda"))
+    except Exception:
+        pass
+
+    options = {"num_speakers": args.speakers} if args.speakers else {}
+    diarization = pipeline(args.audio, **options)
+    turns = [
+        {"start": round(turn.start, 3), "end": round(turn.end, 3), "speaker": speaker}
+        for turn, _, speaker in diarization.itertracks(yield_label=True)
+    ]
+    print(json.dumps(turns))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,41 @@
+## Vendor calls and the error mapper
+
+unsupported-vendor = Nicht unterstützter Anbieter
+network-error = Netzwerkfehler: { $error }
+network-tls-error = TLS-Zertifikat konnte nicht überprüft werden: { $error }. Wenn Ihr Netzwerk HTTPS-Verkehr prüft, fügen Sie das CA-Zertifikat Ihrer Organisation in den Netzwerkeinstellungen hinzu.
+network-blocked = Von der Netzwerk-Firewall blockiert: { $host } ist kein erlaubter Host. Fügen Sie ihn in den Netzwerkeinstellungen zu den erlaubten Hosts hinzu.
+stream-error = Fehler im Datenstrom: { $error }
+api-error = API-Fehler ({ $status }): { $details }
+vendor-api-error = { $vendor }-API-Fehler ({ $status }): { $details }
+no-response = Keine Antwort von der KI erhalten. Bitte API-Schlüssel und Modellauswahl prüfen.
+no-response-vendor = Keine Antwort von { $vendor } erhalten. Bitte API-Schlüssel prüfen.
+error-invalid-google-key = Ungültiger Google-API-Schlüssel. Bitte in den Einstellungen (Strg+S) prüfen.
+error-model-not-found = Modell '{ $model }' nicht gefunden. Bitte ein anderes Modell wählen.
+error-rate-limited = API-Ratenlimit überschritten. Bitte kurz warten und erneut versuchen.
+error-google-quota = API-Kontingent aufgebraucht. Bitte die Google-Cloud-Abrechnung prüfen.
+error-vertex-auth = Vertex AI hat die Anmeldedaten abgelehnt. Prüfen Sie, ob das Dienstkonto bzw. die gcloud-Anmeldung die Rolle „Vertex AI User“ im konfigurierten Projekt hat.
+error-hf-model-loading = Das Modell { $model } wird auf Hugging Face noch geladen. Versuchen Sie es in einer Minute erneut.
+unknown-api-error = Unbekannter API-Fehler
+
+## Translation stage
+
+translation-language-missing = Bitte in den Einstellungen eine Übersetzungssprache festlegen, um die Ausgabe zu übersetzen.
+translation-failed = Übersetzung fehlgeschlagen: { $error }
+
+## Connection health checks
+
+health-connected = Verbunden
+health-missing-key = Kein API-Schlüssel für { $vendor } konfiguriert.
+health-invalid-key = Der API-Schlüssel wurde abgelehnt. Bitte in den Einstellungen prüfen.
+health-permission-denied = Der Schlüssel ist gültig, hat aber keine Berechtigung für diese API.
+health-rate-limited = Vom Anbieter gedrosselt. Bitte gleich erneut versuchen.
+health-quota-exceeded = Kontingent aufgebraucht. Bitte die Abrechnung beim Anbieter prüfen.
+health-model-not-found = Das angeforderte Modell ist für diesen Schlüssel nicht verfügbar.
+health-provider-outage = { $vendor } hat Serverprobleme. Das liegt nicht an Ihrer Konfiguration.
+health-timeout = Der Anbieter hat nicht rechtzeitig geantwortet.
+health-network-unreachable = Anbieter nicht erreichbar. Bitte Internetverbindung oder Proxy prüfen.
+health-unknown = Unerwartete Antwort des Anbieters.
+
+## Setup wizard
+
+setup-no-fabric-config = Keine fabric-CLI-Konfiguration in ~/.config/fabric gefunden.
@@ -0,0 +1,41 @@
+## Vendor calls and the error mapper
+
+unsupported-vendor = Unsupported vendor
+network-error = Network error: { $error }
+network-tls-error = TLS certificate could not be verified: { $error }. If your network inspects HTTPS traffic, add your organization's CA certificate in the network settings.
+network-blocked = Blocked by the network firewall: { $host } is not an allowed host. Add it to the allowed hosts in the network settings to let the app contact it.
+stream-error = Stream error: { $error }
+api-error = API Error ({ $status }): { $details }
+vendor-api-error = { $vendor } API Error ({ $status }): { $details }
+no-response = No response received from AI. Please check your API key and model selection.
+no-response-vendor = No response received from { $vendor }. Please check your API key.
+error-invalid-google-key = Invalid Google API Key. Please check your API key in Settings (Ctrl+S).
+error-model-not-found = Model '{ $model }' not found. Please select a different model.
+error-rate-limited = API rate limit exceeded. Please wait a moment and try again.
+error-google-quota = API quota exceeded. Please check your Google Cloud billing.
+error-vertex-auth = Vertex AI rejected the credentials. Check that the service account or gcloud login has the "Vertex AI User" role on the configured project.
+error-hf-model-loading = The model { $model } is still loading on Hugging Face. Try again in a minute.
+unknown-api-error = Unknown API error
+
+## Translation stage
+
+translation-language-missing = Set a translation language in Settings to translate output.
+translation-failed = Translation failed: { $error }
+
+## Connection health checks
+
+health-connected = Connected
+health-missing-key = No API key configured for { $vendor }.
+health-invalid-key = The API key was rejected. Check it in Settings.
+health-permission-denied = The key is valid but lacks permission for this API.
+health-rate-limited = Rate limited by the provider. Try again shortly.
+health-quota-exceeded = Quota exhausted. Check billing with the provider.
+health-model-not-found = The requested model is not available for this key.
+health-provider-outage = { $vendor } is having server problems. This is not your configuration.
+health-timeout = The provider did not respond in time.
+health-network-unreachable = Could not reach the provider. Check your internet connection or proxy.
+health-unknown = Unexpected response from the provider.
+
+## Setup wizard
+
+setup-no-fabric-config = No fabric CLI configuration found in ~/.config/fabric.
@@ -0,0 +1,41 @@
+## Vendor calls and the error mapper
+
+unsupported-vendor = Proveedor no compatible
+network-error = Error de red: { $error }
+network-tls-error = No se pudo verificar el certificado TLS: { $error }. Si tu red inspecciona el tráfico HTTPS, añade el certificado de la CA de tu organización en la configuración de red.
+network-blocked = Bloqueado por el cortafuegos de red: { $host } no es un host permitido. Añádalo a los hosts permitidos en la configuración de red.
+stream-error = Error en la transmisión: { $error }
+api-error = Error de la API ({ $status }): { $details }
+vendor-api-error = Error de la API de { $vendor } ({ $status }): { $details }
+no-response = No se recibió respuesta de la IA. Comprueba tu clave de API y el modelo seleccionado.
+no-response-vendor = No se recibió respuesta de { $vendor }. Comprueba tu clave de API.
+error-invalid-google-key = Clave de API de Google no válida. Revísala en Ajustes (Ctrl+S).
+error-model-not-found = No se encontró el modelo '{ $model }'. Selecciona otro modelo.
+error-rate-limited = Se superó el límite de solicitudes de la API. Espera un momento e inténtalo de nuevo.
+error-google-quota = Se agotó la cuota de la API. Revisa la facturación de Google Cloud.
+error-vertex-auth = Vertex AI rechazó las credenciales. Comprueba que la cuenta de servicio o el inicio de sesión de gcloud tenga el rol "Vertex AI User" en el proyecto configurado.
+error-hf-model-loading = El modelo { $model } todavía se está cargando en Hugging Face. Inténtalo de nuevo en un minuto.
+unknown-api-error = Error desconocido de la API
+
+## Translation stage
+
+translation-language-missing = Define un idioma de traducción en Ajustes para traducir la salida.
+translation-failed = La traducción falló: { $error }
+
+## Connection health checks
+
+health-connected = Conectado
+health-missing-key = No hay ninguna clave de API configurada para { $vendor }.
+health-invalid-key = La clave de API fue rechazada. Revísala en Ajustes.
+health-permission-denied = La clave es válida pero no tiene permiso para esta API.
+health-rate-limited = El proveedor limitó las solicitudes. Inténtalo de nuevo en breve.
+health-quota-exceeded = Cuota agotada. Revisa la facturación con el proveedor.
+health-model-not-found = El modelo solicitado no está disponible para esta clave.
+health-provider-outage = { $vendor } tiene problemas en sus servidores. No es un problema de tu configuración.
+health-timeout = El proveedor no respondió a tiempo.
+health-network-unreachable = No se pudo contactar con el proveedor. Revisa tu conexión a internet o el proxy.
+health-unknown = Respuesta inesperada del proveedor.
+
+## Setup wizard
+
+setup-no-fabric-config = No se encontró configuración de fabric CLI en ~/.config/fabric.
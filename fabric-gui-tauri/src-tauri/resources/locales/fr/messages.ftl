@@ -0,0 +1,41 @@
+## Vendor calls and the error mapper
+
+unsupported-vendor = Fournisseur non pris en charge
+network-error = Erreur réseau : { $error }
+network-tls-error = Le certificat TLS n'a pas pu être vérifié : { $error }. Si votre réseau inspecte le trafic HTTPS, ajoutez le certificat de l'autorité de votre organisation dans les paramètres réseau.
+network-blocked = Bloqué par le pare-feu réseau : { $host } n'est pas un hôte autorisé. Ajoutez-le aux hôtes autorisés dans les paramètres réseau.
+stream-error = Erreur de flux : { $error }
+api-error = Erreur de l'API ({ $status }) : { $details }
+vendor-api-error = Erreur de l'API { $vendor } ({ $status }) : { $details }
+no-response = Aucune réponse reçue de l'IA. Vérifiez votre clé API et le modèle sélectionné.
+no-response-vendor = Aucune réponse reçue de { $vendor }. Vérifiez votre clé API.
+error-invalid-google-key = Clé API Google invalide. Vérifiez-la dans les Paramètres (Ctrl+S).
+error-model-not-found = Modèle '{ $model }' introuvable. Veuillez choisir un autre modèle.
+error-rate-limited = Limite de requêtes de l'API dépassée. Patientez un instant puis réessayez.
+error-google-quota = Quota de l'API épuisé. Vérifiez la facturation Google Cloud.
+error-vertex-auth = Vertex AI a refusé les identifiants. Vérifiez que le compte de service ou la connexion gcloud dispose du rôle « Vertex AI User » sur le projet configuré.
+error-hf-model-loading = Le modèle { $model } est encore en cours de chargement sur Hugging Face. Réessayez dans une minute.
+unknown-api-error = Erreur d'API inconnue
+
+## Translation stage
+
+translation-language-missing = Définissez une langue de traduction dans les Paramètres pour traduire la sortie.
+translation-failed = Échec de la traduction : { $error }
+
+## Connection health checks
+
+health-connected = Connecté
+health-missing-key = Aucune clé API configurée pour { $vendor }.
+health-invalid-key = La clé API a été refusée. Vérifiez-la dans les Paramètres.
+health-permission-denied = La clé est valide mais n'a pas accès à cette API.
+health-rate-limited = Requêtes limitées par le fournisseur. Réessayez dans un instant.
+health-quota-exceeded = Quota épuisé. Vérifiez la facturation auprès du fournisseur.
+health-model-not-found = Le modèle demandé n'est pas disponible pour cette clé.
+health-provider-outage = { $vendor } rencontre des problèmes de serveur. Votre configuration n'est pas en cause.
+health-timeout = Le fournisseur n'a pas répondu à temps.
+health-network-unreachable = Impossible de joindre le fournisseur. Vérifiez votre connexion internet ou votre proxy.
+health-unknown = Réponse inattendue du fournisseur.
+
+## Setup wizard
+
+setup-no-fabric-config = Aucune configuration de fabric CLI trouvée dans ~/.config/fabric.
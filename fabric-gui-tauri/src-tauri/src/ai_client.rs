@@ -1,10 +1,29 @@
 use serde::{Deserialize, Serialize};
-use tauri::{Window, Emitter};
-use reqwest::Client;
+use tauri::{AppHandle, Window, Manager};
 use futures::StreamExt;
+use std::time::Duration;
 use serde_json::json;
+use uuid::Uuid;
+use crate::compose::{self, ComposeSpec};
+use crate::dry_run::{self, DryRunReport};
+use crate::emitter::{self, RunEmitter};
+use crate::history::HistoryState;
+use crate::http::{self, AuditedSend};
+use crate::huggingface;
+use crate::lmstudio;
+use crate::rag;
+use crate::residency;
+use crate::scripting::Scripts;
+use crate::settings::{Settings, SettingsState};
+use crate::snippets::SnippetStore;
+use crate::stream_ack::StreamAcks;
+use crate::translate;
+use crate::validate;
+use crate::variables::VariableStore;
+use crate::vertex;
+use crate::i18n::{tr, tr_args};
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct AIRequest {
     pub vendor: String,
     pub model: String,
@@ -14,49 +33,460 @@ pub struct AIRequest {
     pub temperature: f32,
     pub top_p: f32,
     pub thinking_level: Option<i32>, // Added for Gemini 3
+    #[serde(default)]
+    pub translate_input: bool,
+    #[serde(default)]
+    pub translate_output: bool,
+    pub pattern: Option<String>,
+    // Lets the UI know the ID up front so it can filter events before the command returns
+    pub run_id: Option<String>,
+    // Gemini cachedContents name from create_context_cache; the cached text precedes user_input
+    #[serde(default)]
+    pub cached_content: Option<String>,
+    // Text the assistant turn must start with, e.g. "{" for strict JSON patterns
+    #[serde(default)]
+    pub assistant_prefill: Option<String>,
+    // When set, the system prompt is built in Rust from these layers instead of taken as-is
+    #[serde(default)]
+    pub compose: Option<ComposeSpec>,
+    // Return the exact outgoing payload instead of calling the vendor
+    #[serde(default)]
+    pub dry_run: bool,
+    // History lineage: the run this one derives from, and how ("replay", "map", "reduce",
+    // "step", "classify", "regenerate", "continuation", "judge")
+    #[serde(default)]
+    pub parent_run_id: Option<String>,
+    #[serde(default)]
+    pub relation: Option<String>,
+    // Output length cap; vendors use their own default when unset
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    // The frontend acknowledges stream events with ack_stream and reading pauses while it lags
+    #[serde(default)]
+    pub acknowledged_stream: bool,
+    // Give the run the closest chunks from a RAG collection as numbered sources to cite
+    #[serde(default)]
+    pub rag: bool,
+    // The collection to retrieve from; the default one when unset
+    #[serde(default)]
+    pub rag_collection: Option<String>,
 }
 
-#[derive(Serialize, Clone)]
-pub struct AIChunk {
-    pub chunk: String,
+// Resolves to a report only for dry runs; real runs deliver output through events
+#[tauri::command]
+pub async fn run_pattern(window: Window, request: AIRequest) -> Result<Option<DryRunReport>, String> {
+    if request.dry_run {
+        return dry_run::build(window.app_handle(), request).map(Some);
+    }
+    run_recorded(window.app_handle(), Some(window.label().to_string()), request)
+        .await
+        .map(|_| None)
 }
 
-#[tauri::command]
-pub async fn run_pattern(
-    window: Window,
-    request: AIRequest,
-) -> Result<(), String> {
-    let result = match request.vendor.as_str() {
-        "google" => call_gemini(window.clone(), request).await,
-        "openai" => call_openai(window.clone(), request).await,
-        "anthropic" => call_anthropic(window.clone(), request).await,
-        _ => Err("Unsupported vendor".to_string()),
+// Full run lifecycle shared by interactive runs and queued jobs: resolves the key, streams,
+// records history and signals completion to the target window (or everyone when `None`)
+pub async fn run_recorded(
+    app_handle: &AppHandle,
+    target: Option<String>,
+    mut request: AIRequest,
+) -> Result<String, String> {
+    let settings = app_handle.state::<SettingsState>().get();
+    let history = app_handle.state::<HistoryState>();
+
+    // Fall back to keys imported during setup when the frontend has none stored
+    if request.api_key.trim().is_empty() {
+        if let Some(key) = settings.api_key(&request.vendor) {
+            request.api_key = key;
+        }
+    }
+
+    let run_id = request.run_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+    let mut emitter = RunEmitter::new(app_handle.clone(), target, run_id.clone());
+    if settings.sanitize_output {
+        emitter = emitter.with_sanitizer();
+    }
+    if let Some(coalescing) = settings.stream_coalescing() {
+        emitter = emitter.with_coalescing(coalescing);
+    }
+    let ack_registration = request
+        .acknowledged_stream
+        .then(|| app_handle.state::<StreamAcks>().register(&run_id));
+    if let Some(registration) = &ack_registration {
+        emitter = emitter.with_ack_window(registration.window());
+    }
+    let scripts = Scripts::load(app_handle, &settings, &request, &run_id);
+    if let Ok(Some(scripts)) = &scripts {
+        if scripts.handles("on_chunk") {
+            emitter = emitter.with_scripts(scripts.clone());
+        }
+    }
+    let heartbeat = emitter.start_heartbeat();
+    let prepared = scripts
+        .clone()
+        .and_then(|scripts| prepare(app_handle, &settings, &mut request, scripts.as_deref()));
+    // Sources go only into the request that is sent; history keeps the one without them, so a
+    // replay retrieves again instead of stacking a second set of sources
+    let mut sent = request.clone();
+    let citations = match prepared {
+        Ok(()) if request.rag => rag::augment(app_handle, &settings, &mut sent).await,
+        Ok(()) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    };
+    if let Some(sources) = citations.as_ref().ok().filter(|c| !c.is_empty()) {
+        emitter = emitter.with_citations(sources.clone());
+    }
+    let result = match citations.as_ref() {
+        Ok(_) => {
+            execute(
+                &emitter,
+                sent,
+                settings.translation_language,
+                settings.resume_interrupted_streams,
+            )
+            .await
+        }
+        Err(e) => Err(e.clone()),
+    };
+    // on_complete may rewrite the streamed output; the UI swaps in the new text
+    let result = match (result, scripts) {
+        (Ok(output), Ok(Some(scripts))) if scripts.handles("on_complete") => {
+            scripts.run("on_complete", output.clone()).inspect(|text| {
+                if *text != output {
+                    let _ = emitter.emit("ai-output-replaced", json!({"run_id": run_id, "text": emitter.sanitize_text(text.clone())}));
+                }
+            })
+        }
+        (result, _) => result,
+    };
+    drop(heartbeat);
+    let metrics = emitter.metrics();
+    let _ = emitter.progress();
+
+    let reasoning = Some(emitter.reasoning()).filter(|r| settings.save_reasoning && !r.is_empty());
+
+    // History is best-effort; a locked or full DB must not turn a good run into an error
+    let _ = match &result {
+        Ok(output) => history.record(&run_id, &request, output, None, Some(&metrics), reasoning.as_deref()),
+        Err(e) => history.record(&run_id, &request, "", Some(e), Some(&metrics), reasoning.as_deref()),
     };
+    if let (Ok(output), Ok(citations)) = (&result, &citations) {
+        let _ = rag::record_citations(&history, &run_id, citations, output);
+    }
     
+    if let Err(e) = &result {
+        let _ = emitter.chunk(&format!("\n\n❌ **Error:** {}\n", e));
+    }
+    let _ = emitter.flush();
+    drop(ack_registration);
+
     // Emit completion signal
     match &result {
         Ok(_) => {
-            let _ = window.emit("ai-complete", json!({"success": true}));
+            let _ = emitter.emit("ai-complete", json!({"success": true, "run_id": run_id, "metrics": metrics}));
         }
         Err(e) => {
-            let _ = window.emit("ai-chunk", AIChunk { chunk: format!("\n\n❌ **Error:** {}\n", e) });
-            let _ = window.emit("ai-complete", json!({"success": false, "error": e}));
+            let _ = emitter.emit("ai-complete", json!({"success": false, "error": e, "run_id": run_id, "metrics": metrics}));
         }
     }
     
     result
 }
 
-async fn call_gemini(window: Window, req: AIRequest) -> Result<(), String> {
-    let client = Client::new();
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?key={}&alt=sse",
-        req.model, req.api_key
-    );
+// History then records the prompt that was actually sent
+pub fn apply_composition(app_handle: &AppHandle, settings: &Settings, request: &mut AIRequest) -> Result<(), String> {
+    if let Some(spec) = request.compose.take() {
+        request.system_prompt = compose::compose(app_handle, settings, &spec)?.system_prompt;
+        if request.pattern.is_none() {
+            request.pattern = spec.pattern;
+        }
+    }
+    // Snippets go in first so they may themselves use variables; {{var:...}} references are
+    // filled in last so they also work inside composed layers
+    request.user_input = app_handle.state::<SnippetStore>().expand(&request.user_input);
+    let variables = app_handle.state::<VariableStore>();
+    request.system_prompt = variables.expand(&request.system_prompt);
+    request.user_input = variables.expand(&request.user_input);
+    Ok(())
+}
+
+// What a request goes through before it's sent, in runs and batches alike: composition,
+// snippets and variables, the on_input script, then validation
+pub fn prepare(
+    app_handle: &AppHandle,
+    settings: &Settings,
+    request: &mut AIRequest,
+    scripts: Option<&Scripts>,
+) -> Result<(), String> {
+    apply_composition(app_handle, settings, request)?;
+    if let Some(scripts) = scripts.filter(|s| s.handles("on_input")) {
+        request.user_input = scripts.run("on_input", std::mem::take(&mut request.user_input))?;
+    }
+    validate::check(app_handle, request)
+}
+
+// Other vendors have no assistant prefill, so the model is told to start with it instead
+pub fn emulate_prefill(request: &mut AIRequest) -> Option<String> {
+    let prefill = request.assistant_prefill.clone().filter(|p| !p.is_empty())?;
+    if request.vendor != "anthropic" {
+        request.system_prompt.push_str(&format!(
+            "\n\nBegin your response with exactly the following text, then continue:\n{}",
+            prefill
+        ));
+    }
+    Some(prefill)
+}
+
+// LM Studio needs no key or model: the running server is found and its first model used
+async fn resolve_local(request: &mut AIRequest) -> Result<(), String> {
+    if request.vendor != "lmstudio" {
+        return Ok(());
+    }
+    let models = lmstudio::ensure_discovered().await?;
+    if request.model.trim().is_empty() {
+        request.model = models
+            .into_iter()
+            .next()
+            .ok_or_else(|| "LM Studio has no model available. Load one in LM Studio first.".to_string())?;
+    }
+    Ok(())
+}
+
+async fn call_vendor(emitter: &RunEmitter, request: AIRequest) -> Result<String, String> {
+    residency::check(&request.vendor, request.pattern.as_deref())?;
+    match request.vendor.as_str() {
+        "google" | "vertex" => call_gemini(emitter, request).await,
+        "openai" | "xai" | "deepseek" | "lmstudio" => call_openai(emitter, request).await,
+        "anthropic" => call_anthropic(emitter, request).await,
+        "huggingface" => call_huggingface(emitter, request).await,
+        _ => Err(tr("unsupported-vendor")),
+    }
+}
+
+async fn stream_vendor(emitter: &RunEmitter, mut request: AIRequest, resume: bool) -> Result<String, String> {
+    resolve_local(&mut request).await?;
+    let original = request.clone();
+    let prefill = emulate_prefill(&mut request);
+    let native_prefill = request.vendor == "anthropic";
+
+    if let Some(prefill) = &prefill {
+        emitter.prefill(prefill)?;
+        if !native_prefill {
+            emitter.expect_echo(prefill);
+        }
+    }
+
+    let output = match call_vendor(emitter, request).await {
+        Ok(output) => output,
+        Err(e) if resume && emitter.take_interrupted() => return resume_stream(emitter, original, e).await,
+        Err(e) => return Err(e),
+    };
+
+    Ok(match prefill {
+        Some(prefill) if native_prefill => format!("{}{}", prefill, output),
+        Some(prefill) => format!("{}{}", prefill, emitter::strip_echo(&output, &prefill)),
+        None => output,
+    })
+}
+
+// Laptop sleep or a network switch drops the connection mid-stream. The request is sent again
+// with the partial answer as the assistant prefill, so the model continues where it stopped
+// and the UI keeps the text it already has.
+async fn resume_stream(emitter: &RunEmitter, original: AIRequest, mut error: String) -> Result<String, String> {
+    for attempt in 1..=MAX_STREAM_RESUMES {
+        // Give the network a moment to come back after wake-up
+        tokio::time::sleep(Duration::from_secs(2 * u64::from(attempt))).await;
+        emitter.emit(
+            "ai-stream-resumed",
+            json!({"run_id": emitter.run_id(), "attempt": attempt, "error": error}),
+        )?;
+
+        let partial = emitter.output();
+        let mut request = original.clone();
+        if !partial.is_empty() {
+            request.assistant_prefill = Some(partial.clone());
+            if request.vendor == "anthropic" {
+                // Claude rejects a prefill combined with extended thinking
+                request.thinking_level = None;
+            } else {
+                emitter.expect_echo(&partial);
+            }
+        }
+        emulate_prefill(&mut request);
+
+        match call_vendor(emitter, request).await {
+            Ok(_) => return Ok(emitter.output()),
+            Err(e) if emitter.take_interrupted() => error = e,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(error)
+}
+
+// Patterns are authored in English, so input is translated to English before the run
+// and the output is translated to the user's language afterwards
+pub async fn execute(
+    emitter: &RunEmitter,
+    mut request: AIRequest,
+    translation_language: Option<String>,
+    resume_streams: bool,
+) -> Result<String, String> {
+    let output_language = match (request.translate_output, translation_language) {
+        (false, _) => None,
+        (true, Some(language)) => Some(language),
+        (true, None) => return Err(tr("translation-language-missing")),
+    };
+
+    if request.translate_input {
+        request.user_input = translate::translate_text(&request, &request.user_input, "English").await?;
+    }
+
+    let output = stream_vendor(emitter, request.clone(), resume_streams).await?;
+
+    if let Some(language) = output_language {
+        let translated = emitter.sanitize_text(translate::translate_text(&request, &output, &language).await?);
+        emitter.emit("ai-translation", json!({"run_id": emitter.run_id(), "language": language, "text": translated}))?;
+    }
+
+    Ok(output)
+}
+
+// Non-streaming call for secondary passes (translation etc.) that the user doesn't watch live
+pub async fn complete(req: &AIRequest) -> Result<String, String> {
+    let mut req = req.clone();
+    resolve_local(&mut req).await?;
+    residency::check(&req.vendor, req.pattern.as_deref())?;
+    let req = &req;
+    let client = http::vendor_client(&req.vendor)?;
+    let request = match req.vendor.as_str() {
+        "google" => client
+            .post(format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+                req.model, req.api_key
+            ))
+            .json(&gemini_generate_body(req)),
+        "vertex" => client
+            .post(vertex::model_url(&req.model, "generateContent")?)
+            .bearer_auth(vertex::bearer_token(&req.api_key).await?)
+            .json(&gemini_generate_body(req)),
+        "openai" | "xai" | "deepseek" | "lmstudio" => {
+            let mut body = openai_payload(req);
+            body["stream"] = json!(false);
+            client
+                .post(openai_compatible_url(&req.vendor))
+                .header("Authorization", format!("Bearer {}", req.api_key))
+                .json(&body)
+        }
+        "anthropic" => {
+            let body = json!({
+                "model": req.model,
+                "system": req.system_prompt,
+                "messages": [{"role": "user", "content": req.user_input}],
+                "max_tokens": req.max_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS)
+            });
+            match residency::bedrock_invoke_url(&req.model)? {
+                Some(url) => client
+                    .post(url)
+                    .bearer_auth(residency::bedrock_api_key()?)
+                    .json(&residency::bedrock_payload(body)),
+                None => client
+                    .post(ANTHROPIC_MESSAGES_URL)
+                    .header("x-api-key", &req.api_key)
+                    .header("anthropic-version", "2023-06-01")
+                    .json(&body),
+            }
+        }
+        "huggingface" => with_bearer(client.post(huggingface::generate_url(&req.model, false)), &req.api_key)
+            .json(&huggingface::payload(req, false)),
+        _ => return Err(tr("unsupported-vendor")),
+    };
+
+    let res = request.send_audited("run", req.run_id.as_deref()).await.map_err(http::network_error)?;
+    let status = res.status();
+    if !status.is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(tr_args("api-error", &[("status", &status.to_string()), ("details", &error_text.chars().take(300).collect::<String>())]));
+    }
+
+    let json: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+    let text = match req.vendor.as_str() {
+        "google" | "vertex" => json["candidates"][0]["content"]["parts"]
+            .as_array()
+            .map(|parts| parts.iter().filter_map(|p| p["text"].as_str()).collect::<String>()),
+        "openai" | "xai" | "deepseek" | "lmstudio" => json["choices"][0]["message"]["content"].as_str().map(|s| s.to_string()),
+        "huggingface" => huggingface::generated_text(&json),
+        _ => json["content"]
+            .as_array()
+            .map(|blocks| blocks.iter().filter_map(|b| b["text"].as_str()).collect::<String>()),
+    };
+
+    text.filter(|t| !t.is_empty())
+        .ok_or_else(|| tr("no-response"))
+}
+
+fn gemini_generate_body(req: &AIRequest) -> serde_json::Value {
+    let mut body = json!({
+        "systemInstruction": {"parts": [{"text": req.system_prompt}]},
+        "contents": [{"role": "user", "parts": [{"text": req.user_input}]}],
+        "generationConfig": {"temperature": req.temperature, "topP": req.top_p}
+    });
+    if let Some(max_tokens) = req.max_tokens {
+        body["generationConfig"]["maxOutputTokens"] = json!(max_tokens);
+    }
+    body
+}
+
+const GEMINI_STREAM_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models/{model}:streamGenerateContent?alt=sse";
+const ANTHROPIC_MESSAGES_URL: &str = "https://api.anthropic.com/v1/messages";
+const MAX_STREAM_RESUMES: u32 = 2;
+
+// The Messages API requires a cap; used when the request doesn't set one
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u32 = 4096;
+const XAI_CHAT_URL: &str = "https://api.x.ai/v1/chat/completions";
+const DEEPSEEK_CHAT_URL: &str = "https://api.deepseek.com/chat/completions";
+
+// xAI, DeepSeek and LM Studio speak the OpenAI chat completions protocol
+fn openai_compatible_url(vendor: &str) -> String {
+    match vendor {
+        "xai" => XAI_CHAT_URL.to_string(),
+        "deepseek" => DEEPSEEK_CHAT_URL.to_string(),
+        "lmstudio" => lmstudio::chat_url(),
+        _ => format!("{}/v1/chat/completions", residency::openai_base_url()),
+    }
+}
+
+fn vendor_name(vendor: &str) -> &'static str {
+    match vendor {
+        "xai" => "xAI",
+        "deepseek" => "DeepSeek",
+        "lmstudio" => "LM Studio",
+        _ => "OpenAI",
+    }
+}
+
+// The streaming URL and body for a request, without credentials; shared by real runs and dry runs
+pub fn vendor_request(req: &AIRequest) -> Result<(String, serde_json::Value), String> {
+    match req.vendor.as_str() {
+        "google" => Ok((GEMINI_STREAM_URL.replace("{model}", &req.model), gemini_payload(req))),
+        // Same request format as the Gemini API, served from the project's Vertex AI endpoint
+        "vertex" => Ok((
+            format!("{}?alt=sse", vertex::model_url(&req.model, "streamGenerateContent")?),
+            gemini_payload(req),
+        )),
+        "openai" | "xai" | "deepseek" | "lmstudio" => Ok((openai_compatible_url(&req.vendor), openai_payload(req))),
+        "anthropic" => Ok(match residency::bedrock_invoke_url(&req.model)? {
+            Some(url) => (url, residency::bedrock_payload(anthropic_payload(req))),
+            None => (ANTHROPIC_MESSAGES_URL.to_string(), anthropic_payload(req)),
+        }),
+        "huggingface" => Ok((huggingface::generate_url(&req.model, true), huggingface::payload(req, true))),
+        _ => Err(tr("unsupported-vendor")),
+    }
+}
 
+fn gemini_payload(req: &AIRequest) -> serde_json::Value {
     let mut payload = json!({
         "contents": [
             {
+                "role": "user",
                 "parts": [
                     {"text": req.system_prompt},
                     {"text": req.user_input}
@@ -69,6 +499,13 @@ async fn call_gemini(window: Window, req: AIRequest) -> Result<(), String> {
         }
     });
 
+    if let Some(cache) = &req.cached_content {
+        payload["cachedContent"] = json!(cache);
+    }
+    if let Some(max_tokens) = req.max_tokens {
+        payload["generationConfig"]["maxOutputTokens"] = json!(max_tokens);
+    }
+
     // Add thinkingConfig if reasoning is enabled (Gemini 3)
     // Values: HIGH (deep), MEDIUM, LOW, MINIMAL (Flash only)
     if let Some(level) = req.thinking_level {
@@ -81,18 +518,32 @@ async fn call_gemini(window: Window, req: AIRequest) -> Result<(), String> {
                         _ => "LOW",       // Minimal reasoning
                     };
                     config_obj.insert("thinkingConfig".to_string(), json!({
-                        "thinkingLevel": thinking_level
+                        "thinkingLevel": thinking_level,
+                        "includeThoughts": true
                     }));
                 }
             }
         }
     }
 
-    let res = client.post(&url)
+    payload
+}
+
+// Also serves Vertex AI, which differs only in URL and OAuth authentication
+async fn call_gemini(emitter: &RunEmitter, req: AIRequest) -> Result<String, String> {
+    let (url, payload) = vendor_request(&req)?;
+    let client = http::client_for(&url)?;
+    let request = if req.vendor == "vertex" {
+        client.post(&url).bearer_auth(vertex::bearer_token(&req.api_key).await?)
+    } else {
+        client.post(format!("{}&key={}", url, req.api_key))
+    };
+
+    let res = request
         .json(&payload)
-        .send()
+        .send_audited("run", Some(emitter.run_id()))
         .await
-        .map_err(|e| format!("Network error: {}", e))?;
+        .map_err(http::network_error)?;
 
     // Check HTTP status
     let status = res.status();
@@ -100,26 +551,29 @@ async fn call_gemini(window: Window, req: AIRequest) -> Result<(), String> {
         let error_text = res.text().await.unwrap_or_default();
         
         // Parse error for user-friendly message
-        let friendly_error = if error_text.contains("API_KEY") || error_text.contains("api_key") {
-            format!("Invalid Google API Key. Please check your API key in Settings (Ctrl+S).")
+        let friendly_error = if req.vendor == "vertex" && (status.as_u16() == 401 || status.as_u16() == 403) {
+            tr("error-vertex-auth")
+        } else if error_text.contains("API_KEY") || error_text.contains("api_key") {
+            tr("error-invalid-google-key")
         } else if error_text.contains("404") || error_text.contains("not found") || error_text.contains("NOT_FOUND") {
-            format!("Model '{}' not found. Please select a different model.", req.model)
+            tr_args("error-model-not-found", &[("model", &req.model)])
         } else if error_text.contains("RATE_LIMIT") || error_text.contains("429") {
-            "API rate limit exceeded. Please wait a moment and try again.".to_string()
+            tr("error-rate-limited")
         } else if error_text.contains("quota") || error_text.contains("QUOTA") {
-            "API quota exceeded. Please check your Google Cloud billing.".to_string()
+            tr("error-google-quota")
         } else {
-            format!("API Error ({}): {}", status, &error_text[..error_text.len().min(300)])
+            tr_args("api-error", &[("status", &status.to_string()), ("details", &error_text.chars().take(300).collect::<String>())])
         };
         
         return Err(friendly_error);
     }
 
     let mut stream = res.bytes_stream();
-    let mut has_content = false;
+    let mut output = String::new();
 
     while let Some(item) = stream.next().await {
-        let chunk = item.map_err(|e| format!("Stream error: {}", e))?;
+        emitter.wait_for_capacity().await;
+        let chunk = item.map_err(|e| emitter.stream_interrupted(e))?;
         let text = String::from_utf8_lossy(&chunk);
         
         for line in text.lines() {
@@ -130,20 +584,19 @@ async fn call_gemini(window: Window, req: AIRequest) -> Result<(), String> {
                     if let Some(error) = json.get("error") {
                         let msg = error.get("message")
                             .and_then(|m| m.as_str())
-                            .unwrap_or("Unknown API error");
-                        return Err(msg.to_string());
+                            .map(|m| m.to_string())
+                            .unwrap_or_else(|| tr("unknown-api-error"));
+                        return Err(msg);
                     }
                     
-                    if let Some(candidates) = json.get("candidates") {
-                        if let Some(content) = candidates[0].get("content") {
-                            if let Some(parts) = content.get("parts") {
-                                if let Some(text_part) = parts[0].get("text") {
-                                    if let Some(chunk_text) = text_part.as_str() {
-                                        has_content = true;
-                                        window.emit("ai-chunk", AIChunk { chunk: chunk_text.to_string() }).map_err(|e| e.to_string())?;
-                                    }
-                                }
-                            }
+                    // Thought summaries arrive as parts flagged `thought`
+                    for part in json["candidates"][0]["content"]["parts"].as_array().into_iter().flatten() {
+                        let Some(chunk_text) = part["text"].as_str() else { continue };
+                        if part["thought"].as_bool().unwrap_or(false) {
+                            emitter.thinking(chunk_text)?;
+                        } else {
+                            output.push_str(chunk_text);
+                            emitter.chunk(chunk_text)?;
                         }
                     }
                 }
@@ -151,18 +604,15 @@ async fn call_gemini(window: Window, req: AIRequest) -> Result<(), String> {
         }
     }
 
-    if !has_content {
-        return Err("No response received from AI. Please check your API key and model selection.".to_string());
+    if output.is_empty() {
+        return Err(tr("no-response"));
     }
 
-    Ok(())
+    Ok(output)
 }
 
-async fn call_openai(window: Window, req: AIRequest) -> Result<(), String> {
-    let client = Client::new();
-    let url = "https://api.openai.com/v1/chat/completions";
-
-    let payload = json!({
+pub fn openai_payload(req: &AIRequest) -> serde_json::Value {
+    let mut payload = json!({
         "model": req.model,
         "messages": [
             {"role": "system", "content": req.system_prompt},
@@ -172,25 +622,37 @@ async fn call_openai(window: Window, req: AIRequest) -> Result<(), String> {
         "top_p": req.top_p,
         "stream": true
     });
+    if let Some(max_tokens) = req.max_tokens {
+        // OpenAI's reasoning models only accept the newer name; compatible servers the older one
+        let key = if req.vendor == "openai" { "max_completion_tokens" } else { "max_tokens" };
+        payload[key] = json!(max_tokens);
+    }
+    payload
+}
 
-    let res = client.post(url)
+async fn call_openai(emitter: &RunEmitter, req: AIRequest) -> Result<String, String> {
+    let client = http::vendor_client(&req.vendor)?;
+    let payload = openai_payload(&req);
+
+    let res = client.post(openai_compatible_url(&req.vendor))
         .header("Authorization", format!("Bearer {}", req.api_key))
         .json(&payload)
-        .send()
+        .send_audited("run", Some(emitter.run_id()))
         .await
-        .map_err(|e| format!("Network error: {}", e))?;
+        .map_err(http::network_error)?;
 
     let status = res.status();
     if !status.is_success() {
         let error_text = res.text().await.unwrap_or_default();
-        return Err(format!("OpenAI API Error ({}): {}", status, &error_text[..error_text.len().min(300)]));
+        return Err(tr_args("vendor-api-error", &[("vendor", vendor_name(&req.vendor)), ("status", &status.to_string()), ("details", &error_text.chars().take(300).collect::<String>())]));
     }
 
     let mut stream = res.bytes_stream();
-    let mut has_content = false;
+    let mut output = String::new();
 
     while let Some(item) = stream.next().await {
-        let chunk = item.map_err(|e| e.to_string())?;
+        emitter.wait_for_capacity().await;
+        let chunk = item.map_err(|e| emitter.stream_interrupted(e))?;
         let text = String::from_utf8_lossy(&chunk);
         
         for line in text.lines() {
@@ -200,10 +662,15 @@ async fn call_openai(window: Window, req: AIRequest) -> Result<(), String> {
                 if let Ok(json) = serde_json::from_str::<serde_json::Value>(json_str) {
                     if let Some(choices) = json.get("choices") {
                         if let Some(delta) = choices[0].get("delta") {
+                            // DeepSeek R1 and Grok use reasoning_content; other compatible servers `reasoning`
+                            let reasoning = delta.get("reasoning_content").or_else(|| delta.get("reasoning"));
+                            if let Some(reasoning) = reasoning.and_then(|r| r.as_str()) {
+                                emitter.thinking(reasoning)?;
+                            }
                             if let Some(content) = delta.get("content") {
                                 if let Some(chunk_text) = content.as_str() {
-                                    has_content = true;
-                                    window.emit("ai-chunk", AIChunk { chunk: chunk_text.to_string() }).map_err(|e| e.to_string())?;
+                                    output.push_str(chunk_text);
+                                    emitter.chunk(chunk_text)?;
                                 }
                             }
                         }
@@ -213,46 +680,169 @@ async fn call_openai(window: Window, req: AIRequest) -> Result<(), String> {
         }
     }
 
-    if !has_content {
-        return Err("No response received from OpenAI. Please check your API key.".to_string());
+    if output.is_empty() {
+        return Err(tr_args("no-response-vendor", &[("vendor", vendor_name(&req.vendor))]));
     }
 
-    Ok(())
+    Ok(output)
+}
+
+// Self-hosted TGI servers often run without a token
+fn with_bearer(request: reqwest::RequestBuilder, token: &str) -> reqwest::RequestBuilder {
+    if token.trim().is_empty() {
+        request
+    } else {
+        request.bearer_auth(token.trim())
+    }
 }
 
-async fn call_anthropic(window: Window, req: AIRequest) -> Result<(), String> {
-    let client = Client::new();
-    let url = "https://api.anthropic.com/v1/messages";
+// Text Generation Inference streams one token per SSE event; the serverless Inference API
+// uses the same format
+async fn call_huggingface(emitter: &RunEmitter, req: AIRequest) -> Result<String, String> {
+    let (url, payload) = vendor_request(&req)?;
+    let res = with_bearer(http::client_for(&url)?.post(&url), &req.api_key)
+        .json(&payload)
+        .send_audited("run", Some(emitter.run_id()))
+        .await
+        .map_err(http::network_error)?;
+
+    let status = res.status();
+    if !status.is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        let friendly_error = if status.as_u16() == 503 && error_text.contains("loading") {
+            tr_args("error-hf-model-loading", &[("model", &req.model)])
+        } else {
+            tr_args("vendor-api-error", &[("vendor", "Hugging Face"), ("status", &status.to_string()), ("details", &error_text.chars().take(300).collect::<String>())])
+        };
+        return Err(friendly_error);
+    }
 
-    let payload = json!({
+    let mut stream = res.bytes_stream();
+    let mut output = String::new();
+    // Events can be split across network chunks, so only complete lines are parsed
+    let mut buffer = String::new();
+
+    while let Some(item) = stream.next().await {
+        emitter.wait_for_capacity().await;
+        let chunk = item.map_err(|e| emitter.stream_interrupted(e))?;
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+        while let Some(pos) = buffer.find('\n') {
+            let line: String = buffer.drain(..=pos).collect();
+            let Some(json_str) = line.trim_end().strip_prefix("data:") else { continue };
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(json_str.trim()) else { continue };
+
+            if let Some(error) = json["error"].as_str() {
+                return Err(error.to_string());
+            }
+            if json["token"]["special"].as_bool().unwrap_or(false) {
+                continue;
+            }
+            if let Some(text) = json["token"]["text"].as_str() {
+                output.push_str(text);
+                emitter.chunk(text)?;
+            }
+        }
+    }
+
+    if output.is_empty() {
+        return Err(tr_args("no-response-vendor", &[("vendor", "Hugging Face")]));
+    }
+
+    Ok(output)
+}
+
+fn anthropic_payload(req: &AIRequest) -> serde_json::Value {
+    let mut messages = vec![json!({"role": "user", "content": req.user_input})];
+    if let Some(prefill) = req.assistant_prefill.as_deref().filter(|p| !p.is_empty()) {
+        // The API rejects a final assistant turn that ends in whitespace
+        messages.push(json!({"role": "assistant", "content": prefill.trim_end()}));
+    }
+
+    let mut payload = json!({
         "model": req.model,
         "system": req.system_prompt,
-        "messages": [
-            {"role": "user", "content": req.user_input}
-        ],
-        "max_tokens": 4096,
+        "messages": messages,
+        "max_tokens": req.max_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS),
         "stream": true
     });
 
-    let res = client.post(url)
+    // Extended thinking, with budgets following the Gemini levels. The budget counts against
+    // max_tokens, so the cap is raised to leave room for the answer.
+    if let Some(level) = req.thinking_level.filter(|&level| level > 0) {
+        let budget: u32 = match level {
+            2 => 16384,
+            1 => 8192,
+            _ => 2048,
+        };
+        payload["thinking"] = json!({"type": "enabled", "budget_tokens": budget});
+        payload["max_tokens"] = json!(budget + req.max_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS));
+    }
+
+    payload
+}
+
+// Bedrock streams in AWS's binary event-stream framing, so runs pinned to Bedrock are sent
+// without streaming and the answer arrives in one piece
+async fn call_bedrock(emitter: &RunEmitter, req: AIRequest, url: String) -> Result<String, String> {
+    let res = http::vendor_client("anthropic")?
+        .post(url)
+        .bearer_auth(residency::bedrock_api_key()?)
+        .json(&residency::bedrock_payload(anthropic_payload(&req)))
+        .send_audited("run", Some(emitter.run_id()))
+        .await
+        .map_err(http::network_error)?;
+
+    let status = res.status();
+    if !status.is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(tr_args("vendor-api-error", &[("vendor", "Amazon Bedrock"), ("status", &status.to_string()), ("details", &error_text.chars().take(300).collect::<String>())]));
+    }
+
+    let json: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+    let mut output = String::new();
+    for block in json["content"].as_array().into_iter().flatten() {
+        if let Some(thinking) = block["thinking"].as_str() {
+            emitter.thinking(thinking)?;
+        }
+        if let Some(text) = block["text"].as_str() {
+            output.push_str(text);
+        }
+    }
+    if output.is_empty() {
+        return Err(tr_args("no-response-vendor", &[("vendor", "Amazon Bedrock")]));
+    }
+    emitter.chunk(&output)?;
+    Ok(output)
+}
+
+async fn call_anthropic(emitter: &RunEmitter, req: AIRequest) -> Result<String, String> {
+    if let Some(url) = residency::bedrock_invoke_url(&req.model)? {
+        return call_bedrock(emitter, req, url).await;
+    }
+    let client = http::vendor_client("anthropic")?;
+    let payload = anthropic_payload(&req);
+
+    let res = client.post(ANTHROPIC_MESSAGES_URL)
         .header("x-api-key", &req.api_key)
         .header("anthropic-version", "2023-06-01")
         .json(&payload)
-        .send()
+        .send_audited("run", Some(emitter.run_id()))
         .await
-        .map_err(|e| format!("Network error: {}", e))?;
+        .map_err(http::network_error)?;
 
     let status = res.status();
     if !status.is_success() {
         let error_text = res.text().await.unwrap_or_default();
-        return Err(format!("Anthropic API Error ({}): {}", status, &error_text[..error_text.len().min(300)]));
+        return Err(tr_args("vendor-api-error", &[("vendor", "Anthropic"), ("status", &status.to_string()), ("details", &error_text.chars().take(300).collect::<String>())]));
     }
 
     let mut stream = res.bytes_stream();
-    let mut has_content = false;
+    let mut output = String::new();
 
     while let Some(item) = stream.next().await {
-        let chunk = item.map_err(|e| e.to_string())?;
+        emitter.wait_for_capacity().await;
+        let chunk = item.map_err(|e| emitter.stream_interrupted(e))?;
         let text = String::from_utf8_lossy(&chunk);
         
         for line in text.lines() {
@@ -262,10 +852,13 @@ async fn call_anthropic(window: Window, req: AIRequest) -> Result<(), String> {
                     if let Some(type_val) = json.get("type") {
                         if type_val == "content_block_delta" {
                             if let Some(delta) = json.get("delta") {
+                                if let Some(thinking) = delta.get("thinking").and_then(|t| t.as_str()) {
+                                    emitter.thinking(thinking)?;
+                                }
                                 if let Some(content_text) = delta.get("text") {
                                     if let Some(chunk_text) = content_text.as_str() {
-                                        has_content = true;
-                                        window.emit("ai-chunk", AIChunk { chunk: chunk_text.to_string() }).map_err(|e| e.to_string())?;
+                                        output.push_str(chunk_text);
+                                        emitter.chunk(chunk_text)?;
                                     }
                                 }
                             }
@@ -276,9 +869,9 @@ async fn call_anthropic(window: Window, req: AIRequest) -> Result<(), String> {
         }
     }
 
-    if !has_content {
-        return Err("No response received from Anthropic. Please check your API key.".to_string());
+    if output.is_empty() {
+        return Err(tr_args("no-response-vendor", &[("vendor", "Anthropic")]));
     }
 
-    Ok(())
+    Ok(output)
 }
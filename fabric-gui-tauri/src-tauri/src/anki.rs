@@ -0,0 +1,179 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::time::Duration;
+use tauri::State;
+use crate::history::HistoryState;
+use crate::http::{self, AuditedSend};
+
+// AnkiConnect add-on's default address; Anki must be running with it installed
+const ANKI_CONNECT_URL: &str = "http://127.0.0.1:8765";
+const ANKI_CONNECT_VERSION: u32 = 6;
+
+#[derive(Serialize, Clone)]
+pub struct Flashcard {
+    pub front: String,
+    pub back: String,
+}
+
+#[derive(Serialize)]
+pub struct AnkiExport {
+    pub cards: usize,
+    // Cards AnkiConnect refused, usually because they already exist in the deck
+    pub skipped: usize,
+    pub path: Option<String>,
+}
+
+fn clean(text: &str) -> String {
+    text.trim().trim_matches('*').trim().to_string()
+}
+
+// Reads the Q/A pairs flashcard patterns produce: "Q: ... A: ..." (also Question/Answer and
+// Front/Back, answers may run over several lines), "front :: back" lines, and two-column
+// markdown tables
+fn parse_flashcards(output: &str) -> Vec<Flashcard> {
+    let question = Regex::new(r"(?i)^\s*(?:[-*]\s*)?(?:\*\*)?(?:\d+[.)]\s*)?(?:q|question|front)\s*\d*\s*[:.](?:\*\*)?\s*(.*)$").unwrap();
+    let answer = Regex::new(r"(?i)^\s*(?:[-*]\s*)?(?:\*\*)?(?:a|answer|back)\s*\d*\s*[:.](?:\*\*)?\s*(.*)$").unwrap();
+    let separator = Regex::new(r"^(?:[-*]\s*)?(.+?)\s*(?:::|\t)\s*(.+)$").unwrap();
+    let table_rule = Regex::new(r"^\|?[\s:|-]+\|?$").unwrap();
+
+    let mut cards = Vec::new();
+    let mut front: Option<String> = None;
+    let mut back: Option<String> = None;
+    let mut table_header_seen = false;
+    let finish = |front: &mut Option<String>, back: &mut Option<String>, cards: &mut Vec<Flashcard>| {
+        if let (Some(f), Some(b)) = (front.take(), back.take()) {
+            if !f.is_empty() && !b.is_empty() {
+                cards.push(Flashcard { front: f, back: b.trim().to_string() });
+            }
+        }
+    };
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if let Some(c) = question.captures(trimmed) {
+            finish(&mut front, &mut back, &mut cards);
+            front = Some(clean(&c[1]));
+        } else if let Some(c) = answer.captures(trimmed).filter(|_| front.is_some()) {
+            back = Some(clean(&c[1]));
+        } else if trimmed.starts_with('|') {
+            finish(&mut front, &mut back, &mut cards);
+            if table_rule.is_match(trimmed) {
+                table_header_seen = true;
+                continue;
+            }
+            let cells: Vec<String> = trimmed.trim_matches('|').split('|').map(clean).collect();
+            // The row above the |---| rule is the header
+            if table_header_seen && cells.len() >= 2 && !cells[0].is_empty() && !cells[1].is_empty() {
+                cards.push(Flashcard { front: cells[0].clone(), back: cells[1].clone() });
+            }
+        } else if let Some(c) = separator.captures(trimmed).filter(|_| front.is_none()) {
+            cards.push(Flashcard { front: clean(&c[1]), back: clean(&c[2]) });
+        } else if trimmed.is_empty() {
+            table_header_seen = false;
+            if back.is_some() {
+                finish(&mut front, &mut back, &mut cards);
+            }
+        } else if let Some(b) = back.as_mut() {
+            b.push('\n');
+            b.push_str(trimmed);
+        }
+    }
+    finish(&mut front, &mut back, &mut cards);
+    cards
+}
+
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('\n', "<br>")
+}
+
+// Anki's own text import format; the header lines tell it the deck, note type and tags so
+// File > Import needs no further setup
+fn anki_text(cards: &[Flashcard], deck: &str, tags: &[String]) -> String {
+    let mut file = format!("#separator:tab\n#html:true\n#notetype:Basic\n#deck:{}\n", deck);
+    if !tags.is_empty() {
+        file.push_str(&format!("#tags:{}\n", tags.join(" ")));
+    }
+    for card in cards {
+        file.push_str(&format!("{}\t{}\n", html(&card.front).replace('\t', " "), html(&card.back).replace('\t', " ")));
+    }
+    file
+}
+
+async fn anki_connect(action: &str, params: Value) -> Result<Value, String> {
+    let res = http::client_for(ANKI_CONNECT_URL)?
+        .post(ANKI_CONNECT_URL)
+        .json(&json!({"action": action, "version": ANKI_CONNECT_VERSION, "params": params}))
+        .timeout(Duration::from_secs(30))
+        .send_audited("anki", None)
+        .await
+        .map_err(|_| "Could not reach Anki; open Anki with the AnkiConnect add-on installed.".to_string())?;
+    let body: Value = res.json().await.map_err(|e| e.to_string())?;
+    match body["error"].as_str() {
+        Some(error) => Err(format!("AnkiConnect: {}", error)),
+        None => Ok(body["result"].clone()),
+    }
+}
+
+async fn push(cards: &[Flashcard], deck: &str, tags: &[String]) -> Result<usize, String> {
+    anki_connect("createDeck", json!({"deck": deck})).await?;
+    let notes: Vec<Value> = cards
+        .iter()
+        .map(|c| {
+            json!({
+                "deckName": deck,
+                "modelName": "Basic",
+                "fields": {"Front": html(&c.front), "Back": html(&c.back)},
+                "tags": tags,
+                "options": {"allowDuplicate": false}
+            })
+        })
+        .collect();
+    // Only the notes that can be added are sent; addNotes fails as a whole on a duplicate
+    let addable = anki_connect("canAddNotes", json!({"notes": notes})).await?;
+    let notes: Vec<Value> = notes
+        .into_iter()
+        .zip(addable.as_array().cloned().unwrap_or_default())
+        .filter(|(_, ok)| ok == true)
+        .map(|(note, _)| note)
+        .collect();
+    if notes.is_empty() {
+        return Ok(0);
+    }
+    let added = anki_connect("addNotes", json!({"notes": notes})).await?;
+    Ok(added.as_array().map(|ids| ids.iter().filter(|id| !id.is_null()).count()).unwrap_or(0))
+}
+
+// Turns a flashcard run's Q/A pairs into Anki notes: written to `path` as an Anki import
+// file, or with no path pushed straight into `deck` through AnkiConnect
+#[tauri::command]
+pub async fn export_anki(
+    history: State<'_, HistoryState>,
+    run_id: String,
+    deck: String,
+    path: Option<String>,
+    tags: Option<Vec<String>>,
+) -> Result<AnkiExport, String> {
+    let entry = history
+        .get(&run_id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))?;
+    let cards = parse_flashcards(&entry.output);
+    if cards.is_empty() {
+        return Err("No question and answer pairs were found in the output.".to_string());
+    }
+    let deck = if deck.trim().is_empty() { "Fabric".to_string() } else { deck.trim().to_string() };
+    // Anki tags can't contain spaces
+    let tags: Vec<String> = tags.unwrap_or_default().iter().map(|t| t.trim().replace(' ', "_")).filter(|t| !t.is_empty()).collect();
+
+    match path {
+        Some(path) => {
+            fs::write(&path, anki_text(&cards, &deck, &tags)).map_err(|e| e.to_string())?;
+            Ok(AnkiExport { cards: cards.len(), skipped: 0, path: Some(path) })
+        }
+        None => {
+            let added = push(&cards, &deck, &tags).await?;
+            Ok(AnkiExport { cards: added, skipped: cards.len() - added, path: None })
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{OnceLock, RwLock};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, Row};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use crate::history::HistoryState;
+
+// Debug bodies are for seeing what was sent, not for archiving uploads
+const MAX_BODY_CHARS: usize = 64 * 1024;
+const DEFAULT_PAGE_SIZE: u32 = 200;
+// JSON and form fields whose values are masked in debug bodies, e.g. client_secret or refresh_token
+const SECRET_FIELDS: &[&str] = &["key", "token", "secret", "password", "assertion"];
+
+// One request that left the machine. Only the endpoint is kept, without the query string, and
+// never headers, so API keys and tokens don't end up in the log; the body only in debug mode.
+#[derive(Serialize, Clone, Default)]
+pub struct AuditEntry {
+    // Assigned by the database
+    pub id: i64,
+    pub at: i64,
+    pub method: String,
+    pub endpoint: String,
+    // What the request was for, e.g. "run", "scrape" or "zotero"
+    pub purpose: String,
+    pub run_id: Option<String>,
+    // Unknown for streamed uploads and for responses without a Content-Length
+    pub bytes_sent: Option<u64>,
+    pub bytes_received: Option<u64>,
+    pub status: Option<u16>,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+    pub body: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct AuditFilters {
+    // Unix seconds, inclusive
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub purpose: Option<String>,
+    pub run_id: Option<String>,
+    // Matches the host part of the endpoint
+    pub host: Option<String>,
+}
+
+#[derive(Default)]
+struct AuditConfig {
+    debug: bool,
+    // Stored API keys, masked in debug bodies
+    secrets: Vec<String>,
+}
+
+fn config() -> &'static RwLock<AuditConfig> {
+    static CONFIG: OnceLock<RwLock<AuditConfig>> = OnceLock::new();
+    CONFIG.get_or_init(RwLock::default)
+}
+
+fn sink() -> &'static OnceLock<UnboundedSender<AuditEntry>> {
+    static SINK: OnceLock<UnboundedSender<AuditEntry>> = OnceLock::new();
+    &SINK
+}
+
+pub fn configure(debug: bool, api_keys: &HashMap<String, String>) {
+    *config().write().unwrap() = AuditConfig {
+        debug,
+        // Short values would mask ordinary words
+        secrets: api_keys.values().filter(|key| key.len() >= 8).cloned().collect(),
+    };
+}
+
+pub fn wants_bodies() -> bool {
+    config().read().unwrap().debug
+}
+
+fn is_secret_field(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SECRET_FIELDS.iter().any(|field| name.contains(field))
+}
+
+fn mask_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (name, value) in map.iter_mut() {
+                if is_secret_field(name) && value.is_string() {
+                    *value = "[redacted]".into();
+                } else {
+                    mask_json(value);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(mask_json),
+        _ => {}
+    }
+}
+
+// The body as kept in debug mode: text only, credential fields and stored keys masked, cut to size
+pub fn loggable_body(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let masked = if let Ok(mut json) = serde_json::from_str::<serde_json::Value>(&text) {
+        mask_json(&mut json);
+        json.to_string()
+    } else if !text.is_empty() && !text.contains(char::is_whitespace) && text.contains('=') {
+        // application/x-www-form-urlencoded
+        text.split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if is_secret_field(name) => format!("{}=[redacted]", name),
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    } else {
+        text.to_string()
+    };
+    let mut body: String = masked.chars().take(MAX_BODY_CHARS).collect();
+    for secret in &config().read().unwrap().secrets {
+        body = body.replace(secret.as_str(), "[redacted]");
+    }
+    body
+}
+
+// Queues an entry for the writer task, so requests never wait on the database
+pub fn record(entry: AuditEntry) {
+    match sink().get() {
+        Some(sender) => {
+            let _ = sender.send(entry);
+        }
+        None => eprintln!("Audit log not started; dropped entry for {}", entry.endpoint),
+    }
+}
+
+// Writes queued entries into the active profile's history database as they come in
+pub fn start(app_handle: AppHandle) {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<AuditEntry>();
+    if sink().set(sender).is_err() {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        while let Some(entry) = receiver.recv().await {
+            let history = app_handle.state::<HistoryState>();
+            let written = insert(&history.conn(), &entry);
+            if let Err(e) = written {
+                eprintln!("Could not write the audit log: {}", e);
+            }
+        }
+    });
+}
+
+fn insert(conn: &Connection, entry: &AuditEntry) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO audit_log (at, method, endpoint, purpose, run_id, bytes_sent, bytes_received,
+                                status, duration_ms, error, body)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            entry.at,
+            entry.method,
+            entry.endpoint,
+            entry.purpose,
+            entry.run_id,
+            entry.bytes_sent.map(|b| b as i64),
+            entry.bytes_received.map(|b| b as i64),
+            entry.status,
+            entry.duration_ms as i64,
+            entry.error,
+            entry.body,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<AuditEntry> {
+    Ok(AuditEntry {
+        id: row.get(0)?,
+        at: row.get(1)?,
+        method: row.get(2)?,
+        endpoint: row.get(3)?,
+        purpose: row.get(4)?,
+        run_id: row.get(5)?,
+        bytes_sent: row.get::<_, Option<i64>>(6)?.map(|b| b as u64),
+        bytes_received: row.get::<_, Option<i64>>(7)?.map(|b| b as u64),
+        status: row.get(8)?,
+        duration_ms: row.get::<_, i64>(9)? as u64,
+        error: row.get(10)?,
+        body: row.get(11)?,
+    })
+}
+
+// Oldest first, so exports read as a timeline
+fn query(conn: &Connection, filters: AuditFilters, limit: Option<(u32, u32)>) -> Result<Vec<AuditEntry>, String> {
+    let mut clauses = Vec::new();
+    let mut values = Vec::new();
+    if let Some(from) = filters.from {
+        clauses.push("at >= ?");
+        values.push(Value::Integer(from));
+    }
+    if let Some(to) = filters.to {
+        clauses.push("at <= ?");
+        values.push(Value::Integer(to));
+    }
+    if let Some(purpose) = filters.purpose {
+        clauses.push("purpose = ?");
+        values.push(Value::Text(purpose));
+    }
+    if let Some(run_id) = filters.run_id {
+        clauses.push("run_id = ?");
+        values.push(Value::Text(run_id));
+    }
+    if let Some(host) = filters.host {
+        clauses.push("(endpoint LIKE '%://' || ? || '/%' OR endpoint LIKE '%://' || ? || ':%')");
+        values.push(Value::Text(host.to_lowercase()));
+        values.push(Value::Text(host.to_lowercase()));
+    }
+
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+    let mut sql = format!(
+        "SELECT id, at, method, endpoint, purpose, run_id, bytes_sent, bytes_received,
+                status, duration_ms, error, body
+         FROM audit_log {} ORDER BY id",
+        where_sql
+    );
+    if let Some((page, page_size)) = limit {
+        // The newest page first for browsing, still in time order within it
+        sql = format!("SELECT * FROM ({} DESC LIMIT ? OFFSET ?) ORDER BY id", sql);
+        values.push(Value::Integer(page_size as i64));
+        values.push(Value::Integer(page as i64 * page_size as i64));
+    }
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(params_from_iter(values.iter()), entry_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+#[tauri::command]
+pub async fn list_audit_log(
+    history: State<'_, HistoryState>,
+    filters: Option<AuditFilters>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+) -> Result<Vec<AuditEntry>, String> {
+    let page_size = page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, 1000);
+    query(&history.conn(), filters.unwrap_or_default(), Some((page.unwrap_or(0), page_size)))
+}
+
+// Writes the matching entries to a file as CSV or JSON Lines ("jsonl", the default) and
+// returns how many were written
+#[tauri::command]
+pub async fn export_audit_log(
+    history: State<'_, HistoryState>,
+    path: String,
+    format: Option<String>,
+    filters: Option<AuditFilters>,
+) -> Result<usize, String> {
+    let entries = query(&history.conn(), filters.unwrap_or_default(), None)?;
+    match format.as_deref().unwrap_or("jsonl") {
+        "csv" => {
+            let mut writer = csv::Writer::from_path(&path).map_err(|e| e.to_string())?;
+            for entry in &entries {
+                writer.serialize(entry).map_err(|e| e.to_string())?;
+            }
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+        "jsonl" => {
+            let mut writer = BufWriter::new(File::create(&path).map_err(|e| e.to_string())?);
+            for entry in &entries {
+                serde_json::to_writer(&mut writer, entry).map_err(|e| e.to_string())?;
+                writer.write_all(b"\n").map_err(|e| e.to_string())?;
+            }
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+        other => return Err(format!("Unknown export format '{}'. Use jsonl or csv.", other)),
+    }
+    Ok(entries.len())
+}
@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use rusqlite::params;
+use tauri::{AppHandle, Manager, State};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::history::{now_secs, open_connection, HistoryState};
+use crate::settings::{Settings, SettingsState};
+use crate::setup::fabric_config_dir;
+
+const BACKUP_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct BackupManifest {
+    format_version: u32,
+    app_version: String,
+    created_at: i64,
+    includes_history: bool,
+    includes_secrets: bool,
+}
+
+#[derive(Serialize, Default)]
+pub struct BackupSummary {
+    pub settings: bool,
+    pub patterns: usize,
+    pub contexts: usize,
+    pub presets: bool,
+    pub history_runs: u64,
+    // Settings the backup wanted to change but that kept their local values
+    pub kept_local: Vec<String>,
+}
+
+pub fn zip_options() -> SimpleFileOptions {
+    SimpleFileOptions::default().compression_method(CompressionMethod::Deflated)
+}
+
+fn add_dir<W: Write + std::io::Seek>(
+    zip: &mut ZipWriter<W>,
+    dir: &Path,
+    prefix: &str,
+) -> Result<usize, String> {
+    let mut count = 0;
+    let mut stack = vec![dir.to_path_buf()];
+
+    while let Some(current) = stack.pop() {
+        for entry in fs::read_dir(&current).map_err(|e| e.to_string())?.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                stack.push(path);
+                continue;
+            }
+            let relative = path.strip_prefix(dir).map_err(|e| e.to_string())?;
+            let name = format!("{}/{}", prefix, relative.to_string_lossy().replace('\\', "/"));
+            zip.start_file(name, zip_options()).map_err(|e| e.to_string())?;
+            zip.write_all(&fs::read(&path).map_err(|e| e.to_string())?)
+                .map_err(|e| e.to_string())?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+fn presets_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_config_dir()
+        .map(|dir| dir.join("presets.json"))
+        .map_err(|e| e.to_string())
+}
+
+fn custom_patterns_dir(app_handle: &AppHandle, settings: &Settings) -> Result<PathBuf, String> {
+    match &settings.custom_patterns_dir {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => app_handle
+            .path()
+            .app_data_dir()
+            .map(|dir| dir.join("custom_patterns"))
+            .map_err(|e| e.to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn export_backup(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsState>,
+    history: State<'_, HistoryState>,
+    path: String,
+    include_history: bool,
+    include_secrets: bool,
+) -> Result<BackupSummary, String> {
+    let mut summary = BackupSummary::default();
+    let file = File::create(&path).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        app_version: app_handle.package_info().version.to_string(),
+        created_at: now_secs(),
+        includes_history: include_history,
+        includes_secrets: include_secrets,
+    };
+    zip.start_file("manifest.json", zip_options()).map_err(|e| e.to_string())?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?)
+        .map_err(|e| e.to_string())?;
+
+    // API keys stay out of the archive unless explicitly requested
+    let mut current = settings.get();
+    if !include_secrets {
+        current.api_keys.clear();
+    }
+    zip.start_file("settings.json", zip_options()).map_err(|e| e.to_string())?;
+    zip.write_all(&serde_json::to_vec_pretty(&current).map_err(|e| e.to_string())?)
+        .map_err(|e| e.to_string())?;
+    summary.settings = true;
+
+    let patterns_dir = custom_patterns_dir(&app_handle, &current)?;
+    if patterns_dir.exists() {
+        summary.patterns = add_dir(&mut zip, &patterns_dir, "patterns")?;
+    }
+
+    if let Some(contexts_dir) = fabric_config_dir().map(|p| p.join("contexts")).filter(|p| p.exists()) {
+        summary.contexts = add_dir(&mut zip, &contexts_dir, "contexts")?;
+    }
+
+    let presets = presets_path(&app_handle)?;
+    if presets.exists() {
+        zip.start_file("presets.json", zip_options()).map_err(|e| e.to_string())?;
+        zip.write_all(&fs::read(&presets).map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        summary.presets = true;
+    }
+
+    if include_history {
+        // VACUUM INTO gives a consistent snapshot without closing the live connection
+        let snapshot = std::env::temp_dir().join(format!("fabric-history-{}.db", now_secs()));
+        {
+            let conn = history.conn();
+            conn.execute("VACUUM INTO ?1", params![snapshot.to_string_lossy()])
+                .map_err(|e| e.to_string())?;
+            summary.history_runs = conn
+                .query_row("SELECT COUNT(*) FROM runs", [], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+        }
+        zip.start_file("history.db", zip_options()).map_err(|e| e.to_string())?;
+        zip.write_all(&fs::read(&snapshot).map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        let _ = fs::remove_file(&snapshot);
+    }
+
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+fn merge_history(history: &HistoryState, db_path: &Path) -> Result<u64, String> {
+    // Brings archives from older versions up to the current schema
+    drop(open_connection(db_path)?);
+
+    let conn = history.conn();
+    conn.execute("ATTACH DATABASE ?1 AS backup", params![db_path.to_string_lossy()])
+        .map_err(|e| e.to_string())?;
+
+    let result = (|| -> rusqlite::Result<u64> {
+        let columns = "id, created_at, pattern, vendor, model, system_prompt, input, output,
+                       temperature, top_p, thinking_level, success, error,
+                       duration_ms, time_to_first_token_ms, tokens_per_sec, parent_run_id, request_json, reasoning, relation,
+                       output_blob";
+        let imported = conn.execute(
+            &format!("INSERT OR IGNORE INTO runs ({0}) SELECT {0} FROM backup.runs", columns),
+            [],
+        )? as u64;
+        conn.execute_batch(
+            "INSERT OR IGNORE INTO run_tags (run_id, tag) SELECT run_id, tag FROM backup.run_tags;
+             INSERT OR IGNORE INTO collections (name, created_at) SELECT name, created_at FROM backup.collections;
+             INSERT OR IGNORE INTO collection_runs (collection_id, run_id, added_at)
+                 SELECT c.id, bcr.run_id, bcr.added_at
+                 FROM backup.collection_runs bcr
+                 JOIN backup.collections bc ON bc.id = bcr.collection_id
+                 JOIN collections c ON c.name = bc.name;",
+        )?;
+        Ok(imported)
+    })();
+
+    let _ = conn.execute("DETACH DATABASE backup", []);
+    result.map_err(|e| e.to_string())
+}
+
+fn keep_local<T: Serialize + Clone>(name: &str, imported: &mut T, local: &T, kept: &mut Vec<String>) {
+    if serde_json::to_value(&*imported).ok() != serde_json::to_value(local).ok() {
+        kept.push(name.to_string());
+    }
+    *imported = local.clone();
+}
+
+// Settings that run programs or decide what the app trusts and may contact. A backup can come
+// from anywhere, so these are never taken from it; the summary lists the ones it would have changed.
+fn keep_local_trust_settings(imported: &mut Settings, local: &Settings) -> Vec<String> {
+    let mut kept = Vec::new();
+    keep_local("post_hooks", &mut imported.post_hooks, &local.post_hooks, &mut kept);
+    keep_local("mcp_servers", &mut imported.mcp_servers, &local.mcp_servers, &mut kept);
+    keep_local("scripts_enabled", &mut imported.scripts_enabled, &local.scripts_enabled, &mut kept);
+    keep_local("headless_browser", &mut imported.headless_browser, &local.headless_browser, &mut kept);
+    keep_local("whisper_command", &mut imported.whisper_command, &local.whisper_command, &mut kept);
+    keep_local("python_command", &mut imported.python_command, &local.python_command, &mut kept);
+    let (network, local_network) = (&mut imported.network, &local.network);
+    keep_local("network.firewall", &mut network.firewall, &local_network.firewall, &mut kept);
+    keep_local("network.insecure_hosts", &mut network.insecure_hosts, &local_network.insecure_hosts, &mut kept);
+    keep_local("network.extra_ca_certs", &mut network.extra_ca_certs, &local_network.extra_ca_certs, &mut kept);
+    kept
+}
+
+#[tauri::command]
+pub async fn import_backup(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsState>,
+    history: State<'_, HistoryState>,
+    path: String,
+) -> Result<BackupSummary, String> {
+    let file = File::open(&path).map_err(|e| e.to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("Not a valid backup archive: {}", e))?;
+
+    let manifest: BackupManifest = {
+        let mut entry = archive
+            .by_name("manifest.json")
+            .map_err(|_| "Backup is missing its manifest.".to_string())?;
+        let mut content = String::new();
+        entry.read_to_string(&mut content).map_err(|e| e.to_string())?;
+        serde_json::from_str(&content).map_err(|e| e.to_string())?
+    };
+    if manifest.format_version > BACKUP_FORMAT_VERSION {
+        return Err("This backup was created by a newer version of the app.".to_string());
+    }
+
+    let mut summary = BackupSummary::default();
+
+    if let Ok(mut entry) = archive.by_name("settings.json") {
+        let mut content = String::new();
+        entry.read_to_string(&mut content).map_err(|e| e.to_string())?;
+        let mut imported: Settings = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+        settings.update(|current| {
+            summary.kept_local = keep_local_trust_settings(&mut imported, current);
+            // Keep local keys when the backup was exported without secrets
+            let local_keys = std::mem::take(&mut current.api_keys);
+            *current = imported;
+            if current.api_keys.is_empty() {
+                current.api_keys = local_keys;
+            }
+        })?;
+        summary.settings = true;
+    }
+
+    let current = settings.get();
+    let patterns_dir = custom_patterns_dir(&app_handle, &current)?;
+    let contexts_dir = fabric_config_dir().map(|p| p.join("contexts"));
+    let presets = presets_path(&app_handle)?;
+    let history_snapshot = std::env::temp_dir().join(format!("fabric-import-{}.db", now_secs()));
+    let mut has_history = false;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        if entry.is_dir() {
+            continue;
+        }
+        // enclosed_name rejects absolute paths and ../ traversal from crafted archives
+        let name = match entry.enclosed_name() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let target = if let Ok(rel) = name.strip_prefix("patterns") {
+            summary.patterns += 1;
+            patterns_dir.join(rel)
+        } else if let Ok(rel) = name.strip_prefix("contexts") {
+            match &contexts_dir {
+                Some(dir) => {
+                    summary.contexts += 1;
+                    dir.join(rel)
+                }
+                None => continue,
+            }
+        } else if name == Path::new("presets.json") {
+            summary.presets = true;
+            presets.clone()
+        } else if name == Path::new("history.db") {
+            has_history = true;
+            history_snapshot.clone()
+        } else {
+            continue;
+        };
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut out = File::create(&target).map_err(|e| e.to_string())?;
+        std::io::copy(&mut entry, &mut out).map_err(|e| e.to_string())?;
+    }
+
+    if summary.patterns > 0 && current.custom_patterns_dir.is_none() {
+        settings.update(|s| s.custom_patterns_dir = Some(patterns_dir.to_string_lossy().to_string()))?;
+    }
+
+    if has_history {
+        let merged = merge_history(&history, &history_snapshot);
+        let _ = fs::remove_file(&history_snapshot);
+        summary.history_runs = merged?;
+    }
+
+    Ok(summary)
+}
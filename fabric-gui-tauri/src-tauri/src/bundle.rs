@@ -0,0 +1,85 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use tauri::{AppHandle, State};
+use zip::ZipWriter;
+use crate::backup::zip_options;
+use crate::history::{now_secs, HistoryState};
+use crate::replay::request_from_entry;
+
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct BundleManifest {
+    format_version: u32,
+    app_version: String,
+    exported_at: i64,
+    run_id: String,
+    created_at: i64,
+    pattern: Option<String>,
+    vendor: String,
+    model: String,
+    success: bool,
+    error: Option<String>,
+    parent_run_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BundleSummary {
+    pub path: String,
+    pub files: Vec<String>,
+}
+
+fn add_file(zip: &mut ZipWriter<File>, files: &mut Vec<String>, name: &str, data: &[u8]) -> Result<(), String> {
+    zip.start_file(name, zip_options()).map_err(|e| e.to_string())?;
+    zip.write_all(data).map_err(|e| e.to_string())?;
+    files.push(name.to_string());
+    Ok(())
+}
+
+// Writes everything needed to audit or reproduce a run: the exact system prompt and input
+// that were sent, the full request (which replay_run or another tool can send again), and the
+// output. The API key is never part of a recorded request.
+#[tauri::command]
+pub async fn export_run_bundle(
+    app_handle: AppHandle,
+    history: State<'_, HistoryState>,
+    run_id: String,
+    path: String,
+) -> Result<BundleSummary, String> {
+    let entry = history
+        .get(&run_id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))?;
+    let mut request = request_from_entry(&entry)?;
+    request.api_key.clear();
+
+    let manifest = BundleManifest {
+        format_version: BUNDLE_FORMAT_VERSION,
+        app_version: app_handle.package_info().version.to_string(),
+        exported_at: now_secs(),
+        run_id: entry.id.clone(),
+        created_at: entry.created_at,
+        pattern: entry.pattern.clone(),
+        vendor: entry.vendor.clone(),
+        model: entry.model.clone(),
+        success: entry.success,
+        error: entry.error.clone(),
+        parent_run_id: entry.parent_run_id.clone(),
+    };
+
+    let mut files = Vec::new();
+    let mut zip = ZipWriter::new(File::create(&path).map_err(|e| e.to_string())?);
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    add_file(&mut zip, &mut files, "manifest.json", &manifest)?;
+    let request = serde_json::to_vec_pretty(&request).map_err(|e| e.to_string())?;
+    add_file(&mut zip, &mut files, "request.json", &request)?;
+    add_file(&mut zip, &mut files, "system.md", entry.system_prompt.as_bytes())?;
+    add_file(&mut zip, &mut files, "input.txt", entry.input.as_bytes())?;
+    add_file(&mut zip, &mut files, "output.md", entry.output.as_bytes())?;
+    if let Some(reasoning) = entry.reasoning.as_deref().filter(|r| !r.is_empty()) {
+        add_file(&mut zip, &mut files, "reasoning.md", reasoning.as_bytes())?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+
+    Ok(BundleSummary { path, files })
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tauri::State;
+use crate::patterns::{get_patterns_dir, pattern_dirs};
+use crate::settings::SettingsState;
+
+const BUNDLED_CATEGORIES: &str = include_str!("../resources/pattern_categories.json");
+const FALLBACK_CATEGORY: &str = "other";
+
+#[derive(Deserialize)]
+struct CategoryFile {
+    categories: Vec<CategoryInfo>,
+    collections: Vec<PatternCollection>,
+    // Pattern name to category IDs
+    patterns: HashMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CategoryInfo {
+    id: String,
+    label: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PatternCollection {
+    pub id: String,
+    pub label: String,
+    pub patterns: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct PatternCategory {
+    pub id: String,
+    pub label: String,
+    pub patterns: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct PatternCatalog {
+    pub categories: Vec<PatternCategory>,
+    // Curated starting points; only patterns that are installed are listed
+    pub collections: Vec<PatternCollection>,
+}
+
+fn bundled() -> &'static CategoryFile {
+    static FILE: OnceLock<CategoryFile> = OnceLock::new();
+    FILE.get_or_init(|| serde_json::from_str(BUNDLED_CATEGORIES).expect("bundled pattern_categories.json is invalid"))
+}
+
+// Patterns the mapping doesn't know (custom or newer than the app) are placed by their name,
+// which in Fabric nearly always starts with the verb or domain
+fn guess_categories(name: &str) -> Vec<&'static str> {
+    const RULES: &[(&[&str], &str)] = &[
+        (&["summarize", "summary"], "summarization"),
+        (&["extract_"], "extraction"),
+        (&["write_", "improve_writing", "essay", "tweet", "newsletter"], "writing"),
+        (&["code", "coding", "git_", "commit", "pull-request", "refactor"], "coding"),
+        (&["threat", "malware", "security", "cve", "incident", "sigma", "semgrep", "vulnerab"], "security"),
+        (&["mermaid", "diagram", "visualiz", "graph", "markmap"], "visualization"),
+        (&["analyze_", "rate_", "judge", "evaluate", "review"], "analysis"),
+        (&["research", "paper", "academic"], "research"),
+        (&["convert", "to_", "translate", "format"], "conversion"),
+        (&["prompt", "agent", "ai_"], "ai"),
+        (&["learn", "teach", "explain", "quiz", "flashcard"], "learning"),
+        (&["business", "strategy", "pitch", "startup", "market"], "business"),
+    ];
+
+    let matched: Vec<&'static str> = RULES
+        .iter()
+        .filter(|(needles, _)| needles.iter().any(|needle| name.contains(needle)))
+        .map(|(_, category)| *category)
+        .collect();
+    if matched.is_empty() {
+        vec![FALLBACK_CATEGORY]
+    } else {
+        matched
+    }
+}
+
+#[tauri::command]
+pub async fn list_patterns_by_category(state: State<'_, SettingsState>) -> Result<PatternCatalog, String> {
+    let settings = state.get();
+    let installed: Vec<String> = pattern_dirs(&settings).into_keys().collect();
+    if installed.is_empty() && !get_patterns_dir(&settings).exists() {
+        return Err("Fabric patterns directory not found. Please install Fabric first.".to_string());
+    }
+
+    let file = bundled();
+    let mut by_category: HashMap<String, Vec<String>> = HashMap::new();
+    for name in &installed {
+        let categories: Vec<String> = match file.patterns.get(name) {
+            Some(categories) => categories.clone(),
+            None => guess_categories(name).into_iter().map(str::to_string).collect(),
+        };
+        for category in categories {
+            by_category.entry(category).or_default().push(name.clone());
+        }
+    }
+
+    // Keeps the mapping file's order; empty categories are left out
+    let categories = file
+        .categories
+        .iter()
+        .filter_map(|info| {
+            by_category.remove(&info.id).map(|patterns| PatternCategory {
+                id: info.id.clone(),
+                label: info.label.clone(),
+                patterns,
+            })
+        })
+        .collect();
+
+    let collections = file
+        .collections
+        .iter()
+        .map(|collection| PatternCollection {
+            patterns: collection
+                .patterns
+                .iter()
+                .filter(|name| installed.contains(name))
+                .cloned()
+                .collect(),
+            ..collection.clone()
+        })
+        .filter(|collection| !collection.patterns.is_empty())
+        .collect();
+
+    Ok(PatternCatalog { categories, collections })
+}
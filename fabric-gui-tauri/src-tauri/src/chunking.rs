@@ -0,0 +1,239 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use crate::docs::estimate_tokens;
+use crate::settings::SettingsState;
+
+const DEFAULT_CHUNK_TOKENS: usize = 2000;
+const DEFAULT_OVERLAP_TOKENS: usize = 150;
+const MIN_CHUNK_TOKENS: usize = 50;
+
+// How long inputs are split. Code splits best at fixed sizes, transcripts by sentence and
+// structured prose by heading, so callers can override the defaults from settings per run.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ChunkOptions {
+    // "heading", "sentence" (default) or "fixed"
+    pub strategy: Option<String>,
+    pub size_tokens: Option<usize>,
+    // Tokens repeated from the end of one chunk at the start of the next, so a point split
+    // across the boundary is seen whole at least once
+    pub overlap_tokens: Option<usize>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Chunk {
+    pub index: usize,
+    pub text: String,
+    pub tokens: usize,
+    // The heading the chunk starts under, with the heading strategy
+    pub heading: Option<String>,
+}
+
+impl ChunkOptions {
+    // Unset fields of `self` fall back to `defaults`, then to the built-in values
+    pub fn or(&self, defaults: &ChunkOptions) -> ChunkOptions {
+        ChunkOptions {
+            strategy: self.strategy.clone().or_else(|| defaults.strategy.clone()),
+            size_tokens: self.size_tokens.or(defaults.size_tokens),
+            overlap_tokens: self.overlap_tokens.or(defaults.overlap_tokens),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size_tokens.unwrap_or(DEFAULT_CHUNK_TOKENS).max(MIN_CHUNK_TOKENS)
+    }
+
+    // Never more than half a chunk, or chunks would mostly repeat each other
+    fn overlap(&self) -> usize {
+        self.overlap_tokens.unwrap_or(DEFAULT_OVERLAP_TOKENS).min(self.size() / 2)
+    }
+}
+
+// Sentences, with paragraph breaks kept as their own boundaries
+fn sentences(text: &str) -> Vec<String> {
+    let boundary = Regex::new(r"[.!?][\)\]\x22']*\s+|\n\s*\n").unwrap();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for m in boundary.find_iter(text) {
+        parts.push(text[start..m.end()].to_string());
+        start = m.end();
+    }
+    if start < text.len() {
+        parts.push(text[start..].to_string());
+    }
+    parts.into_iter().filter(|s| !s.trim().is_empty()).collect()
+}
+
+// Pieces of at most `size` tokens, cut at whitespace where possible
+fn fixed_pieces(text: &str, size: usize) -> Vec<String> {
+    let max_chars = size * 4;
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.chars().count() > max_chars {
+        let limit = rest.char_indices().nth(max_chars).map(|(i, _)| i).unwrap_or(rest.len());
+        let cut = rest[..limit].rfind(char::is_whitespace).filter(|&i| i > limit / 2).unwrap_or(limit);
+        pieces.push(rest[..cut].to_string());
+        rest = &rest[cut..];
+    }
+    if !rest.trim().is_empty() {
+        pieces.push(rest.to_string());
+    }
+    pieces
+}
+
+// Packs units into chunks of up to `size` tokens, starting each new chunk with the trailing
+// units of the previous one that fit in `overlap`
+fn pack(units: Vec<String>, size: usize, overlap: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut tokens = 0;
+    for unit in units.into_iter().flat_map(|u| if estimate_tokens(&u) > size { fixed_pieces(&u, size) } else { vec![u] }) {
+        let unit_tokens = estimate_tokens(&unit);
+        if tokens + unit_tokens > size && !current.is_empty() {
+            chunks.push(current.concat());
+            let mut carried = Vec::new();
+            let mut carried_tokens = 0;
+            for previous in current.iter().rev() {
+                let t = estimate_tokens(previous);
+                if carried_tokens + t > overlap || carried_tokens + t + unit_tokens > size {
+                    break;
+                }
+                carried.insert(0, previous.clone());
+                carried_tokens += t;
+            }
+            current = carried;
+            tokens = carried_tokens;
+        }
+        tokens += unit_tokens;
+        current.push(unit);
+    }
+    if !current.is_empty() {
+        chunks.push(current.concat());
+    }
+    chunks
+}
+
+fn fixed(text: &str, size: usize, overlap: usize) -> Vec<String> {
+    // Small pieces packed with overlap give overlapping fixed windows
+    let step = (overlap.max(MIN_CHUNK_TOKENS / 2)).min(size);
+    pack(fixed_pieces(text, step), size, overlap)
+}
+
+// Markdown sections (a heading and its text); text before the first heading is a section too
+fn sections(text: &str) -> Vec<(Option<String>, String)> {
+    let mut found: Vec<(Option<String>, String)> = Vec::new();
+    let mut in_code = false;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+        }
+        let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+        if !in_code && (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+            found.push((Some(trimmed[hashes..].trim().to_string()), String::new()));
+        } else if found.is_empty() {
+            found.push((None, String::new()));
+        }
+        found.last_mut().unwrap().1.push_str(line);
+    }
+    found
+}
+
+pub fn chunk(text: &str, options: &ChunkOptions) -> Vec<Chunk> {
+    let (size, overlap) = (options.size(), options.overlap());
+    let pieces: Vec<(Option<String>, String)> = match options.strategy.as_deref().unwrap_or("sentence") {
+        "fixed" => fixed(text, size, overlap).into_iter().map(|c| (None, c)).collect(),
+        // Whole sections are kept together when they fit; longer ones split by sentence
+        "heading" => sections(text)
+            .into_iter()
+            .flat_map(|(heading, body)| {
+                if estimate_tokens(&body) <= size {
+                    vec![(heading, body)]
+                } else {
+                    pack(sentences(&body), size, overlap).into_iter().map(|c| (heading.clone(), c)).collect()
+                }
+            })
+            .collect(),
+        _ => pack(sentences(text), size, overlap).into_iter().map(|c| (None, c)).collect(),
+    };
+    pieces
+        .into_iter()
+        .map(|(heading, text)| (heading, text.trim().to_string()))
+        .filter(|(_, text)| !text.is_empty())
+        .enumerate()
+        .map(|(index, (heading, text))| Chunk { index, tokens: estimate_tokens(&text), text, heading })
+        .collect()
+}
+
+// Shows how an input would be split with the given options (or the defaults from settings)
+// so sizes can be tuned before a long run
+#[tauri::command]
+pub async fn preview_chunks(
+    state: State<'_, SettingsState>,
+    text: String,
+    options: Option<ChunkOptions>,
+) -> Result<Vec<Chunk>, String> {
+    let options = options.unwrap_or_default().or(&state.get().chunking);
+    if let Some(strategy) = options.strategy.as_deref().filter(|s| !["heading", "sentence", "fixed"].contains(s)) {
+        return Err(format!("Unknown chunking strategy '{}'. Use heading, sentence or fixed.", strategy));
+    }
+    Ok(chunk(&text, &options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(strategy: &str, size: usize, overlap: usize) -> ChunkOptions {
+        ChunkOptions { strategy: Some(strategy.to_string()), size_tokens: Some(size), overlap_tokens: Some(overlap) }
+    }
+
+    fn prose(sentences: usize) -> String {
+        (0..sentences).map(|i| format!("Sentence number {} says something short. ", i)).collect()
+    }
+
+    #[test]
+    fn chunks_stay_within_size_and_are_numbered() {
+        for strategy in ["sentence", "fixed", "heading"] {
+            let chunks = chunk(&prose(200), &options(strategy, 100, 20));
+            assert!(chunks.len() > 1, "{} made one chunk", strategy);
+            for (i, chunk) in chunks.iter().enumerate() {
+                assert_eq!(chunk.index, i);
+                assert!(chunk.tokens <= 100, "{} chunk {} has {} tokens", strategy, i, chunk.tokens);
+                assert!(!chunk.text.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn sentence_chunks_overlap() {
+        let chunks = chunk(&prose(200), &options("sentence", 100, 20));
+        let last_sentence = chunks[0].text.rsplit(". ").next().unwrap();
+        assert!(chunks[1].text.contains(last_sentence.trim_end_matches('.')));
+    }
+
+    #[test]
+    fn heading_chunks_keep_their_heading_and_skip_code_fences() {
+        let text = "Intro text.\n\n# First\n\nOne.\n\n```\n# not a heading\n```\n\n## Second\n\nTwo.\n";
+        let chunks = chunk(text, &options("heading", 500, 0));
+        let headings: Vec<Option<&str>> = chunks.iter().map(|c| c.heading.as_deref()).collect();
+        assert_eq!(headings, [None, Some("First"), Some("Second")]);
+        assert!(chunks[1].text.contains("# not a heading"));
+    }
+
+    #[test]
+    fn long_words_and_multibyte_text_are_split_safely() {
+        let text = "é".repeat(5000);
+        let chunks = chunk(&text, &options("sentence", 100, 10));
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.tokens <= 100));
+        assert!(chunk("", &ChunkOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn overlap_is_at_most_half_a_chunk() {
+        assert_eq!(options("sentence", 100, 90).overlap(), 50);
+        assert_eq!(options("sentence", 1, 0).size(), MIN_CHUNK_TOKENS);
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use rusqlite::{params, OptionalExtension};
+use tauri::State;
+use crate::history::{now_secs, HistoryState};
+
+#[derive(Serialize)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub created_at: i64,
+    pub run_count: u64,
+}
+
+#[derive(Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u64,
+}
+
+fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#').to_lowercase();
+    if tag.is_empty() {
+        None
+    } else {
+        Some(tag)
+    }
+}
+
+fn ensure_run_exists(conn: &rusqlite::Connection, run_id: &str) -> Result<(), String> {
+    let exists: Option<i64> = conn
+        .query_row("SELECT 1 FROM runs WHERE id = ?1", params![run_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    exists
+        .map(|_| ())
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))
+}
+
+// Replaces the full tag set of a run so the UI can send its edited tag list as-is
+#[tauri::command]
+pub async fn tag_session(
+    history: State<'_, HistoryState>,
+    run_id: String,
+    tags: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let mut conn = history.conn();
+    ensure_run_exists(&conn, &run_id)?;
+
+    let mut tags: Vec<String> = tags.iter().filter_map(|t| normalize_tag(t)).collect();
+    tags.sort();
+    tags.dedup();
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM run_tags WHERE run_id = ?1", params![run_id])
+        .map_err(|e| e.to_string())?;
+    for tag in &tags {
+        tx.execute("INSERT INTO run_tags (run_id, tag) VALUES (?1, ?2)", params![run_id, tag])
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(tags)
+}
+
+#[tauri::command]
+pub async fn get_session_tags(history: State<'_, HistoryState>, run_id: String) -> Result<Vec<String>, String> {
+    let conn = history.conn();
+    let mut stmt = conn
+        .prepare("SELECT tag FROM run_tags WHERE run_id = ?1 ORDER BY tag")
+        .map_err(|e| e.to_string())?;
+    let tags = stmt
+        .query_map(params![run_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tags)
+}
+
+#[tauri::command]
+pub async fn list_tags(history: State<'_, HistoryState>) -> Result<Vec<TagCount>, String> {
+    let conn = history.conn();
+    let mut stmt = conn
+        .prepare("SELECT tag, COUNT(*) FROM run_tags GROUP BY tag ORDER BY COUNT(*) DESC, tag")
+        .map_err(|e| e.to_string())?;
+    let tags = stmt
+        .query_map([], |row| Ok(TagCount { tag: row.get(0)?, count: row.get(1)? }))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tags)
+}
+
+#[tauri::command]
+pub async fn list_collections(history: State<'_, HistoryState>) -> Result<Vec<Collection>, String> {
+    let conn = history.conn();
+    let mut stmt = conn
+        .prepare(
+            "SELECT c.id, c.name, c.created_at, COUNT(cr.run_id)
+             FROM collections c LEFT JOIN collection_runs cr ON cr.collection_id = c.id
+             GROUP BY c.id ORDER BY c.name",
+        )
+        .map_err(|e| e.to_string())?;
+    let collections = stmt
+        .query_map([], |row| {
+            Ok(Collection {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: row.get(2)?,
+                run_count: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(collections)
+}
+
+// Creates the collection on first use so "add to new collection" is a single call
+#[tauri::command]
+pub async fn add_to_collection(
+    history: State<'_, HistoryState>,
+    collection: String,
+    run_id: String,
+) -> Result<(), String> {
+    let name = collection.trim();
+    if name.is_empty() {
+        return Err("Collection name cannot be empty.".to_string());
+    }
+
+    let conn = history.conn();
+    ensure_run_exists(&conn, &run_id)?;
+
+    conn.execute(
+        "INSERT OR IGNORE INTO collections (name, created_at) VALUES (?1, ?2)",
+        params![name, now_secs()],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR IGNORE INTO collection_runs (collection_id, run_id, added_at)
+         SELECT id, ?2, ?3 FROM collections WHERE name = ?1",
+        params![name, run_id, now_secs()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_from_collection(
+    history: State<'_, HistoryState>,
+    collection: String,
+    run_id: String,
+) -> Result<(), String> {
+    let conn = history.conn();
+    conn.execute(
+        "DELETE FROM collection_runs
+         WHERE run_id = ?2 AND collection_id = (SELECT id FROM collections WHERE name = ?1)",
+        params![collection, run_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_collection(history: State<'_, HistoryState>, collection: String) -> Result<(), String> {
+    let conn = history.conn();
+    conn.execute("DELETE FROM collections WHERE name = ?1", params![collection])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use similar::{ChangeTag, TextDiff};
+use tauri::{AppHandle, Manager, State, WebviewUrl, WebviewWindowBuilder, Window};
+use crate::history::{HistoryEntry, HistoryState};
+
+#[derive(Serialize)]
+pub struct DiffLine {
+    // "equal", "insert" (only in B) or "delete" (only in A)
+    pub tag: &'static str,
+    pub text: String,
+}
+
+#[derive(Serialize)]
+pub struct ParamDifference {
+    pub field: &'static str,
+    pub a: String,
+    pub b: String,
+}
+
+#[derive(Serialize)]
+pub struct CompareData {
+    pub a: HistoryEntry,
+    pub b: HistoryEntry,
+    pub diff: Vec<DiffLine>,
+    pub added: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    pub param_differences: Vec<ParamDifference>,
+}
+
+// Which pair of runs each compare window was opened for, keyed by window label
+#[derive(Default)]
+pub struct CompareSessions(Mutex<HashMap<String, (String, String)>>);
+
+fn param_differences(a: &HistoryEntry, b: &HistoryEntry) -> Vec<ParamDifference> {
+    let fields = [
+        ("pattern", a.pattern.clone().unwrap_or_default(), b.pattern.clone().unwrap_or_default()),
+        ("vendor", a.vendor.clone(), b.vendor.clone()),
+        ("model", a.model.clone(), b.model.clone()),
+        ("temperature", a.temperature.to_string(), b.temperature.to_string()),
+        ("top_p", a.top_p.to_string(), b.top_p.to_string()),
+        (
+            "thinking_level",
+            a.thinking_level.map(|l| l.to_string()).unwrap_or_default(),
+            b.thinking_level.map(|l| l.to_string()).unwrap_or_default(),
+        ),
+        ("system_prompt", a.system_prompt.clone(), b.system_prompt.clone()),
+        ("input", a.input.clone(), b.input.clone()),
+    ];
+
+    fields
+        .into_iter()
+        .filter(|(_, a, b)| a != b)
+        .map(|(field, a, b)| ParamDifference { field, a, b })
+        .collect()
+}
+
+pub fn build_compare_data(history: &HistoryState, run_id_a: &str, run_id_b: &str) -> Result<CompareData, String> {
+    let a = history
+        .get(run_id_a)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id_a))?;
+    let b = history
+        .get(run_id_b)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id_b))?;
+
+    let mut diff = Vec::new();
+    let (mut added, mut removed, mut unchanged) = (0, 0, 0);
+    for change in TextDiff::from_lines(&a.output, &b.output).iter_all_changes() {
+        let tag = match change.tag() {
+            ChangeTag::Equal => {
+                unchanged += 1;
+                "equal"
+            }
+            ChangeTag::Insert => {
+                added += 1;
+                "insert"
+            }
+            ChangeTag::Delete => {
+                removed += 1;
+                "delete"
+            }
+        };
+        diff.push(DiffLine {
+            tag,
+            text: change.to_string_lossy().trim_end_matches('\n').to_string(),
+        });
+    }
+
+    Ok(CompareData {
+        param_differences: param_differences(&a, &b),
+        a,
+        b,
+        diff,
+        added,
+        removed,
+        unchanged,
+    })
+}
+
+#[tauri::command]
+pub async fn open_compare_window(
+    app_handle: AppHandle,
+    history: State<'_, HistoryState>,
+    sessions: State<'_, CompareSessions>,
+    run_id_a: String,
+    run_id_b: String,
+) -> Result<String, String> {
+    // Fail before opening a window that would only show an error
+    build_compare_data(&history, &run_id_a, &run_id_b)?;
+
+    let label = (1..)
+        .map(|n| format!("compare-{}", n))
+        .find(|label| app_handle.get_webview_window(label).is_none())
+        .unwrap_or_default();
+
+    sessions.0.lock().unwrap().insert(label.clone(), (run_id_a, run_id_b));
+
+    let window = WebviewWindowBuilder::new(&app_handle, &label, WebviewUrl::App("index.html".into()))
+        .title("Fabric — Compare runs")
+        .inner_size(1400.0, 800.0)
+        .build();
+
+    if let Err(e) = window {
+        sessions.0.lock().unwrap().remove(&label);
+        return Err(e.to_string());
+    }
+
+    Ok(label)
+}
+
+// Called by a compare window on load to fetch the pair it was opened for
+#[tauri::command]
+pub async fn get_compare_data(
+    window: Window,
+    history: State<'_, HistoryState>,
+    sessions: State<'_, CompareSessions>,
+) -> Result<CompareData, String> {
+    let (a, b) = sessions
+        .0
+        .lock()
+        .unwrap()
+        .get(window.label())
+        .cloned()
+        .ok_or_else(|| "This window is not a compare window.".to_string())?;
+
+    build_compare_data(&history, &a, &b)
+}
+
+#[tauri::command]
+pub async fn compare_runs(
+    history: State<'_, HistoryState>,
+    run_id_a: String,
+    run_id_b: String,
+) -> Result<CompareData, String> {
+    build_compare_data(&history, &run_id_a, &run_id_b)
+}
+
+pub fn forget_window(app_handle: &AppHandle, label: &str) {
+    if let Some(sessions) = app_handle.try_state::<CompareSessions>() {
+        sessions.0.lock().unwrap().remove(label);
+    }
+}
@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use crate::patterns::{check_pattern_name, find_pattern_dir};
+use crate::settings::{Settings, SettingsState};
+use crate::setup::fabric_config_dir;
+
+// Which layers make up the system prompt; every part is optional
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ComposeSpec {
+    pub pattern: Option<String>,
+    pub persona: Option<String>,
+    pub context: Option<String>,
+    // Applied in order, e.g. ["cot", "self-refine"]
+    pub strategies: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct PromptSection {
+    // "strategy", "persona", "context" or "pattern"
+    pub kind: &'static str,
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct ComposedPrompt {
+    pub system_prompt: String,
+    pub sections: Vec<PromptSection>,
+}
+
+#[derive(Serialize)]
+pub struct CompositionSources {
+    pub personas: Vec<String>,
+    pub contexts: Vec<String>,
+    pub strategies: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct StrategyFile {
+    prompt: String,
+}
+
+// Personas are app-specific; contexts and strategies are shared with the fabric CLI
+fn personas_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_config_dir()
+        .map(|dir| dir.join("personas"))
+        .map_err(|e| e.to_string())
+}
+
+fn fabric_dir(name: &str) -> Result<PathBuf, String> {
+    fabric_config_dir()
+        .map(|dir| dir.join(name))
+        .ok_or_else(|| "Could not determine the home directory.".to_string())
+}
+
+// Names come from the UI, so anything that could escape the source directory is rejected
+fn source_file(dir: &Path, name: &str, extension: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!("Invalid name '{}'.", name));
+    }
+    let with_extension = dir.join(format!("{}{}", name, extension));
+    if with_extension.exists() {
+        return Ok(with_extension);
+    }
+    Ok(dir.join(name))
+}
+
+fn read_source(path: &Path, kind: &str, name: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|_| format!("Could not find {} '{}'.", kind, name))
+}
+
+fn list_names(dir: &Path, extension: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().is_file())
+                .map(|e| e.file_name().to_string_lossy().trim_end_matches(extension).to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+// Same layering as the fabric CLI: strategies first, then the persona, then the context,
+// with the pattern last so its output instructions have the final word
+pub fn compose(app_handle: &AppHandle, settings: &Settings, spec: &ComposeSpec) -> Result<ComposedPrompt, String> {
+    let mut sections = Vec::new();
+
+    for name in &spec.strategies {
+        let path = source_file(&fabric_dir("strategies")?, name, ".json")?;
+        let strategy: StrategyFile = serde_json::from_str(&read_source(&path, "strategy", name)?)
+            .map_err(|e| format!("Strategy '{}' is not valid: {}", name, e))?;
+        sections.push(PromptSection {
+            kind: "strategy",
+            name: name.clone(),
+            content: strategy.prompt,
+        });
+    }
+
+    if let Some(name) = &spec.persona {
+        let path = source_file(&personas_dir(app_handle)?, name, ".md")?;
+        sections.push(PromptSection {
+            kind: "persona",
+            name: name.clone(),
+            content: read_source(&path, "persona", name)?,
+        });
+    }
+
+    if let Some(name) = &spec.context {
+        let path = source_file(&fabric_dir("contexts")?, name, ".md")?;
+        sections.push(PromptSection {
+            kind: "context",
+            name: name.clone(),
+            content: read_source(&path, "context", name)?,
+        });
+    }
+
+    if let Some(name) = &spec.pattern {
+        check_pattern_name(name)?;
+        let dir = find_pattern_dir(settings, name).ok_or_else(|| format!("Could not find pattern '{}'.", name))?;
+        sections.push(PromptSection {
+            kind: "pattern",
+            name: name.clone(),
+            content: read_source(&dir.join("system.md"), "pattern", name)?,
+        });
+    }
+
+    let system_prompt = sections
+        .iter()
+        .map(|s| s.content.trim())
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    Ok(ComposedPrompt { system_prompt, sections })
+}
+
+#[tauri::command]
+pub async fn preview_composed_prompt(
+    app_handle: AppHandle,
+    state: State<'_, SettingsState>,
+    spec: ComposeSpec,
+) -> Result<ComposedPrompt, String> {
+    compose(&app_handle, &state.get(), &spec)
+}
+
+#[tauri::command]
+pub async fn list_composition_sources(app_handle: AppHandle) -> Result<CompositionSources, String> {
+    Ok(CompositionSources {
+        personas: list_names(&personas_dir(&app_handle)?, ".md"),
+        contexts: list_names(&fabric_dir("contexts")?, ".md"),
+        strategies: list_names(&fabric_dir("strategies")?, ".json"),
+    })
+}
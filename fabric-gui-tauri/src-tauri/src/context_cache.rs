@@ -0,0 +1,102 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::State;
+use crate::http::{self, AuditedSend};
+use crate::i18n::tr_args;
+use crate::residency;
+use crate::settings::SettingsState;
+
+const CACHE_API: &str = "https://generativelanguage.googleapis.com/v1beta";
+const DEFAULT_TTL_SECONDS: u64 = 3600;
+
+#[derive(Serialize)]
+pub struct ContextCache {
+    // Pass this as `cached_content` on later runs with the same model
+    pub name: String,
+    pub model: String,
+    pub expire_time: Option<String>,
+    pub token_count: Option<u64>,
+}
+
+fn google_key(state: &SettingsState, api_key: Option<String>) -> Result<String, String> {
+    api_key
+        .filter(|k| !k.trim().is_empty())
+        .or_else(|| state.get().api_key("google"))
+        .ok_or_else(|| tr_args("health-missing-key", &[("vendor", "google")]))
+}
+
+// Uploads a large input (e.g. a transcript) once so several patterns can run against it
+// while only paying full input price for the first upload. `pattern` is the one the cache is
+// for, so EU-only patterns are refused like their runs would be.
+#[tauri::command]
+pub async fn create_context_cache(
+    state: State<'_, SettingsState>,
+    api_key: Option<String>,
+    model: String,
+    content: String,
+    display_name: Option<String>,
+    ttl_seconds: Option<u64>,
+    pattern: Option<String>,
+) -> Result<ContextCache, String> {
+    residency::check("google", pattern.as_deref())?;
+    let api_key = google_key(&state, api_key)?;
+    let model_path = format!("models/{}", model.trim_start_matches("models/"));
+
+    let mut body = json!({
+        "model": model_path,
+        "contents": [{"role": "user", "parts": [{"text": content}]}],
+        "ttl": format!("{}s", ttl_seconds.unwrap_or(DEFAULT_TTL_SECONDS)),
+    });
+    if let Some(name) = display_name {
+        body["displayName"] = json!(name);
+    }
+
+    let res = http::client_for(CACHE_API)?
+        .post(format!("{}/cachedContents?key={}", CACHE_API, api_key))
+        .json(&body)
+        .send_audited("context_cache", None)
+        .await
+        .map_err(http::network_error)?;
+
+    let status = res.status();
+    let text = res.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(tr_args(
+            "api-error",
+            &[("status", &status.to_string()), ("details", &text.chars().take(300).collect::<String>())],
+        ));
+    }
+
+    let json: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    Ok(ContextCache {
+        name: json["name"].as_str().unwrap_or_default().to_string(),
+        model,
+        expire_time: json["expireTime"].as_str().map(String::from),
+        token_count: json["usageMetadata"]["totalTokenCount"].as_u64(),
+    })
+}
+
+// Caches are billed for storage until they expire, so the UI drops them when the user is done
+#[tauri::command]
+pub async fn delete_context_cache(
+    state: State<'_, SettingsState>,
+    api_key: Option<String>,
+    name: String,
+) -> Result<(), String> {
+    let api_key = google_key(&state, api_key)?;
+    let res = http::client_for(CACHE_API)?
+        .delete(format!("{}/{}?key={}", CACHE_API, name, api_key))
+        .send_audited("context_cache", None)
+        .await
+        .map_err(http::network_error)?;
+
+    let status = res.status();
+    if !status.is_success() {
+        let text = res.text().await.unwrap_or_default();
+        return Err(tr_args(
+            "api-error",
+            &[("status", &status.to_string()), ("details", &text.chars().take(300).collect::<String>())],
+        ));
+    }
+    Ok(())
+}
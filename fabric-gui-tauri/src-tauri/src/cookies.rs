@@ -0,0 +1,247 @@
+use rusqlite::{params, Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use home::home_dir;
+use reqwest::Url;
+use uuid::Uuid;
+use crate::history::now_secs;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredCookie {
+    // The domain the user allowed this cookie for; removing it removes the cookie
+    scope: String,
+    // Cookie domain as set by the site; a leading dot also matches subdomains
+    domain: String,
+    path: String,
+    secure: bool,
+    // Unix seconds, 0 for session cookies
+    expires: i64,
+    name: String,
+    value: String,
+}
+
+#[derive(Default)]
+struct CookieJar {
+    path: Option<PathBuf>,
+    cookies: Vec<StoredCookie>,
+}
+
+#[derive(Serialize)]
+pub struct CookieDomain {
+    pub domain: String,
+    pub cookies: usize,
+}
+
+fn jar() -> &'static RwLock<CookieJar> {
+    static JAR: OnceLock<RwLock<CookieJar>> = OnceLock::new();
+    JAR.get_or_init(|| RwLock::new(CookieJar::default()))
+}
+
+// Called once at startup with the file imported cookies are kept in
+pub fn load(path: PathBuf) {
+    let cookies = fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    *jar().write().unwrap() = CookieJar { path: Some(path), cookies };
+}
+
+fn save(jar: &CookieJar) -> Result<(), String> {
+    let Some(path) = &jar.path else {
+        return Ok(());
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(&jar.cookies).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    let domain = domain.trim_start_matches('.').to_lowercase();
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+fn normalize_scope(domain: &str) -> String {
+    domain.trim().trim_start_matches('.').to_lowercase()
+}
+
+// The Cookie header for a request to `url`, from cookies whose scope and own domain both
+// cover the host; secure cookies only go over HTTPS
+pub fn header_for(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    let now = now_secs();
+    let jar = jar().read().unwrap();
+    let pairs: Vec<String> = jar
+        .cookies
+        .iter()
+        .filter(|c| domain_matches(&host, &c.scope) && domain_matches(&host, &c.domain))
+        .filter(|c| url.path().starts_with(&c.path))
+        .filter(|c| !c.secure || url.scheme() == "https")
+        .filter(|c| c.expires == 0 || c.expires > now)
+        .map(|c| format!("{}={}", c.name, c.value))
+        .collect();
+    (!pairs.is_empty()).then(|| pairs.join("; "))
+}
+
+// Replaces the cookies of the given scopes with `cookies` that fall under one of them
+fn store(scopes: &[String], cookies: Vec<StoredCookie>) -> Result<Vec<CookieDomain>, String> {
+    let mut jar = jar().write().unwrap();
+    jar.cookies.retain(|c| !scopes.contains(&c.scope));
+    jar.cookies.extend(cookies);
+    save(&jar)?;
+    Ok(domains(&jar))
+}
+
+fn domains(jar: &CookieJar) -> Vec<CookieDomain> {
+    let mut scopes: Vec<String> = jar.cookies.iter().map(|c| c.scope.clone()).collect();
+    scopes.sort();
+    scopes.dedup();
+    scopes
+        .into_iter()
+        .map(|domain| CookieDomain {
+            cookies: jar.cookies.iter().filter(|c| c.scope == domain).count(),
+            domain,
+        })
+        .collect()
+}
+
+fn scope_for(cookie_domain: &str, scopes: &[String]) -> Option<String> {
+    let host = normalize_scope(cookie_domain);
+    scopes.iter().find(|scope| domain_matches(&host, scope)).cloned()
+}
+
+fn check_scopes(domains: Vec<String>) -> Result<Vec<String>, String> {
+    let scopes: Vec<String> = domains.iter().map(|d| normalize_scope(d)).filter(|d| !d.is_empty()).collect();
+    if scopes.is_empty() {
+        return Err("Choose at least one domain the cookies may be used for.".to_string());
+    }
+    Ok(scopes)
+}
+
+// Netscape cookies.txt, as written by browser extensions and curl: seven tab-separated
+// fields per line; "#HttpOnly_" prefixed lines are cookies too
+fn parse_cookies_txt(text: &str, scopes: &[String]) -> Vec<StoredCookie> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+            if line.starts_with('#') || line.trim().is_empty() {
+                return None;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            Some(StoredCookie {
+                scope: scope_for(fields[0], scopes)?,
+                domain: fields[0].to_string(),
+                path: fields[2].to_string(),
+                secure: fields[3].eq_ignore_ascii_case("TRUE"),
+                expires: fields[4].parse().unwrap_or(0),
+                name: fields[5].to_string(),
+                value: fields[6].trim_end_matches('\r').to_string(),
+            })
+        })
+        .collect()
+}
+
+fn firefox_profiles_dir() -> Option<PathBuf> {
+    let home = home_dir()?;
+    [
+        home.join("AppData").join("Roaming").join("Mozilla").join("Firefox").join("Profiles"),
+        home.join("Library").join("Application Support").join("Firefox").join("Profiles"),
+        home.join(".mozilla").join("firefox"),
+    ]
+    .into_iter()
+    .find(|p| p.is_dir())
+}
+
+// The most recently used profile is the one whose cookie database changed last
+fn firefox_cookie_db() -> Option<PathBuf> {
+    fs::read_dir(firefox_profiles_dir()?)
+        .ok()?
+        .flatten()
+        .map(|e| e.path().join("cookies.sqlite"))
+        .filter(|p| p.is_file())
+        .max_by_key(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+}
+
+fn read_firefox_cookies(db: &Path, scopes: &[String]) -> Result<Vec<StoredCookie>, String> {
+    // Firefox keeps the database locked while running, so a copy is read instead
+    let copy = std::env::temp_dir().join(format!("fabric-cookies-{}.sqlite", Uuid::new_v4()));
+    fs::copy(db, &copy).map_err(|e| e.to_string())?;
+    let result = (|| {
+        let conn = Connection::open_with_flags(&copy, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT host, path, isSecure, expiry, name, value FROM moz_cookies")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        Ok::<_, String>(
+            rows.flatten()
+                .filter_map(|(host, path, secure, expiry, name, value)| {
+                    Some(StoredCookie {
+                        scope: scope_for(&host, scopes)?,
+                        domain: host,
+                        path,
+                        secure: secure != 0,
+                        // Firefox stores milliseconds in newer versions
+                        expires: if expiry > 100_000_000_000 { expiry / 1000 } else { expiry },
+                        name,
+                        value,
+                    })
+                })
+                .collect(),
+        )
+    })();
+    let _ = fs::remove_file(&copy);
+    result
+}
+
+// Only cookies under `domains` are imported, and they are only ever sent to those domains
+#[tauri::command]
+pub async fn import_cookies_txt(path: String, domains: Vec<String>) -> Result<Vec<CookieDomain>, String> {
+    let scopes = check_scopes(domains)?;
+    let text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let cookies = parse_cookies_txt(&text, &scopes);
+    if cookies.is_empty() {
+        return Err("The file has no cookies for the chosen domains.".to_string());
+    }
+    store(&scopes, cookies)
+}
+
+// Chrome and Edge encrypt their cookie stores with OS-specific keys, so only Firefox can
+// be imported from directly; export a cookies.txt from other browsers instead
+#[tauri::command]
+pub async fn import_firefox_cookies(domains: Vec<String>) -> Result<Vec<CookieDomain>, String> {
+    let scopes = check_scopes(domains)?;
+    let db = firefox_cookie_db().ok_or("No Firefox profile with cookies was found.")?;
+    let cookies = read_firefox_cookies(&db, &scopes)?;
+    if cookies.is_empty() {
+        return Err("Firefox has no cookies for the chosen domains.".to_string());
+    }
+    store(&scopes, cookies)
+}
+
+#[tauri::command]
+pub async fn list_cookie_domains() -> Result<Vec<CookieDomain>, String> {
+    Ok(domains(&jar().read().unwrap()))
+}
+
+#[tauri::command]
+pub async fn remove_cookie_domain(domain: String) -> Result<Vec<CookieDomain>, String> {
+    store(&[normalize_scope(&domain)], Vec::new())
+}
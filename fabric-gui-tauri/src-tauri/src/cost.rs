@@ -0,0 +1,226 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use tauri::{AppHandle, Manager};
+use crate::ai_client::{self, AIRequest};
+use crate::docs::estimate_tokens;
+use crate::models::ModelRegistryState;
+use crate::pipeline_run::{classifier_request, step_request};
+use crate::pipelines::{self, PipelineStep};
+use crate::settings::{Settings, SettingsState};
+
+// Output length can't be known before a run. Most patterns condense their input, so a quarter
+// of it is assumed, at least MIN_OUTPUT_TOKENS and at most the run's output cap.
+const MIN_OUTPUT_TOKENS: usize = 300;
+const CLASSIFIER_OUTPUT_TOKENS: usize = 10;
+// OpenAI's Batch API bills half the regular price
+const BATCH_API_DISCOUNT: f64 = 0.5;
+
+#[derive(Serialize)]
+pub struct CostLine {
+    // The pattern, "classifier", or the batch item
+    pub label: String,
+    pub vendor: String,
+    pub model: String,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    // None when the capability registry has no price for the model
+    pub cost_usd: Option<f64>,
+    // Only runs when a branch picks it
+    pub conditional: bool,
+}
+
+#[derive(Serialize, Default)]
+pub struct CostEstimate {
+    pub lines: Vec<CostLine>,
+    // Totals follow the most expensive branch where a pipeline branches
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    pub total_cost_usd: f64,
+    // Left out of the total because the registry has no price for them
+    pub unpriced_models: BTreeSet<String>,
+    pub notes: Vec<String>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Totals {
+    input: usize,
+    output: usize,
+    cost: f64,
+}
+
+impl Totals {
+    fn add(&mut self, other: Totals) {
+        self.input += other.input;
+        self.output += other.output;
+        self.cost += other.cost;
+    }
+}
+
+struct Estimator<'a> {
+    app_handle: &'a AppHandle,
+    settings: Settings,
+    base: &'a AIRequest,
+    estimate: CostEstimate,
+}
+
+impl Estimator<'_> {
+    // Prices one run whose variable input is `text_tokens` long; returns its output tokens
+    fn line(
+        &mut self,
+        mut request: AIRequest,
+        label: &str,
+        text_tokens: usize,
+        output: Option<usize>,
+        conditional: bool,
+    ) -> Result<(Totals, usize), String> {
+        // Composing with an empty input gives the system prompt the run would send
+        request.user_input = String::new();
+        ai_client::apply_composition(self.app_handle, &self.settings, &mut request)?;
+        let model = self.app_handle.state::<ModelRegistryState>().find(&request.model);
+        let input_tokens = estimate_tokens(&request.system_prompt) + text_tokens;
+        let cap = request
+            .max_tokens
+            .map(|t| t as usize)
+            .or(model.as_ref().map(|m| m.max_output_tokens as usize))
+            .unwrap_or(usize::MAX);
+        let output_tokens = output.unwrap_or((text_tokens / 4).max(MIN_OUTPUT_TOKENS)).min(cap);
+        let cost_usd = model.as_ref().map(|m| {
+            (input_tokens as f64 * m.input_price_per_mtok + output_tokens as f64 * m.output_price_per_mtok) / 1_000_000.0
+        });
+        if cost_usd.is_none() {
+            self.estimate.unpriced_models.insert(request.model.clone());
+        }
+        self.estimate.lines.push(CostLine {
+            label: label.to_string(),
+            vendor: request.vendor,
+            model: request.model,
+            input_tokens,
+            output_tokens,
+            cost_usd,
+            conditional,
+        });
+        let totals = Totals { input: input_tokens, output: output_tokens, cost: cost_usd.unwrap_or(0.0) };
+        Ok((totals, output_tokens))
+    }
+
+    // Walks the steps like the pipeline runner does, each step's input being the one before's
+    // estimated output; returns the totals and the final output tokens
+    fn steps(&mut self, steps: &[PipelineStep], mut text_tokens: usize, conditional: bool) -> Result<(Totals, usize), String> {
+        let mut totals = Totals::default();
+        for step in steps {
+            let (step_totals, output) = self.step(step, text_tokens, conditional)?;
+            totals.add(step_totals);
+            text_tokens = output;
+        }
+        Ok((totals, text_tokens))
+    }
+
+    fn step(&mut self, step: &PipelineStep, text_tokens: usize, conditional: bool) -> Result<(Totals, usize), String> {
+        if let Some(pattern) = &step.pattern {
+            let request = step_request(self.base, step, pattern, "", None);
+            return self.line(request, pattern, text_tokens, None, conditional);
+        }
+
+        if let Some(fan_out) = &step.fan_out {
+            let mut totals = Totals::default();
+            let mut combined = 0;
+            for branch in &fan_out.branches {
+                let (branch_totals, output) = self.steps(std::slice::from_ref(branch), text_tokens, conditional)?;
+                totals.add(branch_totals);
+                combined += output;
+            }
+            let Some(merge) = &fan_out.merge_pattern else {
+                return Ok((totals, combined));
+            };
+            let (merge_totals, output) = self.line(step_request(self.base, step, merge, "", None), merge, combined, None, conditional)?;
+            totals.add(merge_totals);
+            return Ok((totals, output));
+        }
+
+        let Some(branch) = &step.branch else {
+            return Ok((Totals::default(), text_tokens));
+        };
+        let mut totals = Totals::default();
+        let mut alternatives: Vec<&[PipelineStep]> = vec![&branch.then, &branch.otherwise];
+        if let Some(classifier) = &branch.classify {
+            let labels: Vec<String> = branch.cases.keys().cloned().collect();
+            let request = classifier_request(self.base, classifier, &labels, "");
+            (totals, _) = self.line(request, "classifier", text_tokens, Some(CLASSIFIER_OUTPUT_TOKENS), conditional)?;
+            alternatives.extend(branch.cases.values().map(|steps| steps.as_slice()));
+        }
+        // Every alternative is listed; the totals count the most expensive one
+        let mut worst: Option<(Totals, usize)> = None;
+        for steps in alternatives {
+            let (alt_totals, output) = self.steps(steps, text_tokens, true)?;
+            if worst.is_none_or(|(w, _)| alt_totals.cost > w.cost) {
+                worst = Some((alt_totals, output));
+            }
+        }
+        let (worst, output) = worst.unwrap_or((Totals::default(), text_tokens));
+        totals.add(worst);
+        Ok((totals, output))
+    }
+
+    fn finish(mut self, totals: Totals) -> CostEstimate {
+        self.estimate.input_tokens = totals.input;
+        self.estimate.output_tokens = totals.output;
+        self.estimate.total_cost_usd = totals.cost;
+        self.estimate.notes.push(format!(
+            "Output lengths are guesses: a quarter of each input, at least {} tokens.",
+            MIN_OUTPUT_TOKENS
+        ));
+        if !self.estimate.unpriced_models.is_empty() {
+            self.estimate.notes.push("Models without registry prices are left out of the total.".to_string());
+        }
+        self.estimate
+    }
+}
+
+// Estimated tokens and cost of running a saved pipeline on `request.user_input`, step by step,
+// for a confirmation dialog before run_pipeline
+#[tauri::command]
+pub async fn estimate_pipeline_cost(app_handle: AppHandle, name: String, request: AIRequest) -> Result<CostEstimate, String> {
+    let pipeline = pipelines::load(&app_handle, &name)?;
+    let mut estimator = Estimator {
+        app_handle: &app_handle,
+        settings: app_handle.state::<SettingsState>().get(),
+        base: &request,
+        estimate: CostEstimate::default(),
+    };
+    let (totals, _) = estimator.steps(&pipeline.steps, estimate_tokens(&request.user_input), false)?;
+    if estimator.estimate.lines.iter().any(|l| l.conditional) {
+        estimator.estimate.notes.push("Branches run only one of their alternatives; all of them are listed.".to_string());
+    }
+    Ok(estimator.finish(totals))
+}
+
+// Estimated tokens and cost of a batch, one line per request. `batch_api` prices the requests
+// at the OpenAI Batch API discount.
+#[tauri::command]
+pub async fn estimate_batch_cost(
+    app_handle: AppHandle,
+    requests: Vec<AIRequest>,
+    batch_api: Option<bool>,
+) -> Result<CostEstimate, String> {
+    let base = AIRequest::default();
+    let mut estimator = Estimator {
+        app_handle: &app_handle,
+        settings: app_handle.state::<SettingsState>().get(),
+        base: &base,
+        estimate: CostEstimate::default(),
+    };
+    let mut totals = Totals::default();
+    for (index, request) in requests.into_iter().enumerate() {
+        let label = request.pattern.clone().unwrap_or_else(|| format!("Item {}", index + 1));
+        let text_tokens = estimate_tokens(&request.user_input);
+        let (line, _) = estimator.line(request, &label, text_tokens, None, false)?;
+        totals.add(line);
+    }
+    if batch_api.unwrap_or(false) {
+        totals.cost *= BATCH_API_DISCOUNT;
+        for line in &mut estimator.estimate.lines {
+            line.cost_usd = line.cost_usd.map(|c| c * BATCH_API_DISCOUNT);
+        }
+    }
+    Ok(estimator.finish(totals))
+}
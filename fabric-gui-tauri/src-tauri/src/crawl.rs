@@ -0,0 +1,258 @@
+use regex::Regex;
+use reqwest::Url;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use uuid::Uuid;
+use crate::extract;
+use crate::history::now_secs;
+use crate::input::{self, TruncationInfo};
+use crate::settings::SettingsState;
+
+// Hard cap whatever max_pages asks for
+const MAX_CRAWL_PAGES: usize = 500;
+// Nested sitemaps read from a sitemap index
+const MAX_SITEMAPS: usize = 20;
+// Pause between page requests so a crawl doesn't hammer the site
+const CRAWL_DELAY: Duration = Duration::from_millis(300);
+// Links to these are never pages worth extracting
+const SKIPPED_EXTENSIONS: &[&str] = &[
+    ".pdf", ".zip", ".gz", ".png", ".jpg", ".jpeg", ".gif", ".svg", ".webp", ".mp3", ".mp4", ".css", ".js", ".xml",
+];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CorpusPage {
+    pub url: String,
+    pub title: Option<String>,
+    pub markdown: String,
+    pub word_count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Corpus {
+    pub id: String,
+    pub root: String,
+    pub created_at: i64,
+    // "sitemap" or "links"
+    pub source: String,
+    pub pages: Vec<CorpusPage>,
+    // URLs that failed to load, with the reason
+    pub failed: Vec<(String, String)>,
+    // Every page under a "# <url>" heading, ready to send to a pattern, cut to the scrape
+    // input limit; the pages themselves are kept whole
+    pub text: String,
+    pub truncation: Option<TruncationInfo>,
+}
+
+fn corpora_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle.path().app_data_dir().map_err(|e| e.to_string())?.join("corpora");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+// "*" matches anything, so "/docs/*" keeps the docs section; a pattern without a wildcard
+// matches anywhere in the URL
+fn include_regexes(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| {
+            let p = p.trim();
+            let escaped = regex::escape(p).replace(r"\*", ".*");
+            let anchored = if p.contains('*') { format!("{}$", escaped) } else { escaped };
+            Regex::new(&anchored).map_err(|e| format!("Invalid include pattern '{}': {}", p, e))
+        })
+        .collect()
+}
+
+fn included(url: &Url, includes: &[Regex]) -> bool {
+    let target = format!("{}{}", url.path(), url.query().map(|q| format!("?{}", q)).unwrap_or_default());
+    includes.is_empty() || includes.iter().any(|r| r.is_match(&target) || r.is_match(url.as_str()))
+}
+
+fn same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme() && a.host_str() == b.host_str() && a.port_or_known_default() == b.port_or_known_default()
+}
+
+fn crawlable(url: &Url, root: &Url, disallowed: &[String]) -> bool {
+    let path = url.path().to_lowercase();
+    same_origin(url, root)
+        && !SKIPPED_EXTENSIONS.iter().any(|ext| path.ends_with(ext))
+        && !disallowed.iter().any(|prefix| url.path().starts_with(prefix.as_str()))
+}
+
+// Disallow rules of robots.txt groups that apply to every crawler
+async fn disallowed_paths(root: &Url) -> Vec<String> {
+    let Ok(robots) = root.join("/robots.txt") else {
+        return Vec::new();
+    };
+    let Ok(text) = extract::fetch_html(robots.as_str()).await else {
+        return Vec::new();
+    };
+    let mut applies = false;
+    let mut paths = Vec::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_lowercase().as_str() {
+            "user-agent" => applies = value == "*",
+            "disallow" if applies && !value.is_empty() => paths.push(value.trim_end_matches('*').to_string()),
+            _ => {}
+        }
+    }
+    paths
+}
+
+fn sitemap_locations(xml: &str) -> Vec<String> {
+    let loc = Regex::new(r"(?is)<loc>\s*(.*?)\s*</loc>").unwrap();
+    loc.captures_iter(xml)
+        .map(|c| c[1].replace("&amp;", "&").trim_start_matches("<![CDATA[").trim_end_matches("]]>").to_string())
+        .collect()
+}
+
+// Page URLs from the site's sitemap, following one level of sitemap index
+async fn sitemap_urls(root: &Url) -> Vec<String> {
+    let sitemap = if root.path().ends_with(".xml") { root.clone() } else { root.join("/sitemap.xml").unwrap() };
+    let Ok(xml) = extract::fetch_html(sitemap.as_str()).await else {
+        return Vec::new();
+    };
+    if !xml.contains("<sitemapindex") {
+        return sitemap_locations(&xml);
+    }
+    let mut urls = Vec::new();
+    for nested in sitemap_locations(&xml).into_iter().take(MAX_SITEMAPS) {
+        if let Ok(xml) = extract::fetch_html(&nested).await {
+            urls.extend(sitemap_locations(&xml));
+        }
+    }
+    urls
+}
+
+fn page_links(html: &str, base: &Url) -> Vec<Url> {
+    let document = Html::parse_document(html);
+    document
+        .select(&Selector::parse("a[href]").unwrap())
+        .filter_map(|a| base.join(a.value().attr("href")?).ok())
+        .map(|mut url| {
+            url.set_fragment(None);
+            url
+        })
+        .collect()
+}
+
+fn page_title(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let title = document.select(&Selector::parse("title").unwrap()).next()?;
+    let title = title.text().collect::<String>().trim().to_string();
+    (!title.is_empty()).then_some(title)
+}
+
+// Crawls up to `max_pages` pages of a site, taken from its sitemap when it has one and
+// otherwise found by following same-origin links from `url`. Only pages whose path matches
+// one of `include_patterns` are kept (all pages when empty); robots.txt disallow rules are
+// honoured. Emits "crawl-progress" per page and saves the corpus under app data.
+#[tauri::command]
+pub async fn crawl_site(
+    app_handle: AppHandle,
+    url: String,
+    max_pages: Option<usize>,
+    include_patterns: Option<Vec<String>>,
+) -> Result<Corpus, String> {
+    let root = Url::parse(url.trim()).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    if !matches!(root.scheme(), "http" | "https") {
+        return Err("Only http and https sites can be crawled.".to_string());
+    }
+    let max_pages = max_pages.unwrap_or(50).clamp(1, MAX_CRAWL_PAGES);
+    let includes = include_regexes(&include_patterns.unwrap_or_default())?;
+    let disallowed = disallowed_paths(&root).await;
+    let id = Uuid::new_v4().to_string();
+
+    let from_sitemap: Vec<Url> = sitemap_urls(&root)
+        .await
+        .iter()
+        .filter_map(|u| Url::parse(u).ok())
+        .filter(|u| crawlable(u, &root, &disallowed) && included(u, &includes))
+        .collect();
+    let source = if from_sitemap.is_empty() { "links" } else { "sitemap" };
+
+    let mut queue: VecDeque<Url> = if from_sitemap.is_empty() { VecDeque::from([root.clone()]) } else { from_sitemap.into() };
+    let mut seen: HashSet<String> = queue.iter().map(|u| u.to_string()).collect();
+    let mut pages = Vec::new();
+    let mut failed = Vec::new();
+    // Link crawls also visit pages outside the include patterns to reach matching ones
+    let mut visited = 0;
+
+    while let Some(page_url) = queue.pop_front() {
+        if pages.len() >= max_pages || visited >= max_pages * 5 {
+            break;
+        }
+        if visited > 0 {
+            tokio::time::sleep(CRAWL_DELAY).await;
+        }
+        visited += 1;
+
+        let html = match extract::fetch_html(page_url.as_str()).await {
+            Ok(html) => html,
+            Err(e) => {
+                failed.push((page_url.to_string(), e));
+                continue;
+            }
+        };
+        if source == "links" {
+            for link in page_links(&html, &page_url) {
+                if crawlable(&link, &root, &disallowed) && seen.insert(link.to_string()) {
+                    queue.push_back(link);
+                }
+            }
+            if !included(&page_url, &includes) {
+                continue;
+            }
+        }
+
+        let result = extract::extract_html(&html, "auto");
+        if result.word_count == 0 {
+            continue;
+        }
+        pages.push(CorpusPage {
+            url: page_url.to_string(),
+            title: page_title(&html),
+            markdown: result.markdown,
+            word_count: result.word_count,
+        });
+        let _ = app_handle.emit(
+            "crawl-progress",
+            json!({"id": id, "url": page_url.as_str(), "pages": pages.len(), "max_pages": max_pages}),
+        );
+    }
+
+    if pages.is_empty() {
+        return Err(format!("No pages with readable content were found on {}.", root));
+    }
+    let text = pages
+        .iter()
+        .map(|p| format!("# {}\n\n{}", p.url, p.markdown))
+        .collect::<Vec<_>>()
+        .join("\n\n---\n\n");
+    let (text, truncation) = input::limit(&app_handle.state::<SettingsState>().get(), "scrape", text);
+    let corpus = Corpus {
+        id,
+        root: root.to_string(),
+        created_at: now_secs(),
+        source: source.to_string(),
+        pages,
+        failed,
+        text,
+        truncation,
+    };
+    let json = serde_json::to_string(&corpus).map_err(|e| e.to_string())?;
+    fs::write(corpora_dir(&app_handle)?.join(format!("{}.json", corpus.id)), json).map_err(|e| e.to_string())?;
+    Ok(corpus)
+}
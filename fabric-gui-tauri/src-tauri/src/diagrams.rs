@@ -0,0 +1,110 @@
+use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::process::Command;
+use crate::history::HistoryState;
+
+const RENDER_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Serialize)]
+pub struct Diagram {
+    pub index: usize,
+    // "mermaid" or "graphviz"
+    pub kind: String,
+    pub source: String,
+    // Rendered image, absent when rendering failed
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
+// Fenced ```mermaid and ```dot / ```graphviz blocks, in output order
+fn diagram_blocks(output: &str) -> Vec<(String, String)> {
+    let fence = Regex::new(r"(?ms)^\s*```\s*(mermaid|dot|graphviz|gv)\s*\n(.*?)^\s*```").unwrap();
+    fence
+        .captures_iter(output)
+        .map(|c| {
+            let kind = if &c[1] == "mermaid" { "mermaid" } else { "graphviz" };
+            (kind.to_string(), c[2].trim().to_string())
+        })
+        .filter(|(_, source)| !source.is_empty())
+        .collect()
+}
+
+fn diagrams_dir(app_handle: &AppHandle, run_id: &str) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("diagrams")
+        .join(run_id);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+// mermaid-cli (npm i -g @mermaid-js/mermaid-cli) and Graphviz's dot, both from the PATH
+async fn render(kind: &str, source: &Path, target: &Path, format: &str) -> Result<(), String> {
+    let (program, mut command) = if kind == "mermaid" {
+        // npm installs a .cmd shim on Windows, which Command doesn't resolve by itself
+        let program = if cfg!(windows) { "mmdc.cmd" } else { "mmdc" };
+        let mut command = Command::new(program);
+        command.arg("-i").arg(source).arg("-o").arg(target).arg("-b").arg("white");
+        (program, command)
+    } else {
+        let mut command = Command::new("dot");
+        command.arg(format!("-T{}", format)).arg(source).arg("-o").arg(target);
+        ("dot", command)
+    };
+    let output = tokio::time::timeout(RENDER_TIMEOUT, command.kill_on_drop(true).output())
+        .await
+        .map_err(|_| "Rendering the diagram timed out.".to_string())?
+        .map_err(|_| match kind {
+            "mermaid" => format!("Rendering Mermaid needs mermaid-cli ('{}') on the PATH.", program),
+            _ => "Rendering Graphviz needs Graphviz ('dot') on the PATH.".to_string(),
+        })?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+// Renders every Mermaid and Graphviz block in a run's output to SVG (default) or PNG under
+// app data; a block that fails to render carries its error so the rest still display
+#[tauri::command]
+pub async fn render_diagrams(
+    app_handle: AppHandle,
+    history: State<'_, HistoryState>,
+    run_id: String,
+    format: Option<String>,
+) -> Result<Vec<Diagram>, String> {
+    let format = format.unwrap_or_else(|| "svg".to_string()).to_lowercase();
+    if format != "svg" && format != "png" {
+        return Err("Diagrams render to svg or png.".to_string());
+    }
+    let entry = history
+        .get(&run_id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))?;
+    let blocks = diagram_blocks(&entry.output);
+    if blocks.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let dir = diagrams_dir(&app_handle, &run_id)?;
+    let mut diagrams = Vec::new();
+    for (index, (kind, source)) in blocks.into_iter().enumerate() {
+        let source_path = dir.join(format!("{}.{}", index + 1, if kind == "mermaid" { "mmd" } else { "dot" }));
+        let target = dir.join(format!("{}.{}", index + 1, format));
+        fs::write(&source_path, &source).map_err(|e| e.to_string())?;
+        let result = render(&kind, &source_path, &target, &format).await;
+        diagrams.push(Diagram {
+            index,
+            kind,
+            source,
+            path: result.as_ref().ok().map(|_| target.to_string_lossy().to_string()),
+            error: result.err(),
+        });
+    }
+    Ok(diagrams)
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use tokio::process::Command;
+use crate::input::{self, TruncationInfo};
+use crate::settings::{Settings, SettingsState};
+use crate::transcribe::{self, Segment};
+
+const DIARIZE_SCRIPT: &str = "diarize.py";
+
+#[derive(Deserialize)]
+struct Turn {
+    start: f64,
+    end: f64,
+    speaker: String,
+}
+
+#[derive(Serialize)]
+pub struct Transcript {
+    pub text: String,
+    pub segments: Vec<Segment>,
+    pub speakers: usize,
+    // Set by transcribe_audio when the text was cut to the transcript input limit; the
+    // segments are kept whole
+    pub truncation: Option<TruncationInfo>,
+}
+
+fn script_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let bundled = app_handle
+        .path()
+        .resource_dir()
+        .map_err(|e| e.to_string())?
+        .join("resources")
+        .join(DIARIZE_SCRIPT);
+    // Running from the source tree in development
+    [bundled.clone(), PathBuf::from("resources").join(DIARIZE_SCRIPT), PathBuf::from("src-tauri").join("resources").join(DIARIZE_SCRIPT)]
+        .into_iter()
+        .find(|p| p.is_file())
+        .ok_or_else(|| format!("Diarization script not found at {}", bundled.display()))
+}
+
+fn python(settings: &Settings) -> String {
+    settings
+        .python_command
+        .clone()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "py" } else { "python3" }.to_string())
+}
+
+// Speaker turns from the pyannote sidecar, which needs a Hugging Face token for its model
+async fn speaker_turns(app_handle: &AppHandle, settings: &Settings, audio: &Path, speakers: Option<u32>) -> Result<Vec<Turn>, String> {
+    let token = settings
+        .api_key("huggingface")
+        .ok_or("Diarization uses a pyannote model from Hugging Face; add a Hugging Face API key in settings.")?;
+    let program = python(settings);
+    let mut command = Command::new(&program);
+    command.arg(script_path(app_handle)?).arg("--audio").arg(audio).env("HF_TOKEN", token);
+    if let Some(speakers) = speakers {
+        command.arg("--speakers").arg(speakers.to_string());
+    }
+    let output = command
+        .output()
+        .await
+        .map_err(|_| format!("Diarization requires Python ('{}') on the PATH.", program))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("Unexpected diarization output: {}", e))
+}
+
+// Each segment takes the speaker it overlaps most; pyannote's SPEAKER_00 style labels become
+// "Speaker 1", "Speaker 2", ... in order of first appearance
+fn label(segments: &mut [Segment], turns: &[Turn]) -> usize {
+    let mut names: HashMap<&str, String> = HashMap::new();
+    for segment in segments.iter_mut() {
+        let best = turns
+            .iter()
+            .map(|t| (t, segment.end.min(t.end) - segment.start.max(t.start)))
+            .filter(|(_, overlap)| *overlap > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((turn, _)) = best {
+            let next = names.len() + 1;
+            let name = names.entry(turn.speaker.as_str()).or_insert_with(|| format!("Speaker {}", next));
+            segment.speaker = Some(name.clone());
+        }
+    }
+    names.len()
+}
+
+pub async fn transcribe_diarized(
+    app_handle: &AppHandle,
+    settings: &Settings,
+    audio: &Path,
+    engine: &str,
+    speakers: Option<u32>,
+) -> Result<Transcript, String> {
+    let mut segments = transcribe::transcribe_segments(settings, audio, engine).await?;
+    let turns = speaker_turns(app_handle, settings, audio, speakers).await?;
+    let speakers = label(&mut segments, &turns);
+    Ok(Transcript {
+        text: transcribe::segments_text(&segments),
+        segments,
+        speakers,
+        truncation: None,
+    })
+}
+
+// Transcribes a recording with segment timings; with `diarize` each segment also gets a
+// speaker label, which patterns like extract_meeting_action_items rely on to assign owners
+#[tauri::command]
+pub async fn transcribe_audio(
+    app_handle: AppHandle,
+    state: State<'_, SettingsState>,
+    path: String,
+    engine: Option<String>,
+    diarize: Option<bool>,
+    speakers: Option<u32>,
+) -> Result<Transcript, String> {
+    let settings = state.get();
+    let engine = engine.as_deref().unwrap_or("local");
+    let transcript = if diarize.unwrap_or(false) {
+        transcribe_diarized(&app_handle, &settings, Path::new(&path), engine, speakers).await?
+    } else {
+        let segments = transcribe::transcribe_segments(&settings, Path::new(&path), engine).await?;
+        Transcript {
+            text: transcribe::segments_text(&segments),
+            segments,
+            speakers: 0,
+            truncation: None,
+        }
+    };
+    let (text, truncation) = input::limit(&settings, "transcript", transcript.text);
+    Ok(Transcript { text, truncation, ..transcript })
+}
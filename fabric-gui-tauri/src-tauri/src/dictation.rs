@@ -0,0 +1,196 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tauri::async_runtime::Mutex as AsyncMutex;
+use tauri::{Emitter, Manager, State, Window};
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::settings::SettingsState;
+use crate::transcribe::{self, Segment};
+
+// Seconds of audio collected before an interim transcription; shorter windows come back
+// sooner but give Whisper less context
+const WINDOW_SECS: f64 = 6.0;
+// Windows are cut at the quietest point of their last stretch so words aren't split
+const CUT_SEARCH_SECS: f64 = 1.5;
+const FRAME_SECS: f64 = 0.1;
+
+struct Session {
+    sample_rate: u32,
+    engine: String,
+    pending: Vec<i16>,
+    // Start of `pending` in seconds from the start of the recording
+    offset: f64,
+    segments: Vec<Segment>,
+}
+
+// Recordings in progress by session id; each session is locked for the whole of a push so
+// windows are transcribed in order
+#[derive(Default)]
+pub struct DictationSessions(Mutex<HashMap<String, Arc<AsyncMutex<Session>>>>);
+
+#[derive(Serialize)]
+pub struct DictationResult {
+    pub transcript: String,
+    pub segments: Vec<Segment>,
+    // Set when a pattern was run on the transcript
+    pub run_id: Option<String>,
+    pub output: Option<String>,
+}
+
+impl DictationSessions {
+    fn get(&self, session_id: &str) -> Result<Arc<AsyncMutex<Session>>, String> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .cloned()
+            .ok_or_else(|| "The dictation session has ended.".to_string())
+    }
+}
+
+// 16-bit mono PCM in a WAV container, which every transcription engine accepts
+fn wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+// Index to cut the pending audio at: the start of the quietest frame near the window's end
+fn cut_point(samples: &[i16], sample_rate: u32) -> usize {
+    let frame = ((sample_rate as f64 * FRAME_SECS) as usize).max(1);
+    let search_from = samples.len().saturating_sub((sample_rate as f64 * CUT_SEARCH_SECS) as usize);
+    (search_from..samples.len().saturating_sub(frame))
+        .step_by(frame)
+        .min_by_key(|&start| samples[start..start + frame].iter().map(|s| (*s as i64).abs()).sum::<i64>())
+        .unwrap_or(samples.len())
+}
+
+async fn transcribe_window(window: &Window, session_id: &str, session: &mut Session, len: usize) -> Result<Vec<Segment>, String> {
+    let samples: Vec<i16> = session.pending.drain(..len).collect();
+    let offset = session.offset;
+    session.offset += samples.len() as f64 / session.sample_rate as f64;
+
+    let path = std::env::temp_dir().join(format!("fabric-dictation-{}.wav", Uuid::new_v4()));
+    fs::write(&path, wav(&samples, session.sample_rate)).map_err(|e| e.to_string())?;
+    let settings = window.app_handle().state::<SettingsState>().get();
+    let result = transcribe::transcribe_segments(&settings, &path, &session.engine).await;
+    let _ = fs::remove_file(&path);
+
+    let segments: Vec<Segment> = result?
+        .into_iter()
+        .map(|s| Segment { start: s.start + offset, end: s.end + offset, ..s })
+        .collect();
+    for segment in &segments {
+        let _ = window.emit("dictation-segment", json!({"session_id": session_id, "segment": segment}));
+    }
+    session.segments.extend(segments.iter().cloned());
+    Ok(segments)
+}
+
+// Starts a dictation session. The UI records the microphone and sends 16-bit mono PCM at
+// `sample_rate` with push_dictation_audio; interim text arrives as dictation-segment events.
+#[tauri::command]
+pub async fn start_dictation(
+    sessions: State<'_, DictationSessions>,
+    sample_rate: u32,
+    engine: Option<String>,
+) -> Result<String, String> {
+    if !(8000..=48000).contains(&sample_rate) {
+        return Err(format!("Unsupported sample rate {} Hz.", sample_rate));
+    }
+    let session_id = Uuid::new_v4().to_string();
+    let session = Session {
+        sample_rate,
+        engine: engine.unwrap_or_else(|| "local".to_string()),
+        pending: Vec::new(),
+        offset: 0.0,
+        segments: Vec::new(),
+    };
+    sessions.0.lock().unwrap().insert(session_id.clone(), Arc::new(AsyncMutex::new(session)));
+    Ok(session_id)
+}
+
+// Takes base64 little-endian PCM and transcribes it a window at a time; returns the
+// segments this push completed
+#[tauri::command]
+pub async fn push_dictation_audio(
+    window: Window,
+    sessions: State<'_, DictationSessions>,
+    session_id: String,
+    pcm: String,
+) -> Result<Vec<Segment>, String> {
+    let bytes = STANDARD.decode(pcm).map_err(|e| format!("Invalid audio chunk: {}", e))?;
+    let session = sessions.get(&session_id)?;
+    let mut session = session.lock().await;
+    session
+        .pending
+        .extend(bytes.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])));
+
+    let mut completed = Vec::new();
+    let window_len = (session.sample_rate as f64 * WINDOW_SECS) as usize;
+    while session.pending.len() >= window_len {
+        let len = cut_point(&session.pending[..window_len], session.sample_rate);
+        completed.extend(transcribe_window(&window, &session_id, &mut session, len).await?);
+    }
+    Ok(completed)
+}
+
+// Ends the session, transcribes what is left and, given a request, runs its pattern on the
+// whole transcript ("run pattern when I stop"); the output streams like a normal run
+#[tauri::command]
+pub async fn stop_dictation(
+    window: Window,
+    sessions: State<'_, DictationSessions>,
+    session_id: String,
+    request: Option<AIRequest>,
+) -> Result<DictationResult, String> {
+    let session = sessions.get(&session_id)?;
+    sessions.0.lock().unwrap().remove(&session_id);
+    let mut session = session.lock().await;
+    // Anything under a quarter second is the click of the stop button
+    if session.pending.len() as f64 > session.sample_rate as f64 * 0.25 {
+        let len = session.pending.len();
+        transcribe_window(&window, &session_id, &mut session, len).await?;
+    }
+    let transcript = transcribe::segments_text(&session.segments);
+    let segments = std::mem::take(&mut session.segments);
+
+    let (run_id, output) = match request {
+        Some(mut request) if !transcript.trim().is_empty() => {
+            let run_id = request.run_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+            request.user_input = transcript.clone();
+            request.run_id = Some(run_id.clone());
+            request.dry_run = false;
+            let output = ai_client::run_recorded(window.app_handle(), Some(window.label().to_string()), request).await?;
+            (Some(run_id), Some(output))
+        }
+        _ => (None, None),
+    };
+    Ok(DictationResult { transcript, segments, run_id, output })
+}
+
+#[tauri::command]
+pub async fn cancel_dictation(sessions: State<'_, DictationSessions>, session_id: String) -> Result<(), String> {
+    sessions.0.lock().unwrap().remove(&session_id);
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::State;
+use crate::scrape;
+use crate::settings::SettingsState;
+
+// Pages kept so picking sections after viewing the outline doesn't fetch the page again
+const CACHED_PAGES: usize = 5;
+
+#[derive(Default)]
+pub struct DocCache {
+    pages: Mutex<Vec<(String, String)>>,
+}
+
+#[derive(Serialize)]
+pub struct DocSection {
+    pub index: usize,
+    pub level: usize,
+    pub title: String,
+    // Estimated tokens of the section's own text, subsections excluded
+    pub tokens: usize,
+}
+
+#[derive(Serialize)]
+pub struct DocOutline {
+    pub url: String,
+    pub title: Option<String>,
+    pub total_tokens: usize,
+    pub sections: Vec<DocSection>,
+}
+
+#[derive(Serialize)]
+pub struct DocSelection {
+    pub text: String,
+    pub tokens: usize,
+    // Sections left out because including them would exceed max_tokens
+    pub skipped: Vec<usize>,
+}
+
+struct Heading {
+    level: usize,
+    title: String,
+    // Line range of the heading and its own text
+    start: usize,
+    end: usize,
+}
+
+// Same ~4 characters per token heuristic the progress metrics use
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+// Finds ATX (## Title) and setext (Title over ---) headings outside code fences
+fn headings(lines: &[&str]) -> Vec<Heading> {
+    let mut found: Vec<Heading> = Vec::new();
+    let mut in_code = false;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+        let heading = if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+            Some((hashes, trimmed[hashes..].trim().trim_end_matches('#').trim().to_string(), i))
+        } else if i > 0
+            && trimmed.len() >= 3
+            && (trimmed.chars().all(|c| c == '=') || trimmed.chars().all(|c| c == '-'))
+            && !lines[i - 1].trim().is_empty()
+        {
+            let level = if trimmed.starts_with('=') { 1 } else { 2 };
+            Some((level, lines[i - 1].trim().to_string(), i - 1))
+        } else {
+            None
+        };
+
+        if let Some((level, title, start)) = heading {
+            if let Some(previous) = found.last_mut() {
+                previous.end = start;
+            }
+            found.push(Heading { level, title, start, end: lines.len() });
+        }
+    }
+
+    // Text above the first heading (e.g. a Wikipedia lead without a title) becomes a section of its own
+    let first_start = found.first().map(|h| h.start).unwrap_or(lines.len());
+    if lines[..first_start].iter().any(|l| !l.trim().is_empty()) {
+        let level = found.first().map(|h| h.level).unwrap_or(1);
+        found.insert(0, Heading { level, title: "Introduction".to_string(), start: 0, end: first_start });
+    }
+    found
+}
+
+async fn page(cache: &DocCache, url: &str, jina_key: Option<String>) -> Result<String, String> {
+    if let Some((_, text)) = cache.pages.lock().unwrap().iter().find(|(u, _)| u == url) {
+        return Ok(text.clone());
+    }
+    let text = scrape::scrape(url, jina_key).await?;
+    let mut pages = cache.pages.lock().unwrap();
+    pages.push((url.to_string(), text.clone()));
+    if pages.len() > CACHED_PAGES {
+        pages.remove(0);
+    }
+    Ok(text)
+}
+
+// Returns a page's table of contents with a size per section, so long references
+// (Wikipedia, MDN, framework docs) can be trimmed to the parts that matter
+#[tauri::command]
+pub async fn fetch_doc_outline(
+    settings: State<'_, SettingsState>,
+    cache: State<'_, DocCache>,
+    url: String,
+) -> Result<DocOutline, String> {
+    let text = page(&cache, &url, settings.get().api_key("jina")).await?;
+    let lines: Vec<&str> = text.lines().collect();
+    let found = headings(&lines);
+
+    Ok(DocOutline {
+        title: found.iter().find(|h| h.level == 1).map(|h| h.title.clone()),
+        total_tokens: estimate_tokens(&text),
+        sections: found
+            .iter()
+            .enumerate()
+            .map(|(index, h)| DocSection {
+                index,
+                level: h.level,
+                title: h.title.clone(),
+                tokens: estimate_tokens(&lines[h.start..h.end].join("\n")),
+            })
+            .collect(),
+        url,
+    })
+}
+
+// Joins the chosen sections (indexes from the outline) in page order. A section brings its
+// subsections along; with `max_tokens`, sections that no longer fit are skipped.
+#[tauri::command]
+pub async fn fetch_doc_sections(
+    settings: State<'_, SettingsState>,
+    cache: State<'_, DocCache>,
+    url: String,
+    sections: Vec<usize>,
+    max_tokens: Option<usize>,
+) -> Result<DocSelection, String> {
+    let text = page(&cache, &url, settings.get().api_key("jina")).await?;
+    let lines: Vec<&str> = text.lines().collect();
+    let found = headings(&lines);
+
+    let mut included = vec![false; found.len()];
+    for &index in &sections {
+        let Some(heading) = found.get(index) else {
+            return Err(format!("The page has no section {}.", index));
+        };
+        included[index] = true;
+        for (offset, sub) in found[index + 1..].iter().enumerate() {
+            if sub.level <= heading.level {
+                break;
+            }
+            included[index + 1 + offset] = true;
+        }
+    }
+
+    let mut parts = Vec::new();
+    let mut tokens = 0;
+    let mut skipped = Vec::new();
+    for (index, heading) in found.iter().enumerate().filter(|(i, _)| included[*i]) {
+        let part = lines[heading.start..heading.end].join("\n");
+        let part_tokens = estimate_tokens(&part);
+        if max_tokens.is_some_and(|max| tokens + part_tokens > max) {
+            skipped.push(index);
+            continue;
+        }
+        tokens += part_tokens;
+        parts.push(part.trim().to_string());
+    }
+
+    Ok(DocSelection {
+        text: parts.join("\n\n"),
+        tokens,
+        skipped,
+    })
+}
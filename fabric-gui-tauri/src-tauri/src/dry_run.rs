@@ -0,0 +1,85 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tauri::{AppHandle, Manager};
+use crate::ai_client::{self, AIRequest};
+use crate::models::ModelRegistryState;
+use crate::settings::SettingsState;
+use crate::validate;
+use crate::vertex;
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Serialize)]
+pub struct DryRunReport {
+    pub vendor: String,
+    pub model: String,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    pub payload: Value,
+    pub estimated_input_tokens: u64,
+    pub context_window: Option<u64>,
+    pub exceeds_context_window: bool,
+    pub api_key_configured: bool,
+    pub notes: Vec<String>,
+}
+
+// Goes through the same preparation as a real run (composition, prefill emulation, payload
+// building) and stops right before the HTTP call; credentials are never included
+pub fn build(app_handle: &AppHandle, mut request: AIRequest) -> Result<DryRunReport, String> {
+    let settings = app_handle.state::<SettingsState>().get();
+    ai_client::apply_composition(app_handle, &settings, &mut request)?;
+    ai_client::emulate_prefill(&mut request);
+    let (url, payload) = ai_client::vendor_request(&request)?;
+    let model = app_handle.state::<ModelRegistryState>().find(&request.model);
+
+    let mut headers = BTreeMap::new();
+    headers.insert("Content-Type".to_string(), "application/json".to_string());
+    let url = match request.vendor.as_str() {
+        "google" => format!("{}&key={}", url, REDACTED),
+        "lmstudio" => url,
+        "openai" | "vertex" | "xai" | "deepseek" | "huggingface" => {
+            headers.insert("Authorization".to_string(), format!("Bearer {}", REDACTED));
+            url
+        }
+        _ => {
+            headers.insert("x-api-key".to_string(), REDACTED.to_string());
+            headers.insert("anthropic-version".to_string(), "2023-06-01".to_string());
+            url
+        }
+    };
+
+    let mut notes: Vec<String> = validate::issues(&request, model.as_ref())
+        .into_iter()
+        .map(|issue| format!("A real run would be rejected: {}", issue.message))
+        .collect();
+    if request.translate_input {
+        notes.push("The input would first be translated to English by a separate call; the payload shows it untranslated.".to_string());
+    }
+    if request.translate_output {
+        notes.push("The output would be translated by a separate call after the run.".to_string());
+    }
+
+    // Same ~4 characters per token heuristic the progress metrics use
+    let chars = request.system_prompt.chars().count()
+        + request.user_input.chars().count()
+        + request.assistant_prefill.as_deref().map(|p| p.chars().count()).unwrap_or(0);
+    let estimated_input_tokens = chars.div_ceil(4) as u64;
+    let context_window = model.as_ref().map(|m| m.context_window);
+
+    Ok(DryRunReport {
+        api_key_configured: !request.api_key.trim().is_empty()
+            || settings.api_key(&request.vendor).is_some()
+            || (request.vendor == "vertex" && vertex::credentials_available())
+            || request.vendor == "lmstudio",
+        vendor: request.vendor,
+        model: request.model,
+        url,
+        headers,
+        payload,
+        estimated_input_tokens,
+        exceeds_context_window: context_window.is_some_and(|window| estimated_input_tokens > window),
+        context_window,
+        notes,
+    })
+}
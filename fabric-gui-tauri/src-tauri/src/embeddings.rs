@@ -0,0 +1,128 @@
+use serde_json::{json, Value};
+use crate::http::{self, AuditedSend};
+use crate::i18n::tr_args;
+use crate::lmstudio;
+use crate::residency;
+
+const GOOGLE_API: &str = "https://generativelanguage.googleapis.com/v1beta";
+const JINA_RERANK_URL: &str = "https://api.jina.ai/v1/rerank";
+const COHERE_RERANK_URL: &str = "https://api.cohere.com/v2/rerank";
+// Both APIs take at least this many inputs per request
+const BATCH_SIZE: usize = 64;
+
+pub fn default_model(vendor: &str) -> Option<&'static str> {
+    match vendor {
+        "openai" => Some("text-embedding-3-small"),
+        "google" => Some("text-embedding-004"),
+        _ => None,
+    }
+}
+
+pub fn default_rerank_model(vendor: &str) -> Option<&'static str> {
+    match vendor {
+        "jina" => Some("jina-reranker-v2-base-multilingual"),
+        "cohere" => Some("rerank-v3.5"),
+        _ => None,
+    }
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+fn floats(values: &Value) -> Vec<f32> {
+    values
+        .as_array()
+        .map(|a| a.iter().filter_map(Value::as_f64).map(|v| v as f32).collect())
+        .unwrap_or_default()
+}
+
+async fn post(url: &str, vendor: &str, api_key: Option<&str>, body: &Value) -> Result<Value, String> {
+    let mut request = http::client_for(url)?.post(url).json(body);
+    if let Some(key) = api_key.filter(|_| vendor != "google") {
+        request = request.bearer_auth(key);
+    }
+    let purpose = if url.ends_with("/rerank") { "rerank" } else { "embedding" };
+    let res = request.send_audited(purpose, None).await.map_err(http::network_error)?;
+    let status = res.status();
+    let text = res.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(tr_args(
+            "vendor-api-error",
+            &[("vendor", vendor), ("status", &status.to_string()), ("details", &text.chars().take(300).collect::<String>())],
+        ));
+    }
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+// OpenAI and LM Studio share the OpenAI embeddings API
+async fn openai_batch(vendor: &str, model: &str, api_key: Option<&str>, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+    let base = if vendor == "lmstudio" { lmstudio::base_url() } else { residency::openai_base_url().to_string() };
+    let json = post(&format!("{}/v1/embeddings", base), vendor, api_key, &json!({"model": model, "input": texts})).await?;
+    let mut data: Vec<&Value> = json["data"].as_array().map(|a| a.iter().collect()).unwrap_or_default();
+    data.sort_by_key(|d| d["index"].as_u64().unwrap_or_default());
+    Ok(data.into_iter().map(|d| floats(&d["embedding"])).collect())
+}
+
+async fn google_batch(model: &str, api_key: Option<&str>, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+    let api_key = api_key.ok_or_else(|| tr_args("health-missing-key", &[("vendor", "google")]))?;
+    let model_path = format!("models/{}", model.trim_start_matches("models/"));
+    let requests: Vec<Value> = texts
+        .iter()
+        .map(|text| json!({"model": model_path, "content": {"parts": [{"text": text}]}}))
+        .collect();
+    let url = format!("{}/{}:batchEmbedContents?key={}", GOOGLE_API, model_path, api_key);
+    let json = post(&url, "google", None, &json!({"requests": requests})).await?;
+    Ok(json["embeddings"]
+        .as_array()
+        .map(|a| a.iter().map(|e| floats(&e["values"])).collect())
+        .unwrap_or_default())
+}
+
+// One vector per text, normalized so that a dot product is the cosine similarity
+pub async fn embed(vendor: &str, model: &str, api_key: Option<&str>, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+    if vendor != "lmstudio" {
+        residency::check(vendor, None)?;
+    }
+    let mut vectors = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(BATCH_SIZE) {
+        let embedded = match vendor {
+            "openai" | "lmstudio" => openai_batch(vendor, model, api_key, batch).await?,
+            "google" => google_batch(model, api_key, batch).await?,
+            _ => return Err(format!("Embeddings are available from OpenAI, Google and LM Studio, not '{}'.", vendor)),
+        };
+        if embedded.len() != batch.len() || embedded.iter().any(Vec::is_empty) {
+            return Err(format!("{} returned {} embeddings for {} texts.", vendor, embedded.len(), batch.len()));
+        }
+        vectors.extend(embedded.into_iter().map(normalized));
+    }
+    Ok(vectors)
+}
+
+// A cross-encoder scores each document against the query; Jina and Cohere share the request
+// shape. Returns (document index, relevance) best first.
+pub async fn rerank(vendor: &str, model: &str, api_key: Option<&str>, query: &str, documents: &[String]) -> Result<Vec<(usize, f32)>, String> {
+    let url = match vendor {
+        "jina" => JINA_RERANK_URL,
+        "cohere" => COHERE_RERANK_URL,
+        _ => return Err(format!("Reranking is available from Jina and Cohere, not '{}'.", vendor)),
+    };
+    residency::check(vendor, None)?;
+    let api_key = api_key.ok_or_else(|| tr_args("health-missing-key", &[("vendor", vendor)]))?;
+    let body = json!({"model": model, "query": query, "documents": documents, "top_n": documents.len()});
+    let json = post(url, vendor, Some(api_key), &body).await?;
+    Ok(json["results"]
+        .as_array()
+        .map(|results| {
+            results
+                .iter()
+                .filter_map(|r| Some((r["index"].as_u64()? as usize, r["relevance_score"].as_f64()? as f32)))
+                .filter(|(index, _)| *index < documents.len())
+                .collect()
+        })
+        .unwrap_or_default())
+}
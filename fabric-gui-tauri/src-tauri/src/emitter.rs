@@ -0,0 +1,500 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter};
+use crate::i18n::tr_args;
+use crate::rag::{self, Citation};
+use crate::sanitize::{self, StreamSanitizer};
+use crate::scripting::Scripts;
+use crate::stream_ack::StreamWindow;
+
+#[derive(Serialize, Clone)]
+pub struct AIChunk {
+    pub run_id: String,
+    pub chunk: String,
+}
+
+#[derive(Serialize, Clone)]
+struct CitationEvent {
+    run_id: String,
+    citation: Citation,
+}
+
+// Stops the heartbeat task when dropped, so a run whose future is dropped before it
+// finishes doesn't keep reporting progress
+pub struct Heartbeat(JoinHandle<()>);
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+// Average adult silent reading speed
+const READING_WORDS_PER_MINUTE: u64 = 238;
+
+#[derive(Serialize, Clone, Default)]
+pub struct RunMetrics {
+    pub elapsed_ms: u64,
+    pub time_to_first_token_ms: Option<u64>,
+    // Streams don't report per-chunk usage, so tokens are estimated at ~4 characters each
+    pub estimated_tokens: u64,
+    pub tokens_per_sec: Option<f64>,
+    // Running stats of the visible output (reasoning excluded), kept here so the UI
+    // doesn't have to re-scan the whole text on every chunk
+    pub word_count: u64,
+    pub char_count: u64,
+    pub reading_time_secs: u64,
+}
+
+#[derive(Serialize, Clone)]
+struct ProgressEvent {
+    run_id: String,
+    #[serde(flatten)]
+    metrics: RunMetrics,
+}
+
+struct Progress {
+    started: Instant,
+    first_token: Option<Instant>,
+    chars: usize,
+    output_chars: u64,
+    words: u64,
+    // Whether the last counted character was part of a word, so words split across chunks count once
+    in_word: bool,
+}
+
+impl Progress {
+    fn count_output(&mut self, text: &str) {
+        for c in text.chars() {
+            self.output_chars += 1;
+            let word_char = !c.is_whitespace();
+            if word_char && !self.in_word {
+                self.words += 1;
+            }
+            self.in_word = word_char;
+        }
+    }
+
+    fn snapshot(&self) -> RunMetrics {
+        let elapsed = self.started.elapsed();
+        let estimated_tokens = self.chars.div_ceil(4) as u64;
+        // Throughput is measured from the first token so slow connects don't skew it
+        let tokens_per_sec = self.first_token.and_then(|first| {
+            let generating = first.elapsed().as_secs_f64();
+            (generating > 0.0).then(|| estimated_tokens as f64 / generating)
+        });
+
+        RunMetrics {
+            elapsed_ms: elapsed.as_millis() as u64,
+            time_to_first_token_ms: self
+                .first_token
+                .map(|first| first.duration_since(self.started).as_millis() as u64),
+            estimated_tokens,
+            tokens_per_sec,
+            word_count: self.words,
+            char_count: self.output_chars,
+            reading_time_secs: (self.words * 60).div_ceil(READING_WORDS_PER_MINUTE),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Coalescing {
+    pub interval: Duration,
+    pub max_bytes: usize,
+}
+
+// Text waiting to go out as one event instead of one per SSE delta
+#[derive(Default)]
+struct Coalescer {
+    buffer: String,
+    since: Option<Instant>,
+}
+
+impl Coalescer {
+    fn push(&mut self, text: &str) {
+        if self.buffer.is_empty() {
+            self.since = Some(Instant::now());
+        }
+        self.buffer.push_str(text);
+    }
+
+    fn is_due(&self, config: Coalescing) -> bool {
+        self.buffer.len() >= config.max_bytes || self.since.is_some_and(|since| since.elapsed() >= config.interval)
+    }
+
+    fn take(&mut self) -> String {
+        self.since = None;
+        std::mem::take(&mut self.buffer)
+    }
+}
+
+// Swallows the model repeating an emulated assistant prefill that was already emitted
+struct EchoFilter {
+    remaining: String,
+    matched_any: bool,
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.chars()
+        .zip(b.chars())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum()
+}
+
+// Final-output counterpart of the streaming echo filter, so history matches what was shown
+pub fn strip_echo<'a>(output: &'a str, prefill: &str) -> &'a str {
+    let trimmed = output.trim_start();
+    match common_prefix_len(trimmed, prefill) {
+        0 => output,
+        common => &trimmed[common..],
+    }
+}
+
+// Routes a run's events to the window that started it, tagged with the run ID,
+// so parallel sessions in other windows never see each other's output
+#[derive(Clone)]
+pub struct RunEmitter {
+    app_handle: AppHandle,
+    target: Option<String>,
+    run_id: String,
+    sanitizer: Option<Arc<Mutex<StreamSanitizer>>>,
+    scripts: Option<Arc<Scripts>>,
+    progress: Arc<Mutex<Progress>>,
+    echo: Arc<Mutex<Option<EchoFilter>>>,
+    reasoning: Arc<Mutex<String>>,
+    coalescing: Option<Coalescing>,
+    pending_chunks: Arc<Mutex<Coalescer>>,
+    pending_thinking: Arc<Mutex<Coalescer>>,
+    ack_window: Option<Arc<StreamWindow>>,
+    // Everything shown as output so far (prefill included), before sanitizing
+    output: Arc<Mutex<String>>,
+    interrupted: Arc<AtomicBool>,
+    // The sources of a RAG run, and the markers the output has cited so far
+    citations: Option<Arc<Vec<Citation>>>,
+    cited: Arc<Mutex<BTreeSet<usize>>>,
+}
+
+impl RunEmitter {
+    // A `None` target broadcasts, which background jobs without a window rely on
+    pub fn new(app_handle: AppHandle, target: Option<String>, run_id: String) -> Self {
+        Self {
+            app_handle,
+            target,
+            run_id,
+            sanitizer: None,
+            scripts: None,
+            progress: Arc::new(Mutex::new(Progress {
+                started: Instant::now(),
+                first_token: None,
+                chars: 0,
+                output_chars: 0,
+                words: 0,
+                in_word: false,
+            })),
+            echo: Arc::new(Mutex::new(None)),
+            reasoning: Arc::new(Mutex::new(String::new())),
+            coalescing: None,
+            pending_chunks: Arc::new(Mutex::new(Coalescer::default())),
+            pending_thinking: Arc::new(Mutex::new(Coalescer::default())),
+            ack_window: None,
+            output: Arc::new(Mutex::new(String::new())),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            citations: None,
+            cited: Arc::new(Mutex::new(BTreeSet::new())),
+        }
+    }
+
+    // Emits `ai-citation` with a source's file and page the first time the output cites its
+    // marker, so the UI can turn markers into footnotes while the answer streams
+    pub fn with_citations(mut self, citations: Vec<Citation>) -> Self {
+        self.citations = Some(Arc::new(citations));
+        self
+    }
+
+    // Acknowledged streaming: vendor loops call `wait_for_capacity` before reading more
+    pub fn with_ack_window(mut self, window: Arc<StreamWindow>) -> Self {
+        self.ack_window = Some(window);
+        self
+    }
+
+    pub async fn wait_for_capacity(&self) {
+        if let Some(window) = &self.ack_window {
+            window.wait_for_capacity().await;
+        }
+    }
+
+    // Batches deltas into one event per interval or once `max_bytes` pile up, so fast models
+    // don't flood the IPC bridge with tiny events
+    pub fn with_coalescing(mut self, coalescing: Coalescing) -> Self {
+        self.coalescing = Some(coalescing);
+        self
+    }
+
+    // Chunks are sanitized before they leave Rust, so the webview can inject rendered output directly
+    pub fn with_sanitizer(mut self) -> Self {
+        self.sanitizer = Some(Arc::new(Mutex::new(StreamSanitizer::default())));
+        self
+    }
+
+    // Streamed text goes through the scripts' on_chunk handlers before anything else sees it
+    pub fn with_scripts(mut self, scripts: Arc<Scripts>) -> Self {
+        self.scripts = Some(scripts);
+        self
+    }
+
+    // Applies the same sanitizing to text emitted in one piece outside the chunk stream
+    pub fn sanitize_text(&self, text: String) -> String {
+        match &self.sanitizer {
+            Some(_) => sanitize::sanitize(&text),
+            None => text,
+        }
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    pub fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) -> Result<(), String> {
+        match &self.target {
+            Some(label) => self.app_handle.emit_to(label.as_str(), event, payload),
+            None => self.app_handle.emit(event, payload),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn metrics(&self) -> RunMetrics {
+        self.progress.lock().unwrap().snapshot()
+    }
+
+    pub fn progress(&self) -> Result<(), String> {
+        self.emit(
+            "ai-progress",
+            ProgressEvent {
+                run_id: self.run_id.clone(),
+                metrics: self.metrics(),
+            },
+        )
+    }
+
+    // Emits `ai-progress` on a fixed interval so the UI can show a live timer even before
+    // the first token arrives, and releases coalesced text once it has waited long enough
+    // when the stream pauses; runs until the returned guard is dropped
+    pub fn start_heartbeat(&self) -> Heartbeat {
+        let emitter = self.clone();
+        let tick = self
+            .coalescing
+            .map(|c| c.interval.min(HEARTBEAT_INTERVAL))
+            .unwrap_or(HEARTBEAT_INTERVAL);
+        Heartbeat(tauri::async_runtime::spawn(async move {
+            let mut last_progress = Instant::now();
+            loop {
+                tokio::time::sleep(tick).await;
+                let _ = emitter.release_pending(false);
+                if last_progress.elapsed() >= HEARTBEAT_INTERVAL {
+                    last_progress = Instant::now();
+                    let _ = emitter.progress();
+                }
+            }
+        }))
+    }
+
+    // Emits coalesced text that is due, or all of it when `all` is set
+    fn release_pending(&self, all: bool) -> Result<(), String> {
+        let Some(config) = self.coalescing else {
+            return Ok(());
+        };
+        for (event, pending) in [("ai-chunk", &self.pending_chunks), ("ai-thinking", &self.pending_thinking)] {
+            let text = {
+                let mut pending = pending.lock().unwrap();
+                if all || pending.is_due(config) { pending.take() } else { String::new() }
+            };
+            self.emit_text(event, text)?;
+        }
+        Ok(())
+    }
+
+    pub fn chunk(&self, text: &str) -> Result<(), String> {
+        let mut text = self.filter_echo(text);
+        if let Some(scripts) = &self.scripts {
+            text = scripts.chunk(&text);
+        }
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        {
+            let mut progress = self.progress.lock().unwrap();
+            if progress.first_token.is_none() {
+                progress.first_token = Some(Instant::now());
+            }
+            progress.chars += text.chars().count();
+            progress.count_output(&text);
+        }
+
+        self.output.lock().unwrap().push_str(&text);
+        self.emit_citations(text.len())?;
+        self.send(&text)
+    }
+
+    // Looks at the new text plus enough before it to catch a marker split across deltas
+    fn emit_citations(&self, new_bytes: usize) -> Result<(), String> {
+        let Some(citations) = &self.citations else {
+            return Ok(());
+        };
+        let markers = {
+            let output = self.output.lock().unwrap();
+            let mut start = output.len().saturating_sub(new_bytes + 32);
+            while !output.is_char_boundary(start) {
+                start -= 1;
+            }
+            rag::cited_markers(&output[start..])
+        };
+        for marker in markers {
+            let Some(citation) = citations.iter().find(|c| c.marker == marker) else {
+                continue;
+            };
+            if self.cited.lock().unwrap().insert(marker) {
+                let citation = Citation { cited: true, ..citation.clone() };
+                self.emit("ai-citation", CitationEvent { run_id: self.run_id.clone(), citation })?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reasoning from every vendor (Gemini thought parts, Claude thinking blocks, reasoning
+    // deltas) goes to its own event so the UI can show it apart from the answer; it never
+    // becomes part of the output.
+    pub fn thinking(&self, text: &str) -> Result<(), String> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.reasoning.lock().unwrap().push_str(text);
+        {
+            let mut progress = self.progress.lock().unwrap();
+            if progress.first_token.is_none() {
+                progress.first_token = Some(Instant::now());
+            }
+            progress.chars += text.chars().count();
+        }
+        let chunk = self.sanitize_text(text.to_string());
+        self.emit_coalesced("ai-thinking", &self.pending_thinking, chunk)
+    }
+
+    pub fn output(&self) -> String {
+        self.output.lock().unwrap().clone()
+    }
+
+    // For errors while reading a response body: the connection dropped after the vendor
+    // accepted the request, which is what a continuation can recover from
+    pub fn stream_interrupted(&self, error: reqwest::Error) -> String {
+        self.interrupted.store(true, Ordering::SeqCst);
+        tr_args("stream-error", &[("error", &error.to_string())])
+    }
+
+    // Returns and clears the interruption flag
+    pub fn take_interrupted(&self) -> bool {
+        self.interrupted.swap(false, Ordering::SeqCst)
+    }
+
+    // Everything passed to `thinking` so far, unsanitized
+    pub fn reasoning(&self) -> String {
+        self.reasoning.lock().unwrap().clone()
+    }
+
+    // Emits an assistant prefill ahead of the model's output without counting it as a
+    // generated token, so time-to-first-token stays honest
+    pub fn prefill(&self, text: &str) -> Result<(), String> {
+        self.progress.lock().unwrap().count_output(text);
+        self.output.lock().unwrap().push_str(text);
+        self.send(text)
+    }
+
+    // For vendors where the prefill is emulated by instruction, the model is expected to
+    // start by repeating it; that repetition is dropped from the stream
+    pub fn expect_echo(&self, prefill: &str) {
+        *self.echo.lock().unwrap() = Some(EchoFilter {
+            remaining: prefill.to_string(),
+            matched_any: false,
+        });
+    }
+
+    fn filter_echo(&self, text: &str) -> String {
+        let mut echo = self.echo.lock().unwrap();
+        let Some(filter) = echo.as_mut() else {
+            return text.to_string();
+        };
+
+        let incoming = if filter.matched_any { text } else { text.trim_start() };
+        let common = common_prefix_len(incoming, &filter.remaining);
+
+        if common == incoming.len() && common < filter.remaining.len() {
+            // Still inside the echo; wait for more
+            filter.remaining.drain(..common);
+            filter.matched_any |= common > 0;
+            return String::new();
+        }
+
+        let rest = if common == 0 && !filter.matched_any { text } else { &incoming[common..] };
+        *echo = None;
+        rest.to_string()
+    }
+
+    fn send(&self, text: &str) -> Result<(), String> {
+        match &self.sanitizer {
+            Some(sanitizer) => {
+                let clean = sanitizer.lock().unwrap().push(text);
+                self.emit_chunk(clean)
+            }
+            None => self.emit_chunk(text.to_string()),
+        }
+    }
+
+    // Emits whatever the sanitizer and coalescing are still holding back; call once the
+    // stream has ended
+    pub fn flush(&self) -> Result<(), String> {
+        if let Some(sanitizer) = &self.sanitizer {
+            let rest = sanitizer.lock().unwrap().finish();
+            self.emit_chunk(rest)?;
+        }
+        self.release_pending(true)
+    }
+
+    fn emit_chunk(&self, chunk: String) -> Result<(), String> {
+        self.emit_coalesced("ai-chunk", &self.pending_chunks, chunk)
+    }
+
+    fn emit_coalesced(&self, event: &str, pending: &Mutex<Coalescer>, text: String) -> Result<(), String> {
+        let Some(config) = self.coalescing else {
+            return self.emit_text(event, text);
+        };
+        let ready = {
+            let mut pending = pending.lock().unwrap();
+            pending.push(&text);
+            if pending.is_due(config) { pending.take() } else { String::new() }
+        };
+        self.emit_text(event, ready)
+    }
+
+    fn emit_text(&self, event: &str, chunk: String) -> Result<(), String> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        if let Some(window) = &self.ack_window {
+            window.record_sent();
+        }
+        self.emit(
+            event,
+            AIChunk {
+                run_id: self.run_id.clone(),
+                chunk,
+            },
+        )
+    }
+}
@@ -0,0 +1,459 @@
+use chrono::{Local, TimeZone};
+use handlebars::{no_escape, Handlebars};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+use tauri::{AppHandle, State};
+use crate::history::{HistoryEntry, HistoryState};
+use crate::http::{self, AuditedSend};
+use crate::onenote;
+use crate::settings::{Settings, SettingsState};
+use crate::templates;
+
+const DEFAULT_NOTE_PATH: &str = "{{date}}/{{pattern}}-{{slug}}.md";
+const MAX_SLUG_LEN: usize = 60;
+// The Web Clipper service's default address; Joplin must be running with it enabled
+const JOPLIN_URL: &str = "http://localhost:41184";
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ExportSettings {
+    // Folder the "obsidian" target writes to, e.g. an Obsidian vault or a Zettelkasten folder
+    pub notes_dir: Option<String>,
+    // Path of a note inside notes_dir, with tokens; defaults to "{{date}}/{{pattern}}-{{slug}}.md"
+    pub note_path: Option<String>,
+    // Per-pattern paths, checked in order before note_path
+    pub note_routes: Vec<NoteRoute>,
+    // Template the note body is rendered with; defaults to "note.md"
+    pub note_template: Option<String>,
+    // Clipper service address for the "joplin" target; the token is api_keys "joplin"
+    pub joplin_url: Option<String>,
+    // Notebook title notes go into, created when missing; unset uses Joplin's default notebook
+    pub joplin_notebook: Option<String>,
+    // Graph folder whose journal the "logseq" target appends to
+    pub logseq_graph_dir: Option<String>,
+    // Apple Notes folder for the "apple_notes" target, created when missing; unset uses Notes'
+    // default folder
+    pub apple_notes_folder: Option<String>,
+    // Client id of an Azure app registration (public client, Notes.Create permission) the
+    // "onenote" target signs in with
+    pub onenote_client_id: Option<String>,
+    // Section pages go into; unset uses the default notebook's default section
+    pub onenote_section: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NoteRoute {
+    // A pattern name, or a prefix ending in '*' such as "extract_*"
+    pub pattern: String,
+    pub path: String,
+}
+
+#[derive(Serialize)]
+pub struct ExportedNote {
+    pub target: String,
+    // The file written, or a link to the new note
+    pub path: String,
+}
+
+impl NoteRoute {
+    fn matches(&self, pattern: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => pattern.starts_with(prefix),
+            None => self.pattern == pattern,
+        }
+    }
+}
+
+// Lowercase ASCII words joined by dashes: "# Why Rust's *borrow checker*" -> "why-rust-s-borrow-checker"
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_SLUG_LEN);
+    slug.trim_end_matches('-').to_string()
+}
+
+// Tokens a note path can use: {{date}}, {{year}}, {{month}}, {{day}}, {{time}}, {{pattern}},
+// {{title}}, {{slug}}, {{vendor}}, {{model}}, {{id}}
+fn path_tokens(entry: &HistoryEntry) -> Value {
+    let created = Local.timestamp_opt(entry.created_at, 0).single().unwrap_or_else(Local::now);
+    let title = templates::title(entry);
+    json!({
+        "date": created.format("%Y-%m-%d").to_string(),
+        "year": created.format("%Y").to_string(),
+        "month": created.format("%m").to_string(),
+        "day": created.format("%d").to_string(),
+        "time": created.format("%H%M").to_string(),
+        "pattern": entry.pattern.as_deref().unwrap_or("output"),
+        "slug": slug(&title),
+        "title": title,
+        "vendor": entry.vendor,
+        "model": entry.model,
+        "id": entry.id,
+    })
+}
+
+// Drops characters file systems reject and any "." or ".." component, so a token can't route
+// a note outside notes_dir
+fn clean_path(rendered: &str) -> Result<PathBuf, String> {
+    let mut path = PathBuf::new();
+    for part in rendered.split(['/', '\\']) {
+        let part: String = part
+            .chars()
+            .filter(|c| !c.is_control() && !"<>:\"|?*".contains(*c))
+            .collect();
+        let part = part.trim().trim_end_matches('.');
+        if !part.is_empty() && part != "." && part != ".." {
+            path.push(part);
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(format!("The note path '{}' is empty.", rendered));
+    }
+    if path.extension().is_none() {
+        path.set_extension("md");
+    }
+    Ok(path)
+}
+
+// The first route matching the run's pattern, else the default path
+fn note_path(settings: &ExportSettings, entry: &HistoryEntry, template: Option<&str>) -> Result<PathBuf, String> {
+    let template = template
+        .map(str::to_string)
+        .or_else(|| {
+            let pattern = entry.pattern.as_deref()?;
+            settings.note_routes.iter().find(|r| r.matches(pattern)).map(|r| r.path.clone())
+        })
+        .or_else(|| settings.note_path.clone())
+        .unwrap_or_else(|| DEFAULT_NOTE_PATH.to_string());
+    let mut engine = Handlebars::new();
+    engine.register_escape_fn(no_escape);
+    engine.set_strict_mode(true);
+    let rendered = engine
+        .render_template(&template, &path_tokens(entry))
+        .map_err(|e| format!("The note path '{}' is not valid: {}", template, e))?;
+    clean_path(&rendered)
+}
+
+// "note.md" taken by an earlier export becomes "note 2.md"
+fn unused_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("note");
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("md");
+    let mut candidate = path.to_path_buf();
+    let mut n = 2;
+    while candidate.exists() {
+        candidate = path.with_file_name(format!("{} {}.{}", stem, n, extension));
+        n += 1;
+    }
+    candidate
+}
+
+fn load_entry(history: &HistoryState, run_id: &str) -> Result<HistoryEntry, String> {
+    history
+        .get(run_id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))
+}
+
+// Where export_note would put a run, relative to notes_dir; `path` tries an unsaved path
+// template from the settings editor
+#[tauri::command]
+pub async fn preview_note_path(
+    settings: State<'_, SettingsState>,
+    history: State<'_, HistoryState>,
+    run_id: String,
+    path: Option<String>,
+) -> Result<String, String> {
+    let entry = load_entry(&history, &run_id)?;
+    let relative = note_path(&settings.get().export, &entry, path.as_deref())?;
+    Ok(relative.to_string_lossy().replace('\\', "/"))
+}
+
+// Writes the note under notes_dir at its routed path. An existing note is never overwritten;
+// the new one gets a number instead.
+fn write_file(export: &ExportSettings, entry: &HistoryEntry, body: &str) -> Result<String, String> {
+    let notes_dir = export
+        .notes_dir
+        .as_deref()
+        .filter(|d| !d.trim().is_empty())
+        .ok_or("Choose a notes folder in settings first.")?;
+    let path = unused_path(&Path::new(notes_dir).join(note_path(export, entry, None)?));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, body).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Joplin and Logseq keep their own metadata, so a template's YAML header would only show up
+// as text there
+fn strip_frontmatter(body: &str) -> &str {
+    body.strip_prefix("---\n")
+        .and_then(|rest| rest.find("\n---\n").map(|end| rest[end + 5..].trim_start()))
+        .unwrap_or(body)
+}
+
+async fn joplin(settings: &Settings, method: reqwest::Method, path: &str, body: Option<Value>) -> Result<Value, String> {
+    let token = settings
+        .api_key("joplin")
+        .ok_or("Add the Joplin Web Clipper token (Options > Web Clipper) in settings first.")?;
+    let base = settings.export.joplin_url.as_deref().filter(|u| !u.trim().is_empty()).unwrap_or(JOPLIN_URL);
+    let separator = if path.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}{}token={}", base.trim().trim_end_matches('/'), path, separator, token);
+    let mut request = http::client_for(&url)?
+        .request(method, &url)
+        .timeout(Duration::from_secs(30));
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    let res = request
+        .send_audited("export", None)
+        .await
+        .map_err(|_| "Could not reach Joplin; open Joplin with the Web Clipper service enabled.".to_string())?;
+    let status = res.status();
+    if status.as_u16() == 403 {
+        return Err("Joplin rejected the Web Clipper token.".to_string());
+    }
+    if !status.is_success() {
+        let text = res.text().await.unwrap_or_default();
+        return Err(format!("Joplin error ({}): {}", status, &text.chars().take(300).collect::<String>()));
+    }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+// The id of the notebook titled `title`, creating it when there is none
+async fn joplin_notebook(settings: &Settings, title: &str) -> Result<String, String> {
+    for page in 1.. {
+        let path = format!("/folders?fields=id,title&page={}", page);
+        let folders = joplin(settings, reqwest::Method::GET, &path, None).await?;
+        let items = folders["items"].as_array().cloned().unwrap_or_default();
+        if let Some(folder) = items.iter().find(|f| f["title"].as_str() == Some(title)) {
+            return Ok(folder["id"].as_str().unwrap_or_default().to_string());
+        }
+        if !folders["has_more"].as_bool().unwrap_or(false) {
+            break;
+        }
+    }
+    let created = joplin(settings, reqwest::Method::POST, "/folders", Some(json!({"title": title}))).await?;
+    Ok(created["id"].as_str().unwrap_or_default().to_string())
+}
+
+async fn send_to_joplin(settings: &Settings, entry: &HistoryEntry, body: &str) -> Result<String, String> {
+    let mut note = json!({"title": templates::title(entry), "body": strip_frontmatter(body)});
+    if let Some(notebook) = settings.export.joplin_notebook.as_deref().filter(|n| !n.trim().is_empty()) {
+        note["parent_id"] = json!(joplin_notebook(settings, notebook.trim()).await?);
+    }
+    let created = joplin(settings, reqwest::Method::POST, "/notes", Some(note)).await?;
+    let id = created["id"].as_str().ok_or("Joplin did not return the new note's id.")?;
+    Ok(format!("joplin://x-callback-url/openNote?id={}", id))
+}
+
+// Logseq pages are outlines: every paragraph, heading and list item of the output becomes a
+// block under one parent block, list nesting kept. Fenced code stays in a single block.
+fn logseq_blocks(entry: &HistoryEntry, body: &str) -> String {
+    let list_item = Regex::new(r"^(\s*)(?:[-*+]|\d+[.)])\s+(.*)$").unwrap();
+    let mut out = format!("- {}", templates::title(entry));
+    if let Some(pattern) = &entry.pattern {
+        out.push_str(&format!(" #[[{}]]", pattern));
+    }
+    out.push_str(&format!("\n  model:: {}/{}\n  fabric-run:: {}\n", entry.vendor, entry.model, entry.id));
+
+    let mut in_code = false;
+    // Indentation of the block the current code fence continues
+    let mut code_indent = String::new();
+    for line in body.lines() {
+        if in_code {
+            out.push_str(&format!("{}  {}\n", code_indent, line));
+            in_code = !line.trim_start().starts_with("```");
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (depth, text) = match list_item.captures(line) {
+            Some(c) => (1 + c[1].replace('\t', "  ").len() / 2, c[2].to_string()),
+            None => (1, line.trim().to_string()),
+        };
+        let indent = "\t".repeat(depth);
+        out.push_str(&format!("{}- {}\n", indent, text));
+        if text.starts_with("```") {
+            in_code = true;
+            code_indent = indent;
+        }
+    }
+    out
+}
+
+// Appends to the journal page of the day the export happens, in Logseq's default
+// journals/yyyy_MM_dd.md naming
+fn append_to_logseq(export: &ExportSettings, entry: &HistoryEntry, body: &str) -> Result<String, String> {
+    let graph = export
+        .logseq_graph_dir
+        .as_deref()
+        .filter(|d| !d.trim().is_empty())
+        .ok_or("Choose a Logseq graph folder in settings first.")?;
+    let journals = Path::new(graph).join("journals");
+    fs::create_dir_all(&journals).map_err(|e| e.to_string())?;
+    let path = journals.join(format!("{}.md", Local::now().format("%Y_%m_%d")));
+    let mut page = fs::read_to_string(&path).unwrap_or_default();
+    // A new journal file holds a single empty "-" block
+    if page.trim() == "-" {
+        page.clear();
+    }
+    if !page.is_empty() && !page.ends_with('\n') {
+        page.push('\n');
+    }
+    page.push_str(&logseq_blocks(entry, strip_frontmatter(body)));
+    fs::write(&path, page).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// The arguments are read from argv so the note text needs no AppleScript quoting
+const APPLE_NOTES_SCRIPT: &str = r#"on run argv
+    set noteBody to item 1 of argv
+    set folderName to item 2 of argv
+    tell application "Notes"
+        if folderName is "" then
+            set newNote to make new note with properties {body:noteBody}
+        else
+            if not (exists folder folderName) then make new folder with properties {name:folderName}
+            set newNote to make new note at folder folderName with properties {body:noteBody}
+        end if
+        return id of newNote
+    end tell
+end run"#;
+
+// Notes takes HTML and uses its first line as the note's title
+async fn send_to_apple_notes(export: &ExportSettings, entry: &HistoryEntry, body: &str) -> Result<String, String> {
+    if !cfg!(target_os = "macos") {
+        return Err("Apple Notes export is only available on macOS.".to_string());
+    }
+    let html = format!(
+        "<h1>{}</h1>{}",
+        escape_html(&templates::title(entry)),
+        templates::markdown_to_html(strip_frontmatter(body))
+    );
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(APPLE_NOTES_SCRIPT)
+        .arg(html)
+        .arg(export.apple_notes_folder.as_deref().unwrap_or("").trim())
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        // -1743: the user hasn't allowed Fabric to control Notes
+        if error.contains("-1743") {
+            return Err("Allow Fabric to control Notes in System Settings > Privacy & Security > Automation.".to_string());
+        }
+        return Err(format!("Apple Notes: {}", error.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+async fn send_to_onenote(app_handle: &AppHandle, export: &ExportSettings, entry: &HistoryEntry, body: &str) -> Result<String, String> {
+    let created = Local.timestamp_opt(entry.created_at, 0).single().unwrap_or_else(Local::now);
+    let page = format!(
+        "<!DOCTYPE html><html><head><title>{}</title><meta name=\"created\" content=\"{}\" /></head><body>{}</body></html>",
+        escape_html(&templates::title(entry)),
+        created.to_rfc3339(),
+        templates::markdown_to_html(strip_frontmatter(body))
+    );
+    let section = export.onenote_section.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    onenote::create_page(app_handle, section, &page).await
+}
+
+// The targets export_note accepts on this platform, for the export menu
+#[tauri::command]
+pub async fn export_targets() -> Result<Vec<String>, String> {
+    let mut targets = vec!["obsidian", "joplin", "logseq", "onenote"];
+    if cfg!(target_os = "macos") {
+        targets.push("apple_notes");
+    }
+    Ok(targets.into_iter().map(str::to_string).collect())
+}
+
+// Exports a run to a note app. `target` is "obsidian" (default; a markdown file under
+// notes_dir at its routed path), "joplin" (a new note through the Web Clipper API),
+// "logseq" (blocks appended to today's journal page), "apple_notes" (macOS only) or
+// "onenote" (a page through Microsoft Graph).
+#[tauri::command]
+pub async fn export_note(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsState>,
+    history: State<'_, HistoryState>,
+    run_id: String,
+    template: Option<String>,
+    target: Option<String>,
+) -> Result<ExportedNote, String> {
+    let settings = settings.get();
+    let export = &settings.export;
+    let entry = load_entry(&history, &run_id)?;
+    let target = target.unwrap_or_else(|| "obsidian".to_string());
+    // Logseq gets the bare output; its blocks carry the metadata
+    let body = match (target.as_str(), template.or_else(|| export.note_template.clone())) {
+        ("logseq", None) => entry.output.clone(),
+        (_, template) => templates::render(&app_handle, template.as_deref().unwrap_or("note.md"), &entry)?,
+    };
+    let path = match target.as_str() {
+        "obsidian" => write_file(export, &entry, &body)?,
+        "joplin" => send_to_joplin(&settings, &entry, &body).await?,
+        "logseq" => append_to_logseq(export, &entry, &body)?,
+        "apple_notes" => send_to_apple_notes(export, &entry, &body).await?,
+        "onenote" => send_to_onenote(&app_handle, export, &entry, &body).await?,
+        other => return Err(format!("Unknown export target '{}'.", other)),
+    };
+    Ok(ExportedNote { target, path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Component;
+
+    fn stays_inside(path: &Path) -> bool {
+        path.components().all(|c| matches!(c, Component::Normal(_)))
+    }
+
+    #[test]
+    fn clean_path_drops_parent_and_current_components() {
+        let path = clean_path("../../notes/./../summary").unwrap();
+        assert_eq!(path, Path::new("notes").join("summary.md"));
+        assert_eq!(clean_path("a/.../b.md").unwrap(), Path::new("a").join("b.md"));
+    }
+
+    #[test]
+    fn clean_path_makes_absolute_paths_relative() {
+        for rendered in ["/etc/passwd", "\\\\server\\share\\note", "C:\\Windows\\note.md", "C:/Users/note"] {
+            let path = clean_path(rendered).unwrap();
+            assert!(path.is_relative() && stays_inside(&path), "{} became {}", rendered, path.display());
+        }
+        assert_eq!(clean_path("C:\\Windows\\note.md").unwrap(), Path::new("C").join("Windows").join("note.md"));
+    }
+
+    #[test]
+    fn clean_path_rejects_paths_with_nothing_left() {
+        assert!(clean_path("../..").is_err());
+        assert!(clean_path(" / . / ").is_err());
+    }
+
+    #[test]
+    fn clean_path_keeps_an_extension_and_adds_md_otherwise() {
+        assert_eq!(clean_path("notes/run.txt").unwrap(), Path::new("notes").join("run.txt"));
+        assert_eq!(clean_path("notes/run<1>?").unwrap(), Path::new("notes").join("run1.md"));
+    }
+}
@@ -0,0 +1,284 @@
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::State;
+use crate::cookies;
+use crate::headless;
+use crate::http::{self, AuditedSend};
+use crate::input::{self, TruncationInfo};
+use crate::scrape;
+use crate::settings::{Settings, SettingsState};
+
+// Some sites serve an empty shell or a bot page to clients without a browser user agent
+const BROWSER_USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0 Safari/537.36";
+// Class or id fragments of page furniture rather than content
+const BOILERPLATE_HINTS: &[&str] = &[
+    "nav", "menu", "footer", "header", "sidebar", "comment", "cookie", "share", "social", "promo",
+    "related", "advert", "banner", "subscribe", "newsletter", "breadcrumb", "popup", "modal",
+];
+const LOCAL_STRATEGIES: [&str; 3] = ["readability", "heuristic", "raw"];
+// Fewer words than this after extraction means the page probably needs JavaScript to render
+const NEAR_EMPTY_WORDS: usize = 80;
+
+#[derive(Serialize)]
+pub struct StrategyScore {
+    pub strategy: String,
+    pub quality: f64,
+    pub word_count: usize,
+}
+
+#[derive(Serialize)]
+pub struct ExtractionResult {
+    pub strategy: String,
+    pub markdown: String,
+    // 0 to 1; below roughly 0.3 the page most likely needs another strategy
+    pub quality: f64,
+    pub word_count: usize,
+    // Every strategy tried in "auto" mode, best first
+    pub candidates: Vec<StrategyScore>,
+    // Extracted from the page as rendered by the headless browser fallback
+    pub rendered: bool,
+    // Set when the markdown was cut to the scrape input limit
+    pub truncation: Option<TruncationInfo>,
+}
+
+pub async fn fetch_html(url: &str) -> Result<String, String> {
+    let mut request = http::client_for(url)?
+        .get(url)
+        .header("User-Agent", BROWSER_USER_AGENT)
+        .timeout(Duration::from_secs(30));
+    if let Some(cookie) = cookies::header_for(url) {
+        request = request.header("Cookie", cookie);
+    }
+    let res = request
+        .send_audited("extract", None)
+        .await
+        .map_err(http::network_error)?;
+    if !res.status().is_success() {
+        return Err(format!("Could not load {} ({})", url, res.status()));
+    }
+    res.text().await.map_err(|e| e.to_string())
+}
+
+fn selector(css: &str) -> Selector {
+    Selector::parse(css).unwrap()
+}
+
+fn is_boilerplate(element: &ElementRef) -> bool {
+    let value = element.value();
+    if matches!(value.name(), "nav" | "header" | "footer" | "aside" | "form" | "script" | "style" | "noscript") {
+        return true;
+    }
+    let marker = format!("{} {}", value.attr("class").unwrap_or(""), value.attr("id").unwrap_or("")).to_lowercase();
+    BOILERPLATE_HINTS.iter().any(|hint| marker.contains(hint))
+}
+
+fn inside_boilerplate(element: &ElementRef) -> bool {
+    element.ancestors().filter_map(ElementRef::wrap).any(|a| is_boilerplate(&a))
+}
+
+fn text_len(element: &ElementRef) -> usize {
+    element.text().map(|t| t.trim().len()).sum()
+}
+
+// Whole document to markdown, minus scripts and styles
+fn raw(document: &Html) -> String {
+    let body = document
+        .select(&selector("body"))
+        .next()
+        .map(|b| b.html())
+        .unwrap_or_else(|| document.html());
+    let scripts = Regex::new(r"(?is)<(script|style|noscript)\b.*?</(script|style|noscript)>").unwrap();
+    html2md::parse_html(&scripts.replace_all(&body, ""))
+}
+
+// Readability-style: each paragraph credits its text to its parent (and half to the
+// grandparent), discounted by how much of the container is link text; the best scoring
+// container is taken as the article. Crediting only near ancestors keeps the page wrapper,
+// which contains every paragraph, from winning.
+fn readability(document: &Html) -> String {
+    let links = selector("a");
+    let mut scores = HashMap::new();
+    for paragraph in document.select(&selector("p")) {
+        let text = text_len(&paragraph) as f64;
+        if text < 25.0 || inside_boilerplate(&paragraph) {
+            continue;
+        }
+        let mut ancestors = paragraph.ancestors().filter_map(ElementRef::wrap);
+        if let Some(parent) = ancestors.next() {
+            *scores.entry(parent.id()).or_insert(0.0) += text;
+        }
+        if let Some(grandparent) = ancestors.next() {
+            *scores.entry(grandparent.id()).or_insert(0.0) += text / 2.0;
+        }
+    }
+
+    let best = scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let element = document.tree.get(id).and_then(ElementRef::wrap)?;
+            let linked: usize = element.select(&links).map(|a| text_len(&a)).sum();
+            let density = linked as f64 / text_len(&element).max(1) as f64;
+            Some((element, score * (1.0 - density)))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    match best {
+        Some((element, score)) if score > 0.0 => html2md::parse_html(&element.html()),
+        _ => String::new(),
+    }
+}
+
+// Trafilatura-style: walks the text blocks in order and keeps those outside page furniture
+// that are long enough to be prose, plus headings, list items and code
+fn heuristic(document: &Html) -> String {
+    let blocks = selector("h1, h2, h3, h4, h5, h6, p, li, pre, blockquote");
+    let mut output = Vec::new();
+    for block in document.select(&blocks) {
+        if inside_boilerplate(&block) {
+            continue;
+        }
+        let text = block.text().collect::<Vec<_>>().join(" ");
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let name = block.value().name();
+        let line = match name {
+            "pre" => format!("```\n{}\n```", block.text().collect::<String>().trim_end()),
+            _ if text.is_empty() => continue,
+            h if h.starts_with('h') => format!("{} {}", "#".repeat(h[1..].parse().unwrap_or(2)), text),
+            "li" if text.len() >= 15 => format!("- {}", text),
+            "blockquote" => format!("> {}", text),
+            "p" if text.len() >= 40 || text.ends_with(['.', '!', '?', ':']) => text,
+            _ => continue,
+        };
+        // Nested blocks (a p inside an li or blockquote) would otherwise repeat
+        if output.last().is_some_and(|last: &String| last.contains(&line)) {
+            continue;
+        }
+        output.push(line);
+    }
+    output.join("\n\n")
+}
+
+// Rewards enough prose, penalises text that is mostly links or short fragments (menus,
+// tag clouds, cookie banners)
+fn quality(markdown: &str) -> (f64, usize) {
+    let words = markdown.split_whitespace().count();
+    if words == 0 {
+        return (0.0, 0);
+    }
+    let link = Regex::new(r"\[([^\]]*)\]\([^)]*\)").unwrap();
+    let link_chars: usize = link.find_iter(markdown).map(|m| m.as_str().len()).sum();
+    let link_ratio = link_chars as f64 / markdown.len() as f64;
+
+    let paragraphs: Vec<&str> = markdown.split("\n\n").map(str::trim).filter(|p| !p.is_empty()).collect();
+    let prose = paragraphs
+        .iter()
+        .filter(|p| p.split_whitespace().count() >= 12 && p.contains(['.', '!', '?']))
+        .count() as f64
+        / paragraphs.len() as f64;
+
+    let length = (words as f64 / 300.0).min(1.0);
+    let score = length * 0.45 + prose * 0.35 + (1.0 - link_ratio).max(0.0) * 0.2;
+    ((score * 100.0).round() / 100.0, words)
+}
+
+fn run_local(strategy: &str, html: &str) -> String {
+    let document = Html::parse_document(html);
+    let markdown = match strategy {
+        "readability" => readability(&document),
+        "heuristic" => heuristic(&document),
+        _ => raw(&document),
+    };
+    markdown.trim().to_string()
+}
+
+pub fn extract_html(html: &str, strategy: &str) -> ExtractionResult {
+    let strategies: Vec<&str> = if strategy == "auto" { LOCAL_STRATEGIES.to_vec() } else { vec![strategy] };
+    let mut results: Vec<(String, String, f64, usize)> = strategies
+        .iter()
+        .map(|s| {
+            let markdown = run_local(s, html);
+            let (quality, words) = quality(&markdown);
+            (s.to_string(), markdown, quality, words)
+        })
+        .collect();
+    results.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let candidates = results
+        .iter()
+        .map(|(strategy, _, quality, word_count)| StrategyScore {
+            strategy: strategy.clone(),
+            quality: *quality,
+            word_count: *word_count,
+        })
+        .collect();
+    let (strategy, markdown, quality, word_count) = results.remove(0);
+    ExtractionResult {
+        strategy,
+        markdown,
+        quality,
+        word_count,
+        candidates,
+        rendered: false,
+        truncation: None,
+    }
+}
+
+// `strategy` is "readability", "heuristic", "raw", "jina" (the remote reader scrape_url uses)
+// or "auto" (default), which runs every local strategy and keeps the best scoring one.
+// With the headless_fallback setting on, a page that comes back near-empty (typically a
+// JavaScript app shell) is rendered in a headless browser and extracted again.
+#[tauri::command]
+pub async fn extract_url(
+    state: State<'_, SettingsState>,
+    url: String,
+    strategy: Option<String>,
+) -> Result<ExtractionResult, String> {
+    let settings = state.get();
+    let mut result = extract(&settings, &url, strategy).await?;
+    let (markdown, truncation) = input::limit(&settings, "scrape", std::mem::take(&mut result.markdown));
+    Ok(ExtractionResult { markdown, truncation, ..result })
+}
+
+async fn extract(settings: &Settings, url: &str, strategy: Option<String>) -> Result<ExtractionResult, String> {
+    let strategy = strategy.unwrap_or_else(|| "auto".to_string());
+    if strategy == "jina" {
+        let markdown = scrape::scrape(url, settings.api_key("jina")).await?;
+        let (quality, word_count) = quality(&markdown);
+        return Ok(ExtractionResult {
+            candidates: vec![StrategyScore { strategy: strategy.clone(), quality, word_count }],
+            strategy,
+            markdown,
+            quality,
+            word_count,
+            rendered: false,
+            truncation: None,
+        });
+    }
+    if strategy != "auto" && !LOCAL_STRATEGIES.contains(&strategy.as_str()) {
+        return Err(format!(
+            "Unknown extraction strategy '{}'. Use auto, readability, heuristic, raw or jina.",
+            strategy
+        ));
+    }
+
+    let result = extract_html(&fetch_html(url).await?, &strategy);
+    if !settings.headless_fallback || result.word_count >= NEAR_EMPTY_WORDS {
+        return Ok(result);
+    }
+    match headless::render(settings, url).await {
+        Ok(html) => {
+            let rendered = extract_html(&html, &strategy);
+            if rendered.word_count > result.word_count {
+                return Ok(ExtractionResult { rendered: true, ..rendered });
+            }
+            Ok(result)
+        }
+        // The plain result is still returned when it has something to offer
+        Err(e) if result.word_count == 0 => Err(e),
+        Err(_) => Ok(result),
+    }
+}
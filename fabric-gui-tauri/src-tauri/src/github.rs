@@ -0,0 +1,231 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+use tauri::State;
+use crate::http::{self, AuditedSend};
+use crate::settings::SettingsState;
+
+const GITHUB_API: &str = "https://api.github.com";
+// One page of each; only the first hundred comments of a busy thread are pulled in
+const MAX_GITHUB_COMMENTS: usize = 100;
+const MAX_PR_COMMITS: usize = 100;
+const MAX_PR_FILES: usize = 100;
+
+#[derive(Serialize)]
+pub struct DiffStat {
+    pub filename: String,
+    pub status: String,
+    pub additions: i64,
+    pub deletions: i64,
+}
+
+#[derive(Serialize)]
+pub struct GithubItem {
+    // "issue" or "pull_request"
+    pub kind: String,
+    pub repo: String,
+    pub number: u64,
+    pub title: String,
+    pub state: String,
+    pub author: String,
+    pub comments: usize,
+    pub files: Vec<DiffStat>,
+    // First lines of the PR's commits, or commits that referenced the issue
+    pub commits: Vec<String>,
+    // The whole item as markdown for a pattern
+    pub text: String,
+}
+
+fn parse_url(url: &str) -> Option<(String, u64)> {
+    let item = Regex::new(r"^https?://(?:www\.)?github\.com/([^/]+/[^/]+)/(?:issues|pull)/(\d+)").unwrap();
+    item.captures(url.trim()).and_then(|c| Some((c[1].to_string(), c[2].parse().ok()?)))
+}
+
+pub fn is_github_item_url(url: &str) -> bool {
+    parse_url(url).is_some()
+}
+
+async fn get(path: &str, token: Option<&str>) -> Result<Value, String> {
+    let url = format!("{}{}", GITHUB_API, path);
+    let mut request = http::client_for(&url)?
+        .get(&url)
+        .header("User-Agent", format!("fabric-gui/{}", env!("CARGO_PKG_VERSION")))
+        .header("Accept", "application/vnd.github+json")
+        .timeout(Duration::from_secs(30));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let res = request.send_audited("github", None).await.map_err(http::network_error)?;
+    let status = res.status();
+    if status.as_u16() == 404 {
+        return Err("Not found; private repositories need a GitHub token with read access.".to_string());
+    }
+    if status.as_u16() == 403 || status.as_u16() == 429 {
+        return Err("GitHub rate limit reached; set a GitHub token to raise it.".to_string());
+    }
+    if !status.is_success() {
+        return Err(format!("GitHub API error ({})", status));
+    }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+fn login(user: &Value) -> String {
+    user["login"].as_str().unwrap_or("ghost").to_string()
+}
+
+fn first_line(message: &str) -> &str {
+    message.lines().next().unwrap_or("")
+}
+
+// Issue comments and, for PRs, review comments on the diff, in the order they were made
+async fn discussion(repo: &str, number: u64, is_pr: bool, token: Option<&str>) -> Result<Vec<(String, String)>, String> {
+    let issue = get(&format!("/repos/{}/issues/{}/comments?per_page={}", repo, number, MAX_GITHUB_COMMENTS), token).await?;
+    let mut comments: Vec<(String, String, String)> = issue
+        .as_array()
+        .map(|c| {
+            c.iter()
+                .map(|c| {
+                    let created = c["created_at"].as_str().unwrap_or("").to_string();
+                    let body = format!("**{}** ({}):\n{}", login(&c["user"]), &created[..created.len().min(10)], c["body"].as_str().unwrap_or("").trim());
+                    (created, login(&c["user"]), body)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if is_pr {
+        let reviews = get(&format!("/repos/{}/pulls/{}/comments?per_page={}", repo, number, MAX_GITHUB_COMMENTS), token).await?;
+        for c in reviews.as_array().into_iter().flatten() {
+            let created = c["created_at"].as_str().unwrap_or("").to_string();
+            let body = format!(
+                "**{}** on `{}` ({}):\n{}",
+                login(&c["user"]),
+                c["path"].as_str().unwrap_or(""),
+                &created[..created.len().min(10)],
+                c["body"].as_str().unwrap_or("").trim()
+            );
+            comments.push((created, login(&c["user"]), body));
+        }
+    }
+    comments.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(comments.into_iter().map(|(_, author, body)| (author, body)).collect())
+}
+
+// Commits whose messages mention the issue show up as "referenced" timeline events
+async fn referencing_commits(repo: &str, number: u64, token: Option<&str>) -> Vec<String> {
+    let Ok(timeline) = get(&format!("/repos/{}/issues/{}/timeline?per_page=100", repo, number), token).await else {
+        return Vec::new();
+    };
+    timeline
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|e| e["event"] == "referenced")
+        .filter_map(|e| e["commit_id"].as_str())
+        .map(|sha| sha[..sha.len().min(7)].to_string())
+        .collect()
+}
+
+// Pulls an issue or pull request with its description, comments (review comments too),
+// diff stats and commits. The token falls back to api_keys "github" in settings; public
+// repositories work without one at a low rate limit.
+#[tauri::command]
+pub async fn fetch_github_item(
+    state: State<'_, SettingsState>,
+    url: String,
+    token: Option<String>,
+) -> Result<GithubItem, String> {
+    let token = token.filter(|t| !t.trim().is_empty()).or_else(|| state.get().api_key("github"));
+    fetch(&url, token.as_deref()).await
+}
+
+pub async fn fetch(url: &str, token: Option<&str>) -> Result<GithubItem, String> {
+    let (repo, number) = parse_url(url).ok_or_else(|| format!("'{}' is not a GitHub issue or pull request link.", url))?;
+    // The issues endpoint serves both; pull requests carry a pull_request key
+    let issue = get(&format!("/repos/{}/issues/{}", repo, number), token).await?;
+    let is_pr = issue["pull_request"].is_object();
+
+    let mut files = Vec::new();
+    let commits;
+    let mut text = format!(
+        "# {} #{}: {}\n\n{} · {} · opened by {}",
+        repo,
+        number,
+        issue["title"].as_str().unwrap_or(""),
+        if is_pr { "Pull request" } else { "Issue" },
+        issue["state"].as_str().unwrap_or(""),
+        login(&issue["user"])
+    );
+    let labels: Vec<&str> = issue["labels"].as_array().into_iter().flatten().filter_map(|l| l["name"].as_str()).collect();
+    if !labels.is_empty() {
+        text.push_str(&format!(" · labels: {}", labels.join(", ")));
+    }
+    text.push_str(&format!("\n\n## Description\n\n{}\n", issue["body"].as_str().unwrap_or("(no description)").trim()));
+
+    if is_pr {
+        let pr = get(&format!("/repos/{}/pulls/{}", repo, number), token).await?;
+        text.push_str(&format!(
+            "\n## Changes\n\n{} → {} · {} commits · +{} −{} in {} files{}\n",
+            pr["head"]["label"].as_str().unwrap_or(""),
+            pr["base"]["ref"].as_str().unwrap_or(""),
+            pr["commits"].as_i64().unwrap_or(0),
+            pr["additions"].as_i64().unwrap_or(0),
+            pr["deletions"].as_i64().unwrap_or(0),
+            pr["changed_files"].as_i64().unwrap_or(0),
+            if pr["merged"] == true { " · merged" } else { "" }
+        ));
+        let changed = get(&format!("/repos/{}/pulls/{}/files?per_page={}", repo, number, MAX_PR_FILES), token).await?;
+        files = changed
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|f| DiffStat {
+                filename: f["filename"].as_str().unwrap_or("").to_string(),
+                status: f["status"].as_str().unwrap_or("").to_string(),
+                additions: f["additions"].as_i64().unwrap_or(0),
+                deletions: f["deletions"].as_i64().unwrap_or(0),
+            })
+            .collect();
+        for f in &files {
+            text.push_str(&format!("- {} ({}, +{} −{})\n", f.filename, f.status, f.additions, f.deletions));
+        }
+
+        let pr_commits = get(&format!("/repos/{}/pulls/{}/commits?per_page={}", repo, number, MAX_PR_COMMITS), token).await?;
+        commits = pr_commits
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|c| {
+                let sha = c["sha"].as_str().unwrap_or("");
+                format!("{} {}", &sha[..sha.len().min(7)], first_line(c["commit"]["message"].as_str().unwrap_or("")))
+            })
+            .collect();
+    } else {
+        commits = referencing_commits(&repo, number, token).await;
+    }
+    if !commits.is_empty() {
+        text.push_str(&format!("\n## Commits\n\n{}\n", commits.iter().map(|c| format!("- {}", c)).collect::<Vec<_>>().join("\n")));
+    }
+
+    let comments = discussion(&repo, number, is_pr, token).await?;
+    if !comments.is_empty() {
+        text.push_str("\n## Comments\n");
+        for (_, body) in &comments {
+            text.push_str(&format!("\n{}\n", body));
+        }
+    }
+
+    Ok(GithubItem {
+        kind: if is_pr { "pull_request" } else { "issue" }.to_string(),
+        number,
+        title: issue["title"].as_str().unwrap_or("").to_string(),
+        state: issue["state"].as_str().unwrap_or("").to_string(),
+        author: login(&issue["user"]),
+        comments: comments.len(),
+        repo,
+        files,
+        commits,
+        text,
+    })
+}
@@ -0,0 +1,166 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+use crate::extract;
+use crate::http::{self, AuditedSend};
+
+const ALGOLIA_ITEM_URL: &str = "https://hn.algolia.com/api/v1/items/";
+const FIREBASE_ITEM_URL: &str = "https://hacker-news.firebaseio.com/v0/item/";
+const DEFAULT_HN_COMMENTS: usize = 40;
+const MAX_REPLY_DEPTH: usize = 3;
+
+#[derive(Serialize)]
+pub struct HnComment {
+    pub author: String,
+    pub text: String,
+    // Replies anywhere below this comment, which is what ranks replies
+    pub descendants: usize,
+    pub replies: Vec<HnComment>,
+}
+
+#[derive(Serialize)]
+pub struct HnItem {
+    pub id: u64,
+    pub title: String,
+    pub url: Option<String>,
+    pub author: Option<String>,
+    pub points: Option<i64>,
+    // Extracted text of the linked article, or the post itself for Ask/Show HN
+    pub article: Option<String>,
+    pub comments: Vec<HnComment>,
+    // Everything above as one markdown digest
+    pub text: String,
+}
+
+// Accepts "41234567", "item?id=41234567" links and hn.algolia.com item links
+fn hn_id(input: &str) -> Option<u64> {
+    let id = Regex::new(r"^(?:https?://(?:news\.ycombinator\.com/item\?id=|hn\.algolia\.com/.*?(?:story|item)[/=]))?(\d+)(?:[&#].*)?$").unwrap();
+    id.captures(input.trim()).and_then(|c| c[1].parse().ok())
+}
+
+pub fn is_hn_url(input: &str) -> bool {
+    input.trim().starts_with("http") && hn_id(input).is_some()
+}
+
+async fn get_json(url: &str) -> Result<Value, String> {
+    let res = http::client_for(url)?
+        .get(url)
+        .timeout(Duration::from_secs(30))
+        .send_audited("hackernews", None)
+        .await
+        .map_err(http::network_error)?;
+    if !res.status().is_success() {
+        return Err(format!("Could not load {} ({})", url, res.status()));
+    }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+fn descendants(item: &Value) -> usize {
+    item["children"]
+        .as_array()
+        .map(|c| c.iter().map(|child| 1 + descendants(child)).sum())
+        .unwrap_or(0)
+}
+
+fn comment(item: &Value, depth: usize, remaining: &mut usize) -> Option<HnComment> {
+    let text = item["text"].as_str().filter(|t| !t.is_empty())?;
+    if *remaining == 0 {
+        return None;
+    }
+    *remaining -= 1;
+    let replies = if depth + 1 < MAX_REPLY_DEPTH { comments(item, None, depth + 1, remaining) } else { Vec::new() };
+    Some(HnComment {
+        author: item["author"].as_str().unwrap_or("[deleted]").to_string(),
+        text: html2md::parse_html(text).trim().to_string(),
+        descendants: descendants(item),
+        replies,
+    })
+}
+
+// Top-level comments follow the site's own ranking when known; replies, which the APIs
+// don't rank, are ordered by how much discussion they drew
+fn comments(item: &Value, ranking: Option<&[u64]>, depth: usize, remaining: &mut usize) -> Vec<HnComment> {
+    let mut children: Vec<&Value> = item["children"].as_array().map(|c| c.iter().collect()).unwrap_or_default();
+    match ranking {
+        Some(ranking) => children.sort_by_key(|c| {
+            let id = c["id"].as_u64().unwrap_or(0);
+            ranking.iter().position(|r| *r == id).unwrap_or(usize::MAX)
+        }),
+        None => children.sort_by_key(|c| std::cmp::Reverse(descendants(c))),
+    }
+    children.into_iter().filter_map(|c| comment(c, depth, remaining)).collect()
+}
+
+fn render_comments(comments: &[HnComment], depth: usize, output: &mut Vec<String>) {
+    for c in comments {
+        let indent = "  ".repeat(depth);
+        let text = c.text.replace('\n', &format!("\n{}  ", indent));
+        output.push(format!("{}- **{}** ({} replies): {}", indent, c.author, c.descendants, text));
+        render_comments(&c.replies, depth + 1, output);
+    }
+}
+
+// Builds a digest of a Hacker News story: the linked article (or the post text) followed
+// by its ranked comment tree, for debate and sentiment patterns
+pub async fn fetch(id_or_url: &str, max_comments: Option<usize>) -> Result<HnItem, String> {
+    let id = hn_id(id_or_url).ok_or_else(|| format!("'{}' is not a Hacker News item ID or link.", id_or_url))?;
+    let item = get_json(&format!("{}{}", ALGOLIA_ITEM_URL, id)).await?;
+    if item["type"] != "story" && item["type"] != "poll" {
+        return Err(format!("Hacker News item {} is a {}, not a story.", id, item["type"].as_str().unwrap_or("deleted item")));
+    }
+    // Only the Firebase API knows the order the site shows comments in
+    let ranking: Vec<u64> = get_json(&format!("{}{}.json", FIREBASE_ITEM_URL, id))
+        .await
+        .ok()
+        .and_then(|i| i["kids"].as_array().map(|k| k.iter().filter_map(|k| k.as_u64()).collect()))
+        .unwrap_or_default();
+
+    let title = item["title"].as_str().unwrap_or("").to_string();
+    let url = item["url"].as_str().filter(|u| !u.is_empty()).map(|u| u.to_string());
+    let author = item["author"].as_str().map(|a| a.to_string());
+    let points = item["points"].as_i64();
+    let article = match (&url, item["text"].as_str().filter(|t| !t.is_empty())) {
+        (_, Some(text)) => Some(html2md::parse_html(text).trim().to_string()),
+        // The discussion is still worth having when the article can't be loaded
+        (Some(url), None) => extract::fetch_html(url).await.ok().map(|html| extract::extract_html(&html, "auto").markdown),
+        (None, None) => None,
+    };
+    let mut remaining = max_comments.unwrap_or(DEFAULT_HN_COMMENTS);
+    let comments = comments(&item, (!ranking.is_empty()).then_some(ranking.as_slice()), 0, &mut remaining);
+
+    let mut text = format!(
+        "# {}\n\n{} points · by {} · https://news.ycombinator.com/item?id={}\n",
+        title,
+        points.unwrap_or(0),
+        author.as_deref().unwrap_or("[deleted]"),
+        id
+    );
+    if let Some(url) = &url {
+        text.push_str(&format!("Link: {}\n", url));
+    }
+    if let Some(article) = article.as_ref().filter(|a| !a.is_empty()) {
+        text.push_str(&format!("\n## Article\n\n{}\n", article));
+    }
+    if !comments.is_empty() {
+        let mut lines = Vec::new();
+        render_comments(&comments, 0, &mut lines);
+        text.push_str(&format!("\n## Discussion\n\n{}\n", lines.join("\n")));
+    }
+
+    Ok(HnItem {
+        id,
+        title,
+        url,
+        author,
+        points,
+        article,
+        comments,
+        text,
+    })
+}
+
+#[tauri::command]
+pub async fn fetch_hn_item(id_or_url: String, max_comments: Option<usize>) -> Result<HnItem, String> {
+    fetch(&id_or_url, max_comments).await
+}
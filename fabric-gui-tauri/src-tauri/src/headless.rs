@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use reqwest::Url;
+use tokio::process::Command;
+use uuid::Uuid;
+use crate::audit::{self, AuditEntry};
+use crate::history::now_secs;
+use crate::http;
+use crate::settings::Settings;
+
+const RENDER_TIMEOUT: Duration = Duration::from_secs(45);
+// Milliseconds of page time scripts get to hydrate before the DOM is dumped
+const VIRTUAL_TIME_BUDGET: &str = "10000";
+
+// Chromium-based browsers that support --dump-dom, looked up on the PATH and in their usual
+// install locations when no browser is configured
+const BROWSER_NAMES: &[&str] = &["chromium", "chromium-browser", "google-chrome", "google-chrome-stable", "chrome", "msedge"];
+const BROWSER_PATHS: &[&str] = &[
+    r"C:\Program Files\Google\Chrome\Application\chrome.exe",
+    r"C:\Program Files (x86)\Google\Chrome\Application\chrome.exe",
+    r"C:\Program Files (x86)\Microsoft\Edge\Application\msedge.exe",
+    "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+    "/Applications/Chromium.app/Contents/MacOS/Chromium",
+    "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge",
+];
+
+fn on_path(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| {
+            std::env::split_paths(&paths).any(|dir| dir.join(name).is_file() || dir.join(format!("{}.exe", name)).is_file())
+        })
+        .unwrap_or(false)
+}
+
+pub fn find_browser(settings: &Settings) -> Option<String> {
+    if let Some(browser) = settings.headless_browser.as_ref().filter(|b| !b.trim().is_empty()) {
+        return Some(browser.clone());
+    }
+    BROWSER_NAMES
+        .iter()
+        .find(|name| on_path(name))
+        .map(|name| name.to_string())
+        .or_else(|| BROWSER_PATHS.iter().find(|p| Path::new(p).is_file()).map(|p| p.to_string()))
+}
+
+// Loads the page in a headless browser and returns the DOM after its scripts have run. The
+// browser fetches the page and whatever it pulls in by itself, so the firewall could only vet
+// the first URL: rendering is refused unless the firewall is off. It goes through the app's
+// proxy, starts from an empty profile and is recorded in the audit log as one request.
+pub async fn render(settings: &Settings, url: &str) -> Result<String, String> {
+    if !http::firewall_is_off() {
+        return Err("Rendering pages in a headless browser needs the network firewall set to off, as the browser loads scripts and images from hosts the firewall can't check.".to_string());
+    }
+    // Denied hosts are blocked even with the firewall off
+    http::check_host(url)?;
+    let proxy_args = http::browser_proxy_args(url)?;
+    let browser = find_browser(settings).ok_or(
+        "No Chromium-based browser found for rendering; install Chrome or Chromium, or set headless_browser in settings.",
+    )?;
+    let profile = std::env::temp_dir().join(format!("fabric-headless-{}", Uuid::new_v4()));
+    let child = Command::new(&browser)
+        .arg("--headless=new")
+        .arg("--disable-gpu")
+        .arg("--no-first-run")
+        .arg(format!("--user-data-dir={}", profile.display()))
+        .args(&proxy_args)
+        .arg(format!("--virtual-time-budget={}", VIRTUAL_TIME_BUDGET))
+        .arg("--dump-dom")
+        .arg(url)
+        .kill_on_drop(true)
+        .output();
+
+    let mut entry = AuditEntry {
+        at: now_secs(),
+        method: "GET".to_string(),
+        endpoint: Url::parse(url).map(|u| http::audit_endpoint(&u)).unwrap_or_default(),
+        purpose: "headless_render".to_string(),
+        bytes_sent: Some(0),
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let result = match tokio::time::timeout(RENDER_TIMEOUT, child).await {
+        Err(_) => Err("The headless browser did not finish rendering the page in time.".to_string()),
+        Ok(Err(e)) => Err(format!("Could not start {}: {}", browser, e)),
+        Ok(Ok(output)) if !output.status.success() => Err(String::from_utf8_lossy(&output.stderr).to_string()),
+        Ok(Ok(output)) => {
+            entry.bytes_received = Some(output.stdout.len() as u64);
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+    };
+    let _ = fs::remove_dir_all(&profile);
+    entry.duration_ms = started.elapsed().as_millis() as u64;
+    entry.error = result.as_ref().err().cloned();
+    audit::record(entry);
+    result
+}
@@ -0,0 +1,198 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use reqwest::Client;
+use tauri::State;
+use crate::http::{self, AuditedSend};
+use crate::huggingface;
+use crate::lmstudio;
+use crate::residency;
+use crate::i18n::{tr, tr_args};
+use crate::settings::SettingsState;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    MissingKey,
+    InvalidKey,
+    PermissionDenied,
+    RateLimited,
+    QuotaExceeded,
+    ModelNotFound,
+    ProviderOutage,
+    Timeout,
+    NetworkUnreachable,
+    Unknown,
+}
+
+#[derive(Serialize)]
+pub struct ConnectionReport {
+    pub vendor: String,
+    pub ok: bool,
+    pub latency_ms: u64,
+    pub status_code: Option<u16>,
+    pub models_available: usize,
+    pub model_available: Option<bool>,
+    pub error_kind: Option<ErrorKind>,
+    pub message: String,
+}
+
+impl ConnectionReport {
+    fn failed(vendor: &str, latency_ms: u64, status_code: Option<u16>, kind: ErrorKind, message: String) -> Self {
+        Self {
+            vendor: vendor.to_string(),
+            ok: false,
+            latency_ms,
+            status_code,
+            models_available: 0,
+            model_available: None,
+            error_kind: Some(kind),
+            message,
+        }
+    }
+}
+
+pub fn classify_status(status: u16, body: &str) -> ErrorKind {
+    let lower = body.to_lowercase();
+    match status {
+        401 => ErrorKind::InvalidKey,
+        // Google reports bad keys as 400 INVALID_ARGUMENT
+        400 if lower.contains("api_key") || lower.contains("api key") => ErrorKind::InvalidKey,
+        403 => ErrorKind::PermissionDenied,
+        404 => ErrorKind::ModelNotFound,
+        429 if lower.contains("quota") || lower.contains("billing") => ErrorKind::QuotaExceeded,
+        429 => ErrorKind::RateLimited,
+        500..=599 => ErrorKind::ProviderOutage,
+        _ => ErrorKind::Unknown,
+    }
+}
+
+fn describe(kind: ErrorKind, vendor: &str) -> String {
+    match kind {
+        ErrorKind::MissingKey => tr_args("health-missing-key", &[("vendor", vendor)]),
+        ErrorKind::InvalidKey => tr("health-invalid-key"),
+        ErrorKind::PermissionDenied => tr("health-permission-denied"),
+        ErrorKind::RateLimited => tr("health-rate-limited"),
+        ErrorKind::QuotaExceeded => tr("health-quota-exceeded"),
+        ErrorKind::ModelNotFound => tr("health-model-not-found"),
+        ErrorKind::ProviderOutage => tr_args("health-provider-outage", &[("vendor", vendor)]),
+        ErrorKind::Timeout => tr("health-timeout"),
+        ErrorKind::NetworkUnreachable => tr("health-network-unreachable"),
+        ErrorKind::Unknown => tr("health-unknown"),
+    }
+}
+
+fn parse_model_ids(vendor: &str, json: &serde_json::Value) -> Vec<String> {
+    let (list_key, id_key) = match vendor {
+        "google" => ("models", "name"),
+        _ => ("data", "id"),
+    };
+
+    json.get(list_key)
+        .and_then(|l| l.as_array())
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| m.get(id_key).and_then(|id| id.as_str()))
+                .map(|id| id.trim_start_matches("models/").to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Lists models rather than generating text so the check costs no tokens
+pub async fn probe(client: &Client, vendor: &str, api_key: &str, model: Option<&str>) -> ConnectionReport {
+    let request = match vendor {
+        "google" => client.get(format!(
+            "https://generativelanguage.googleapis.com/v1beta/models?pageSize=1000&key={}",
+            api_key
+        )),
+        "openai" => client
+            .get(format!("{}/v1/models", residency::openai_base_url()))
+            .header("Authorization", format!("Bearer {}", api_key)),
+        "xai" => client
+            .get("https://api.x.ai/v1/models")
+            .header("Authorization", format!("Bearer {}", api_key)),
+        "deepseek" => client
+            .get("https://api.deepseek.com/models")
+            .header("Authorization", format!("Bearer {}", api_key)),
+        "lmstudio" => client.get(lmstudio::models_url()),
+        "huggingface" if api_key.is_empty() => client.get(huggingface::probe_url()),
+        "huggingface" => client
+            .get(huggingface::probe_url())
+            .header("Authorization", format!("Bearer {}", api_key)),
+        "anthropic" => client
+            .get("https://api.anthropic.com/v1/models?limit=1000")
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01"),
+        _ => return ConnectionReport::failed(vendor, 0, None, ErrorKind::Unknown, tr("unsupported-vendor")),
+    };
+
+    let started = Instant::now();
+    let result = request.timeout(Duration::from_secs(15)).send_audited("health_check", None).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let res = match result {
+        Ok(res) => res,
+        Err(e) => {
+            let kind = if e.is_timeout() { ErrorKind::Timeout } else { ErrorKind::NetworkUnreachable };
+            return ConnectionReport::failed(vendor, latency_ms, None, kind, format!("{} ({})", describe(kind, vendor), e));
+        }
+    };
+
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+    if !status.is_success() {
+        let kind = classify_status(status.as_u16(), &body);
+        return ConnectionReport::failed(vendor, latency_ms, Some(status.as_u16()), kind, describe(kind, vendor));
+    }
+
+    let model_ids = serde_json::from_str::<serde_json::Value>(&body)
+        .map(|json| parse_model_ids(vendor, &json))
+        .unwrap_or_default();
+    // Hugging Face has no per-account model list to check against
+    let model_available = model
+        .filter(|_| vendor != "huggingface")
+        .map(|m| model_ids.iter().any(|id| id == m));
+
+    let (ok, error_kind, message) = match model_available {
+        Some(false) => (false, Some(ErrorKind::ModelNotFound), describe(ErrorKind::ModelNotFound, vendor)),
+        _ => (true, None, tr("health-connected")),
+    };
+
+    ConnectionReport {
+        vendor: vendor.to_string(),
+        ok,
+        latency_ms,
+        status_code: Some(status.as_u16()),
+        models_available: model_ids.len(),
+        model_available,
+        error_kind,
+        message,
+    }
+}
+
+#[tauri::command]
+pub async fn test_vendor_connection(
+    state: State<'_, SettingsState>,
+    vendor: String,
+    api_key: Option<String>,
+    model: Option<String>,
+) -> Result<ConnectionReport, String> {
+    let key = api_key
+        .filter(|k| !k.trim().is_empty())
+        .or_else(|| state.get().api_key(&vendor));
+
+    let key = match key {
+        Some(key) => key,
+        // Local servers take no key
+        None if vendor == "lmstudio" => String::new(),
+        // A self-hosted TGI server may not require one
+        None if vendor == "huggingface" && !huggingface::is_serverless() => String::new(),
+        None => {
+            let message = describe(ErrorKind::MissingKey, &vendor);
+            return Ok(ConnectionReport::failed(&vendor, 0, None, ErrorKind::MissingKey, message));
+        }
+    };
+
+    Ok(probe(&http::vendor_client(&vendor)?, &vendor, &key, model.as_deref()).await)
+}
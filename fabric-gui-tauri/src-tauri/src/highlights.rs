@@ -0,0 +1,291 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tauri::State;
+use crate::http::{self, AuditedSend};
+use crate::settings::{Settings, SettingsState};
+
+const READWISE_API: &str = "https://readwise.io/api/v2";
+const RAINDROP_API: &str = "https://api.raindrop.io/rest/v1";
+const DEFAULT_LIMIT: usize = 20;
+const READWISE_SEARCH_BOOKS: usize = 1000;
+// Raindrop's page size cap
+const RAINDROP_PAGE: usize = 50;
+
+#[derive(Serialize)]
+pub struct HighlightSource {
+    // Readwise book id or Raindrop bookmark id, for push_highlight_note
+    pub id: String,
+    pub title: String,
+    pub author: Option<String>,
+    pub url: Option<String>,
+    pub highlights: usize,
+}
+
+#[derive(Serialize)]
+pub struct Highlights {
+    // "readwise" or "raindrop"
+    pub service: String,
+    pub sources: Vec<HighlightSource>,
+    // Every source with its highlights and notes as markdown for a pattern
+    pub text: String,
+}
+
+fn token(settings: &Settings, service: &str) -> Result<String, String> {
+    settings
+        .api_key(service)
+        .ok_or_else(|| format!("Add a {} API token in settings first.", service))
+}
+
+// Readwise takes "Token <key>", Raindrop a bearer token
+async fn call(settings: &Settings, service: &str, method: reqwest::Method, url: &str, body: Option<Value>) -> Result<Value, String> {
+    let token = token(settings, service)?;
+    let authorization = match service {
+        "readwise" => format!("Token {}", token),
+        _ => format!("Bearer {}", token),
+    };
+    let mut request = http::client_for(url)?
+        .request(method, url)
+        .header("Authorization", authorization)
+        .timeout(Duration::from_secs(30));
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    let res = request.send_audited("highlights", None).await.map_err(http::network_error)?;
+    let status = res.status();
+    if status.as_u16() == 401 || status.as_u16() == 403 {
+        return Err(format!("{} rejected the API token ({}).", service, status));
+    }
+    if status.as_u16() == 429 {
+        return Err(format!("{} is rate limiting requests; try again in a minute.", service));
+    }
+    if !status.is_success() {
+        let text = res.text().await.unwrap_or_default();
+        return Err(format!("{} API error ({}): {}", service, status, &text.chars().take(300).collect::<String>()));
+    }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+fn source_text(title: &str, author: Option<&str>, url: Option<&str>, quotes: &[(String, Option<String>)]) -> String {
+    let mut text = format!("## {}", title);
+    if let Some(author) = author {
+        text.push_str(&format!(" — {}", author));
+    }
+    text.push('\n');
+    if let Some(url) = url {
+        text.push_str(&format!("Source: {}\n", url));
+    }
+    for (quote, note) in quotes {
+        text.push_str(&format!("\n> {}\n", quote.trim().replace('\n', "\n> ")));
+        if let Some(note) = note.as_deref().filter(|n| !n.trim().is_empty()) {
+            text.push_str(&format!("\nNote: {}\n", note.trim()));
+        }
+    }
+    text
+}
+
+// Book, collection and bookmark ids are numbers; Raindrop's system collections are negative
+fn check_id(id: Option<&str>) -> Result<(), String> {
+    match id {
+        Some(id) if id.parse::<i64>().is_err() => Err(format!("'{}' is not a Readwise or Raindrop id.", id)),
+        _ => Ok(()),
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value[key].as_str().filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+// Books (articles, tweets, ...) with their highlights through the export endpoint, newest
+// first; `source` limits it to one book id
+async fn readwise_books(settings: &Settings, source: Option<&str>, limit: usize) -> Result<Vec<Value>, String> {
+    let mut books = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut url = format!("{}/export/?", READWISE_API);
+        if let Some(id) = source {
+            url.push_str(&format!("ids={}&", id));
+        }
+        if let Some(cursor) = &cursor {
+            url.push_str(&format!("pageCursor={}", cursor));
+        }
+        let page = call(settings, "readwise", reqwest::Method::GET, &url, None).await?;
+        books.extend(page["results"].as_array().cloned().unwrap_or_default());
+        cursor = match &page["nextPageCursor"] {
+            Value::String(c) => Some(c.clone()),
+            Value::Number(c) => Some(c.to_string()),
+            _ => None,
+        };
+        if cursor.is_none() || books.len() >= limit {
+            break;
+        }
+    }
+    books.truncate(limit);
+    Ok(books)
+}
+
+async fn fetch_readwise(settings: &Settings, source: Option<&str>, search: Option<&str>, limit: usize) -> Result<Highlights, String> {
+    let search = search.map(str::to_lowercase);
+    let mut sources = Vec::new();
+    let mut texts = Vec::new();
+    // Readwise has no search endpoint, so searching filters the most recent books locally
+    let fetch = if search.is_some() { READWISE_SEARCH_BOOKS } else { limit };
+    for book in readwise_books(settings, source, fetch).await? {
+        let title = str_field(&book, "readable_title").or_else(|| str_field(&book, "title")).unwrap_or_default();
+        let author = str_field(&book, "author");
+        let url = str_field(&book, "source_url");
+        let quotes: Vec<(String, Option<String>)> = book["highlights"]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter(|h| h["is_deleted"] != true)
+                    .map(|h| (str_field(h, "text").unwrap_or_default(), str_field(h, "note")))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let Some(search) = &search {
+            let hit = title.to_lowercase().contains(search)
+                || quotes.iter().any(|(q, n)| q.to_lowercase().contains(search) || n.as_deref().is_some_and(|n| n.to_lowercase().contains(search)));
+            if !hit {
+                continue;
+            }
+        }
+        texts.push(source_text(&title, author.as_deref(), url.as_deref(), &quotes));
+        sources.push(HighlightSource {
+            id: book["user_book_id"].as_i64().map(|id| id.to_string()).unwrap_or_default(),
+            title,
+            author,
+            url,
+            highlights: quotes.len(),
+        });
+        if sources.len() >= limit {
+            break;
+        }
+    }
+    Ok(Highlights { service: "readwise".to_string(), sources, text: texts.join("\n\n") })
+}
+
+// Bookmarks in a collection (default: all of them) with their excerpt, note and highlights
+async fn fetch_raindrop(settings: &Settings, source: Option<&str>, search: Option<&str>, limit: usize) -> Result<Highlights, String> {
+    let collection = source.unwrap_or("0");
+    let mut items = Vec::new();
+    for page in 0.. {
+        let mut url = reqwest::Url::parse(&format!("{}/raindrops/{}", RAINDROP_API, collection)).map_err(|e| e.to_string())?;
+        url.query_pairs_mut()
+            .append_pair("perpage", &RAINDROP_PAGE.to_string())
+            .append_pair("page", &page.to_string());
+        if let Some(search) = search {
+            url.query_pairs_mut().append_pair("search", search);
+        }
+        let body = call(settings, "raindrop", reqwest::Method::GET, url.as_str(), None).await?;
+        let batch = body["items"].as_array().cloned().unwrap_or_default();
+        let done = batch.len() < RAINDROP_PAGE;
+        items.extend(batch);
+        if done || items.len() >= limit {
+            break;
+        }
+    }
+    items.truncate(limit);
+
+    let mut sources = Vec::new();
+    let mut texts = Vec::new();
+    for item in items {
+        let title = str_field(&item, "title").unwrap_or_default();
+        let url = str_field(&item, "link");
+        let mut quotes: Vec<(String, Option<String>)> = item["highlights"]
+            .as_array()
+            .map(|h| h.iter().map(|h| (str_field(h, "text").unwrap_or_default(), str_field(h, "note"))).collect())
+            .unwrap_or_default();
+        let highlights = quotes.len();
+        // The excerpt and the bookmark's own note stand in when nothing was highlighted
+        if quotes.is_empty() {
+            if let Some(excerpt) = str_field(&item, "excerpt") {
+                quotes.push((excerpt, None));
+            }
+        }
+        let mut text = source_text(&title, None, url.as_deref(), &quotes);
+        if let Some(note) = str_field(&item, "note") {
+            text.push_str(&format!("\nNote: {}\n", note.trim()));
+        }
+        texts.push(text);
+        sources.push(HighlightSource {
+            id: item["_id"].as_i64().map(|id| id.to_string()).unwrap_or_default(),
+            title,
+            author: None,
+            url,
+            highlights,
+        });
+    }
+    Ok(Highlights { service: "raindrop".to_string(), sources, text: texts.join("\n\n") })
+}
+
+// Pulls highlights from Readwise or bookmarks from Raindrop as pattern input. `source` is a
+// Readwise book id or a Raindrop collection id; `search` narrows to matching items.
+#[tauri::command]
+pub async fn fetch_highlights(
+    state: State<'_, SettingsState>,
+    service: String,
+    source: Option<String>,
+    search: Option<String>,
+    limit: Option<usize>,
+) -> Result<Highlights, String> {
+    let settings = state.get();
+    let source = source.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    check_id(source)?;
+    let search = search.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).max(1);
+    match service.as_str() {
+        "readwise" => fetch_readwise(&settings, source, search, limit).await,
+        "raindrop" => fetch_raindrop(&settings, source, search, limit).await,
+        other => Err(format!("Unknown service '{}'. Use readwise or raindrop.", other)),
+    }
+}
+
+// Saves a generated summary back with its source: on Readwise as a highlight in the same
+// book, on Raindrop appended to the bookmark's note
+#[tauri::command]
+pub async fn push_highlight_note(
+    state: State<'_, SettingsState>,
+    service: String,
+    source_id: String,
+    text: String,
+) -> Result<(), String> {
+    if text.trim().is_empty() {
+        return Err("The note is empty.".to_string());
+    }
+    check_id(Some(source_id.trim()))?;
+    let source_id = source_id.trim();
+    let settings = state.get();
+    match service.as_str() {
+        "readwise" => {
+            // Readwise files a highlight under the book with the same title, author and URL
+            let book = readwise_books(&settings, Some(source_id), 1)
+                .await?
+                .pop()
+                .ok_or_else(|| format!("Readwise has no book {}.", source_id))?;
+            let highlight = json!({
+                "text": text.trim(),
+                "title": str_field(&book, "title"),
+                "author": str_field(&book, "author"),
+                "source_url": str_field(&book, "source_url"),
+                "category": str_field(&book, "category"),
+                "note": "Fabric summary",
+            });
+            let url = format!("{}/highlights/", READWISE_API);
+            call(&settings, "readwise", reqwest::Method::POST, &url, Some(json!({"highlights": [highlight]}))).await?;
+            Ok(())
+        }
+        "raindrop" => {
+            let url = format!("{}/raindrop/{}", RAINDROP_API, source_id);
+            let existing = call(&settings, "raindrop", reqwest::Method::GET, &url, None).await?;
+            let note = match str_field(&existing["item"], "note") {
+                Some(note) => format!("{}\n\n{}", note.trim_end(), text.trim()),
+                None => text.trim().to_string(),
+            };
+            call(&settings, "raindrop", reqwest::Method::PUT, &url, Some(json!({"note": note}))).await?;
+            Ok(())
+        }
+        other => Err(format!("Unknown service '{}'. Use readwise or raindrop.", other)),
+    }
+}
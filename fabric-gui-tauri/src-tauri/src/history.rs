@@ -0,0 +1,584 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use rusqlite::types::{Type, Value};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use tauri::State;
+use crate::ai_client::AIRequest;
+use crate::emitter::RunMetrics;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id TEXT PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+    pattern TEXT,
+    vendor TEXT NOT NULL,
+    model TEXT NOT NULL,
+    system_prompt TEXT NOT NULL,
+    input TEXT NOT NULL,
+    output TEXT NOT NULL,
+    temperature REAL NOT NULL,
+    top_p REAL NOT NULL,
+    thinking_level INTEGER,
+    success INTEGER NOT NULL,
+    error TEXT,
+    duration_ms INTEGER,
+    time_to_first_token_ms INTEGER,
+    tokens_per_sec REAL,
+    parent_run_id TEXT,
+    request_json TEXT,
+    reasoning TEXT,
+    relation TEXT,
+    output_blob BLOB
+);
+CREATE INDEX IF NOT EXISTS runs_created_at ON runs(created_at);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS runs_fts USING fts5(
+    input, output, content='runs', content_rowid='rowid'
+);
+CREATE TRIGGER IF NOT EXISTS runs_ai AFTER INSERT ON runs BEGIN
+    INSERT INTO runs_fts(rowid, input, output) VALUES (new.rowid, new.input, new.output);
+END;
+CREATE TRIGGER IF NOT EXISTS runs_ad AFTER DELETE ON runs BEGIN
+    INSERT INTO runs_fts(runs_fts, rowid, input, output) VALUES ('delete', old.rowid, old.input, old.output);
+END;
+CREATE TRIGGER IF NOT EXISTS runs_au AFTER UPDATE ON runs BEGIN
+    INSERT INTO runs_fts(runs_fts, rowid, input, output) VALUES ('delete', old.rowid, old.input, old.output);
+    INSERT INTO runs_fts(rowid, input, output) VALUES (new.rowid, new.input, new.output);
+END;
+
+CREATE TABLE IF NOT EXISTS run_tags (
+    run_id TEXT NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (run_id, tag)
+);
+CREATE TABLE IF NOT EXISTS run_citations (
+    run_id TEXT NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    marker INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    page INTEGER,
+    heading TEXT,
+    text TEXT NOT NULL,
+    score REAL NOT NULL,
+    cited INTEGER NOT NULL,
+    PRIMARY KEY (run_id, marker)
+);
+CREATE TABLE IF NOT EXISTS collections (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS collection_runs (
+    collection_id INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+    run_id TEXT NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    added_at INTEGER NOT NULL,
+    PRIMARY KEY (collection_id, run_id)
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY,
+    at INTEGER NOT NULL,
+    method TEXT NOT NULL,
+    endpoint TEXT NOT NULL,
+    purpose TEXT NOT NULL,
+    run_id TEXT,
+    bytes_sent INTEGER,
+    bytes_received INTEGER,
+    status INTEGER,
+    duration_ms INTEGER NOT NULL,
+    error TEXT,
+    body TEXT
+);
+CREATE INDEX IF NOT EXISTS audit_log_at ON audit_log(at);
+CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log BEGIN
+    SELECT RAISE(ABORT, 'The audit log is append-only.');
+END;
+CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log BEGIN
+    SELECT RAISE(ABORT, 'The audit log is append-only.');
+END;
+";
+
+// Columns added after the first release; databases created before them get them via ALTER TABLE
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("duration_ms", "INTEGER"),
+    ("time_to_first_token_ms", "INTEGER"),
+    ("tokens_per_sec", "REAL"),
+    ("parent_run_id", "TEXT"),
+    ("request_json", "TEXT"),
+    ("reasoning", "TEXT"),
+    ("relation", "TEXT"),
+    ("output_blob", "BLOB"),
+];
+
+// Outputs larger than this (books, long transcripts) are stored deflate-compressed in
+// output_blob. The output column then keeps only the start, which is what snippets and
+// full-text search see, so the table and its FTS index stay small.
+const LARGE_OUTPUT_BYTES: usize = 1024 * 1024;
+const OUTPUT_PREVIEW_BYTES: usize = 64 * 1024;
+
+#[derive(Serialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub created_at: i64,
+    pub pattern: Option<String>,
+    pub vendor: String,
+    pub model: String,
+    pub system_prompt: String,
+    pub input: String,
+    pub output: String,
+    pub temperature: f64,
+    pub top_p: f64,
+    pub thinking_level: Option<i32>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub time_to_first_token_ms: Option<i64>,
+    pub tokens_per_sec: Option<f64>,
+    // Set when this run derives from another one, e.g. a replay or a map-reduce part
+    pub parent_run_id: Option<String>,
+    pub relation: Option<String>,
+    // The request fields without a column of their own, so replays send exactly the same thing
+    #[serde(skip)]
+    pub request_json: Option<String>,
+    // The model's thinking, kept only when the save_reasoning setting is on
+    pub reasoning: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HistorySummary {
+    pub id: String,
+    pub created_at: i64,
+    pub pattern: Option<String>,
+    pub vendor: String,
+    pub model: String,
+    pub success: bool,
+    pub snippet: String,
+}
+
+#[derive(Serialize)]
+pub struct HistoryPage {
+    pub entries: Vec<HistorySummary>,
+    pub total: u64,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct HistoryFilters {
+    pub pattern: Option<String>,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    // Unix seconds, inclusive
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub tag: Option<String>,
+    pub collection: Option<String>,
+    // Lists the runs derived from a run
+    pub parent_run_id: Option<String>,
+}
+
+pub struct HistoryState {
+    conn: Mutex<Connection>,
+}
+
+impl HistoryState {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self { conn: Mutex::new(open_connection(path)?) })
+    }
+
+    // Swaps in another profile's database; the old connection closes when dropped
+    pub fn reopen(&self, path: &Path) -> Result<(), String> {
+        let conn = open_connection(path)?;
+        *self.conn.lock().unwrap() = conn;
+        Ok(())
+    }
+
+    pub fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+
+    pub fn record(
+        &self,
+        id: &str,
+        request: &AIRequest,
+        output: &str,
+        error: Option<&str>,
+        metrics: Option<&RunMetrics>,
+        reasoning: Option<&str>,
+    ) -> Result<(), String> {
+        // The columns already keep the prompt, input and sampling settings; request_json only
+        // gets the rest, so long prompts aren't stored twice
+        let mut stored = serde_json::to_value(request).map_err(|e| e.to_string())?;
+        if let Some(fields) = stored.as_object_mut() {
+            for field in [
+                "api_key", "pattern", "vendor", "model", "system_prompt", "user_input",
+                "temperature", "top_p", "thinking_level", "parent_run_id", "relation",
+            ] {
+                fields.remove(field);
+            }
+        }
+        let request_json = stored.to_string();
+        let (output, output_blob) = pack_output(output)?;
+
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO runs (id, created_at, pattern, vendor, model, system_prompt, input, output,
+                               temperature, top_p, thinking_level, success, error,
+                               duration_ms, time_to_first_token_ms, tokens_per_sec, parent_run_id, request_json,
+                               reasoning, relation, output_blob)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)",
+            params![
+                id,
+                now_secs(),
+                request.pattern,
+                request.vendor,
+                request.model,
+                request.system_prompt,
+                request.user_input,
+                output,
+                request.temperature,
+                request.top_p,
+                request.thinking_level,
+                error.is_none(),
+                error,
+                metrics.map(|m| m.elapsed_ms as i64),
+                metrics.and_then(|m| m.time_to_first_token_ms).map(|ms| ms as i64),
+                metrics.and_then(|m| m.tokens_per_sec),
+                request.parent_run_id,
+                request_json,
+                reasoning,
+                request.relation,
+                output_blob,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<HistoryEntry>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, created_at, pattern, vendor, model, system_prompt, input, output,
+                    temperature, top_p, thinking_level, success, error,
+                    duration_ms, time_to_first_token_ms, tokens_per_sec, parent_run_id, request_json,
+                    reasoning, relation, output_blob
+             FROM runs WHERE id = ?1",
+            params![id],
+            entry_from_row,
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+}
+
+// Also used on backup snapshots so older archives gain the current columns before merging
+pub fn open_connection(path: &Path) -> Result<Connection, String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "foreign_keys", "ON").map_err(|e| e.to_string())?;
+    conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+    add_missing_columns(&conn).map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+fn add_missing_columns(conn: &Connection) -> rusqlite::Result<()> {
+    let existing = conn
+        .prepare("SELECT name FROM pragma_table_info('runs')")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (column, kind) in ADDED_COLUMNS {
+        if !existing.iter().any(|name| name == column) {
+            conn.execute(&format!("ALTER TABLE runs ADD COLUMN {} {}", column, kind), [])?;
+        }
+    }
+    Ok(())
+}
+
+fn preview(text: &str) -> &str {
+    let mut end = OUTPUT_PREVIEW_BYTES.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn compress(text: &str) -> Result<Vec<u8>, String> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())
+}
+
+fn decompress(blob: &[u8]) -> Result<String, String> {
+    let mut text = String::new();
+    DeflateDecoder::new(blob)
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Could not decompress a stored output: {}", e))?;
+    Ok(text)
+}
+
+// The output column's text and, for a large output, the compressed whole
+fn pack_output(output: &str) -> Result<(&str, Option<Vec<u8>>), String> {
+    if output.len() <= LARGE_OUTPUT_BYTES {
+        return Ok((output, None));
+    }
+    Ok((preview(output), Some(compress(output)?)))
+}
+
+// Moves large outputs stored before compression existed into output_blob; returns how many
+pub fn compact_large_outputs(conn: &Connection) -> Result<u64, String> {
+    let large: Vec<(i64, String)> = conn
+        .prepare("SELECT rowid, output FROM runs WHERE output_blob IS NULL AND length(CAST(output AS BLOB)) > ?1")
+        .and_then(|mut stmt| {
+            stmt.query_map(params![LARGE_OUTPUT_BYTES as i64], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(|e| e.to_string())?;
+    for (rowid, output) in &large {
+        let (preview, blob) = pack_output(output)?;
+        conn.execute("UPDATE runs SET output = ?1, output_blob = ?2 WHERE rowid = ?3", params![preview, blob, rowid])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(large.len() as u64)
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        pattern: row.get(2)?,
+        vendor: row.get(3)?,
+        model: row.get(4)?,
+        system_prompt: row.get(5)?,
+        input: row.get(6)?,
+        output: match row.get::<_, Option<Vec<u8>>>(20)? {
+            Some(blob) => decompress(&blob).map_err(|e| rusqlite::Error::FromSqlConversionFailure(20, Type::Blob, e.into()))?,
+            None => row.get(7)?,
+        },
+        temperature: row.get(8)?,
+        top_p: row.get(9)?,
+        thinking_level: row.get(10)?,
+        success: row.get(11)?,
+        error: row.get(12)?,
+        duration_ms: row.get(13)?,
+        time_to_first_token_ms: row.get(14)?,
+        tokens_per_sec: row.get(15)?,
+        parent_run_id: row.get(16)?,
+        request_json: row.get(17)?,
+        reasoning: row.get(18)?,
+        relation: row.get(19)?,
+    })
+}
+
+pub fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+// Quotes every term so user input can't trip FTS5 query syntax; the last term matches as a prefix
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+    Some(format!("{}*", terms.join(" ")))
+}
+
+#[tauri::command]
+pub async fn search_history(
+    history: State<'_, HistoryState>,
+    query: Option<String>,
+    filters: Option<HistoryFilters>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+) -> Result<HistoryPage, String> {
+    let filters = filters.unwrap_or_default();
+    let page = page.unwrap_or(0);
+    let page_size = page_size.unwrap_or(25).clamp(1, 200);
+    let fts = query.as_deref().and_then(fts_query);
+
+    let mut clauses = Vec::new();
+    let mut values: Vec<Value> = Vec::new();
+
+    if let Some(fts) = &fts {
+        clauses.push("runs.rowid IN (SELECT rowid FROM runs_fts WHERE runs_fts MATCH ?)");
+        values.push(Value::Text(fts.clone()));
+    }
+    if let Some(pattern) = filters.pattern {
+        clauses.push("runs.pattern = ?");
+        values.push(Value::Text(pattern));
+    }
+    if let Some(vendor) = filters.vendor {
+        clauses.push("runs.vendor = ?");
+        values.push(Value::Text(vendor));
+    }
+    if let Some(model) = filters.model {
+        clauses.push("runs.model = ?");
+        values.push(Value::Text(model));
+    }
+    if let Some(tag) = filters.tag {
+        clauses.push("runs.id IN (SELECT run_id FROM run_tags WHERE tag = ?)");
+        values.push(Value::Text(tag));
+    }
+    if let Some(collection) = filters.collection {
+        clauses.push(
+            "runs.id IN (SELECT cr.run_id FROM collection_runs cr
+                         JOIN collections c ON c.id = cr.collection_id WHERE c.name = ?)",
+        );
+        values.push(Value::Text(collection));
+    }
+    if let Some(parent) = filters.parent_run_id {
+        clauses.push("runs.parent_run_id = ?");
+        values.push(Value::Text(parent));
+    }
+    if let Some(from) = filters.from {
+        clauses.push("runs.created_at >= ?");
+        values.push(Value::Integer(from));
+    }
+    if let Some(to) = filters.to {
+        clauses.push("runs.created_at <= ?");
+        values.push(Value::Integer(to));
+    }
+
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+
+    let conn = history.conn.lock().unwrap();
+
+    let total: u64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM runs {}", where_sql),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let sql = format!(
+        "SELECT id, created_at, pattern, vendor, model, success, substr(output, 1, 200)
+         FROM runs {} ORDER BY created_at DESC LIMIT ? OFFSET ?",
+        where_sql
+    );
+    values.push(Value::Integer(page_size as i64));
+    values.push(Value::Integer(page as i64 * page_size as i64));
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(params_from_iter(values.iter()), |row| {
+            Ok(HistorySummary {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                pattern: row.get(2)?,
+                vendor: row.get(3)?,
+                model: row.get(4)?,
+                success: row.get(5)?,
+                snippet: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(HistoryPage {
+        entries,
+        total,
+        page,
+        page_size,
+    })
+}
+
+#[tauri::command]
+pub async fn get_history_entry(history: State<'_, HistoryState>, id: String) -> Result<HistoryEntry, String> {
+    history
+        .get(&id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", id))
+}
+
+#[derive(Serialize)]
+pub struct RunNode {
+    pub id: String,
+    pub created_at: i64,
+    pub pattern: Option<String>,
+    pub vendor: String,
+    pub model: String,
+    pub success: bool,
+    pub duration_ms: Option<i64>,
+    pub relation: Option<String>,
+    pub snippet: String,
+    pub children: Vec<RunNode>,
+}
+
+// Everything derived from `root_id` (replays, pipeline steps, map-reduce parts, judge passes
+// and so on) as a nested tree, oldest children first, for drawing the execution graph
+#[tauri::command]
+pub async fn get_run_tree(history: State<'_, HistoryState>, root_id: String) -> Result<RunNode, String> {
+    let conn = history.conn.lock().unwrap();
+    // UNION drops rows already seen, so a parent cycle in imported data can't recurse forever
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE tree(id) AS (
+                 SELECT ?1
+                 UNION SELECT runs.id FROM runs JOIN tree ON runs.parent_run_id = tree.id
+             )
+             SELECT id, created_at, pattern, vendor, model, success, duration_ms, relation,
+                    substr(output, 1, 200), parent_run_id
+             FROM runs WHERE id IN (SELECT id FROM tree) ORDER BY created_at, rowid",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![root_id], |row| {
+            let node = RunNode {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                pattern: row.get(2)?,
+                vendor: row.get(3)?,
+                model: row.get(4)?,
+                success: row.get(5)?,
+                duration_ms: row.get(6)?,
+                relation: row.get(7)?,
+                snippet: row.get(8)?,
+                children: Vec::new(),
+            };
+            Ok((node, row.get::<_, Option<String>>(9)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut children: HashMap<String, Vec<RunNode>> = HashMap::new();
+    let mut root = None;
+    for (node, parent) in rows {
+        if node.id == root_id {
+            root = Some(node);
+        } else if let Some(parent) = parent {
+            children.entry(parent).or_default().push(node);
+        }
+    }
+    let mut root = root.ok_or_else(|| format!("History entry '{}' not found.", root_id))?;
+    attach_children(&mut root, &mut children);
+    Ok(root)
+}
+
+fn attach_children(node: &mut RunNode, children: &mut HashMap<String, Vec<RunNode>>) {
+    node.children = children.remove(&node.id).unwrap_or_default();
+    for child in &mut node.children {
+        attach_children(child, children);
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use uuid::Uuid;
+use crate::history::HistoryState;
+use crate::settings::SettingsState;
+use crate::templates;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const MAX_TIMEOUT_SECS: u64 = 600;
+// What a hook prints is shown in the UI, so very chatty ones are cut
+const MAX_CAPTURE_BYTES: usize = 64 * 1024;
+
+// A shell command a preset can run after its runs finish, with the output on stdin. Hooks live
+// in settings and presets refer to them by name, so the webview can't run commands the user
+// didn't set up.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PostHook {
+    pub name: String,
+    // Run by sh -c, or cmd /C on Windows
+    pub command: String,
+    // Defaults to 30 seconds, at most 600
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct HookResult {
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u64,
+    pub timed_out: bool,
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+fn captured(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_CAPTURE_BYTES)]).to_string();
+    if bytes.len() > MAX_CAPTURE_BYTES {
+        format!("{}\n[truncated]", text)
+    } else {
+        text
+    }
+}
+
+// Each run gets an empty working directory of its own under app data, removed afterwards, so
+// relative paths in a hook can't touch the app's files or another run's leftovers. The run is
+// described in FABRIC_RUN_ID, FABRIC_PATTERN, FABRIC_VENDOR, FABRIC_MODEL and FABRIC_TITLE.
+#[tauri::command]
+pub async fn run_post_hook(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsState>,
+    history: State<'_, HistoryState>,
+    name: String,
+    run_id: String,
+) -> Result<HookResult, String> {
+    let hook = settings
+        .get()
+        .post_hooks
+        .into_iter()
+        .find(|h| h.name == name)
+        .ok_or_else(|| format!("There is no post-run hook named '{}'.", name))?;
+    if hook.command.trim().is_empty() {
+        return Err(format!("The hook '{}' has no command.", name));
+    }
+    let entry = history
+        .get(&run_id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))?;
+
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("hooks")
+        .join(Uuid::new_v4().to_string());
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let mut command = shell(&hook.command);
+    command
+        .current_dir(&dir)
+        .env("FABRIC_RUN_ID", &entry.id)
+        .env("FABRIC_PATTERN", entry.pattern.as_deref().unwrap_or(""))
+        .env("FABRIC_VENDOR", &entry.vendor)
+        .env("FABRIC_MODEL", &entry.model)
+        .env("FABRIC_TITLE", templates::title(&entry))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let started = Instant::now();
+    let timeout = Duration::from_secs(hook.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS).clamp(1, MAX_TIMEOUT_SECS));
+    let result = async {
+        let mut child = command.spawn().map_err(|e| format!("Could not start the hook: {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // Written alongside so a hook that doesn't read its input can't block past the
+            // timeout; one that exits early closing the pipe is not an error
+            let input = entry.output.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = stdin.write_all(input.as_bytes()).await;
+            });
+        }
+        match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(output) => {
+                let output = output.map_err(|e| e.to_string())?;
+                Ok(HookResult {
+                    exit_code: output.status.code(),
+                    stdout: captured(&output.stdout),
+                    stderr: captured(&output.stderr),
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    timed_out: false,
+                })
+            }
+            // Dropping the wait future kills the process
+            Err(_) => Ok(HookResult {
+                exit_code: None,
+                stdout: String::new(),
+                stderr: format!("Stopped after {} seconds.", timeout.as_secs()),
+                duration_ms: started.elapsed().as_millis() as u64,
+                timed_out: true,
+            }),
+        }
+    }
+    .await;
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
@@ -0,0 +1,610 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use reqwest::{redirect, Certificate, Client, Proxy, RequestBuilder, Response, Url};
+use crate::audit::{self, AuditEntry};
+use crate::history::now_secs;
+use crate::huggingface;
+use crate::i18n::tr_args;
+use crate::lmstudio;
+use crate::residency;
+use crate::vertex;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct NetworkSettings {
+    // PEM files with extra trusted roots, e.g. the CA of a TLS-intercepting corporate proxy
+    pub extra_ca_certs: Vec<String>,
+    // Hosts whose certificates are not verified at all. Last resort only: anyone on the
+    // network path can then read and alter that traffic, API keys included.
+    pub insecure_hosts: Vec<String>,
+    // Proxy per vendor ("google", "openai", "anthropic", ...) or "default" for everything else,
+    // e.g. "socks5h://127.0.0.1:9050" for Tor. "direct" bypasses the system proxy.
+    pub proxies: HashMap<String, String>,
+    pub firewall: FirewallSettings,
+}
+
+// Which hosts the app may contact at all, so an untrusted pattern, script or tool can't send
+// data anywhere else. Settings are per profile, and so is the policy.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct FirewallSettings {
+    // "vendors" (default): the AI vendors' endpoints, local servers and allowed_hosts only;
+    // "allow_list": allowed_hosts only; "off": any host. Integrations such as web scraping,
+    // Jira or Zotero need their hosts in allowed_hosts unless the firewall is off.
+    pub mode: Option<String>,
+    // "example.com" covers its subdomains too
+    pub allowed_hosts: Vec<String>,
+    // Blocked in every mode, even when also allowed
+    pub denied_hosts: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FirewallViolation {
+    pub host: String,
+    // Without the query string, which may hold a token
+    pub url: String,
+    pub at: i64,
+}
+
+#[derive(Default)]
+struct NetworkConfig {
+    certificates: Vec<Certificate>,
+    certificate_errors: Vec<String>,
+    insecure_hosts: Vec<String>,
+    proxies: HashMap<String, String>,
+    firewall: FirewallSettings,
+}
+
+const MAX_LOGGED_VIOLATIONS: usize = 200;
+// Vendor hosts besides the APIs themselves: sign-in, key checks and status pages
+const VENDOR_SERVICE_HOSTS: &[&str] = &[
+    "oauth2.googleapis.com",
+    "huggingface.co",
+    "status.openai.com",
+    "status.anthropic.com",
+    "status.cloud.google.com",
+    // Rerank APIs for RAG collections
+    "api.jina.ai",
+    "api.cohere.com",
+];
+
+#[derive(Serialize)]
+pub struct NetworkReport {
+    pub certificates_loaded: usize,
+    pub certificate_errors: Vec<String>,
+    pub proxy_errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ProxyTestReport {
+    pub vendor: String,
+    pub proxy: Option<String>,
+    pub ok: bool,
+    pub latency_ms: u64,
+    pub status_code: Option<u16>,
+    pub exit_ip: Option<String>,
+    pub is_tor: Option<bool>,
+    pub message: String,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TorCheck {
+    #[serde(rename = "IsTor")]
+    is_tor: bool,
+    #[serde(rename = "IP")]
+    ip: String,
+}
+
+const TOR_CHECK_URL: &str = "https://check.torproject.org/api/ip";
+const DIRECT: &str = "direct";
+
+fn config() -> &'static RwLock<NetworkConfig> {
+    static CONFIG: OnceLock<RwLock<NetworkConfig>> = OnceLock::new();
+    CONFIG.get_or_init(|| RwLock::new(NetworkConfig::default()))
+}
+
+// Called whenever settings load or change, like the UI locale
+pub fn configure(settings: &NetworkSettings) {
+    let mut certificates = Vec::new();
+    let mut certificate_errors = Vec::new();
+
+    for path in &settings.extra_ca_certs {
+        match fs::read(path).map_err(|e| e.to_string()).and_then(|pem| {
+            Certificate::from_pem_bundle(&pem).map_err(|e| e.to_string())
+        }) {
+            Ok(certs) if !certs.is_empty() => certificates.extend(certs),
+            Ok(_) => certificate_errors.push(format!("{}: no certificates found", path)),
+            Err(e) => certificate_errors.push(format!("{}: {}", path, e)),
+        }
+    }
+
+    *config().write().unwrap() = NetworkConfig {
+        certificates,
+        certificate_errors,
+        insecure_hosts: settings.insecure_hosts.iter().map(|h| h.trim().to_lowercase()).collect(),
+        proxies: settings
+            .proxies
+            .iter()
+            .map(|(key, url)| (key.trim().to_lowercase(), url.trim().to_string()))
+            .filter(|(_, url)| !url.is_empty())
+            .collect(),
+        firewall: FirewallSettings {
+            mode: settings.firewall.mode.as_deref().map(|m| m.trim().to_lowercase()),
+            allowed_hosts: normalized_hosts(&settings.firewall.allowed_hosts),
+            denied_hosts: normalized_hosts(&settings.firewall.denied_hosts),
+        },
+    };
+}
+
+fn normalized_hosts(hosts: &[String]) -> Vec<String> {
+    hosts
+        .iter()
+        .map(|h| h.trim().trim_start_matches("*.").trim_end_matches('.').to_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect()
+}
+
+fn violations() -> &'static Mutex<VecDeque<FirewallViolation>> {
+    static VIOLATIONS: OnceLock<Mutex<VecDeque<FirewallViolation>>> = OnceLock::new();
+    VIOLATIONS.get_or_init(Mutex::default)
+}
+
+fn host_listed(host: &str, list: &[String]) -> bool {
+    list.iter().any(|entry| host == entry || host.ends_with(&format!(".{}", entry)))
+}
+
+fn is_loopback(host: &str) -> bool {
+    ["localhost", "127.0.0.1", "[::1]", "::1"].contains(&host)
+}
+
+// The vendors' public APIs plus the endpoints configured for Vertex AI, Hugging Face and LM Studio
+fn is_vendor_host(host: &str) -> bool {
+    let configured = [vertex::base_url(), huggingface::base_url(), lmstudio::base_url()];
+    vendor_for_host(host).is_some()
+        || VENDOR_SERVICE_HOSTS.contains(&host)
+        || configured
+            .iter()
+            .any(|url| Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_lowercase)).as_deref() == Some(host))
+}
+
+// `host` is lowercase and the lists are normalized, as `configure` stores them
+fn host_allowed(host: &str, firewall: &FirewallSettings) -> bool {
+    !host_listed(host, &firewall.denied_hosts)
+        && match firewall.mode.as_deref().unwrap_or("vendors") {
+            "off" => true,
+            "allow_list" => host_listed(host, &firewall.allowed_hosts),
+            _ => host_listed(host, &firewall.allowed_hosts) || is_loopback(host) || is_vendor_host(host),
+        }
+}
+
+// Every request and redirect is checked here; a blocked one is logged and fails with an error
+// that says how to allow it
+pub fn check_host(url: &str) -> Result<(), String> {
+    let Ok(parsed) = Url::parse(url) else {
+        return Ok(());
+    };
+    let Some(host) = parsed.host_str().map(str::to_lowercase) else {
+        return Ok(());
+    };
+    if host_allowed(&host, &config().read().unwrap().firewall) {
+        return Ok(());
+    }
+
+    let mut logged = parsed.clone();
+    logged.set_query(None);
+    eprintln!("Network firewall blocked a request to {}", logged);
+    let mut log = violations().lock().unwrap();
+    if log.len() == MAX_LOGGED_VIOLATIONS {
+        log.pop_front();
+    }
+    log.push_back(FirewallViolation { host: host.clone(), url: logged.to_string(), at: now_secs() });
+    Err(tr_args("network-blocked", &[("host", &host)]))
+}
+
+fn parse_proxy(url: &str) -> Result<Proxy, String> {
+    let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_lowercase()).unwrap_or_default();
+    if !["http", "https", "socks5", "socks5h"].contains(&scheme.as_str()) {
+        return Err(format!(
+            "Unsupported proxy '{}'. Use http://, https://, socks5:// or socks5h:// (or \"{}\").",
+            url, DIRECT
+        ));
+    }
+    Proxy::all(url).map_err(|e| format!("Invalid proxy '{}': {}", url, e))
+}
+
+// Hides the password when a proxy URL is shown back to the user
+fn display_proxy(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("***"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+fn vendor_for_host(host: &str) -> Option<&'static str> {
+    // Vertex AI hosts are regional, e.g. europe-west4-aiplatform.googleapis.com
+    if host == "aiplatform.googleapis.com" || host.ends_with("-aiplatform.googleapis.com") {
+        return Some("vertex");
+    }
+    // Regional endpoints chosen in the data residency settings
+    if host == "eu.api.openai.com" {
+        return Some("openai");
+    }
+    if host.starts_with("bedrock-runtime.") && host.ends_with(".amazonaws.com") {
+        return Some("anthropic");
+    }
+    ["google", "openai", "anthropic", "xai", "deepseek", "huggingface"]
+        .into_iter()
+        .find(|vendor| vendor_base_url(vendor).strip_prefix("https://") == Some(host))
+}
+
+// Local servers such as LM Studio are never reachable through a remote proxy
+fn configured_proxy(config: &NetworkConfig, host: Option<&str>) -> Option<String> {
+    if host.is_some_and(|h| ["localhost", "127.0.0.1", "[::1]"].contains(&h)) {
+        return Some(DIRECT.to_string());
+    }
+    host.and_then(vendor_for_host)
+        .and_then(|vendor| config.proxies.get(vendor))
+        .or_else(|| config.proxies.get("default"))
+        .cloned()
+}
+
+// Proxy arguments for a Chromium launched by the app, so its page loads take the same route
+// as the app's own requests. Chromium can't be given extra CA roots or skip verification for
+// single hosts, so pages that need either fail there rather than going out unchecked.
+pub fn browser_proxy_args(url: &str) -> Result<Vec<String>, String> {
+    let host = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_lowercase));
+    let Some(proxy) = configured_proxy(&config().read().unwrap(), host.as_deref()) else {
+        return Ok(Vec::new());
+    };
+    if proxy == DIRECT {
+        return Ok(vec!["--no-proxy-server".to_string()]);
+    }
+    let parsed = Url::parse(&proxy).map_err(|e| format!("Invalid proxy '{}': {}", display_proxy(&proxy), e))?;
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err("The headless browser can't use a proxy that needs a password.".to_string());
+    }
+    // Chromium resolves host names through SOCKS5 proxies itself, so socks5h is plain socks5 there
+    let scheme = if parsed.scheme() == "socks5h" { "socks5" } else { parsed.scheme() };
+    let mut server = format!("{}://{}", scheme, parsed.host_str().unwrap_or_default());
+    if let Some(port) = parsed.port() {
+        server.push_str(&format!(":{}", port));
+    }
+    Ok(vec![format!("--proxy-server={}", server)])
+}
+
+pub fn firewall_is_off() -> bool {
+    config().read().unwrap().firewall.mode.as_deref() == Some("off")
+}
+
+// What the audit log keeps of a URL: no query string, fragment or credentials
+pub fn audit_endpoint(url: &Url) -> String {
+    let mut endpoint = url.clone();
+    endpoint.set_query(None);
+    endpoint.set_fragment(None);
+    let _ = endpoint.set_username("");
+    let _ = endpoint.set_password(None);
+    endpoint.to_string()
+}
+
+// reqwest puts the whole URL in its errors, and Gemini's has the API key in the query
+fn error_without_url(e: &reqwest::Error) -> String {
+    let text = e.to_string();
+    match e.url() {
+        Some(url) => text.replace(url.as_str(), &audit_endpoint(url)),
+        None => text,
+    }
+}
+
+pub fn vendor_base_url(vendor: &str) -> &'static str {
+    match vendor {
+        "google" => "https://generativelanguage.googleapis.com",
+        "openai" => "https://api.openai.com",
+        "anthropic" => "https://api.anthropic.com",
+        "xai" => "https://api.x.ai",
+        "deepseek" => "https://api.deepseek.com",
+        "huggingface" => "https://api-inference.huggingface.co",
+        _ => "",
+    }
+}
+
+// Every outgoing request goes through a client from here so the TLS and proxy settings
+// apply everywhere
+pub fn client_for(url: &str) -> Result<Client, String> {
+    check_host(url)?;
+    let host = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_lowercase));
+    let proxy = configured_proxy(&config().read().unwrap(), host.as_deref());
+    build_client(host.as_deref(), proxy.as_deref())
+}
+
+// Sending through here records the request in the audit log; every outbound call uses it
+// instead of RequestBuilder::send
+pub trait AuditedSend {
+    async fn send_audited(self, purpose: &str, run_id: Option<&str>) -> reqwest::Result<Response>;
+}
+
+impl AuditedSend for RequestBuilder {
+    async fn send_audited(self, purpose: &str, run_id: Option<&str>) -> reqwest::Result<Response> {
+        let (client, request) = self.build_split();
+        let request = request?;
+        let endpoint = audit_endpoint(request.url());
+        let body = request.body().and_then(|b| b.as_bytes());
+        // A streamed body, such as a multipart upload, has no length up front
+        let bytes_sent = match request.body() {
+            None => Some(0),
+            Some(_) => body.map(|b| b.len() as u64),
+        };
+        let mut entry = AuditEntry {
+            at: now_secs(),
+            method: request.method().to_string(),
+            endpoint,
+            purpose: purpose.to_string(),
+            run_id: run_id.map(str::to_string),
+            bytes_sent,
+            body: body.filter(|_| audit::wants_bodies()).map(audit::loggable_body),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let result = client.execute(request).await;
+        entry.duration_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(res) => {
+                entry.status = Some(res.status().as_u16());
+                entry.bytes_received = res.content_length();
+            }
+            Err(e) => entry.error = Some(error_without_url(e)),
+        }
+        audit::record(entry);
+        result
+    }
+}
+
+fn build_client(host: Option<&str>, proxy: Option<&str>) -> Result<Client, String> {
+    let config = config().read().unwrap();
+    let mut builder = Client::builder()
+        .tls_certs_merge(config.certificates.clone())
+        .redirect(redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= 10 {
+                return attempt.error("too many redirects");
+            }
+            match check_host(attempt.url().as_str()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        }));
+
+    if let Some(host) = host.filter(|h| config.insecure_hosts.iter().any(|i| i == h)) {
+        eprintln!("WARNING: TLS certificate verification is disabled for {}", host);
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    builder = match proxy {
+        Some(DIRECT) => builder.no_proxy(),
+        Some(url) => builder.proxy(parse_proxy(url)?),
+        None => builder,
+    };
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+pub fn vendor_client(vendor: &str) -> Result<Client, String> {
+    match vendor {
+        "vertex" => return client_for(&vertex::base_url()),
+        "lmstudio" => return client_for(&lmstudio::base_url()),
+        "huggingface" => return client_for(&huggingface::base_url()),
+        "openai" => return client_for(residency::openai_base_url()),
+        "anthropic" => {
+            if let Some(url) = residency::bedrock_base_url() {
+                return client_for(&url);
+            }
+        }
+        _ => {}
+    }
+    client_for(vendor_base_url(vendor))
+}
+
+// Certificate failures get their own message pointing at the CA setting instead of
+// the generic network error, since they're the usual symptom of TLS interception
+pub fn network_error(e: reqwest::Error) -> String {
+    let mut source: Option<&dyn Error> = Some(&e);
+    while let Some(err) = source {
+        let text = err.to_string().to_lowercase();
+        if text.contains("certificate") || text.contains("unknownissuer") {
+            return tr_args("network-tls-error", &[("error", &e.to_string())]);
+        }
+        source = err.source();
+    }
+    tr_args("network-error", &[("error", &e.to_string())])
+}
+
+#[tauri::command]
+pub async fn check_network_config() -> Result<NetworkReport, String> {
+    let config = config().read().unwrap();
+    Ok(NetworkReport {
+        certificates_loaded: config.certificates.len(),
+        certificate_errors: config.certificate_errors.clone(),
+        proxy_errors: config
+            .proxies
+            .iter()
+            .filter(|(_, url)| url.as_str() != DIRECT)
+            .filter_map(|(key, url)| parse_proxy(url).err().map(|e| format!("{}: {}", key, e)))
+            .collect(),
+        warnings: config
+            .insecure_hosts
+            .iter()
+            .map(|host| {
+                format!(
+                    "Certificate verification is DISABLED for {}. Anyone between you and that host can read and change the traffic, including your API key.",
+                    host
+                )
+            })
+            .chain(match config.firewall.mode.as_deref() {
+                Some("off") => Some("The network firewall is off; patterns, scripts and tools can send data to any host.".to_string()),
+                Some("allow_list") if config.firewall.allowed_hosts.is_empty() => {
+                    Some("The network firewall only allows listed hosts, and none are listed, so every request will be blocked.".to_string())
+                }
+                _ => None,
+            })
+            .collect(),
+    })
+}
+
+// Requests the firewall blocked recently, newest last
+#[tauri::command]
+pub async fn get_firewall_log() -> Result<Vec<FirewallViolation>, String> {
+    Ok(violations().lock().unwrap().iter().cloned().collect())
+}
+
+#[tauri::command]
+pub async fn clear_firewall_log() -> Result<(), String> {
+    violations().lock().unwrap().clear();
+    Ok(())
+}
+
+// Sends one request to the vendor through the proxy (the one passed in, so a new setting can be
+// tried before saving, or else the configured one). Any HTTP response counts as reachable,
+// since no API key is sent. Through a proxy the exit IP is looked up as well.
+#[tauri::command]
+pub async fn test_proxy(vendor: String, proxy: Option<String>) -> Result<ProxyTestReport, String> {
+    let base_url = match vendor.as_str() {
+        "default" => TOR_CHECK_URL,
+        vendor => vendor_base_url(vendor),
+    };
+    if base_url.is_empty() {
+        return Err(format!("Unknown vendor '{}'.", vendor));
+    }
+
+    check_host(base_url)?;
+    let host = Url::parse(base_url).ok().and_then(|u| u.host_str().map(str::to_lowercase));
+    let proxy = proxy
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .or_else(|| configured_proxy(&config().read().unwrap(), host.as_deref()));
+    let client = build_client(host.as_deref(), proxy.as_deref())?;
+
+    let mut warnings = Vec::new();
+    if proxy.as_deref().is_some_and(|p| p.to_lowercase().starts_with("socks5://")) {
+        warnings.push(
+            "socks5:// resolves host names locally, so DNS lookups bypass the proxy. Use socks5h:// to resolve them through it (required for Tor).".to_string(),
+        );
+    }
+
+    let started = Instant::now();
+    let result = client.get(base_url).timeout(Duration::from_secs(30)).send_audited("proxy_test", None).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let mut report = ProxyTestReport {
+        vendor: vendor.clone(),
+        proxy: proxy.as_deref().map(display_proxy),
+        ok: false,
+        latency_ms,
+        status_code: None,
+        exit_ip: None,
+        is_tor: None,
+        message: String::new(),
+        warnings,
+    };
+
+    match result {
+        Ok(res) => {
+            report.ok = true;
+            report.status_code = Some(res.status().as_u16());
+            report.message = format!("Reached {} in {} ms.", base_url, latency_ms);
+        }
+        Err(e) => {
+            report.message = network_error(e);
+            return Ok(report);
+        }
+    }
+
+    if proxy.as_deref().is_some_and(|p| p != DIRECT) {
+        if let Ok(res) = client.get(TOR_CHECK_URL).timeout(Duration::from_secs(30)).send_audited("proxy_test", None).await {
+            if let Ok(check) = res.json::<TorCheck>().await {
+                report.exit_ip = Some(check.ip);
+                report.is_tor = Some(check.is_tor);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts(list: &[&str]) -> Vec<String> {
+        list.iter().map(|h| h.to_string()).collect()
+    }
+
+    fn firewall(mode: &str, allowed: &[&str], denied: &[&str]) -> FirewallSettings {
+        FirewallSettings {
+            mode: Some(mode.to_string()),
+            allowed_hosts: normalized_hosts(&hosts(allowed)),
+            denied_hosts: normalized_hosts(&hosts(denied)),
+        }
+    }
+
+    #[test]
+    fn host_listed_matches_the_host_and_its_subdomains_only() {
+        let list = hosts(&["example.com"]);
+        assert!(host_listed("example.com", &list));
+        assert!(host_listed("api.example.com", &list));
+        assert!(host_listed("a.b.example.com", &list));
+        assert!(!host_listed("notexample.com", &list));
+        assert!(!host_listed("example.com.evil.net", &list));
+        assert!(!host_listed("com", &list));
+    }
+
+    #[tokio::test]
+    async fn a_failed_gemini_send_logs_no_key() {
+        let url = "http://127.0.0.1:9/v1beta/models/gemini:streamGenerateContent?alt=sse&key=SECRET";
+        let e = Client::builder().no_proxy().build().unwrap().get(url).send().await.unwrap_err();
+        let logged = error_without_url(&e);
+        assert!(!logged.contains("key="), "{}", logged);
+        assert!(logged.contains("http://127.0.0.1:9/v1beta/models/gemini:streamGenerateContent"));
+    }
+
+    #[test]
+    fn vendors_mode_allows_vendors_local_servers_and_allowed_hosts() {
+        let settings = firewall("vendors", &["Example.com"], &["bad.example.com"]);
+        assert!(host_allowed("api.openai.com", &settings));
+        assert!(host_allowed("localhost", &settings));
+        assert!(host_allowed("docs.example.com", &settings));
+        assert!(!host_allowed("unknown.net", &settings));
+        assert!(!host_allowed("notexample.com", &settings));
+    }
+
+    #[test]
+    fn denied_hosts_win_over_allowed_ones_in_every_mode() {
+        for mode in ["vendors", "allow_list", "off"] {
+            let settings = firewall(mode, &["example.com"], &["*.bad.example.com"]);
+            assert!(!host_allowed("bad.example.com", &settings), "{} allowed it", mode);
+            assert!(!host_allowed("cdn.bad.example.com", &settings), "{} allowed it", mode);
+        }
+    }
+
+    #[test]
+    fn allow_list_mode_allows_only_listed_hosts() {
+        let settings = firewall("allow_list", &["example.com"], &[]);
+        assert!(host_allowed("example.com", &settings));
+        assert!(!host_allowed("api.openai.com", &settings));
+        assert!(!host_allowed("localhost", &settings));
+    }
+
+    #[test]
+    fn off_mode_allows_any_host_not_denied() {
+        let settings = firewall("off", &[], &["tracker.net"]);
+        assert!(host_allowed("unknown.net", &settings));
+        assert!(!host_allowed("a.tracker.net", &settings));
+    }
+}
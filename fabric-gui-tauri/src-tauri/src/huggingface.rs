@@ -0,0 +1,76 @@
+use serde_json::json;
+use std::sync::{OnceLock, RwLock};
+use crate::ai_client::AIRequest;
+
+const SERVERLESS_URL: &str = "https://api-inference.huggingface.co";
+// The generate endpoints default to a few dozen tokens, far too short for pattern output
+const MAX_NEW_TOKENS: u32 = 2048;
+
+fn endpoint() -> &'static RwLock<Option<String>> {
+    static ENDPOINT: OnceLock<RwLock<Option<String>>> = OnceLock::new();
+    ENDPOINT.get_or_init(|| RwLock::new(None))
+}
+
+// Called whenever settings load or change. `None` uses the serverless Inference API;
+// otherwise the URL of a dedicated Inference Endpoint or a self-hosted TGI server.
+pub fn configure(url: Option<&str>) {
+    *endpoint().write().unwrap() = url
+        .map(|u| u.trim().trim_end_matches('/').to_string())
+        .filter(|u| !u.is_empty());
+}
+
+pub fn is_serverless() -> bool {
+    endpoint().read().unwrap().is_none()
+}
+
+pub fn base_url() -> String {
+    endpoint().read().unwrap().clone().unwrap_or_else(|| SERVERLESS_URL.to_string())
+}
+
+// A TGI server hosts a single model, so only the serverless API puts the model in the path
+pub fn generate_url(model: &str, stream: bool) -> String {
+    match endpoint().read().unwrap().as_deref() {
+        Some(base) if stream => format!("{}/generate_stream", base),
+        Some(base) => format!("{}/generate", base),
+        None => format!("{}/models/{}", SERVERLESS_URL, model),
+    }
+}
+
+// Used by the connection check: whoami validates a hub token, /info answers on any TGI server
+pub fn probe_url() -> String {
+    match endpoint().read().unwrap().as_deref() {
+        Some(base) => format!("{}/info", base),
+        None => "https://huggingface.co/api/whoami-v2".to_string(),
+    }
+}
+
+// The raw generate API applies no chat template, so the prompt is plain text with the
+// system prompt first
+pub fn payload(req: &AIRequest, stream: bool) -> serde_json::Value {
+    let mut parameters = json!({
+        "max_new_tokens": req.max_tokens.unwrap_or(MAX_NEW_TOKENS),
+        "return_full_text": false,
+    });
+    // TGI rejects temperature 0 and top_p 1; greedy decoding and no nucleus cut are the equivalents
+    if req.temperature > 0.0 {
+        parameters["temperature"] = json!(req.temperature);
+        parameters["do_sample"] = json!(true);
+    } else {
+        parameters["do_sample"] = json!(false);
+    }
+    if req.top_p > 0.0 && req.top_p < 1.0 {
+        parameters["top_p"] = json!(req.top_p);
+    }
+
+    json!({
+        "inputs": format!("{}\n\n{}\n\n", req.system_prompt.trim(), req.user_input.trim()),
+        "parameters": parameters,
+        "stream": stream,
+    })
+}
+
+// Serverless answers with a list, TGI's /generate with a single object
+pub fn generated_text(json: &serde_json::Value) -> Option<String> {
+    let item = if json.is_array() { &json[0] } else { json };
+    item["generated_text"].as_str().map(|s| s.to_string())
+}
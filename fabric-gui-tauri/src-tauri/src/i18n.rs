@@ -0,0 +1,82 @@
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use unic_langid::LanguageIdentifier;
+
+const DEFAULT_LOCALE: &str = "en";
+
+// Message files are compiled in so a missing resource dir can never break error reporting
+const LOCALES: [(&str, &str); 4] = [
+    ("en", include_str!("../resources/locales/en/messages.ftl")),
+    ("es", include_str!("../resources/locales/es/messages.ftl")),
+    ("de", include_str!("../resources/locales/de/messages.ftl")),
+    ("fr", include_str!("../resources/locales/fr/messages.ftl")),
+];
+
+static BUNDLES: OnceLock<HashMap<&'static str, FluentBundle<FluentResource>>> = OnceLock::new();
+static CURRENT_LOCALE: RwLock<&'static str> = RwLock::new(DEFAULT_LOCALE);
+
+fn bundles() -> &'static HashMap<&'static str, FluentBundle<FluentResource>> {
+    BUNDLES.get_or_init(|| {
+        LOCALES
+            .iter()
+            .map(|(locale, source)| {
+                let langid: LanguageIdentifier = locale.parse().expect("invalid locale id");
+                let resource = FluentResource::try_new(source.to_string())
+                    .unwrap_or_else(|_| panic!("invalid messages.ftl for locale {}", locale));
+                let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+                // Isolation marks end up as invisible characters in plain-text error strings
+                bundle.set_use_isolating(false);
+                bundle
+                    .add_resource(resource)
+                    .unwrap_or_else(|_| panic!("duplicate message ids for locale {}", locale));
+                (*locale, bundle)
+            })
+            .collect()
+    })
+}
+
+// Accepts full tags like "es-MX" and falls back to English for unknown languages
+pub fn set_locale(locale: Option<&str>) {
+    let language = locale
+        .and_then(|l| l.split(['-', '_']).next())
+        .map(|l| l.to_lowercase())
+        .unwrap_or_default();
+
+    let matched = LOCALES
+        .iter()
+        .map(|(id, _)| *id)
+        .find(|id| *id == language)
+        .unwrap_or(DEFAULT_LOCALE);
+
+    *CURRENT_LOCALE.write().unwrap() = matched;
+}
+
+fn format(locale: &str, key: &str, args: Option<&FluentArgs>) -> Option<String> {
+    let bundle = bundles().get(locale)?;
+    let pattern = bundle.get_message(key)?.value()?;
+    let mut errors = Vec::new();
+    Some(bundle.format_pattern(pattern, args, &mut errors).to_string())
+}
+
+pub fn tr_args(key: &str, args: &[(&str, &str)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.to_string());
+    }
+
+    let locale = *CURRENT_LOCALE.read().unwrap();
+    format(locale, key, Some(&fluent_args))
+        .or_else(|| format(DEFAULT_LOCALE, key, Some(&fluent_args)))
+        .unwrap_or_else(|| key.to_string())
+}
+
+pub fn tr(key: &str) -> String {
+    tr_args(key, &[])
+}
+
+#[tauri::command]
+pub async fn get_available_locales() -> Result<Vec<String>, String> {
+    Ok(LOCALES.iter().map(|(id, _)| id.to_string()).collect())
+}
@@ -0,0 +1,226 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+use regex::Regex;
+use tauri::State;
+use zip::ZipArchive;
+use crate::input::{self, LimitedText};
+use crate::media_cache;
+use crate::settings::{Settings, SettingsState};
+use crate::transcribe::Segment;
+
+// Text files larger than this are read only up to it; no input limit keeps more anyway
+const MAX_TEXT_FILE_BYTES: u64 = 20 * 1024 * 1024;
+// A pause this long between subtitle cues starts a new paragraph
+const PARAGRAPH_GAP_SECS: f64 = 2.0;
+
+// PDFs go through poppler's pdftotext, the same way transcripts go through an external script
+fn pdf_text(path: &Path) -> Result<String, String> {
+    let cache_key = media_cache::file_key(path, &[]).ok();
+    if let Some(text) = cache_key.as_deref().and_then(|key| media_cache::get("pdf", key)) {
+        return Ok(text);
+    }
+
+    let output = Command::new("pdftotext")
+        .arg("-layout")
+        .arg(path)
+        .arg("-")
+        .output()
+        .map_err(|_| "Reading PDFs requires pdftotext (poppler-utils) on the PATH.".to_string())?;
+
+    if output.status.success() {
+        let text = String::from_utf8_lossy(&output.stdout).to_string();
+        if let Some(key) = &cache_key {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            media_cache::put("pdf", key, &name, &text);
+        }
+        Ok(text)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+// A .docx is a zip whose body lives in word/document.xml; paragraphs become lines
+fn docx_text(path: &Path) -> Result<String, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("Not a valid .docx file: {}", e))?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|_| "The .docx file has no document body.".to_string())?
+        .read_to_string(&mut xml)
+        .map_err(|e| e.to_string())?;
+
+    let xml = xml.replace("</w:p>", "\n").replace("<w:tab/>", "\t").replace("<w:br/>", "\n");
+    let tags = Regex::new(r"<[^>]+>").unwrap();
+    let text = tags.replace_all(&xml, "");
+    Ok(text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&"))
+}
+
+// "00:01:02,345" (SRT), "00:01:02.345" or "01:02.345" (VTT) in seconds
+fn subtitle_time(value: &str) -> Option<f64> {
+    let normalized = value.trim().replace(',', ".");
+    let fields: Vec<&str> = normalized.split(':').collect();
+    let (hours, minutes, seconds) = match fields.as_slice() {
+        [h, m, s] => (h.parse::<f64>().ok()?, m.parse::<f64>().ok()?, s.parse::<f64>().ok()?),
+        [m, s] => (0.0, m.parse::<f64>().ok()?, s.parse::<f64>().ok()?),
+        _ => return None,
+    };
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+// Cues of an SRT or WebVTT file with markup removed. Auto-generated captions repeat the
+// previous line at the top of each cue as they roll; those repeats are dropped.
+pub fn parse_subtitles(text: &str) -> Vec<Segment> {
+    let tags = Regex::new(r"<[^>]*>|\{\\[^}]*\}").unwrap();
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut cues = Vec::new();
+    let mut previous_line = String::new();
+    for block in text.split("\n\n") {
+        let lines: Vec<&str> = block.lines().collect();
+        // The timing line follows an optional cue number or identifier
+        let Some(timing) = lines.iter().position(|l| l.contains("-->")) else {
+            continue;
+        };
+        let (start, rest) = lines[timing].split_once("-->").unwrap();
+        // VTT cue settings ("align:start position:0%") follow the end time
+        let end = rest.split_whitespace().next().unwrap_or("");
+        let (Some(start), Some(end)) = (subtitle_time(start), subtitle_time(end)) else {
+            continue;
+        };
+        let mut cue_lines = Vec::new();
+        for line in &lines[timing + 1..] {
+            let line = tags.replace_all(line, "").replace("&nbsp;", " ").replace("&amp;", "&").trim().to_string();
+            if line.is_empty() || line == previous_line {
+                continue;
+            }
+            previous_line = line.clone();
+            cue_lines.push(line);
+        }
+        if !cue_lines.is_empty() {
+            cues.push(Segment { start, end, text: cue_lines.join(" "), speaker: None });
+        }
+    }
+    cues
+}
+
+pub fn timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    format!("{:02}:{:02}:{:02}", total / 3600, total / 60 % 60, total % 60)
+}
+
+// Clean transcript of a subtitle file: flowing paragraphs split at pauses, or one
+// "[hh:mm:ss] text" line per cue with timestamps
+pub fn subtitle_transcript(text: &str, timestamps: bool) -> Result<String, String> {
+    let cues = parse_subtitles(text);
+    if cues.is_empty() {
+        return Err("The subtitle file has no cues.".to_string());
+    }
+    if timestamps {
+        return Ok(cues.iter().map(|c| format!("[{}] {}", timestamp(c.start), c.text)).collect::<Vec<_>>().join("\n"));
+    }
+    let mut transcript = String::new();
+    let mut last_end = cues[0].start;
+    for cue in &cues {
+        if !transcript.is_empty() {
+            transcript.push_str(if cue.start - last_end >= PARAGRAPH_GAP_SECS { "\n\n" } else { " " });
+        }
+        transcript.push_str(&cue.text);
+        last_end = cue.end;
+    }
+    Ok(transcript)
+}
+
+// UTF-8 continuation bytes don't start a character
+fn count_chars(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|b| **b & 0xC0 != 0x80).count()
+}
+
+// The file's text, or only its first MAX_TEXT_FILE_BYTES for larger files, together with the
+// length of the whole file in characters
+fn read_text(path: &Path) -> Result<(String, Option<usize>), String> {
+    let not_text = || "Unsupported file type: the file is not text.".to_string();
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut head = Vec::new();
+    (&mut file).take(MAX_TEXT_FILE_BYTES).read_to_end(&mut head).map_err(|e| e.to_string())?;
+
+    let mut rest_chars = 0;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        rest_chars += count_chars(&buffer[..read]);
+    }
+    if rest_chars == 0 {
+        return String::from_utf8(head).map(|text| (text, None)).map_err(|_| not_text());
+    }
+
+    let total_chars = count_chars(&head) + rest_chars;
+    let text = match String::from_utf8(head) {
+        Ok(text) => text,
+        // The read stopped inside a character
+        Err(e) if e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            String::from_utf8(bytes).map_err(|_| not_text())?
+        }
+        Err(_) => return Err(not_text()),
+    };
+    Ok((text, Some(total_chars)))
+}
+
+pub fn ingest(path: &Path) -> Result<String, String> {
+    ingest_with_timestamps(path, false)
+}
+
+// `timestamps` only affects subtitle files
+pub fn ingest_with_timestamps(path: &Path, timestamps: bool) -> Result<String, String> {
+    read_input(path, timestamps).map(|(text, _)| text)
+}
+
+// The text and, when the file was too large to read whole, its full length in characters
+// (of the subtitle file itself for subtitles)
+pub fn read_input(path: &Path, timestamps: bool) -> Result<(String, Option<usize>), String> {
+    if !path.is_file() {
+        return Err(format!("File not found: {}", path.display()));
+    }
+
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "pdf" => pdf_text(path).map(|text| (text, None)),
+        "docx" => docx_text(path).map(|text| (text, None)),
+        "srt" | "vtt" => {
+            let (subtitles, total_chars) = read_text(path)?;
+            Ok((subtitle_transcript(&subtitles, timestamps)?, total_chars))
+        }
+        _ => read_text(path),
+    }
+}
+
+// Read and cut to the file input limit
+pub fn read_limited(settings: &Settings, path: &Path, timestamps: bool) -> Result<LimitedText, String> {
+    let (text, total_chars) = read_input(path, timestamps)?;
+    let (text, truncation) = input::limit_read(settings, "file", text, total_chars);
+    Ok(LimitedText { text, truncation })
+}
+
+#[tauri::command]
+pub async fn ingest_file(
+    state: State<'_, SettingsState>,
+    path: String,
+    include_timestamps: Option<bool>,
+) -> Result<LimitedText, String> {
+    read_limited(&state.get(), Path::new(&path), include_timestamps.unwrap_or(false))
+}
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use home::home_dir;
+use regex::Regex;
+use tauri::{AppHandle, State};
+use crate::github;
+use crate::hackernews;
+use crate::ingest;
+use crate::scrape;
+use crate::settings::{Settings, SettingsState};
+use crate::threads;
+use crate::youtube;
+
+// Roughly 250k tokens, more than any model takes in one request
+const DEFAULT_MAX_CHARS: usize = 1_000_000;
+// How far from the limit a cut may move to end at a paragraph or line
+const CUT_SEARCH_BYTES: usize = 8_000;
+
+// Largest text, in characters, that fetching from each kind of source returns. Pasted text is
+// never cut. 0 turns a limit off.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct InputLimits {
+    pub file_chars: Option<usize>,
+    // Web pages, threads, Hacker News and GitHub items
+    pub scrape_chars: Option<usize>,
+    pub transcript_chars: Option<usize>,
+    // "head" (default) keeps the beginning; "head_tail" keeps the beginning and the end
+    pub strategy: Option<String>,
+}
+
+// Tells the UI that the text was cut and how, so it can say so and offer chunking instead
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TruncationInfo {
+    // "file", "scrape" or "transcript"
+    pub source_type: String,
+    pub original_chars: usize,
+    pub kept_chars: usize,
+    pub limit_chars: usize,
+    pub strategy: String,
+}
+
+// What commands that fetch or read a single text return
+#[derive(Serialize)]
+pub struct LimitedText {
+    pub text: String,
+    // Set when the text was longer than the limit for its source
+    pub truncation: Option<TruncationInfo>,
+}
+
+#[derive(Serialize)]
+pub struct PreparedInput {
+    // "youtube", "thread", "hn", "github", "url", "file" or "text"
+    pub kind: &'static str,
+    // The URL or path the text came from
+    pub source: Option<String>,
+    pub text: String,
+    // Set when the text was longer than the limit for its source
+    pub truncation: Option<TruncationInfo>,
+}
+
+fn floor_boundary(text: &str, mut i: usize) -> usize {
+    while !text.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+// Where to cut to keep about `chars` characters from the start (or, with from_end, the end),
+// moved to a paragraph or line break when there is one close by
+fn cut_point(text: &str, chars: usize, from_end: bool) -> usize {
+    if from_end {
+        let exact = text.char_indices().rev().nth(chars.saturating_sub(1)).map_or(0, |(i, _)| i);
+        let window = &text[exact..floor_boundary(text, (exact + CUT_SEARCH_BYTES).min(text.len()))];
+        window
+            .find("\n\n")
+            .map(|i| exact + i + 2)
+            .or_else(|| window.find('\n').map(|i| exact + i + 1))
+            .unwrap_or(exact)
+    } else {
+        let exact = text.char_indices().nth(chars).map_or(text.len(), |(i, _)| i);
+        let start = floor_boundary(text, exact.saturating_sub(CUT_SEARCH_BYTES));
+        let window = &text[start..exact];
+        window
+            .rfind("\n\n")
+            .or_else(|| window.rfind('\n'))
+            .map_or(exact, |i| start + i)
+    }
+}
+
+pub fn limit(settings: &Settings, source_type: &str, text: String) -> (String, Option<TruncationInfo>) {
+    limit_read(settings, source_type, text, None)
+}
+
+// Like limit, for text that is only the beginning of its source, such as a file too large to
+// read whole; `total_chars` is the length of the whole source
+pub fn limit_read(
+    settings: &Settings,
+    source_type: &str,
+    text: String,
+    total_chars: Option<usize>,
+) -> (String, Option<TruncationInfo>) {
+    let limits = &settings.input_limits;
+    let limit = match source_type {
+        "file" => limits.file_chars,
+        "transcript" => limits.transcript_chars,
+        _ => limits.scrape_chars,
+    }
+    .unwrap_or(DEFAULT_MAX_CHARS);
+    let read_chars = text.chars().count();
+    let original_chars = total_chars.unwrap_or(read_chars).max(read_chars);
+    let info = |kept: &str, strategy: &str| TruncationInfo {
+        source_type: source_type.to_string(),
+        original_chars,
+        kept_chars: kept.chars().count(),
+        limit_chars: limit,
+        strategy: strategy.to_string(),
+    };
+    if limit == 0 || read_chars <= limit {
+        let truncation = (original_chars > read_chars).then(|| info(&text, "head"));
+        return (text, truncation);
+    }
+
+    // Without the real end, only the beginning can be kept
+    let strategy = limits
+        .strategy
+        .as_deref()
+        .filter(|s| *s == "head_tail" && original_chars == read_chars)
+        .unwrap_or("head");
+    let kept = match strategy {
+        "head_tail" => {
+            let head = cut_point(&text, limit / 2, false);
+            let tail = cut_point(&text, limit / 2, true).max(head);
+            let omitted = original_chars - text[..head].chars().count() - text[tail..].chars().count();
+            format!("{}\n\n[… {} characters omitted …]\n\n{}", text[..head].trim_end(), omitted, text[tail..].trim_start())
+        }
+        _ => text[..cut_point(&text, limit, false)].trim_end().to_string(),
+    };
+    let truncation = info(&kept, strategy);
+    (kept, Some(truncation))
+}
+
+pub fn limited(settings: &Settings, source_type: &str, text: String) -> LimitedText {
+    let (text, truncation) = limit(settings, source_type, text);
+    LimitedText { text, truncation }
+}
+
+fn is_youtube_url(input: &str) -> bool {
+    Regex::new(r"^https?://(www\.|m\.|music\.)?(youtube\.com/(watch\?|shorts/|live/|embed/)|youtu\.be/)")
+        .unwrap()
+        .is_match(input)
+}
+
+// Accepts pasted paths with surrounding quotes (as copied from Explorer) and a leading ~
+fn as_file_path(input: &str) -> Option<PathBuf> {
+    let trimmed = input.trim_matches(|c| c == '"' || c == '\'');
+    let path = match trimmed.strip_prefix("~/").or_else(|| trimmed.strip_prefix("~\\")) {
+        Some(rest) => home_dir()?.join(rest),
+        None => PathBuf::from(trimmed),
+    };
+    path.is_file().then_some(path)
+}
+
+fn prepared(settings: &Settings, kind: &'static str, source: String, text: String) -> PreparedInput {
+    let source_type = if kind == "youtube" { "transcript" } else { "scrape" };
+    let (text, truncation) = limit(settings, source_type, text);
+    PreparedInput { kind, source: Some(source), text, truncation }
+}
+
+// Multi-line input is always content; a single line may instead point at the content to use
+#[tauri::command]
+pub async fn prepare_input(
+    app_handle: AppHandle,
+    state: State<'_, SettingsState>,
+    input: String,
+    include_timestamps: Option<bool>,
+) -> Result<PreparedInput, String> {
+    let trimmed = input.trim();
+    let settings = state.get();
+    let single_line = !trimmed.contains('\n');
+
+    if single_line && !trimmed.contains(' ') && is_youtube_url(trimmed) {
+        let text = youtube::transcript(app_handle, trimmed.to_string(), include_timestamps.unwrap_or(false)).await?;
+        return Ok(prepared(&settings, "youtube", trimmed.to_string(), text));
+    }
+
+    if single_line && threads::is_thread_url(trimmed) {
+        let thread = threads::fetch(&settings, trimmed, None).await?;
+        return Ok(prepared(&settings, "thread", trimmed.to_string(), thread.text));
+    }
+
+    if single_line && hackernews::is_hn_url(trimmed) {
+        let item = hackernews::fetch(trimmed, None).await?;
+        return Ok(prepared(&settings, "hn", trimmed.to_string(), item.text));
+    }
+
+    if single_line && github::is_github_item_url(trimmed) {
+        let item = github::fetch(trimmed, settings.api_key("github").as_deref()).await?;
+        return Ok(prepared(&settings, "github", trimmed.to_string(), item.text));
+    }
+
+    if single_line && !trimmed.contains(' ') && (trimmed.starts_with("http://") || trimmed.starts_with("https://")) {
+        let text = scrape::scrape(trimmed, settings.api_key("jina")).await?;
+        return Ok(prepared(&settings, "url", trimmed.to_string(), text));
+    }
+
+    if single_line {
+        if let Some(path) = as_file_path(trimmed) {
+            let read = ingest::read_limited(&settings, &path, include_timestamps.unwrap_or(false))?;
+            return Ok(PreparedInput {
+                kind: "file",
+                source: Some(path.to_string_lossy().to_string()),
+                text: read.text,
+                truncation: read.truncation,
+            });
+        }
+    }
+
+    Ok(PreparedInput { kind: "text", source: None, text: input, truncation: None })
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, Value, INDEXED, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+// tantivy's smallest indexing budget is 15 MB per thread
+const WRITER_BYTES: usize = 20_000_000;
+
+// BM25 search over a RAG collection's chunks, next to its index.db. Documents carry the chunk's
+// row ID, so hits are read back from SQLite; the index can always be rebuilt from there.
+pub struct KeywordIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    id: Field,
+    path: Field,
+    text: Field,
+}
+
+fn schema() -> (Schema, Field, Field, Field) {
+    let mut builder = Schema::builder();
+    let id = builder.add_i64_field("id", INDEXED | STORED);
+    let path = builder.add_text_field("path", STRING);
+    let text = builder.add_text_field("text", TEXT);
+    (builder.build(), id, path, text)
+}
+
+impl KeywordIndex {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let (schema, id, path, text) = schema();
+        let open = || -> tantivy::Result<Index> {
+            fs::create_dir_all(dir)?;
+            Index::open_or_create(MmapDirectory::open(dir)?, schema.clone())
+        };
+        // An unreadable or outdated index is only derived data, so it's started over
+        let index = match open() {
+            Ok(index) => index,
+            Err(e) => {
+                eprintln!("Recreating the keyword index in {}: {}", dir.display(), e);
+                let _ = fs::remove_dir_all(dir);
+                open().map_err(|e| e.to_string())?
+            }
+        };
+        let writer = index.writer_with_num_threads(1, WRITER_BYTES).map_err(|e| e.to_string())?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(|e| e.to_string())?;
+        Ok(Self { index, reader, writer: Mutex::new(writer), id, path, text })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    // Changes are seen by searches after the next commit
+    pub fn replace(&self, path: &str, chunks: &[(i64, &str)]) -> Result<(), String> {
+        let writer = self.writer.lock().unwrap();
+        writer.delete_term(Term::from_field_text(self.path, path));
+        for (id, text) in chunks {
+            writer
+                .add_document(doc!(self.id => *id, self.path => path, self.text => *text))
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, path: &str) {
+        self.writer.lock().unwrap().delete_term(Term::from_field_text(self.path, path));
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        self.writer.lock().unwrap().delete_all_documents().map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    pub fn commit(&self) -> Result<(), String> {
+        self.writer.lock().unwrap().commit().map_err(|e| e.to_string())?;
+        self.reader.reload().map_err(|e| e.to_string())
+    }
+
+    // The `k` best BM25 matches for any of the query's words, as (chunk ID, score). The words
+    // are looked up as they are, so quotes, colons or AND in an input are not query syntax.
+    pub fn search(&self, query: &str, k: usize) -> Result<Vec<(i64, f32)>, String> {
+        let mut tokenizer = self.index.tokenizer_for_field(self.text).map_err(|e| e.to_string())?;
+        let mut stream = tokenizer.token_stream(query);
+        let mut words = BTreeSet::new();
+        while stream.advance() {
+            words.insert(stream.token().text.clone());
+        }
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        let clauses: Vec<(Occur, Box<dyn Query>)> = words
+            .into_iter()
+            .map(|word| {
+                let term = TermQuery::new(Term::from_field_text(self.text, &word), IndexRecordOption::WithFreqs);
+                (Occur::Should, Box::new(term) as Box<dyn Query>)
+            })
+            .collect();
+
+        let searcher = self.reader.searcher();
+        let hits = searcher
+            .search(&BooleanQuery::new(clauses), &TopDocs::with_limit(k))
+            .map_err(|e| e.to_string())?;
+        hits.into_iter()
+            .map(|(score, address)| {
+                let doc: TantivyDocument = searcher.doc(address).map_err(|e| e.to_string())?;
+                let id = doc.get_first(self.id).and_then(|v| v.as_i64()).ok_or("Keyword hit without a chunk ID")?;
+                Ok((id, score))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+use reqwest::Url;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+const DEEP_LINK_SCHEME: &str = "fabric";
+
+// A run asked for on the command line, e.g. `fabric-gui --run summarize --url https://…`, or by
+// a fabric://run/summarize?url=… link. The window fills in the rest from its current settings.
+#[derive(Serialize, Clone, Default, PartialEq)]
+pub struct LaunchRequest {
+    pub pattern: Option<String>,
+    pub url: Option<String>,
+    pub input: Option<String>,
+    // Absolute, resolved against the directory the command was started in
+    pub file: Option<String>,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+}
+
+// The request of the first launch, kept until the window has loaded and asks for it
+#[derive(Default)]
+pub struct PendingLaunch(Mutex<Option<LaunchRequest>>);
+
+fn from_deep_link(link: &str) -> Option<LaunchRequest> {
+    let url = Url::parse(link).ok().filter(|u| u.scheme() == DEEP_LINK_SCHEME)?;
+    let mut request = LaunchRequest::default();
+    // fabric://run/<pattern>: the host is "run" and the pattern the first path segment
+    if url.host_str() == Some("run") {
+        request.pattern = url.path_segments().and_then(|mut s| s.next()).filter(|p| !p.is_empty()).map(str::to_string);
+    }
+    for (key, value) in url.query_pairs() {
+        let value = Some(value.to_string());
+        match key.as_ref() {
+            "pattern" => request.pattern = value,
+            "url" => request.url = value,
+            "input" => request.input = value,
+            "vendor" => request.vendor = value,
+            "model" => request.model = value,
+            // Files are left out on purpose: a web page could otherwise pick which local file to send
+            _ => {}
+        }
+    }
+    Some(request)
+}
+
+// argv without the program name. Unknown arguments, such as those the OS adds, are ignored.
+pub fn parse(args: &[String], cwd: &Path) -> Option<LaunchRequest> {
+    if let Some(link) = args.iter().find(|a| a.starts_with(&format!("{}://", DEEP_LINK_SCHEME))) {
+        return from_deep_link(link);
+    }
+    let mut request = LaunchRequest::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let slot = match flag {
+            "--run" | "--pattern" => &mut request.pattern,
+            "--url" => &mut request.url,
+            "--input" => &mut request.input,
+            "--file" => &mut request.file,
+            "--vendor" => &mut request.vendor,
+            "--model" => &mut request.model,
+            _ => continue,
+        };
+        *slot = inline.or_else(|| args.next().cloned());
+    }
+    if let Some(file) = &request.file {
+        request.file = Some(cwd.join(file).to_string_lossy().to_string());
+    }
+    (request != LaunchRequest::default()).then_some(request)
+}
+
+fn show_main(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+// Called in the running instance when the app is launched again: the new process exits and
+// its arguments come here, so there is never a second window
+pub fn forward(app_handle: &AppHandle, argv: Vec<String>, cwd: String) {
+    show_main(app_handle);
+    if let Some(request) = parse(argv.get(1..).unwrap_or_default(), Path::new(&cwd)) {
+        let _ = app_handle.emit_to("main", "launch-request", request);
+    }
+}
+
+pub fn remember_first(app_handle: &AppHandle) {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    *app_handle.state::<PendingLaunch>().0.lock().unwrap() = parse(&args, &cwd);
+}
+
+// The window asks once it has loaded, for the request the app was started with
+#[tauri::command]
+pub async fn take_launch_request(pending: State<'_, PendingLaunch>) -> Result<Option<LaunchRequest>, String> {
+    Ok(pending.0.lock().unwrap().take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_reads_flags_with_separate_and_inline_values() {
+        let request = parse(&args(&["--run", "summarize", "--url=https://example.com/a?b=c", "--model", "gpt"]), Path::new("/"))
+            .unwrap();
+        assert_eq!(request.pattern.as_deref(), Some("summarize"));
+        assert_eq!(request.url.as_deref(), Some("https://example.com/a?b=c"));
+        assert_eq!(request.model.as_deref(), Some("gpt"));
+        assert_eq!(request.vendor, None);
+    }
+
+    #[test]
+    fn parse_resolves_relative_files_against_cwd() {
+        let cwd = Path::new("/home/user");
+        let request = parse(&args(&["--file", "notes/../talk.txt"]), cwd).unwrap();
+        assert_eq!(request.file, Some(cwd.join("notes/../talk.txt").to_string_lossy().to_string()));
+        let request = parse(&args(&["--file=/tmp/talk.txt"]), cwd).unwrap();
+        assert_eq!(request.file.as_deref(), Some("/tmp/talk.txt"));
+    }
+
+    #[test]
+    fn parse_ignores_unknown_arguments() {
+        assert!(parse(&args(&[]), Path::new("/")).is_none());
+        assert!(parse(&args(&["-psn_0_12345", "--verbose", "C:\\file.txt"]), Path::new("/")).is_none());
+        let request = parse(&args(&["--unknown", "--pattern", "extract_wisdom"]), Path::new("/")).unwrap();
+        assert_eq!(request.pattern.as_deref(), Some("extract_wisdom"));
+    }
+
+    #[test]
+    fn deep_links_never_choose_a_file() {
+        let link = "fabric://run/summarize?url=https%3A%2F%2Fexample.com&file=..%2F..%2Fetc%2Fpasswd&input=hi";
+        let request = parse(&args(&["--file", "x.txt", link]), Path::new("/")).unwrap();
+        assert_eq!(request.pattern.as_deref(), Some("summarize"));
+        assert_eq!(request.url.as_deref(), Some("https://example.com"));
+        assert_eq!(request.input.as_deref(), Some("hi"));
+        assert_eq!(request.file, None);
+    }
+
+    #[test]
+    fn other_schemes_are_not_deep_links() {
+        assert!(from_deep_link("https://run/summarize").is_none());
+        assert!(from_deep_link("not a url").is_none());
+    }
+}
@@ -0,0 +1,164 @@
+use serde::Serialize;
+use std::fs;
+use tauri::State;
+use crate::models::ModelRegistryState;
+use crate::patterns::{check_pattern_name, find_pattern_dir};
+use crate::settings::SettingsState;
+
+// Local models commonly run with 8K of context; a prompt taking half of it leaves little for the input
+const TYPICAL_CONTEXT_TOKENS: usize = 8192;
+
+// Opposing directives that show up when patterns are merged or edited piecemeal
+const CONTRADICTIONS: &[(&str, &str, &str)] = &[
+    ("output only json", "do not output json", "JSON output"),
+    ("output in markdown", "do not output markdown", "Markdown output"),
+    ("only output markdown", "do not use markdown", "Markdown output"),
+    ("use bullet points", "do not use bullet points", "bullet points"),
+    ("use bullet points", "don't use bullet points", "bullet points"),
+    ("use bulleted lists", "do not use bulleted lists", "bulleted lists"),
+    ("use numbered lists", "do not use numbered lists", "numbered lists"),
+    ("include warnings", "do not give warnings", "warnings"),
+    ("use emojis", "do not use emojis", "emojis"),
+    ("use code blocks", "do not use code blocks", "code blocks"),
+    ("explain your reasoning", "do not explain", "explanations"),
+];
+
+#[derive(Serialize)]
+pub struct LintWarning {
+    // "error" stops the pattern from working, "warning" likely degrades it, "info" is advisory
+    pub severity: &'static str,
+    pub code: &'static str,
+    pub message: String,
+    // 1-based line in system.md, when the problem is tied to one
+    pub line: Option<usize>,
+}
+
+fn warning(severity: &'static str, code: &'static str, message: String, line: Option<usize>) -> LintWarning {
+    LintWarning { severity, code, message, line }
+}
+
+// Fabric fills {{input}} itself and resolves {{plugin:...}} / {{ext:...}} templates; any other
+// variable needs a value the GUI never supplies, so it would reach the model verbatim
+fn check_variables(content: &str, warnings: &mut Vec<LintWarning>) {
+    for (index, line) in content.lines().enumerate() {
+        let mut rest = line;
+        while let Some(start) = rest.find("{{") {
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}") else {
+                warnings.push(warning(
+                    "warning",
+                    "unclosed-variable",
+                    "A '{{' is never closed with '}}' on this line.".to_string(),
+                    Some(index + 1),
+                ));
+                break;
+            };
+            let name = after[..end].trim();
+            if name.is_empty() {
+                warnings.push(warning("warning", "empty-variable", "Empty '{{}}' placeholder.".to_string(), Some(index + 1)));
+            } else if name != "input" && !name.contains(':') {
+                warnings.push(warning(
+                    "warning",
+                    "unresolved-variable",
+                    format!("'{{{{{}}}}}' has no value when run from the GUI and will be sent to the model as-is.", name),
+                    Some(index + 1),
+                ));
+            }
+            rest = &after[end + 2..];
+        }
+    }
+}
+
+fn line_of(content: &str, needle: &str) -> Option<usize> {
+    content
+        .lines()
+        .position(|line| line.to_lowercase().contains(needle))
+        .map(|i| i + 1)
+}
+
+fn check_contradictions(content: &str, warnings: &mut Vec<LintWarning>) {
+    let lower = content.to_lowercase();
+    for (positive, negative, topic) in CONTRADICTIONS {
+        if lower.contains(positive) && lower.contains(negative) {
+            warnings.push(warning(
+                "warning",
+                "contradictory-instructions",
+                format!(
+                    "The pattern both asks for and forbids {} (\"{}\" vs \"{}\"); models follow one of them unpredictably.",
+                    topic, positive, negative
+                ),
+                line_of(content, negative),
+            ));
+        }
+    }
+}
+
+// Checks a pattern for problems that would make its output unreliable. With `model`, the
+// length check uses that model's context window instead of a typical local one.
+#[tauri::command]
+pub async fn lint_pattern(
+    state: State<'_, SettingsState>,
+    registry: State<'_, ModelRegistryState>,
+    name: String,
+    model: Option<String>,
+) -> Result<Vec<LintWarning>, String> {
+    check_pattern_name(&name)?;
+    let dir = find_pattern_dir(&state.get(), &name).ok_or_else(|| format!("Pattern '{}' not found.", name))?;
+
+    let mut warnings = Vec::new();
+    let system_path = dir.join("system.md");
+    if !system_path.exists() {
+        warnings.push(warning(
+            "error",
+            "missing-system",
+            "The pattern has no system.md, so it can't be run.".to_string(),
+            None,
+        ));
+        return Ok(warnings);
+    }
+
+    let content = fs::read_to_string(&system_path).map_err(|e| e.to_string())?;
+    if content.trim().is_empty() {
+        warnings.push(warning("error", "empty-system", "system.md is empty.".to_string(), None));
+        return Ok(warnings);
+    }
+
+    check_variables(&content, &mut warnings);
+    check_contradictions(&content, &mut warnings);
+
+    // Same ~4 characters per token heuristic the progress metrics use
+    let tokens = content.chars().count().div_ceil(4);
+    let (window, window_name) = match model.as_deref().and_then(|m| registry.find(m)) {
+        Some(caps) => (caps.context_window as usize, caps.id),
+        None => (TYPICAL_CONTEXT_TOKENS, "a typical 8K local model".to_string()),
+    };
+    if tokens > window {
+        warnings.push(warning(
+            "error",
+            "exceeds-context",
+            format!("The prompt is about {} tokens, more than the {} tokens of {}.", tokens, window, window_name),
+            None,
+        ));
+    } else if tokens > window / 2 {
+        warnings.push(warning(
+            "warning",
+            "excessive-length",
+            format!(
+                "The prompt is about {} tokens, over half the {} token context of {}, leaving little room for input.",
+                tokens, window, window_name
+            ),
+            None,
+        ));
+    }
+
+    if dir.join("user.md").exists() && fs::read_to_string(dir.join("user.md")).is_ok_and(|u| u.trim().is_empty()) {
+        warnings.push(warning(
+            "info",
+            "empty-user",
+            "user.md exists but is empty; remove it or add the user-side template.".to_string(),
+            None,
+        ));
+    }
+
+    Ok(warnings)
+}
@@ -0,0 +1,113 @@
+use serde::Serialize;
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use crate::http::{self, AuditedSend};
+
+// LM Studio's default port first, then the ones it falls back to when that is taken
+const CANDIDATE_PORTS: [u16; 4] = [1234, 1235, 1236, 1237];
+const PROBE_TIMEOUT: Duration = Duration::from_millis(800);
+
+#[derive(Serialize, Clone)]
+pub struct LmStudioStatus {
+    pub running: bool,
+    pub base_url: Option<String>,
+    pub models: Vec<String>,
+}
+
+fn discovered() -> &'static RwLock<Option<String>> {
+    static BASE_URL: OnceLock<RwLock<Option<String>>> = OnceLock::new();
+    BASE_URL.get_or_init(|| RwLock::new(None))
+}
+
+// The last server found, or the default address when discovery hasn't succeeded yet
+pub fn base_url() -> String {
+    discovered()
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| format!("http://localhost:{}", CANDIDATE_PORTS[0]))
+}
+
+pub fn chat_url() -> String {
+    format!("{}/v1/chat/completions", base_url())
+}
+
+pub fn models_url() -> String {
+    format!("{}/v1/models", base_url())
+}
+
+async fn list_models(base_url: &str) -> Option<Vec<String>> {
+    let url = format!("{}/v1/models", base_url);
+    let res = http::client_for(&url)
+        .ok()?
+        .get(&url)
+        .timeout(PROBE_TIMEOUT)
+        .send_audited("lmstudio_discovery", None)
+        .await
+        .ok()?;
+    if !res.status().is_success() {
+        return None;
+    }
+    let json: serde_json::Value = res.json().await.ok()?;
+    let models = json["data"]
+        .as_array()?
+        .iter()
+        .filter_map(|m| m["id"].as_str())
+        .map(|id| id.to_string())
+        .collect();
+    Some(models)
+}
+
+// Probes the local ports for an OpenAI-compatible /v1/models endpoint
+pub async fn discover() -> LmStudioStatus {
+    for port in CANDIDATE_PORTS {
+        let base = format!("http://localhost:{}", port);
+        if let Some(models) = list_models(&base).await {
+            *discovered().write().unwrap() = Some(base.clone());
+            return LmStudioStatus {
+                running: true,
+                base_url: Some(base),
+                models,
+            };
+        }
+    }
+
+    *discovered().write().unwrap() = None;
+    LmStudioStatus {
+        running: false,
+        base_url: None,
+        models: Vec::new(),
+    }
+}
+
+// Runs are pointed at the discovered server; the ports are only probed again when the
+// last known one stopped answering. Returns the models the server offers.
+pub async fn ensure_discovered() -> Result<Vec<String>, String> {
+    let known = discovered().read().unwrap().clone();
+    if let Some(base) = known {
+        if let Some(models) = list_models(&base).await {
+            return Ok(models);
+        }
+    }
+
+    let status = discover().await;
+    if status.running {
+        Ok(status.models)
+    } else {
+        Err("LM Studio is not running. Start its local server (Developer tab) and load a model.".to_string())
+    }
+}
+
+// One probe at startup so the vendor list can offer LM Studio without the user asking
+pub fn spawn_discovery(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let status = discover().await;
+        let _ = app_handle.emit("lmstudio-status", status);
+    });
+}
+
+#[tauri::command]
+pub async fn discover_lmstudio() -> Result<LmStudioStatus, String> {
+    Ok(discover().await)
+}
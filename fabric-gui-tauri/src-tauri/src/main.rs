@@ -4,17 +4,337 @@
 mod patterns;
 mod ai_client;
 mod youtube;
+mod updater;
+mod settings;
+mod setup;
+mod health;
+mod provider_status;
+mod models;
+mod translate;
+mod i18n;
+mod history;
+mod collections;
+mod retention;
+mod backup;
+mod profiles;
+mod emitter;
+mod windows;
+mod compare;
+mod sanitize;
+mod queue;
+mod tray;
+mod context_cache;
+mod openai_batch;
+mod compose;
+mod dry_run;
+mod replay;
+mod suggest;
+mod scrape;
+mod ingest;
+mod embeddings;
+mod rag;
+mod keyword_index;
+mod input;
+mod http;
+mod vertex;
+mod lmstudio;
+mod huggingface;
+mod validate;
+mod stream_ack;
+mod catalog;
+mod marketplace;
+mod lint;
+mod revisions;
+mod bundle;
+mod notebook;
+mod variables;
+mod snippets;
+mod reading_list;
+mod transcribe;
+mod podcast;
+mod paper;
+mod docs;
+mod extract;
+mod headless;
+mod cookies;
+mod crawl;
+mod threads;
+mod hackernews;
+mod github;
+mod tickets;
+mod meeting;
+mod diarize;
+mod dictation;
+mod subtitles;
+mod anki;
+mod diagrams;
+mod tables;
+mod chunking;
+mod map_reduce;
+mod pipelines;
+mod pipeline_run;
+mod cost;
+mod post_filter;
+mod templates;
+mod export;
+mod onenote;
+mod highlights;
+mod zotero;
+mod notify;
+mod hooks;
+mod mcp;
+mod wasm_plugins;
+mod scripting;
+mod audit;
+mod residency;
+mod launch;
+mod startup;
+mod pattern_index;
+mod media_cache;
+
+use tauri::{Manager, WindowEvent};
 
 fn main() {
+    let startup_metrics = startup::StartupMetrics::default();
     tauri::Builder::default()
+        // Registered first so a second launch exits before setting anything up
+        .plugin(tauri_plugin_single_instance::init(launch::forward))
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .setup(move |app| {
+            app.manage(startup_metrics);
+            let metrics = app.state::<startup::StartupMetrics>();
+            let profile = profiles::profile_paths(app.handle(), &profiles::active_profile(app.handle()))?;
+            app.manage(metrics.measure("settings", || settings::SettingsState::load(profile.settings)));
+            app.manage(variables::VariableStore::load(profile.variables));
+            app.manage(snippets::SnippetStore::load(profile.snippets));
+            app.manage(provider_status::ProviderStatusCache::default());
+            app.manage(stream_ack::StreamAcks::default());
+            app.manage(compare::CompareSessions::default());
+            app.manage(docs::DocCache::default());
+            app.manage(dictation::DictationSessions::default());
+            app.manage(pipeline_run::PipelinePauses::default());
+            app.manage(mcp::McpHost::default());
+            app.manage(wasm_plugins::WasmPlugins::default());
+            app.manage(launch::PendingLaunch::default());
+            launch::remember_first(app.handle());
+            let data_dir = app.path().app_data_dir()?;
+            app.manage(metrics.measure("model_registry", || models::ModelRegistryState::load(data_dir.join("models.json"))));
+            app.manage(pattern_index::PatternIndex::load(data_dir.join("pattern_index.json")));
+            app.manage(metrics.measure("history", || history::HistoryState::open(&profile.history))?);
+            app.manage(rag::RagCollections::new(data_dir.join("rag")));
+            app.manage(metrics.measure("queue", || queue::RunQueue::load(data_dir.join("jobs.json"))));
+            app.manage(openai_batch::BatchJobsState::load(data_dir.join("openai_batches.json")));
+            app.manage(reading_list::ReadingList::load(data_dir.join("reading_list.json")));
+            cookies::load(data_dir.join("cookies.json"));
+            marketplace::init(data_dir.join("community_patterns"));
+            media_cache::init(data_dir.join("media_cache"));
+            metrics.measure("tray", || tray::create(app.handle()))?;
+            audit::start(app.handle().clone());
+            // Resumes jobs that were interrupted by the last shutdown
+            queue::dispatch(app.handle());
+            retention::spawn_cleanup_task(app.handle().clone());
+            rag::spawn_watcher(app.handle().clone());
+            provider_status::spawn_poller(app.handle().clone());
+            openai_batch::spawn_poller(app.handle().clone());
+            lmstudio::spawn_discovery(app.handle().clone());
+            pattern_index::spawn_indexing(app.handle().clone());
+            models::spawn_refresh(app.handle().clone());
+            Ok(())
+        })
+        .on_window_event(|window, event| match event {
+            WindowEvent::CloseRequested { api, .. } if tray::keeps_running(window) => {
+                api.prevent_close();
+                let _ = window.hide();
+            }
+            WindowEvent::Destroyed => compare::forget_window(window.app_handle(), window.label()),
+            _ => {}
+        })
         .invoke_handler(tauri::generate_handler![
             patterns::list_patterns,
             patterns::get_pattern_content,
             ai_client::run_pattern,
-            youtube::get_youtube_transcript
+            youtube::get_youtube_transcript,
+            updater::check_for_updates,
+            updater::install_update,
+            updater::get_changelog,
+            settings::get_settings,
+            settings::save_settings,
+            setup::detect_fabric_install,
+            setup::import_fabric_config,
+            setup::test_configured_vendors,
+            setup::get_setup_status,
+            setup::complete_setup,
+            health::test_vendor_connection,
+            provider_status::get_provider_status,
+            models::get_model_capabilities,
+            models::refresh_model_registry,
+            i18n::get_available_locales,
+            history::search_history,
+            history::get_history_entry,
+            history::get_run_tree,
+            collections::tag_session,
+            collections::get_session_tags,
+            collections::list_tags,
+            collections::list_collections,
+            collections::add_to_collection,
+            collections::remove_from_collection,
+            collections::delete_collection,
+            retention::vacuum_history,
+            backup::export_backup,
+            backup::import_backup,
+            profiles::list_profiles,
+            profiles::create_profile,
+            profiles::switch_profile,
+            profiles::delete_profile,
+            windows::open_new_session_window,
+            compare::open_compare_window,
+            compare::get_compare_data,
+            compare::compare_runs,
+            queue::enqueue_run,
+            queue::get_queue,
+            queue::cancel_queued,
+            queue::enqueue_batch,
+            queue::pause_batch,
+            queue::resume_batch,
+            queue::abort_batch,
+            context_cache::create_context_cache,
+            context_cache::delete_context_cache,
+            openai_batch::submit_openai_batch,
+            openai_batch::list_batch_jobs,
+            openai_batch::get_batch_results,
+            compose::preview_composed_prompt,
+            compose::list_composition_sources,
+            replay::replay_run,
+            suggest::suggest_patterns,
+            scrape::scrape_url,
+            ingest::ingest_file,
+            rag::index_rag_folder,
+            rag::reindex,
+            rag::get_index_stats,
+            rag::list_rag_collections,
+            rag::create_rag_collection,
+            rag::configure_rag_collection,
+            rag::delete_rag_collection,
+            rag::configure_rag_retrieval,
+            rag::get_run_citations,
+            input::prepare_input,
+            http::check_network_config,
+            http::test_proxy,
+            http::get_firewall_log,
+            http::clear_firewall_log,
+            vertex::check_vertex_auth,
+            lmstudio::discover_lmstudio,
+            validate::validate_request,
+            stream_ack::ack_stream,
+            catalog::list_patterns_by_category,
+            marketplace::list_marketplace,
+            marketplace::preview_community_pattern,
+            marketplace::install_community_pattern,
+            marketplace::remove_community_pattern,
+            marketplace::check_community_updates,
+            lint::lint_pattern,
+            revisions::save_pattern,
+            revisions::list_pattern_revisions,
+            revisions::get_pattern_revision,
+            revisions::restore_pattern_revision,
+            bundle::export_run_bundle,
+            notebook::create_notebook,
+            notebook::list_notebooks,
+            notebook::get_notebook,
+            notebook::delete_notebook,
+            notebook::add_cell,
+            notebook::remove_cell,
+            notebook::reorder_cells,
+            notebook::run_cell,
+            notebook::run_notebook,
+            variables::set_variable,
+            variables::delete_variable,
+            variables::list_variables,
+            snippets::save_snippet,
+            snippets::delete_snippet,
+            snippets::list_snippets,
+            snippets::expand_snippets,
+            reading_list::add_to_reading_list,
+            reading_list::remove_from_reading_list,
+            reading_list::clear_processed_links,
+            reading_list::get_reading_list,
+            reading_list::process_reading_list,
+            podcast::process_podcast_episode,
+            paper::fetch_paper,
+            docs::fetch_doc_outline,
+            docs::fetch_doc_sections,
+            extract::extract_url,
+            cookies::import_cookies_txt,
+            cookies::import_firefox_cookies,
+            cookies::list_cookie_domains,
+            cookies::remove_cookie_domain,
+            crawl::crawl_site,
+            threads::fetch_thread,
+            hackernews::fetch_hn_item,
+            github::fetch_github_item,
+            tickets::fetch_ticket,
+            tickets::post_ticket_comment,
+            meeting::parse_ics_file,
+            meeting::summarize_meeting,
+            diarize::transcribe_audio,
+            dictation::start_dictation,
+            dictation::push_dictation_audio,
+            dictation::stop_dictation,
+            dictation::cancel_dictation,
+            subtitles::export_subtitles,
+            anki::export_anki,
+            diagrams::render_diagrams,
+            tables::export_tables,
+            chunking::preview_chunks,
+            map_reduce::run_map_reduce,
+            pipelines::list_pipelines,
+            pipelines::save_pipeline,
+            pipelines::delete_pipeline,
+            pipelines::import_pipeline,
+            pipelines::export_pipeline,
+            pipeline_run::run_pipeline,
+            pipeline_run::resume_pipeline,
+            pipeline_run::cancel_pipeline,
+            cost::estimate_pipeline_cost,
+            cost::estimate_batch_cost,
+            post_filter::apply_post_filters,
+            templates::list_templates,
+            templates::get_template,
+            templates::save_template,
+            templates::delete_template,
+            templates::render_output,
+            export::preview_note_path,
+            export::export_note,
+            export::export_targets,
+            onenote::onenote_sign_in,
+            onenote::onenote_sign_out,
+            highlights::fetch_highlights,
+            highlights::push_highlight_note,
+            zotero::list_zotero_collections,
+            zotero::list_zotero_items,
+            zotero::fetch_zotero_item,
+            zotero::attach_zotero_note,
+            notify::publish_run,
+            hooks::run_post_hook,
+            mcp::list_mcp_tools,
+            mcp::call_mcp_tool,
+            mcp::list_mcp_resources,
+            mcp::read_mcp_resource,
+            mcp::stop_mcp_server,
+            wasm_plugins::list_wasm_plugins,
+            wasm_plugins::run_wasm_plugin,
+            scripting::list_scripts,
+            scripting::test_scripts,
+            audit::list_audit_log,
+            audit::export_audit_log,
+            residency::residency_routes,
+            launch::take_launch_request,
+            pattern_index::search_patterns,
+            startup::get_startup_metrics,
+            media_cache::clear_media_cache
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
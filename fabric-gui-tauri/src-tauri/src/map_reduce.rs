@@ -0,0 +1,174 @@
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager, Window};
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::chunking::{self, ChunkOptions};
+use crate::docs::estimate_tokens;
+use crate::settings::SettingsState;
+
+const DEFAULT_CONCURRENCY: usize = 3;
+const MAX_CONCURRENCY: usize = 16;
+const MERGE_PROMPT: &str = "The input below is the result of applying these instructions separately to consecutive parts of one long document. Combine the parts into a single result for the whole document, in the same format each part uses. Merge duplicate points, keep every distinct one, and keep the original order where it matters.";
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct MapReduceOptions {
+    // Overrides the chunking defaults from settings
+    pub chunking: ChunkOptions,
+    // Chunk runs in flight at once
+    pub concurrency: Option<usize>,
+    // Instructions for the combine pass, replacing the built-in merge prompt
+    pub merge_prompt: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct MapReduceResult {
+    pub run_id: String,
+    pub chunks: usize,
+    // Intermediate runs, recorded in history with the final run as their parent
+    pub map_run_ids: Vec<String>,
+    pub reduce_levels: usize,
+    pub output: String,
+}
+
+fn emit_stage(app_handle: &AppHandle, run_id: &str, stage: &str, completed: usize, total: usize) {
+    let _ = app_handle.emit(
+        "map-reduce-progress",
+        json!({"run_id": run_id, "stage": stage, "completed": completed, "total": total}),
+    );
+}
+
+fn merge_input(merge_prompt: &str, parts: &[String], offset: usize, total: usize) -> String {
+    let parts: Vec<String> = parts
+        .iter()
+        .enumerate()
+        .map(|(i, p)| format!("## Part {} of {}\n\n{}", offset + i + 1, total, p.trim()))
+        .collect();
+    format!("{}\n\n{}", merge_prompt, parts.join("\n\n"))
+}
+
+// Runs every input through the template request with at most `concurrency` in flight,
+// keeping results in input order
+async fn run_all(
+    window: &Window,
+    template: &AIRequest,
+    parent: &str,
+    stage: &str,
+    inputs: Vec<String>,
+    concurrency: usize,
+) -> Result<Vec<(String, String)>, String> {
+    let app_handle = window.app_handle();
+    let total = inputs.len();
+    let mut completed = 0;
+    let mut results: Vec<Option<(String, String)>> = vec![None; total];
+    let mut runs = stream::iter(inputs.into_iter().enumerate().map(|(index, input)| {
+        let mut request = template.clone();
+        let run_id = Uuid::new_v4().to_string();
+        request.user_input = input;
+        request.run_id = Some(run_id.clone());
+        request.parent_run_id = Some(parent.to_string());
+        request.relation = Some(if stage == "map" { "map" } else { "reduce" }.to_string());
+        request.dry_run = false;
+        async move {
+            let output = ai_client::run_recorded(app_handle, Some(window.label().to_string()), request).await;
+            (index, run_id, output)
+        }
+    }))
+    .buffer_unordered(concurrency);
+
+    while let Some((index, run_id, output)) = runs.next().await {
+        let output = output.map_err(|e| format!("Part {} of {} failed: {}", index + 1, total, e))?;
+        results[index] = Some((run_id, output));
+        completed += 1;
+        emit_stage(app_handle, parent, stage, completed, total);
+    }
+    Ok(results.into_iter().flatten().collect())
+}
+
+// Map-reduce for inputs too long for one request: the pattern runs on each chunk (map),
+// then the chunk results are combined with a merge prompt (reduce). When the combined
+// results are still too long they are reduced in groups first, as many levels as needed.
+// map-reduce-progress events report each stage; the final pass streams under `run_id`.
+#[tauri::command]
+pub async fn run_map_reduce(
+    window: Window,
+    request: AIRequest,
+    options: Option<MapReduceOptions>,
+) -> Result<MapReduceResult, String> {
+    let app_handle = window.app_handle();
+    let options = options.unwrap_or_default();
+    let chunk_options = options.chunking.or(&app_handle.state::<SettingsState>().get().chunking);
+    let concurrency = options.concurrency.unwrap_or(DEFAULT_CONCURRENCY).clamp(1, MAX_CONCURRENCY);
+    let merge_prompt = options.merge_prompt.filter(|p| !p.trim().is_empty()).unwrap_or_else(|| MERGE_PROMPT.to_string());
+    let run_id = request.run_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let chunks: Vec<String> = chunking::chunk(&request.user_input, &chunk_options).into_iter().map(|c| c.text).collect();
+    if chunks.is_empty() {
+        return Err("The input is empty.".to_string());
+    }
+    let total_chunks = chunks.len();
+
+    let mut final_request = request.clone();
+    final_request.run_id = Some(run_id.clone());
+    final_request.dry_run = false;
+    // Short inputs need no map-reduce at all
+    if total_chunks == 1 {
+        let output = ai_client::run_recorded(app_handle, Some(window.label().to_string()), final_request).await?;
+        return Ok(MapReduceResult { run_id, chunks: 1, map_run_ids: Vec::new(), reduce_levels: 0, output });
+    }
+
+    emit_stage(app_handle, &run_id, "map", 0, total_chunks);
+    let mapped = run_all(&window, &request, &run_id, "map", chunks, concurrency).await?;
+    let mut map_run_ids: Vec<String> = mapped.iter().map(|(id, _)| id.clone()).collect();
+    let mut partials: Vec<String> = mapped.into_iter().map(|(_, output)| output).collect();
+
+    // Intermediate reduces while the partial results together exceed one chunk
+    let size = chunk_options.size();
+    let mut reduce_levels = 0;
+    while partials.len() > 2 && estimate_tokens(&partials.concat()) > size {
+        reduce_levels += 1;
+        let mut groups: Vec<Vec<String>> = vec![Vec::new()];
+        let mut group_tokens = 0;
+        for partial in partials {
+            let tokens = estimate_tokens(&partial);
+            if group_tokens + tokens > size && !groups.last().unwrap().is_empty() {
+                groups.push(Vec::new());
+                group_tokens = 0;
+            }
+            group_tokens += tokens;
+            groups.last_mut().unwrap().push(partial);
+        }
+        // A level that can't combine anything would loop forever
+        if groups.iter().all(|g| g.len() == 1) {
+            partials = groups.into_iter().flatten().collect();
+            break;
+        }
+        let group_count = groups.len();
+        let inputs = groups
+            .iter()
+            .enumerate()
+            .map(|(i, g)| merge_input(&merge_prompt, g, i, group_count))
+            .collect();
+        let stage = format!("reduce-{}", reduce_levels);
+        emit_stage(app_handle, &run_id, &stage, 0, group_count);
+        let reduced = run_all(&window, &request, &run_id, &stage, inputs, concurrency).await?;
+        map_run_ids.extend(reduced.iter().map(|(id, _)| id.clone()));
+        partials = reduced.into_iter().map(|(_, output)| output).collect();
+    }
+
+    emit_stage(app_handle, &run_id, "combine", 0, 1);
+    let count = partials.len();
+    final_request.user_input = merge_input(&merge_prompt, &partials, 0, count);
+    let output = ai_client::run_recorded(app_handle, Some(window.label().to_string()), final_request).await?;
+    emit_stage(app_handle, &run_id, "done", 1, 1);
+
+    Ok(MapReduceResult {
+        run_id,
+        chunks: total_chunks,
+        map_run_ids,
+        reduce_levels,
+        output,
+    })
+}
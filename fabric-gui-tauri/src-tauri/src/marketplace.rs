@@ -0,0 +1,310 @@
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::State;
+use crate::history::now_secs;
+use crate::http::{self, AuditedSend};
+use crate::settings::{Settings, SettingsState};
+
+const DEFAULT_INDEX_URL: &str =
+    "https://raw.githubusercontent.com/coolman1984/Fabric/main/community/index.json";
+const PROVENANCE_FILE: &str = ".provenance.json";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CommunityPattern {
+    pub name: String,
+    pub description: String,
+    pub author: String,
+    pub version: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub system_url: String,
+    #[serde(default)]
+    pub user_url: Option<String>,
+    // Hex SHA-256 of system.md and of user.md. Patterns without them can be listed and
+    // previewed but not installed, and installs are refused when a download doesn't match.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub user_sha256: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CommunityIndex {
+    patterns: Vec<CommunityPattern>,
+}
+
+// Written next to each installed pattern so updates and origins can be traced later
+#[derive(Serialize, Deserialize)]
+pub struct Provenance {
+    pub name: String,
+    pub author: String,
+    pub version: String,
+    pub index_url: String,
+    pub source_url: String,
+    pub sha256: String,
+    #[serde(default)]
+    pub user_sha256: Option<String>,
+    pub installed_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct MarketplaceEntry {
+    #[serde(flatten)]
+    pub pattern: CommunityPattern,
+    pub installed_version: Option<String>,
+    pub update_available: bool,
+}
+
+#[derive(Serialize)]
+pub struct PatternPreview {
+    pub name: String,
+    pub system: String,
+    pub user: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CommunityUpdate {
+    pub name: String,
+    pub installed_version: String,
+    pub available_version: String,
+}
+
+fn index_url(settings: &Settings) -> String {
+    settings
+        .marketplace_index_url
+        .clone()
+        .unwrap_or_else(|| DEFAULT_INDEX_URL.to_string())
+}
+
+fn default_community_dir() -> &'static OnceLock<PathBuf> {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    &DIR
+}
+
+// Called once at startup with the folder used when community_patterns_dir isn't set
+pub fn init(default_dir: PathBuf) {
+    let _ = default_community_dir().set(default_dir);
+}
+
+// Kept apart from the user's own patterns so a bad community pattern can't overwrite one of them
+pub fn community_dir(settings: &Settings) -> Result<PathBuf, String> {
+    match &settings.community_patterns_dir {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => default_community_dir()
+            .get()
+            .cloned()
+            .ok_or_else(|| "The community patterns folder is not set up yet.".to_string()),
+    }
+}
+
+// Names become directory names, so anything that could escape the community directory is rejected
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a valid pattern name.", name))
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    digest(&SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+async fn fetch_text(url: &str) -> Result<String, String> {
+    if !url.starts_with("https://") {
+        return Err(format!("Refusing to download community content over plain HTTP: {}", url));
+    }
+    let res = http::client_for(url)?
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send_audited("marketplace", None)
+        .await
+        .map_err(http::network_error)?;
+    if !res.status().is_success() {
+        return Err(format!("Could not download {} ({})", url, res.status()));
+    }
+    res.text().await.map_err(|e| e.to_string())
+}
+
+// Refuses content the index has no checksum for, or whose checksum doesn't match
+fn verify(name: &str, file: &str, content: &str, expected: Option<&str>) -> Result<String, String> {
+    let Some(expected) = expected else {
+        return Err(format!(
+            "The community index has no checksum for the {} of '{}', so it can't be verified and was not installed.",
+            file, name
+        ));
+    };
+    let hash = sha256_hex(content.as_bytes());
+    if !expected.eq_ignore_ascii_case(&hash) {
+        return Err(format!(
+            "The downloaded {} for '{}' doesn't match the checksum in the index; it was not installed.",
+            file, name
+        ));
+    }
+    Ok(hash)
+}
+
+async fn fetch_index(url: &str) -> Result<Vec<CommunityPattern>, String> {
+    let body = fetch_text(url).await?;
+    let index: CommunityIndex =
+        serde_json::from_str(&body).map_err(|e| format!("Invalid community index: {}", e))?;
+    Ok(index.patterns)
+}
+
+async fn find_pattern(settings: &Settings, name: &str) -> Result<CommunityPattern, String> {
+    check_name(name)?;
+    fetch_index(&index_url(settings))
+        .await?
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| format!("Pattern '{}' is not in the community index.", name))
+}
+
+fn read_provenance(dir: &Path, name: &str) -> Option<Provenance> {
+    let text = fs::read_to_string(dir.join(name).join(PROVENANCE_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+#[tauri::command]
+pub async fn list_marketplace(settings: State<'_, SettingsState>) -> Result<Vec<MarketplaceEntry>, String> {
+    let settings = settings.get();
+    let dir = community_dir(&settings)?;
+    let patterns = fetch_index(&index_url(&settings)).await?;
+
+    Ok(patterns
+        .into_iter()
+        .map(|pattern| {
+            let installed_version = read_provenance(&dir, &pattern.name).map(|p| p.version);
+            let update_available = installed_version.as_ref().is_some_and(|v| *v != pattern.version);
+            MarketplaceEntry {
+                pattern,
+                installed_version,
+                update_available,
+            }
+        })
+        .collect())
+}
+
+// Shows the prompt before anything is written to disk
+#[tauri::command]
+pub async fn preview_community_pattern(
+    settings: State<'_, SettingsState>,
+    name: String,
+) -> Result<PatternPreview, String> {
+    let pattern = find_pattern(&settings.get(), &name).await?;
+    let system = fetch_text(&pattern.system_url).await?;
+    let user = match &pattern.user_url {
+        Some(url) => Some(fetch_text(url).await?),
+        None => None,
+    };
+    Ok(PatternPreview { name, system, user })
+}
+
+// Installing again over an existing copy is how updates are applied
+#[tauri::command]
+pub async fn install_community_pattern(
+    settings: State<'_, SettingsState>,
+    name: String,
+) -> Result<Provenance, String> {
+    let settings = settings.get();
+    let pattern = find_pattern(&settings, &name).await?;
+
+    let system = fetch_text(&pattern.system_url).await?;
+    let hash = verify(&name, "system.md", &system, pattern.sha256.as_deref())?;
+    let (user, user_hash) = match &pattern.user_url {
+        Some(url) => {
+            let user = fetch_text(url).await?;
+            let hash = verify(&name, "user.md", &user, pattern.user_sha256.as_deref())?;
+            (Some(user), Some(hash))
+        }
+        None => (None, None),
+    };
+
+    let target = community_dir(&settings)?.join(&name);
+    fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+    fs::write(target.join("system.md"), &system).map_err(|e| e.to_string())?;
+    match &user {
+        Some(user) => fs::write(target.join("user.md"), user).map_err(|e| e.to_string())?,
+        None => {
+            // A user.md left over from an older version no longer belongs to the pattern
+            let _ = fs::remove_file(target.join("user.md"));
+        }
+    }
+
+    let provenance = Provenance {
+        name,
+        author: pattern.author,
+        version: pattern.version,
+        index_url: index_url(&settings),
+        source_url: pattern.system_url,
+        sha256: hash,
+        user_sha256: user_hash,
+        installed_at: now_secs(),
+    };
+    fs::write(
+        target.join(PROVENANCE_FILE),
+        serde_json::to_vec_pretty(&provenance).map_err(|e| e.to_string())?,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(provenance)
+}
+
+#[tauri::command]
+pub async fn remove_community_pattern(settings: State<'_, SettingsState>, name: String) -> Result<(), String> {
+    check_name(&name)?;
+    let target = community_dir(&settings.get())?.join(&name);
+    if !target.exists() {
+        return Err(format!("Community pattern '{}' is not installed.", name));
+    }
+    fs::remove_dir_all(target).map_err(|e| e.to_string())
+}
+
+// Compares every installed community pattern against the index its provenance points to
+#[tauri::command]
+pub async fn check_community_updates(settings: State<'_, SettingsState>) -> Result<Vec<CommunityUpdate>, String> {
+    let settings = settings.get();
+    let dir = community_dir(&settings)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let installed: Vec<Provenance> = fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .filter_map(|e| read_provenance(&dir, &e.file_name().to_string_lossy()))
+        .collect();
+
+    let mut index_urls: Vec<&str> = installed.iter().map(|p| p.index_url.as_str()).collect();
+    index_urls.sort();
+    index_urls.dedup();
+
+    let mut updates = Vec::new();
+    for url in index_urls {
+        let available = fetch_index(url).await?;
+        for provenance in installed.iter().filter(|p| p.index_url == url) {
+            if let Some(latest) = available.iter().find(|p| p.name == provenance.name) {
+                if latest.version != provenance.version {
+                    updates.push(CommunityUpdate {
+                        name: provenance.name.clone(),
+                        installed_version: provenance.version.clone(),
+                        available_version: latest.version.clone(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(updates)
+}
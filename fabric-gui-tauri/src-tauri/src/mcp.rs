@@ -0,0 +1,371 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+use crate::settings::SettingsState;
+
+const PROTOCOL_VERSION: &str = "2025-06-18";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+// JSON-RPC's code for a method the client doesn't implement
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// An external tool server speaking the Model Context Protocol over stdio, started on first use
+#[derive(Serialize, Deserialize, Clone)]
+pub struct McpServerConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct McpTool {
+    pub server: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub input_schema: Value,
+}
+
+#[derive(Serialize)]
+pub struct McpResource {
+    pub server: String,
+    pub uri: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct McpServerError {
+    pub server: String,
+    pub error: String,
+}
+
+#[derive(Serialize)]
+pub struct McpToolList {
+    pub tools: Vec<McpTool>,
+    // Servers that failed to start or answer; the others' tools are still listed
+    pub errors: Vec<McpServerError>,
+}
+
+#[derive(Serialize, Clone)]
+struct McpLogLine {
+    server: String,
+    line: String,
+}
+
+#[derive(Serialize)]
+pub struct McpToolResult {
+    // The text parts of the result; other content is described in brackets
+    pub text: String,
+    pub is_error: bool,
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>;
+
+struct Connection {
+    _child: Child,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    next_id: AtomicU64,
+    // Set when the process's stdout closes; the next use starts it again
+    closed: Arc<AtomicBool>,
+}
+
+impl Connection {
+    async fn start(app_handle: &AppHandle, config: &McpServerConfig) -> Result<Connection, String> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Could not start '{}': {}", config.command, e))?;
+        let stdin = child.stdin.take().ok_or("The server has no stdin.")?;
+        let stdout = child.stdout.take().ok_or("The server has no stdout.")?;
+        let pending: Pending = Arc::default();
+        let closed = Arc::new(AtomicBool::new(false));
+
+        // Servers log to stderr; each line goes to the UI as an mcp-log event, since release
+        // builds have no console
+        if let Some(stderr) = child.stderr.take() {
+            let name = config.name.clone();
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let _ = app_handle.emit("mcp-log", McpLogLine { server: name.clone(), line });
+                }
+            });
+        }
+
+        let stdin = Arc::new(tokio::sync::Mutex::new(stdin));
+        let connection = Connection {
+            _child: child,
+            stdin: stdin.clone(),
+            pending: pending.clone(),
+            next_id: AtomicU64::new(1),
+            closed: closed.clone(),
+        };
+        tauri::async_runtime::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                match (message.get("id"), message.get("method")) {
+                    // A request from the server, e.g. ping or sampling
+                    (Some(id), Some(method)) => {
+                        let reply = if method == "ping" {
+                            json!({"jsonrpc": "2.0", "id": id, "result": {}})
+                        } else {
+                            json!({"jsonrpc": "2.0", "id": id, "error": {"code": METHOD_NOT_FOUND, "message": "Not supported"}})
+                        };
+                        let _ = stdin.lock().await.write_all(format!("{}\n", reply).as_bytes()).await;
+                    }
+                    (Some(id), None) => {
+                        let Some(sender) = id.as_u64().and_then(|id| pending.lock().unwrap().remove(&id)) else {
+                            continue;
+                        };
+                        let result = match message.get("error") {
+                            Some(error) => Err(error["message"].as_str().unwrap_or("Unknown error").to_string()),
+                            None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                        };
+                        let _ = sender.send(result);
+                    }
+                    // Notifications (logging, list changes) need no answer
+                    _ => {}
+                }
+            }
+            closed.store(true, Ordering::SeqCst);
+            for (_, sender) in pending.lock().unwrap().drain() {
+                let _ = sender.send(Err("The server exited.".to_string()));
+            }
+        });
+
+        connection
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "fabric-gui", "version": env!("CARGO_PKG_VERSION")},
+                }),
+            )
+            .await?;
+        connection.send(json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).await?;
+        Ok(connection)
+    }
+
+    async fn send(&self, message: Value) -> Result<(), String> {
+        let mut stdin = self.stdin.lock().await;
+        stdin
+            .write_all(format!("{}\n", message).as_bytes())
+            .await
+            .map_err(|_| "The server is not running.".to_string())?;
+        stdin.flush().await.map_err(|e| e.to_string())
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        if let Err(e) = self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("The server exited.".to_string()),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(format!("The server did not answer '{}' in time.", method))
+            }
+        }
+    }
+
+    // Follows nextCursor through a paginated list method, collecting `field`
+    async fn list(&self, method: &str, field: &str) -> Result<Vec<Value>, String> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let page = self.request(method, params).await?;
+            items.extend(page[field].as_array().cloned().unwrap_or_default());
+            cursor = page["nextCursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+}
+
+// Running servers by name
+#[derive(Default)]
+pub struct McpHost(Mutex<HashMap<String, Arc<Connection>>>);
+
+impl McpHost {
+    async fn connection(&self, app_handle: &AppHandle, settings: &SettingsState, name: &str) -> Result<Arc<Connection>, String> {
+        if let Some(connection) = self.0.lock().unwrap().get(name).filter(|c| !c.closed.load(Ordering::SeqCst)) {
+            return Ok(connection.clone());
+        }
+        let config = settings
+            .get()
+            .mcp_servers
+            .into_iter()
+            .find(|s| s.name == name && !s.disabled)
+            .ok_or_else(|| format!("There is no enabled tool server named '{}'.", name))?;
+        let connection = Arc::new(
+            Connection::start(app_handle, &config)
+                .await
+                .map_err(|e| format!("Tool server '{}': {}", name, e))?,
+        );
+        self.0.lock().unwrap().insert(name.to_string(), connection.clone());
+        Ok(connection)
+    }
+}
+
+fn enabled_servers(settings: &SettingsState, server: Option<String>) -> Vec<String> {
+    match server {
+        Some(server) => vec![server],
+        None => settings.get().mcp_servers.into_iter().filter(|s| !s.disabled).map(|s| s.name).collect(),
+    }
+}
+
+fn optional(value: &Value, key: &str) -> Option<String> {
+    value[key].as_str().map(str::to_string)
+}
+
+// Tools of one server, or of every enabled server
+#[tauri::command]
+pub async fn list_mcp_tools(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsState>,
+    host: State<'_, McpHost>,
+    server: Option<String>,
+) -> Result<McpToolList, String> {
+    let mut list = McpToolList { tools: Vec::new(), errors: Vec::new() };
+    for name in enabled_servers(&settings, server) {
+        let tools = match host.connection(&app_handle, &settings, &name).await {
+            Ok(connection) => connection.list("tools/list", "tools").await,
+            Err(e) => Err(e),
+        };
+        match tools {
+            Ok(tools) => list.tools.extend(tools.iter().map(|t| McpTool {
+                server: name.clone(),
+                name: optional(t, "name").unwrap_or_default(),
+                description: optional(t, "description"),
+                input_schema: t["inputSchema"].clone(),
+            })),
+            Err(error) => list.errors.push(McpServerError { server: name, error }),
+        }
+    }
+    Ok(list)
+}
+
+#[tauri::command]
+pub async fn call_mcp_tool(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsState>,
+    host: State<'_, McpHost>,
+    server: String,
+    tool: String,
+    arguments: Option<Value>,
+) -> Result<McpToolResult, String> {
+    let connection = host.connection(&app_handle, &settings, &server).await?;
+    let result = connection
+        .request("tools/call", json!({"name": tool, "arguments": arguments.unwrap_or_else(|| json!({}))}))
+        .await?;
+    let text = result["content"]
+        .as_array()
+        .map(|parts| {
+            parts
+                .iter()
+                .map(|part| match part["type"].as_str() {
+                    Some("text") => part["text"].as_str().unwrap_or_default().to_string(),
+                    Some("resource") => part["resource"]["text"]
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("[resource: {}]", part["resource"]["uri"].as_str().unwrap_or("?"))),
+                    Some(kind) => format!("[{}: {}]", kind, part["mimeType"].as_str().unwrap_or("unknown type")),
+                    None => String::new(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default();
+    Ok(McpToolResult { text, is_error: result["isError"] == true })
+}
+
+#[tauri::command]
+pub async fn list_mcp_resources(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsState>,
+    host: State<'_, McpHost>,
+    server: String,
+) -> Result<Vec<McpResource>, String> {
+    let connection = host.connection(&app_handle, &settings, &server).await?;
+    let resources = connection.list("resources/list", "resources").await?;
+    Ok(resources
+        .iter()
+        .map(|r| McpResource {
+            server: server.clone(),
+            uri: optional(r, "uri").unwrap_or_default(),
+            name: optional(r, "name"),
+            description: optional(r, "description"),
+            mime_type: optional(r, "mimeType"),
+        })
+        .collect())
+}
+
+// A resource's text as pattern input; binary resources are only accepted when they're text
+// in a blob
+#[tauri::command]
+pub async fn read_mcp_resource(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsState>,
+    host: State<'_, McpHost>,
+    server: String,
+    uri: String,
+) -> Result<String, String> {
+    let connection = host.connection(&app_handle, &settings, &server).await?;
+    let result = connection.request("resources/read", json!({"uri": uri})).await?;
+    let mut texts = Vec::new();
+    for content in result["contents"].as_array().cloned().unwrap_or_default() {
+        if let Some(text) = content["text"].as_str() {
+            texts.push(text.to_string());
+        } else if let Some(blob) = content["blob"].as_str() {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(blob)
+                .map_err(|e| e.to_string())?;
+            let text = String::from_utf8(bytes)
+                .map_err(|_| format!("'{}' is binary ({}) and can't be used as input.", uri, content["mimeType"].as_str().unwrap_or("unknown type")))?;
+            texts.push(text);
+        }
+    }
+    Ok(texts.join("\n\n"))
+}
+
+// Stops a server, e.g. after its settings changed; it starts again on next use
+#[tauri::command]
+pub async fn stop_mcp_server(host: State<'_, McpHost>, server: String) -> Result<(), String> {
+    host.0.lock().unwrap().remove(&server);
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use ring::digest::{Context, SHA256};
+use crate::history::now_secs;
+
+const DEFAULT_TTL_HOURS: u64 = 7 * 24;
+const KINDS: &[&str] = &["youtube", "page", "pdf", "transcription"];
+
+// Text that was expensive to get: fetched transcripts and pages, PDF text and transcriptions.
+// Entries live in media_cache/<kind>/<sha256>.json, keyed by the URL or by the file's contents,
+// so running another pattern on the same source doesn't fetch or process it again.
+#[derive(Serialize, Deserialize)]
+struct CachedMedia {
+    // The URL or file name, to tell what an entry is
+    source: String,
+    created: i64,
+    text: String,
+}
+
+#[derive(Default)]
+struct CacheConfig {
+    dir: Option<PathBuf>,
+    // 0 turns the cache off
+    ttl_hours: u64,
+}
+
+#[derive(Serialize, Default)]
+pub struct MediaCacheReport {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+fn config() -> &'static RwLock<CacheConfig> {
+    static CONFIG: OnceLock<RwLock<CacheConfig>> = OnceLock::new();
+    CONFIG.get_or_init(RwLock::default)
+}
+
+pub fn configure(ttl_hours: Option<u64>) {
+    config().write().unwrap().ttl_hours = ttl_hours.unwrap_or(DEFAULT_TTL_HOURS);
+}
+
+// Called once at startup; entries past their TTL are removed in the background
+pub fn init(dir: PathBuf) {
+    config().write().unwrap().dir = Some(dir);
+    tauri::async_runtime::spawn_blocking(|| {
+        let _ = remove(None, true);
+    });
+}
+
+fn hash_parts(mut context: Context, parts: &[&str]) -> String {
+    for part in parts {
+        context.update(&[0]);
+        context.update(part.as_bytes());
+    }
+    context.finish().as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// For fetched sources: the URL and whatever else changes the result
+pub fn key(parts: &[&str]) -> String {
+    hash_parts(Context::new(&SHA256), parts)
+}
+
+// For files: their contents, so a moved or renamed file still hits
+pub fn file_key(path: &Path, parts: &[&str]) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut context = Context::new(&SHA256);
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+    Ok(hash_parts(context, parts))
+}
+
+fn entry_path(kind: &str, key: &str) -> Option<PathBuf> {
+    let config = config().read().unwrap();
+    if config.ttl_hours == 0 {
+        return None;
+    }
+    config.dir.as_ref().map(|dir| dir.join(kind).join(format!("{}.json", key)))
+}
+
+fn is_expired(created: i64) -> bool {
+    let ttl_secs = config().read().unwrap().ttl_hours as i64 * 3600;
+    now_secs() - created >= ttl_secs
+}
+
+pub fn get(kind: &str, key: &str) -> Option<String> {
+    let path = entry_path(kind, key)?;
+    let cached: CachedMedia = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+    if is_expired(cached.created) {
+        let _ = fs::remove_file(&path);
+        return None;
+    }
+    Some(cached.text)
+}
+
+// A failed write only means the next run fetches again
+pub fn put(kind: &str, key: &str, source: &str, text: &str) {
+    let Some(path) = entry_path(kind, key) else {
+        return;
+    };
+    let cached = CachedMedia { source: source.to_string(), created: now_secs(), text: text.to_string() };
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_string(&cached).map_err(|e| e.to_string()))
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        eprintln!("Could not cache {} for {}: {}", kind, source, e);
+    }
+}
+
+// Removes the entries of one kind or all, or only those past their TTL
+fn remove(kind: Option<&str>, expired_only: bool) -> Result<MediaCacheReport, String> {
+    let Some(dir) = config().read().unwrap().dir.clone() else {
+        return Ok(MediaCacheReport::default());
+    };
+    let mut report = MediaCacheReport::default();
+    for kind in KINDS.iter().filter(|k| kind.is_none_or(|kind| kind == **k)) {
+        let Ok(entries) = fs::read_dir(dir.join(kind)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if expired_only {
+                let created = fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|s| serde_json::from_str::<CachedMedia>(&s).ok())
+                    .map(|c| c.created)
+                    .unwrap_or_default();
+                if !is_expired(created) {
+                    continue;
+                }
+            }
+            let size = entry.metadata().map(|m| m.len()).unwrap_or_default();
+            if fs::remove_file(&path).is_ok() {
+                report.entries += 1;
+                report.bytes += size;
+            }
+        }
+    }
+    Ok(report)
+}
+
+// kind is one of youtube, page, pdf or transcription; without it the whole cache is cleared
+#[tauri::command]
+pub async fn clear_media_cache(kind: Option<String>) -> Result<MediaCacheReport, String> {
+    if let Some(kind) = kind.as_deref().filter(|k| !KINDS.contains(k)) {
+        return Err(format!("Unknown media cache kind '{}'. Use youtube, page, pdf or transcription.", kind));
+    }
+    remove(kind.as_deref(), false)
+}
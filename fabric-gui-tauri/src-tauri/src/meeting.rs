@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use tauri::{Manager, State, Window};
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::diarize;
+use crate::ingest;
+use crate::settings::SettingsState;
+use crate::transcribe;
+
+#[derive(Serialize, Clone)]
+pub struct Attendee {
+    pub name: String,
+    pub email: Option<String>,
+    // ACCEPTED, DECLINED, TENTATIVE or NEEDS-ACTION as the invite recorded it
+    pub status: Option<String>,
+    pub optional: bool,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct CalendarEvent {
+    pub title: String,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub organizer: Option<String>,
+    pub attendees: Vec<Attendee>,
+    pub location: Option<String>,
+    // The invite's description, which is where agendas live
+    pub agenda: Option<String>,
+}
+
+// How a recording passed as the transcript is transcribed
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RecordingOptions {
+    // "local" (default) or "openai"
+    pub engine: Option<String>,
+    pub diarize: bool,
+}
+
+#[derive(Serialize)]
+pub struct MeetingResult {
+    pub event: CalendarEvent,
+    pub run_id: String,
+    pub output: String,
+}
+
+// Long lines are folded onto continuation lines that start with a space or tab
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", "\n")
+        .replace("\\N", "\n")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+// "ATTENDEE;CN=Ann;PARTSTAT=ACCEPTED:mailto:ann@example.com" into its name, parameters and value
+fn property(line: &str) -> Option<Property> {
+    // Colons inside quoted parameter values don't end the parameters
+    let mut in_quotes = false;
+    let split = line.char_indices().find(|&(_, c)| {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        c == ':' && !in_quotes
+    })?;
+    let (head, value) = (&line[..split.0], &line[split.0 + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.to_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Some(Property { name, params, value: value.to_string() })
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+}
+
+// 20240312T150000Z, 20240312T150000 with a TZID, or an all-day 20240312
+fn format_time(value: &str, params: &[(String, String)]) -> String {
+    let v = value.trim();
+    // Anything that isn't digits where they belong is shown as it came
+    let digits = |range: Range<usize>| v.get(range).filter(|part| part.bytes().all(|b| b.is_ascii_digit()));
+    let (Some(year), Some(month), Some(day)) = (digits(0..4), digits(4..6), digits(6..8)) else {
+        return v.to_string();
+    };
+    let date = format!("{}-{}-{}", year, month, day);
+    if v.len() < 13 {
+        return date;
+    }
+    let (Some(hour), Some(minute)) = (digits(9..11), digits(11..13)) else {
+        return v.to_string();
+    };
+    let zone = if v.ends_with('Z') { "UTC" } else { param(params, "TZID").unwrap_or("local time") };
+    format!("{} {}:{} {}", date, hour, minute, zone)
+}
+
+fn person(params: &[(String, String)], value: &str) -> (String, Option<String>) {
+    let email = value
+        .strip_prefix("mailto:")
+        .or_else(|| value.strip_prefix("MAILTO:"))
+        .map(|e| e.to_string());
+    let name = param(params, "CN")
+        .map(|n| n.to_string())
+        .or_else(|| email.clone())
+        .unwrap_or_else(|| value.to_string());
+    (name, email)
+}
+
+pub fn parse_ics(text: &str) -> Vec<CalendarEvent> {
+    let mut events = Vec::new();
+    let mut current: Option<CalendarEvent> = None;
+    // Alarms and other components nested in an event have their own DESCRIPTION etc.
+    let mut nested = 0;
+    for line in unfold(text) {
+        let Some(Property { name, params, value }) = property(&line) else {
+            continue;
+        };
+        match (name.as_str(), value.to_uppercase().as_str()) {
+            ("BEGIN", "VEVENT") => current = Some(CalendarEvent::default()),
+            ("END", "VEVENT") => events.extend(current.take()),
+            ("BEGIN", _) if current.is_some() => nested += 1,
+            ("END", _) if current.is_some() => nested -= 1,
+            _ => {}
+        }
+        let Some(event) = current.as_mut().filter(|_| nested == 0) else {
+            continue;
+        };
+        match name.as_str() {
+            "SUMMARY" => event.title = unescape(&value),
+            "DTSTART" => event.start = Some(format_time(&value, &params)),
+            "DTEND" => event.end = Some(format_time(&value, &params)),
+            "LOCATION" if !value.is_empty() => event.location = Some(unescape(&value)),
+            "DESCRIPTION" if !value.trim().is_empty() => event.agenda = Some(unescape(&value).trim().to_string()),
+            "ORGANIZER" => event.organizer = Some(person(&params, &value).0),
+            "ATTENDEE" => {
+                let (name, email) = person(&params, &value);
+                event.attendees.push(Attendee {
+                    name,
+                    email,
+                    status: param(&params, "PARTSTAT").map(|s| s.to_string()),
+                    optional: param(&params, "ROLE") == Some("OPT-PARTICIPANT"),
+                });
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+fn meeting_context(event: &CalendarEvent) -> String {
+    let mut text = format!("Meeting: {}\n", event.title);
+    if let Some(start) = &event.start {
+        let end = event.end.as_ref().map(|e| format!(" to {}", e)).unwrap_or_default();
+        text.push_str(&format!("When: {}{}\n", start, end));
+    }
+    if let Some(location) = &event.location {
+        text.push_str(&format!("Where: {}\n", location));
+    }
+    if let Some(organizer) = &event.organizer {
+        text.push_str(&format!("Organizer: {}\n", organizer));
+    }
+    if !event.attendees.is_empty() {
+        text.push_str("Attendees:\n");
+        for a in &event.attendees {
+            let mut notes = Vec::new();
+            if a.optional {
+                notes.push("optional".to_string());
+            }
+            if let Some(status) = &a.status {
+                notes.push(status.to_lowercase());
+            }
+            let notes = if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) };
+            text.push_str(&format!("- {}{}\n", a.name, notes));
+        }
+    }
+    if let Some(agenda) = &event.agenda {
+        text.push_str(&format!("\nAgenda:\n{}\n", agenda));
+    }
+    text
+}
+
+fn read_invite(path: &str) -> Result<Vec<CalendarEvent>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let events = parse_ics(&text);
+    if events.is_empty() {
+        return Err("The calendar file has no events.".to_string());
+    }
+    Ok(events)
+}
+
+#[tauri::command]
+pub async fn parse_ics_file(path: String) -> Result<Vec<CalendarEvent>, String> {
+    read_invite(&path)
+}
+
+// Summarizes a meeting in one step: the invite's details (attendees, time, agenda) go in
+// front of the transcript so the pattern can attribute action items to people. The
+// transcript may be text (txt, md, docx, ...) or a recording, which is transcribed first,
+// with speaker labels when `recording.diarize` is set.
+#[tauri::command]
+pub async fn summarize_meeting(
+    window: Window,
+    settings: State<'_, SettingsState>,
+    ics_path: String,
+    transcript_path: String,
+    mut request: AIRequest,
+    event_index: Option<usize>,
+    recording: Option<RecordingOptions>,
+) -> Result<MeetingResult, String> {
+    let events = read_invite(&ics_path)?;
+    let index = event_index.unwrap_or(0);
+    let event = events
+        .get(index)
+        .cloned()
+        .ok_or_else(|| format!("The calendar file has no event {}.", index))?;
+
+    let transcript_path = Path::new(&transcript_path);
+    let recording = recording.unwrap_or_default();
+    let engine = recording.engine.as_deref().unwrap_or("local");
+    let transcript = if transcribe::is_audio(transcript_path) && recording.diarize {
+        diarize::transcribe_diarized(window.app_handle(), &settings.get(), transcript_path, engine, None).await?.text
+    } else if transcribe::is_audio(transcript_path) {
+        transcribe::transcribe_file(&settings.get(), transcript_path, engine).await?
+    } else {
+        ingest::ingest(transcript_path)?
+    };
+
+    let run_id = request.run_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+    request.user_input = format!("{}\nTranscript:\n{}", meeting_context(&event), transcript.trim());
+    request.run_id = Some(run_id.clone());
+    request.dry_run = false;
+    let output = ai_client::run_recorded(window.app_handle(), Some(window.label().to_string()), request).await?;
+    Ok(MeetingResult { event, run_id, output })
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+use crate::http::{self, AuditedSend};
+use crate::settings::SettingsState;
+use crate::startup;
+
+const BUNDLED_REGISTRY: &str = include_str!("../resources/models.json");
+const DEFAULT_REGISTRY_URL: &str =
+    "https://raw.githubusercontent.com/coolman1984/Fabric/main/fabric-gui-tauri/src-tauri/resources/models.json";
+const REFRESH_AFTER: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ModelCapabilities {
+    pub id: String,
+    pub vendor: String,
+    pub context_window: u64,
+    pub max_output_tokens: u64,
+    pub supports_vision: bool,
+    pub supports_tools: bool,
+    pub supports_thinking: bool,
+    pub supports_temperature: bool,
+    pub input_price_per_mtok: f64,
+    pub output_price_per_mtok: f64,
+    pub deprecation_date: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ModelRegistry {
+    pub version: u32,
+    pub updated: String,
+    pub models: Vec<ModelCapabilities>,
+}
+
+#[derive(Serialize)]
+pub struct RegistryInfo {
+    pub version: u32,
+    pub updated: String,
+    pub model_count: usize,
+}
+
+pub struct ModelRegistryState {
+    cache_path: PathBuf,
+    inner: Mutex<ModelRegistry>,
+}
+
+impl ModelRegistryState {
+    pub fn load(cache_path: PathBuf) -> Self {
+        let bundled: ModelRegistry =
+            serde_json::from_str(BUNDLED_REGISTRY).expect("bundled models.json is invalid");
+
+        // A previously downloaded registry wins only if it is newer than the one we shipped with
+        let cached = fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<ModelRegistry>(&s).ok())
+            .filter(|r| r.version >= bundled.version && r.updated >= bundled.updated);
+
+        Self {
+            cache_path,
+            inner: Mutex::new(cached.unwrap_or(bundled)),
+        }
+    }
+
+    pub fn find(&self, model: &str) -> Option<ModelCapabilities> {
+        let registry = self.inner.lock().unwrap();
+        registry
+            .models
+            .iter()
+            .find(|m| m.id == model)
+            // Dated snapshots (e.g. gpt-4o-2024-08-06) inherit their family's entry
+            .or_else(|| {
+                registry
+                    .models
+                    .iter()
+                    .filter(|m| model.starts_with(&format!("{}-", m.id)))
+                    .max_by_key(|m| m.id.len())
+            })
+            .cloned()
+    }
+
+    fn info(&self) -> RegistryInfo {
+        let registry = self.inner.lock().unwrap();
+        RegistryInfo {
+            version: registry.version,
+            updated: registry.updated.clone(),
+            model_count: registry.models.len(),
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_model_capabilities(
+    registry: State<'_, ModelRegistryState>,
+    vendor: Option<String>,
+    model: Option<String>,
+) -> Result<Vec<ModelCapabilities>, String> {
+    if let Some(model) = model {
+        return registry
+            .find(&model)
+            .map(|m| vec![m])
+            .ok_or_else(|| format!("No capability data for model '{}'.", model));
+    }
+
+    let registry = registry.inner.lock().unwrap();
+    Ok(registry
+        .models
+        .iter()
+        .filter(|m| vendor.as_ref().map(|v| &m.vendor == v).unwrap_or(true))
+        .cloned()
+        .collect())
+}
+
+async fn download(registry: &ModelRegistryState, settings: &SettingsState) -> Result<RegistryInfo, String> {
+    let url = settings
+        .get()
+        .model_registry_url
+        .unwrap_or_else(|| DEFAULT_REGISTRY_URL.to_string());
+
+    let res = http::client_for(&url)?
+        .get(&url)
+        .timeout(Duration::from_secs(15))
+        .send_audited("model_registry", None)
+        .await
+        .map_err(http::network_error)?;
+
+    if !res.status().is_success() {
+        return Err(format!("Could not download model registry ({})", res.status()));
+    }
+
+    let body = res.text().await.map_err(|e| e.to_string())?;
+    let fetched: ModelRegistry =
+        serde_json::from_str(&body).map_err(|e| format!("Invalid model registry: {}", e))?;
+
+    if let Some(parent) = registry.cache_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&registry.cache_path, &body).map_err(|e| e.to_string())?;
+
+    *registry.inner.lock().unwrap() = fetched;
+    Ok(registry.info())
+}
+
+// Updates a registry downloaded more than a day ago (or never) in the background, so the
+// window starts with the cached copy instead of waiting for the network
+pub fn spawn_refresh(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let started = Instant::now();
+        let registry = app_handle.state::<ModelRegistryState>();
+        let fresh = fs::metadata(&registry.cache_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|at| at.elapsed().ok())
+            .is_some_and(|age| age < REFRESH_AFTER);
+        let result = match fresh {
+            true => Ok(()),
+            false => download(&registry, &app_handle.state::<SettingsState>()).await.map(|_| ()),
+        };
+        startup::ready(&app_handle, "model_registry", started, result.err());
+    });
+}
+
+#[tauri::command]
+pub async fn refresh_model_registry(
+    registry: State<'_, ModelRegistryState>,
+    settings: State<'_, SettingsState>,
+) -> Result<RegistryInfo, String> {
+    download(&registry, &settings).await
+}
@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Window};
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::history::now_secs;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NotebookCell {
+    pub id: String,
+    // The run to perform; the API key is never stored and comes from settings at run time
+    pub request: AIRequest,
+    // ID of an earlier cell whose output becomes this cell's input, followed by the cell's
+    // own user_input when that isn't empty
+    pub input_from: Option<String>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub last_run_id: Option<String>,
+    pub ran_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Notebook {
+    pub id: String,
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub cells: Vec<NotebookCell>,
+}
+
+#[derive(Serialize)]
+pub struct NotebookSummary {
+    pub id: String,
+    pub title: String,
+    pub updated_at: i64,
+    pub cell_count: usize,
+}
+
+fn notebooks_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("notebooks"))
+        .map_err(|e| e.to_string())
+}
+
+fn notebook_path(app_handle: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    // IDs are UUIDs; anything else could point outside the notebooks directory
+    Uuid::parse_str(id).map_err(|_| format!("Notebook '{}' not found.", id))?;
+    Ok(notebooks_dir(app_handle)?.join(format!("{}.json", id)))
+}
+
+fn load(app_handle: &AppHandle, id: &str) -> Result<Notebook, String> {
+    let text = fs::read_to_string(notebook_path(app_handle, id)?).map_err(|_| format!("Notebook '{}' not found.", id))?;
+    serde_json::from_str(&text).map_err(|e| format!("Notebook '{}' is corrupt: {}", id, e))
+}
+
+fn save(app_handle: &AppHandle, notebook: &mut Notebook) -> Result<(), String> {
+    notebook.updated_at = now_secs();
+    fs::create_dir_all(notebooks_dir(app_handle)?).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(notebook).map_err(|e| e.to_string())?;
+    fs::write(notebook_path(app_handle, &notebook.id)?, json).map_err(|e| e.to_string())
+}
+
+fn cell_index(notebook: &Notebook, cell_id: &str) -> Result<usize, String> {
+    notebook
+        .cells
+        .iter()
+        .position(|c| c.id == cell_id)
+        .ok_or_else(|| format!("Cell '{}' not found.", cell_id))
+}
+
+#[tauri::command]
+pub async fn create_notebook(app_handle: AppHandle, title: String) -> Result<Notebook, String> {
+    let mut notebook = Notebook {
+        id: Uuid::new_v4().to_string(),
+        title,
+        created_at: now_secs(),
+        updated_at: now_secs(),
+        cells: Vec::new(),
+    };
+    save(&app_handle, &mut notebook)?;
+    Ok(notebook)
+}
+
+#[tauri::command]
+pub async fn list_notebooks(app_handle: AppHandle) -> Result<Vec<NotebookSummary>, String> {
+    let dir = notebooks_dir(&app_handle)?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut notebooks: Vec<NotebookSummary> = entries
+        .flatten()
+        .filter_map(|e| fs::read_to_string(e.path()).ok())
+        .filter_map(|text| serde_json::from_str::<Notebook>(&text).ok())
+        .map(|n| NotebookSummary {
+            id: n.id,
+            title: n.title,
+            updated_at: n.updated_at,
+            cell_count: n.cells.len(),
+        })
+        .collect();
+    notebooks.sort_by_key(|n| std::cmp::Reverse(n.updated_at));
+    Ok(notebooks)
+}
+
+#[tauri::command]
+pub async fn get_notebook(app_handle: AppHandle, notebook_id: String) -> Result<Notebook, String> {
+    load(&app_handle, &notebook_id)
+}
+
+#[tauri::command]
+pub async fn delete_notebook(app_handle: AppHandle, notebook_id: String) -> Result<(), String> {
+    fs::remove_file(notebook_path(&app_handle, &notebook_id)?).map_err(|e| e.to_string())
+}
+
+// Inserts a cell at `position` (appended when unset) and returns its ID
+#[tauri::command]
+pub async fn add_cell(
+    app_handle: AppHandle,
+    notebook_id: String,
+    mut request: AIRequest,
+    input_from: Option<String>,
+    position: Option<usize>,
+) -> Result<String, String> {
+    let mut notebook = load(&app_handle, &notebook_id)?;
+    let position = position.unwrap_or(notebook.cells.len()).min(notebook.cells.len());
+    if let Some(source) = &input_from {
+        if cell_index(&notebook, source)? >= position {
+            return Err("A cell can only take its input from a cell above it.".to_string());
+        }
+    }
+
+    request.api_key.clear();
+    request.run_id = None;
+    request.dry_run = false;
+    let id = Uuid::new_v4().to_string();
+    notebook.cells.insert(
+        position,
+        NotebookCell {
+            id: id.clone(),
+            request,
+            input_from,
+            output: None,
+            error: None,
+            last_run_id: None,
+            ran_at: None,
+        },
+    );
+    save(&app_handle, &mut notebook)?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn remove_cell(app_handle: AppHandle, notebook_id: String, cell_id: String) -> Result<(), String> {
+    let mut notebook = load(&app_handle, &notebook_id)?;
+    let index = cell_index(&notebook, &cell_id)?;
+    notebook.cells.remove(index);
+    // Cells that read from the removed one fall back to their own input
+    for cell in notebook.cells.iter_mut().filter(|c| c.input_from.as_deref() == Some(&cell_id)) {
+        cell.input_from = None;
+    }
+    save(&app_handle, &mut notebook)
+}
+
+// `cell_ids` is the complete new order. An order that puts a cell above the cell it reads
+// its input from is rejected.
+#[tauri::command]
+pub async fn reorder_cells(app_handle: AppHandle, notebook_id: String, cell_ids: Vec<String>) -> Result<(), String> {
+    let mut notebook = load(&app_handle, &notebook_id)?;
+    if cell_ids.len() != notebook.cells.len() {
+        return Err("The new order must list every cell exactly once.".to_string());
+    }
+
+    let mut reordered = Vec::with_capacity(cell_ids.len());
+    for id in &cell_ids {
+        let index = cell_index(&notebook, id)?;
+        reordered.push(notebook.cells[index].clone());
+    }
+    for (position, cell) in reordered.iter().enumerate() {
+        if let Some(source) = &cell.input_from {
+            if !cell_ids[..position].contains(source) {
+                return Err("A cell can only take its input from a cell above it.".to_string());
+            }
+        }
+    }
+    notebook.cells = reordered;
+    save(&app_handle, &mut notebook)
+}
+
+// Streams like an interactive run (events carry the returned run ID) and stores the
+// output in the cell so later cells can use it
+async fn run_one(window: &Window, notebook_id: &str, cell_id: &str) -> Result<String, String> {
+    let app_handle = window.app_handle();
+    let notebook = load(app_handle, notebook_id)?;
+    let index = cell_index(&notebook, cell_id)?;
+    let cell = &notebook.cells[index];
+
+    let mut request = cell.request.clone();
+    if let Some(source) = &cell.input_from {
+        let source = &notebook.cells[cell_index(&notebook, source)?];
+        let Some(output) = &source.output else {
+            return Err("Run the cell this one takes its input from first.".to_string());
+        };
+        request.user_input = if request.user_input.trim().is_empty() {
+            output.clone()
+        } else {
+            format!("{}\n\n{}", output, request.user_input)
+        };
+    }
+    let run_id = Uuid::new_v4().to_string();
+    request.run_id = Some(run_id.clone());
+
+    let result = ai_client::run_recorded(app_handle, Some(window.label().to_string()), request).await;
+
+    // Reloaded so edits made while the run streamed aren't overwritten
+    let mut notebook = load(app_handle, notebook_id)?;
+    if let Ok(index) = cell_index(&notebook, cell_id) {
+        let cell = &mut notebook.cells[index];
+        cell.last_run_id = Some(run_id);
+        cell.ran_at = Some(now_secs());
+        match &result {
+            Ok(output) => {
+                cell.output = Some(output.clone());
+                cell.error = None;
+            }
+            Err(e) => cell.error = Some(e.clone()),
+        }
+        save(app_handle, &mut notebook)?;
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn run_cell(window: Window, notebook_id: String, cell_id: String) -> Result<String, String> {
+    run_one(&window, &notebook_id, &cell_id).await
+}
+
+// Runs every cell top to bottom, stopping at the first failure; returns the final notebook
+#[tauri::command]
+pub async fn run_notebook(window: Window, notebook_id: String) -> Result<Notebook, String> {
+    let cell_ids: Vec<String> = load(window.app_handle(), &notebook_id)?
+        .cells
+        .into_iter()
+        .map(|c| c.id)
+        .collect();
+    for cell_id in cell_ids {
+        run_one(&window, &notebook_id, &cell_id).await?;
+    }
+    load(window.app_handle(), &notebook_id)
+}
@@ -0,0 +1,212 @@
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS, Transport};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+use crate::audit::{self, AuditEntry};
+use crate::history::{now_secs, HistoryState};
+use crate::http::{self, AuditedSend};
+use crate::settings::{Settings, SettingsState};
+use crate::templates;
+
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(15);
+// rumqttc's 10 KB default would reject most summaries
+const MAX_PACKET_BYTES: usize = 1024 * 1024;
+
+// Output channels for runs that finish with nobody watching, such as the reading list
+// processed from the tray: dashboards and e-ink displays pick the result up from there
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct NotifySettings {
+    pub mqtt: Option<MqttChannel>,
+    pub home_assistant: Option<HomeAssistantChannel>,
+    // Outputs longer than this are cut at a line break; small displays can't show more anyway
+    pub max_output_chars: Option<usize>,
+    // Publish each reading list digest when it's done
+    pub publish_reading_digest: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MqttChannel {
+    // mqtt://host[:1883] or mqtts://host[:8883]; the password is api_keys "mqtt"
+    pub broker: String,
+    pub topic: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    // Retained messages show the last output to subscribers that connect later
+    #[serde(default = "retain_default")]
+    pub retain: bool,
+}
+
+fn retain_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HomeAssistantChannel {
+    // e.g. http://homeassistant.local:8123
+    pub url: String,
+    // The id of a webhook trigger in an automation; webhooks need no token
+    pub webhook_id: String,
+}
+
+// The message both channels carry, as JSON
+#[derive(Serialize)]
+pub struct Publication {
+    pub title: String,
+    pub pattern: Option<String>,
+    pub run_id: Option<String>,
+    pub output: String,
+    pub created_at: i64,
+    pub truncated: bool,
+}
+
+impl Publication {
+    fn limit(mut self, max_chars: Option<usize>) -> Self {
+        let Some(max) = max_chars.filter(|&m| self.output.chars().count() > m) else {
+            return self;
+        };
+        let cut: String = self.output.chars().take(max).collect();
+        self.output = match cut.rfind('\n') {
+            Some(end) if end > 0 => cut[..end].trim_end().to_string(),
+            _ => cut,
+        };
+        self.truncated = true;
+        self
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChannelResult {
+    // "mqtt" or "home_assistant"
+    pub channel: String,
+    pub error: Option<String>,
+}
+
+async fn publish_mqtt(settings: &Settings, channel: &MqttChannel, payload: Vec<u8>) -> Result<(), String> {
+    let url = reqwest::Url::parse(channel.broker.trim()).map_err(|_| format!("'{}' is not an MQTT broker URL.", channel.broker))?;
+    let tls = match url.scheme() {
+        "mqtt" | "tcp" => false,
+        "mqtts" | "ssl" => true,
+        other => return Err(format!("Unsupported MQTT scheme '{}'. Use mqtt:// or mqtts://.", other)),
+    };
+    let host = url.host_str().ok_or("The MQTT broker URL has no host.")?;
+    http::check_host(url.as_str())?;
+    let port = url.port().unwrap_or(if tls { 8883 } else { 1883 });
+    let mut options = MqttOptions::new(format!("fabric-{}", now_secs()), host, port);
+    options.set_keep_alive(Duration::from_secs(10));
+    options.set_max_packet_size(MAX_PACKET_BYTES, MAX_PACKET_BYTES);
+    if let Some(username) = channel.username.as_deref().filter(|u| !u.is_empty()) {
+        options.set_credentials(username, settings.api_key("mqtt").unwrap_or_default());
+    }
+    if tls {
+        options.set_transport(Transport::tls_with_default_config());
+    }
+
+    let mut entry = AuditEntry {
+        at: now_secs(),
+        method: "PUBLISH".to_string(),
+        endpoint: format!("{}://{}:{}/{}", url.scheme(), host, port, channel.topic),
+        purpose: "notify".to_string(),
+        bytes_sent: Some(payload.len() as u64),
+        body: Some(&payload).filter(|_| audit::wants_bodies()).map(|p| audit::loggable_body(p)),
+        ..Default::default()
+    };
+    let started = Instant::now();
+
+    let (client, mut eventloop) = AsyncClient::new(options, 10);
+    client
+        .publish(channel.topic.as_str(), QoS::AtLeastOnce, channel.retain, payload)
+        .await
+        .map_err(|e| e.to_string())?;
+    // The event loop does the connecting and sending; the broker's PUBACK means it has the message
+    let delivered = tokio::time::timeout(PUBLISH_TIMEOUT, async {
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Packet::PubAck(_))) => return Ok(()),
+                Ok(_) => {}
+                Err(e) => return Err(format!("MQTT: {}", e)),
+            }
+        }
+    })
+    .await
+    .map_err(|_| "The MQTT broker did not acknowledge the message.".to_string())
+    .and_then(|delivered| delivered);
+    let _ = client.disconnect().await;
+    entry.duration_ms = started.elapsed().as_millis() as u64;
+    entry.error = delivered.clone().err();
+    audit::record(entry);
+    delivered
+}
+
+async fn publish_home_assistant(channel: &HomeAssistantChannel, publication: &Publication) -> Result<(), String> {
+    let webhook = channel.webhook_id.trim();
+    if webhook.is_empty() || !webhook.chars().all(|c| c.is_ascii_alphanumeric() || "-_".contains(c)) {
+        return Err(format!("'{}' is not a Home Assistant webhook id.", webhook));
+    }
+    let url = format!("{}/api/webhook/{}", channel.url.trim().trim_end_matches('/'), webhook);
+    let res = http::client_for(&url)?
+        .post(&url)
+        .json(publication)
+        .timeout(PUBLISH_TIMEOUT)
+        .send_audited("notify", None)
+        .await
+        .map_err(http::network_error)?;
+    if !res.status().is_success() {
+        return Err(format!("Home Assistant answered {}.", res.status()));
+    }
+    Ok(())
+}
+
+// Sends to every configured channel; one failing doesn't stop the others
+pub async fn publish(settings: &Settings, publication: Publication) -> Vec<ChannelResult> {
+    let notify = &settings.notify;
+    let publication = publication.limit(notify.max_output_chars);
+    let mut results = Vec::new();
+    if let Some(channel) = &notify.mqtt {
+        let payload = serde_json::to_vec(&publication).unwrap_or_default();
+        let error = publish_mqtt(settings, channel, payload).await.err();
+        results.push(ChannelResult { channel: "mqtt".to_string(), error });
+    }
+    if let Some(channel) = &notify.home_assistant {
+        let error = publish_home_assistant(channel, &publication).await.err();
+        results.push(ChannelResult { channel: "home_assistant".to_string(), error });
+    }
+    results
+}
+
+// For unattended runs: publishes when the settings ask for it and logs failures, since there is
+// no window to report them to
+pub async fn publish_unattended(app_handle: &AppHandle, publication: Publication) {
+    let settings = app_handle.state::<SettingsState>().get();
+    for result in publish(&settings, publication).await {
+        if let Some(error) = result.error {
+            eprintln!("Publishing to {} failed: {}", result.channel, error);
+        }
+    }
+}
+
+// Publishes a recorded run to the configured channels, e.g. from a "Send to dashboard" action
+// or to test the settings
+#[tauri::command]
+pub async fn publish_run(
+    settings: State<'_, SettingsState>,
+    history: State<'_, HistoryState>,
+    run_id: String,
+) -> Result<Vec<ChannelResult>, String> {
+    let settings = settings.get();
+    if settings.notify.mqtt.is_none() && settings.notify.home_assistant.is_none() {
+        return Err("Set up an MQTT broker or a Home Assistant webhook in settings first.".to_string());
+    }
+    let entry = history
+        .get(&run_id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))?;
+    let publication = Publication {
+        title: templates::title(&entry),
+        pattern: entry.pattern.clone(),
+        run_id: Some(entry.id.clone()),
+        output: entry.output.clone(),
+        created_at: entry.created_at,
+        truncated: false,
+    };
+    Ok(publish(&settings, publication).await)
+}
@@ -0,0 +1,187 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::http::{self, AuditedSend};
+use crate::settings::SettingsState;
+use crate::vertex::form_body;
+
+const DEVICE_CODE_URL: &str = "https://login.microsoftonline.com/common/oauth2/v2.0/devicecode";
+const TOKEN_URL: &str = "https://login.microsoftonline.com/common/oauth2/v2.0/token";
+const GRAPH_URL: &str = "https://graph.microsoft.com/v1.0/me/onenote";
+const SCOPES: &str = "Notes.Create Notes.Read offline_access";
+// The refresh token is kept with the API keys under this name
+const KEY_NAME: &str = "onenote";
+
+#[derive(Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    message: String,
+    interval: u64,
+    expires_in: u64,
+}
+
+#[derive(Serialize)]
+pub struct OneNoteSignIn {
+    pub user_code: String,
+    pub verification_uri: String,
+    pub message: String,
+}
+
+fn client_id(app_handle: &AppHandle) -> Result<String, String> {
+    app_handle
+        .state::<SettingsState>()
+        .get()
+        .export
+        .onenote_client_id
+        .filter(|id| !id.trim().is_empty())
+        .ok_or_else(|| "Set the Azure app's client id for OneNote in settings first.".to_string())
+}
+
+async fn post_form(url: &str, params: &[(&str, &str)]) -> Result<(u16, Value), String> {
+    let res = http::client_for(url)?
+        .post(url)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(form_body(params))
+        .timeout(Duration::from_secs(30))
+        .send_audited("onenote", None)
+        .await
+        .map_err(http::network_error)?;
+    let status = res.status().as_u16();
+    Ok((status, res.json().await.map_err(|e| e.to_string())?))
+}
+
+// Microsoft rotates refresh tokens; the newest one replaces the stored one
+fn store_refresh_token(app_handle: &AppHandle, token: &Value) -> Result<(), String> {
+    if let Some(refresh) = token["refresh_token"].as_str() {
+        app_handle.state::<SettingsState>().update(|s| {
+            s.api_keys.insert(KEY_NAME.to_string(), refresh.to_string());
+        })?;
+    }
+    Ok(())
+}
+
+// Polls until the user has entered the code, then stores the refresh token and emits
+// "onenote-signed-in" with true, or false when the code expired or was declined
+async fn await_sign_in(app_handle: AppHandle, client_id: String, code: DeviceCode) {
+    let mut interval = Duration::from_secs(code.interval.max(1));
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(code.expires_in);
+    let mut signed_in = false;
+    while tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(interval).await;
+        let params = [
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ("client_id", client_id.as_str()),
+            ("device_code", code.device_code.as_str()),
+        ];
+        let Ok((status, token)) = post_form(TOKEN_URL, &params).await else {
+            continue;
+        };
+        if status == 200 {
+            signed_in = store_refresh_token(&app_handle, &token).is_ok();
+            break;
+        }
+        match token["error"].as_str() {
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval += Duration::from_secs(5),
+            _ => break,
+        }
+    }
+    let _ = app_handle.emit("onenote-signed-in", signed_in);
+}
+
+// Starts Microsoft's device code sign-in: the UI shows the code and link, the user enters it
+// in a browser, and "onenote-signed-in" reports the outcome
+#[tauri::command]
+pub async fn onenote_sign_in(app_handle: AppHandle) -> Result<OneNoteSignIn, String> {
+    let client_id = client_id(&app_handle)?;
+    let (status, body) = post_form(DEVICE_CODE_URL, &[("client_id", &client_id), ("scope", SCOPES)]).await?;
+    if status != 200 {
+        let error = body["error_description"].as_str().unwrap_or("unknown error");
+        return Err(format!("Microsoft sign-in failed: {}", error));
+    }
+    let code: DeviceCode = serde_json::from_value(body).map_err(|e| e.to_string())?;
+    let sign_in = OneNoteSignIn {
+        user_code: code.user_code.clone(),
+        verification_uri: code.verification_uri.clone(),
+        message: code.message.clone(),
+    };
+    tauri::async_runtime::spawn(await_sign_in(app_handle, client_id, code));
+    Ok(sign_in)
+}
+
+#[tauri::command]
+pub async fn onenote_sign_out(settings: State<'_, SettingsState>) -> Result<(), String> {
+    settings.update(|s| {
+        s.api_keys.remove(KEY_NAME);
+    })?;
+    Ok(())
+}
+
+async fn access_token(app_handle: &AppHandle) -> Result<String, String> {
+    let refresh = app_handle
+        .state::<SettingsState>()
+        .get()
+        .api_key(KEY_NAME)
+        .ok_or("Sign in to OneNote in settings first.")?;
+    let client_id = client_id(app_handle)?;
+    let params = [
+        ("grant_type", "refresh_token"),
+        ("client_id", client_id.as_str()),
+        ("refresh_token", refresh.as_str()),
+        ("scope", SCOPES),
+    ];
+    let (status, token) = post_form(TOKEN_URL, &params).await?;
+    if status != 200 {
+        return Err("The OneNote sign-in has expired; sign in again in settings.".to_string());
+    }
+    store_refresh_token(app_handle, &token)?;
+    token["access_token"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "Microsoft returned no access token.".to_string())
+}
+
+async fn graph(request: reqwest::RequestBuilder, token: &str) -> Result<Value, String> {
+    let res = request
+        .bearer_auth(token)
+        .timeout(Duration::from_secs(60))
+        .send_audited("onenote", None)
+        .await
+        .map_err(http::network_error)?;
+    let status = res.status();
+    if !status.is_success() {
+        let text = res.text().await.unwrap_or_default();
+        return Err(format!("OneNote error ({}): {}", status, &text.chars().take(300).collect::<String>()));
+    }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+// Creates a page from an HTML body in the section named `section` (the first one with that
+// name in any notebook), or in the default section when unset; returns the page's web link
+pub async fn create_page(app_handle: &AppHandle, section: Option<&str>, html: &str) -> Result<String, String> {
+    let token = access_token(app_handle).await?;
+    let pages_url = match section {
+        Some(name) => {
+            let mut url = Url::parse(&format!("{}/sections", GRAPH_URL)).map_err(|e| e.to_string())?;
+            url.query_pairs_mut()
+                .append_pair("$filter", &format!("displayName eq '{}'", name.replace('\'', "''")))
+                .append_pair("$select", "id");
+            let sections = graph(http::client_for(url.as_str())?.get(url.as_str()), &token).await?;
+            let id = sections["value"][0]["id"]
+                .as_str()
+                .ok_or_else(|| format!("OneNote has no section named '{}'.", name))?;
+            format!("{}/sections/{}/pages", GRAPH_URL, id)
+        }
+        None => format!("{}/pages", GRAPH_URL),
+    };
+    let request = http::client_for(&pages_url)?
+        .post(&pages_url)
+        .header("Content-Type", "text/html")
+        .body(html.to_string());
+    let page = graph(request, &token).await?;
+    Ok(page["links"]["oneNoteWebUrl"]["href"].as_str().unwrap_or_default().to_string())
+}
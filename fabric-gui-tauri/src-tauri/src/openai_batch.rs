@@ -0,0 +1,373 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use reqwest::multipart::{Form, Part};
+use reqwest::Client;
+use tauri::{AppHandle, Emitter, Manager, State};
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::emitter;
+use crate::history::{now_secs, HistoryState};
+use crate::http::{self, AuditedSend};
+use crate::i18n::tr_args;
+use crate::residency;
+use crate::settings::SettingsState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+// Follows the OpenAI region chosen in the data residency settings
+fn openai_api() -> String {
+    format!("{}/v1", residency::openai_base_url())
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BatchJob {
+    pub id: String,
+    pub created_at: i64,
+    // OpenAI's batch status: validating, in_progress, finalizing, completed, failed, expired, cancelled...
+    pub status: String,
+    pub request_count: usize,
+    pub completed_count: usize,
+    pub failed_count: usize,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    // Set once the results have been written to history
+    pub imported: bool,
+    // Keyed by run ID (the batch custom_id); API keys are stripped before saving
+    #[serde(default)]
+    requests: HashMap<String, AIRequest>,
+}
+
+#[derive(Serialize)]
+pub struct BatchResult {
+    pub run_id: String,
+    pub output: String,
+    pub error: Option<String>,
+}
+
+impl BatchJob {
+    fn is_finished(&self) -> bool {
+        matches!(self.status.as_str(), "completed" | "failed" | "expired" | "cancelled")
+    }
+}
+
+// Batch jobs outlive the app session (OpenAI allows up to 24h), so they're tracked on disk
+pub struct BatchJobsState {
+    path: PathBuf,
+    jobs: Mutex<Vec<BatchJob>>,
+}
+
+impl BatchJobsState {
+    pub fn load(path: PathBuf) -> Self {
+        let jobs = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            jobs: Mutex::new(jobs),
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(&*self.jobs.lock().unwrap()).map_err(|e| e.to_string())?;
+        fs::write(&self.path, json).map_err(|e| e.to_string())
+    }
+
+    fn get(&self, id: &str) -> Option<BatchJob> {
+        self.jobs.lock().unwrap().iter().find(|j| j.id == id).cloned()
+    }
+
+    fn upsert(&self, job: BatchJob) -> Result<(), String> {
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            match jobs.iter_mut().find(|j| j.id == job.id) {
+                Some(existing) => *existing = job,
+                None => jobs.push(job),
+            }
+        }
+        self.save()
+    }
+}
+
+fn openai_key(app_handle: &AppHandle, api_key: Option<String>) -> Result<String, String> {
+    api_key
+        .filter(|k| !k.trim().is_empty())
+        .or_else(|| app_handle.state::<SettingsState>().get().api_key("openai"))
+        .ok_or_else(|| tr_args("health-missing-key", &[("vendor", "openai")]))
+}
+
+async fn check(res: reqwest::Response) -> Result<String, String> {
+    let status = res.status();
+    let text = res.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(tr_args(
+            "vendor-api-error",
+            &[("vendor", "OpenAI"), ("status", &status.to_string()), ("details", &text.chars().take(300).collect::<String>())],
+        ));
+    }
+    Ok(text)
+}
+
+async fn upload_batch_file(client: &Client, api_key: &str, jsonl: String) -> Result<String, String> {
+    let file = Part::text(jsonl)
+        .file_name("batch.jsonl")
+        .mime_str("application/jsonl")
+        .map_err(|e| e.to_string())?;
+    let form = Form::new().text("purpose", "batch").part("file", file);
+
+    let res = client
+        .post(format!("{}/files", openai_api()))
+        .bearer_auth(api_key)
+        .multipart(form)
+        .send_audited("batch", None)
+        .await
+        .map_err(http::network_error)?;
+
+    let json: Value = serde_json::from_str(&check(res).await?).map_err(|e| e.to_string())?;
+    json["id"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| "OpenAI did not return a file ID.".to_string())
+}
+
+fn apply_remote(job: &mut BatchJob, remote: &Value) {
+    if let Some(status) = remote["status"].as_str() {
+        job.status = status.to_string();
+    }
+    job.completed_count = remote["request_counts"]["completed"].as_u64().unwrap_or(0) as usize;
+    job.failed_count = remote["request_counts"]["failed"].as_u64().unwrap_or(0) as usize;
+    job.output_file_id = remote["output_file_id"].as_str().map(String::from);
+    job.error_file_id = remote["error_file_id"].as_str().map(String::from);
+}
+
+async fn refresh_job(client: &Client, api_key: &str, job: &mut BatchJob) -> Result<(), String> {
+    let res = client
+        .get(format!("{}/batches/{}", openai_api(), job.id))
+        .bearer_auth(api_key)
+        .send_audited("batch", None)
+        .await
+        .map_err(http::network_error)?;
+    let remote: Value = serde_json::from_str(&check(res).await?).map_err(|e| e.to_string())?;
+    apply_remote(job, &remote);
+    Ok(())
+}
+
+async fn download_results(client: &Client, api_key: &str, job: &BatchJob) -> Result<Vec<BatchResult>, String> {
+    let mut results = Vec::new();
+
+    for file_id in [&job.output_file_id, &job.error_file_id].into_iter().flatten() {
+        let res = client
+            .get(format!("{}/files/{}/content", openai_api(), file_id))
+            .bearer_auth(api_key)
+            .send_audited("batch", None)
+            .await
+            .map_err(http::network_error)?;
+
+        for line in check(res).await?.lines().filter(|l| !l.trim().is_empty()) {
+            let item: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+            let run_id = item["custom_id"].as_str().unwrap_or_default().to_string();
+            let body = &item["response"]["body"];
+            let output = body["choices"][0]["message"]["content"].as_str().unwrap_or_default().to_string();
+            let error = if !item["error"].is_null() {
+                Some(item["error"]["message"].as_str().unwrap_or("Request failed").to_string())
+            } else if item["response"]["status_code"].as_u64().is_some_and(|c| c >= 400) {
+                Some(body["error"]["message"].as_str().unwrap_or("Request failed").to_string())
+            } else {
+                None
+            };
+            results.push(BatchResult { run_id, output, error });
+        }
+    }
+
+    Ok(results)
+}
+
+// Records each result under its run ID, led by the request's prefill as in a normal run; runs
+// already in history are left alone
+fn import_results(history: &HistoryState, job: &BatchJob, results: &[BatchResult]) {
+    for result in results {
+        let Some(request) = job.requests.get(&result.run_id) else { continue };
+        if matches!(history.get(&result.run_id), Ok(Some(_))) {
+            continue;
+        }
+        let output = match request.assistant_prefill.as_deref().filter(|p| !p.is_empty()) {
+            Some(prefill) if result.error.is_none() => format!("{}{}", prefill, emitter::strip_echo(&result.output, prefill)),
+            _ => result.output.clone(),
+        };
+        let _ = history.record(&result.run_id, request, &output, result.error.as_deref(), None, None);
+    }
+}
+
+// Refreshes unfinished jobs and pulls completed results into history; returns whether anything changed
+async fn sync_jobs(app_handle: &AppHandle, api_key: &str) -> Result<bool, String> {
+    let state = app_handle.state::<BatchJobsState>();
+    let pending: Vec<BatchJob> = state
+        .jobs
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|j| !j.is_finished() || !j.imported)
+        .cloned()
+        .collect();
+
+    let client = http::client_for(&openai_api())?;
+    let mut changed = false;
+    for mut job in pending {
+        let before = (job.status.clone(), job.completed_count, job.failed_count);
+        if !job.is_finished() {
+            refresh_job(&client, api_key, &mut job).await?;
+        }
+        if job.is_finished() && !job.imported {
+            let results = download_results(&client, api_key, &job).await?;
+            import_results(&app_handle.state::<HistoryState>(), &job, &results);
+            job.imported = true;
+            changed = true;
+        }
+        changed |= before != (job.status.clone(), job.completed_count, job.failed_count);
+        state.upsert(job)?;
+    }
+
+    Ok(changed)
+}
+
+pub fn spawn_poller(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let Ok(api_key) = openai_key(&app_handle, None) else { continue };
+            if let Ok(true) = sync_jobs(&app_handle, &api_key).await {
+                let jobs = app_handle.state::<BatchJobsState>().jobs.lock().unwrap().clone();
+                let _ = app_handle.emit("batch-status", jobs);
+            }
+        }
+    });
+}
+
+// Submits the requests through OpenAI's Batch API: results arrive within 24h at half the price.
+// Each request keeps its run ID (or gets one) so results land in history under a known ID.
+#[tauri::command]
+pub async fn submit_openai_batch(
+    app_handle: AppHandle,
+    state: State<'_, BatchJobsState>,
+    requests: Vec<AIRequest>,
+    api_key: Option<String>,
+) -> Result<BatchJob, String> {
+    if requests.is_empty() {
+        return Err("A batch needs at least one request.".to_string());
+    }
+    if requests.iter().any(|r| r.vendor != "openai") {
+        return Err("The Batch API only accepts OpenAI requests.".to_string());
+    }
+    let settings = app_handle.state::<SettingsState>().get();
+    let api_key = openai_key(&app_handle, api_key)?;
+
+    let mut lines = Vec::new();
+    let mut stored = HashMap::new();
+    for mut request in requests {
+        // Prepared like a run, and checked now since rejections would otherwise only surface
+        // hours later in the batch's error file. Scripts are skipped: the app may not be running
+        // when the results arrive.
+        ai_client::prepare(&app_handle, &settings, &mut request, None)?;
+        residency::check(&request.vendor, request.pattern.as_deref())?;
+        let run_id = request.run_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+        let mut sent = request.clone();
+        ai_client::emulate_prefill(&mut sent);
+        let mut body = ai_client::openai_payload(&sent);
+        if let Some(body) = body.as_object_mut() {
+            body.remove("stream");
+        }
+        let line = json!({
+            "custom_id": run_id,
+            "method": "POST",
+            "url": "/v1/chat/completions",
+            "body": body,
+        });
+        lines.push(line.to_string());
+        request.api_key.clear();
+        request.run_id = Some(run_id.clone());
+        stored.insert(run_id, request);
+    }
+
+    let client = http::client_for(&openai_api())?;
+    let file_id = upload_batch_file(&client, &api_key, lines.join("\n")).await?;
+
+    let res = client
+        .post(format!("{}/batches", openai_api()))
+        .bearer_auth(&api_key)
+        .json(&json!({
+            "input_file_id": file_id,
+            "endpoint": "/v1/chat/completions",
+            "completion_window": "24h"
+        }))
+        .send_audited("batch", None)
+        .await
+        .map_err(http::network_error)?;
+    let remote: Value = serde_json::from_str(&check(res).await?).map_err(|e| e.to_string())?;
+
+    let mut job = BatchJob {
+        id: remote["id"].as_str().unwrap_or_default().to_string(),
+        created_at: now_secs(),
+        status: String::new(),
+        request_count: stored.len(),
+        completed_count: 0,
+        failed_count: 0,
+        output_file_id: None,
+        error_file_id: None,
+        imported: false,
+        requests: stored,
+    };
+    apply_remote(&mut job, &remote);
+    state.upsert(job.clone())?;
+    Ok(job)
+}
+
+#[tauri::command]
+pub async fn list_batch_jobs(
+    app_handle: AppHandle,
+    state: State<'_, BatchJobsState>,
+    refresh: Option<bool>,
+) -> Result<Vec<BatchJob>, String> {
+    if refresh.unwrap_or(false) {
+        let api_key = openai_key(&app_handle, None)?;
+        sync_jobs(&app_handle, &api_key).await?;
+    }
+
+    let mut jobs = state.jobs.lock().unwrap().clone();
+    jobs.sort_by_key(|j| std::cmp::Reverse(j.created_at));
+    Ok(jobs)
+}
+
+#[tauri::command]
+pub async fn get_batch_results(
+    app_handle: AppHandle,
+    state: State<'_, BatchJobsState>,
+    history: State<'_, HistoryState>,
+    batch_id: String,
+    api_key: Option<String>,
+) -> Result<Vec<BatchResult>, String> {
+    let api_key = openai_key(&app_handle, api_key)?;
+    let mut job = state
+        .get(&batch_id)
+        .ok_or_else(|| format!("Batch '{}' not found.", batch_id))?;
+
+    let client = http::client_for(&openai_api())?;
+    refresh_job(&client, &api_key, &mut job).await?;
+    if job.status != "completed" && job.output_file_id.is_none() {
+        state.upsert(job.clone())?;
+        return Err(format!("Batch '{}' is not finished yet (status: {}).", batch_id, job.status));
+    }
+
+    let results = download_results(&client, &api_key, &job).await?;
+    import_results(&history, &job, &results);
+    job.imported = true;
+    state.upsert(job)?;
+    Ok(results)
+}
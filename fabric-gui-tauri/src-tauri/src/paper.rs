@@ -0,0 +1,191 @@
+use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::time::Duration;
+use tauri::State;
+use uuid::Uuid;
+use crate::http::{self, AuditedSend};
+use crate::ingest;
+use crate::input::{self, TruncationInfo};
+use crate::settings::SettingsState;
+
+const UNPAYWALL_API: &str = "https://api.unpaywall.org/v2/";
+// Headings papers use without numbering
+const NAMED_SECTIONS: &[&str] = &[
+    "abstract",
+    "introduction",
+    "background",
+    "related work",
+    "method",
+    "methods",
+    "methodology",
+    "experiments",
+    "results",
+    "discussion",
+    "conclusion",
+    "conclusions",
+    "limitations",
+    "acknowledgments",
+    "acknowledgements",
+    "references",
+    "bibliography",
+    "appendix",
+];
+
+#[derive(Serialize)]
+pub struct Paper {
+    pub source: String,
+    pub title: Option<String>,
+    pub pdf_url: String,
+    pub sections: Vec<String>,
+    // Markdown with a heading per detected section
+    pub text: String,
+    // Set when the text was cut to the scrape input limit
+    pub truncation: Option<TruncationInfo>,
+}
+
+// Accepts "2301.01234", "arXiv:2301.01234v2", old-style "hep-th/9901001" and abs/pdf URLs
+fn arxiv_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let id = Regex::new(r"(?i)(?:arxiv\.org/(?:abs|pdf)/|^arxiv:|^)(\d{4}\.\d{4,5}(?:v\d+)?|[a-z\-]+(?:\.[A-Z]{2})?/\d{7}(?:v\d+)?)(?:\.pdf)?$")
+        .unwrap();
+    id.captures(input).map(|c| c[1].to_string())
+}
+
+fn doi(input: &str) -> Option<String> {
+    let doi = Regex::new(r"(?i)(?:doi\.org/|^doi:\s*|^)(10\.\d{4,9}/\S+)$").unwrap();
+    doi.captures(input.trim()).map(|c| c[1].to_string())
+}
+
+// Unpaywall knows the legal open-access copies of a DOI; it asks callers for a contact email
+async fn resolve_doi(doi: &str, email: Option<String>) -> Result<(String, Option<String>), String> {
+    // arXiv's own DOIs need no lookup
+    if let Some(id) = doi.to_lowercase().strip_prefix("10.48550/arxiv.") {
+        return Ok((format!("https://arxiv.org/pdf/{}", id), None));
+    }
+
+    let email = email.ok_or("Resolving DOIs uses Unpaywall, which needs a contact email; set unpaywall_email in settings.")?;
+    let url = format!("{}{}?email={}", UNPAYWALL_API, doi, email);
+    let res = http::client_for(&url)?
+        .get(&url)
+        .timeout(Duration::from_secs(20))
+        .send_audited("paper", None)
+        .await
+        .map_err(http::network_error)?;
+    if res.status().as_u16() == 404 {
+        return Err(format!("DOI {} was not found.", doi));
+    }
+    if !res.status().is_success() {
+        return Err(format!("DOI lookup failed ({})", res.status()));
+    }
+    let json: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+    let title = json["title"].as_str().map(|t| t.to_string());
+    let pdf_url = json["best_oa_location"]["url_for_pdf"]
+        .as_str()
+        .or_else(|| {
+            json["oa_locations"]
+                .as_array()?
+                .iter()
+                .find_map(|l| l["url_for_pdf"].as_str())
+        })
+        .ok_or_else(|| format!("No open-access PDF is known for DOI {}.", doi))?;
+    Ok((pdf_url.to_string(), title))
+}
+
+async fn download_pdf_text(url: &str) -> Result<String, String> {
+    let res = http::client_for(url)?
+        .get(url)
+        .timeout(Duration::from_secs(120))
+        .send_audited("paper", None)
+        .await
+        .map_err(http::network_error)?;
+    if !res.status().is_success() {
+        return Err(format!("Could not download the PDF ({})", res.status()));
+    }
+    let bytes = res.bytes().await.map_err(|e| e.to_string())?;
+    if !bytes.starts_with(b"%PDF") {
+        return Err("The download is not a PDF; the publisher may require a login.".to_string());
+    }
+    pdf_bytes_text(&bytes)
+}
+
+pub fn pdf_bytes_text(bytes: &[u8]) -> Result<String, String> {
+    // pdftotext needs a file, so the PDF only lives as long as the extraction
+    let path = std::env::temp_dir().join(format!("fabric-paper-{}.pdf", Uuid::new_v4()));
+    fs::write(&path, bytes).map_err(|e| e.to_string())?;
+    let text = ingest::ingest(&path);
+    let _ = fs::remove_file(&path);
+    text
+}
+
+// Turns heading lines of the extracted text into markdown headings ("3.2 Training" becomes
+// "### 3.2 Training") and drops the reference list unless asked for, as it rarely helps a
+// summary and can be a quarter of the paper
+pub fn structure(text: &str, include_references: bool) -> (String, Vec<String>) {
+    let numbered = Regex::new(r"^(\d{1,2}(?:\.\d{1,2}){0,2})\.?\s+([A-Z][A-Za-z0-9 ,:&()\-]{1,80})$").unwrap();
+    let roman = Regex::new(r"(?i)^[ivx]{1,4}\.?\s+").unwrap();
+    let mut sections = Vec::new();
+    let mut output = String::with_capacity(text.len());
+    let mut in_references = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        let lower = trimmed.trim_end_matches(':').to_lowercase();
+        let named = NAMED_SECTIONS.contains(&roman.replace(&lower, "").as_ref());
+        let heading = if let Some(c) = numbered.captures(trimmed) {
+            let depth = c[1].split('.').count().min(3);
+            Some((format!("{} {}", "#".repeat(depth + 1), trimmed), c[2].to_lowercase()))
+        } else if named {
+            Some((format!("## {}", trimmed.trim_end_matches(':')), lower.clone()))
+        } else {
+            None
+        };
+
+        match heading {
+            Some((markdown, name)) => {
+                // Any later heading (usually an appendix) ends the reference list
+                in_references = name.contains("references") || name.contains("bibliography");
+                if in_references && !include_references {
+                    continue;
+                }
+                sections.push(trimmed.trim_end_matches(':').to_string());
+                output.push_str(&format!("\n{}\n\n", markdown));
+            }
+            None if in_references && !include_references => {}
+            None => {
+                output.push_str(trimmed);
+                output.push('\n');
+            }
+        }
+    }
+    (output.trim().to_string(), sections)
+}
+
+#[tauri::command]
+pub async fn fetch_paper(
+    state: State<'_, SettingsState>,
+    id_or_url: String,
+    include_references: Option<bool>,
+) -> Result<Paper, String> {
+    let (pdf_url, title) = if let Some(id) = arxiv_id(&id_or_url) {
+        (format!("https://arxiv.org/pdf/{}", id), None)
+    } else if let Some(doi) = doi(&id_or_url) {
+        resolve_doi(&doi, state.get().unpaywall_email).await?
+    } else if id_or_url.trim().starts_with("https://") || id_or_url.trim().starts_with("http://") {
+        (id_or_url.trim().to_string(), None)
+    } else {
+        return Err(format!("'{}' is not an arXiv ID, DOI or PDF link.", id_or_url));
+    };
+
+    let raw = download_pdf_text(&pdf_url).await?;
+    let (text, sections) = structure(&raw, include_references.unwrap_or(false));
+    let (text, truncation) = input::limit(&state.get(), "scrape", text);
+    Ok(Paper {
+        source: id_or_url,
+        title,
+        pdf_url,
+        sections,
+        text,
+        truncation,
+    })
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Instant, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+use crate::patterns::{pattern_dirs, pattern_sources};
+use crate::settings::SettingsState;
+use crate::startup;
+
+const MAX_DESCRIPTION_CHARS: usize = 200;
+const MAX_KEYWORDS: usize = 200;
+
+// What search needs from a pattern, kept in pattern_index.json between runs so only patterns
+// whose system.md changed are read again
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexedPattern {
+    name: String,
+    // The first paragraph of the prompt
+    description: String,
+    keywords: BTreeSet<String>,
+    // system.md's modification time in Unix milliseconds, so a save right after another is seen
+    modified: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct IndexFile {
+    // The pattern folders the index was built from, in lookup order
+    sources: Vec<String>,
+    patterns: Vec<IndexedPattern>,
+}
+
+pub struct PatternIndex {
+    path: PathBuf,
+    inner: RwLock<IndexFile>,
+}
+
+#[derive(Serialize)]
+pub struct PatternMatch {
+    pub name: String,
+    pub description: String,
+    pub score: u32,
+}
+
+fn description(prompt: &str) -> String {
+    let paragraph = prompt
+        .split("\n\n")
+        .map(str::trim)
+        .find(|p| !p.is_empty() && !p.starts_with('#'))
+        .unwrap_or_default();
+    let text = paragraph.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(MAX_DESCRIPTION_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+// The prompt's first distinct longer words; the opening says best what a pattern is about
+fn keywords(prompt: &str) -> BTreeSet<String> {
+    let mut keywords = BTreeSet::new();
+    for word in prompt.split(|c: char| !c.is_alphanumeric()).filter(|w| w.chars().count() >= 4) {
+        if keywords.len() == MAX_KEYWORDS {
+            break;
+        }
+        keywords.insert(word.to_lowercase());
+    }
+    keywords
+}
+
+fn source_names(sources: &[PathBuf]) -> Vec<String> {
+    sources.iter().map(|dir| dir.to_string_lossy().to_string()).collect()
+}
+
+fn modified(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl PatternIndex {
+    // The index from the last run is usable right away, before the folder is checked again
+    pub fn load(path: PathBuf) -> Self {
+        let cached = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self { path, inner: RwLock::new(cached) }
+    }
+
+    // Brings the index in line with the pattern folders, reading only new and changed patterns
+    pub fn refresh(&self, sources: &[PathBuf], found: BTreeMap<String, PathBuf>) -> Result<(), String> {
+        let sources = source_names(sources);
+        let mut previous: HashMap<String, IndexedPattern> = {
+            let index = self.inner.read().unwrap();
+            if index.sources == sources {
+                index.patterns.iter().map(|p| (p.name.clone(), p.clone())).collect()
+            } else {
+                HashMap::new()
+            }
+        };
+
+        let mut patterns = Vec::new();
+        let mut changed = previous.is_empty();
+        for (name, dir) in found {
+            let prompt_path = dir.join("system.md");
+            let modified = modified(&prompt_path);
+            match previous.remove(&name).filter(|p| p.modified == modified) {
+                Some(pattern) => patterns.push(pattern),
+                None => {
+                    let prompt = fs::read_to_string(&prompt_path).unwrap_or_default();
+                    patterns.push(IndexedPattern { description: description(&prompt), keywords: keywords(&prompt), name, modified });
+                    changed = true;
+                }
+            }
+        }
+        // Whatever is left was deleted
+        changed |= !previous.is_empty();
+        patterns.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut index = self.inner.write().unwrap();
+        *index = IndexFile { sources, patterns };
+        if changed {
+            let json = serde_json::to_string(&*index).map_err(|e| e.to_string())?;
+            fs::write(&self.path, json).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+fn score(pattern: &IndexedPattern, terms: &[String]) -> Option<u32> {
+    let name = pattern.name.to_lowercase();
+    let description = pattern.description.to_lowercase();
+    let mut total = 0;
+    for term in terms {
+        let hit = if name == *term {
+            100
+        } else if name.starts_with(term.as_str()) {
+            50
+        } else if name.contains(term.as_str()) {
+            30
+        } else if description.contains(term.as_str()) {
+            10
+        } else if pattern.keywords.iter().any(|k| k.starts_with(term.as_str())) {
+            3
+        } else {
+            return None;
+        };
+        total += hit;
+    }
+    Some(total)
+}
+
+// Indexes the patterns folder after startup without holding up the window
+pub fn spawn_indexing(app_handle: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let started = Instant::now();
+        let settings = app_handle.state::<SettingsState>().get();
+        let result = app_handle.state::<PatternIndex>().refresh(&pattern_sources(&settings), pattern_dirs(&settings));
+        startup::ready(&app_handle, "pattern_index", started, result.err());
+    });
+}
+
+// Patterns matching every word of the query in their name, description or prompt, best first
+#[tauri::command]
+pub async fn search_patterns(
+    index: State<'_, PatternIndex>,
+    settings: State<'_, SettingsState>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<PatternMatch>, String> {
+    let settings = settings.get();
+    // Patterns are saved, restored, installed and edited on disk behind the index's back; a
+    // refresh only lists the folders and compares modification times, so it runs every search
+    index.refresh(&pattern_sources(&settings), pattern_dirs(&settings))?;
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let index = index.inner.read().unwrap();
+    let mut matches: Vec<PatternMatch> = index
+        .patterns
+        .iter()
+        .filter_map(|p| {
+            let score = score(p, &terms)?;
+            Some(PatternMatch { name: p.name.clone(), description: p.description.clone(), score })
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    matches.truncate(limit.unwrap_or(50));
+    Ok(matches)
+}
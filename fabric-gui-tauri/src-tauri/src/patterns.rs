@@ -1,7 +1,11 @@
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 use home::home_dir;
+use tauri::State;
+use crate::marketplace;
+use crate::settings::{Settings, SettingsState};
 
 #[derive(Serialize)]
 pub struct Pattern {
@@ -9,7 +13,15 @@ pub struct Pattern {
     pub path: String,
 }
 
-pub fn get_patterns_dir() -> PathBuf {
+pub fn get_patterns_dir(settings: &Settings) -> PathBuf {
+    // 0. Check directory configured in settings (or imported from the fabric CLI)
+    if let Some(dir) = &settings.patterns_dir {
+        let path = PathBuf::from(dir);
+        if path.exists() {
+            return path;
+        }
+    }
+
     // 1. Check environment variable
     if let Ok(env_path) = std::env::var("FABRIC_PATTERNS_DIR") {
         let path = PathBuf::from(env_path);
@@ -49,43 +61,62 @@ pub fn get_patterns_dir() -> PathBuf {
         .unwrap_or_else(|| PathBuf::from(".config/fabric/patterns"))
 }
 
-#[tauri::command]
-pub async fn list_patterns() -> Result<Vec<String>, String> {
-    let patterns_dir = get_patterns_dir();
-    
-    if !patterns_dir.exists() {
-        return Err("Fabric patterns directory not found. Please install Fabric first.".to_string());
-    }
+// Where patterns come from, in lookup order: the Fabric patterns folder, then the patterns
+// installed from the marketplace. The user's own pattern wins when both have the same name.
+pub fn pattern_sources(settings: &Settings) -> Vec<PathBuf> {
+    let mut sources = vec![get_patterns_dir(settings)];
+    sources.extend(marketplace::community_dir(settings).ok());
+    sources
+}
 
-    let mut patterns = Vec::new();
-    let entries = fs::read_dir(patterns_dir).map_err(|e| e.to_string())?;
-
-    for entry in entries {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if path.is_dir() {
-                if let Some(name) = path.file_name() {
-                    if let Some(name_str) = name.to_str() {
-                        patterns.push(name_str.to_string());
-                    }
-                }
+// The folder of the pattern with this name, from the first source that has it
+pub fn find_pattern_dir(settings: &Settings, name: &str) -> Option<PathBuf> {
+    check_pattern_name(name).ok()?;
+    pattern_sources(settings).into_iter().map(|dir| dir.join(name)).find(|dir| dir.is_dir())
+}
+
+// Every available pattern by name, each from the first source that has it
+pub fn pattern_dirs(settings: &Settings) -> BTreeMap<String, PathBuf> {
+    let mut found = BTreeMap::new();
+    for source in pattern_sources(settings) {
+        let Ok(entries) = fs::read_dir(&source) else {
+            continue;
+        };
+        for entry in entries.flatten().filter(|e| e.path().is_dir()) {
+            if let Some(name) = entry.file_name().to_str() {
+                found.entry(name.to_string()).or_insert_with(|| entry.path());
             }
         }
     }
+    found
+}
 
-    patterns.sort();
-    Ok(patterns)
+// Pattern names become path components, so nothing that could leave the patterns dir is accepted
+pub fn check_pattern_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!("'{}' is not a valid pattern name.", name));
+    }
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn get_pattern_content(name: String) -> Result<String, String> {
-    let mut path = get_patterns_dir();
-    path.push(name);
-    path.push("system.md");
+pub async fn list_patterns(state: State<'_, SettingsState>) -> Result<Vec<String>, String> {
+    let settings = state.get();
+    let patterns = pattern_dirs(&settings);
 
-    if !path.exists() {
-        return Err("Pattern content (system.md) not found.".to_string());
+    if patterns.is_empty() && !get_patterns_dir(&settings).exists() {
+        return Err("Fabric patterns directory not found. Please install Fabric first.".to_string());
     }
 
+    Ok(patterns.into_keys().collect())
+}
+
+#[tauri::command]
+pub async fn get_pattern_content(state: State<'_, SettingsState>, name: String) -> Result<String, String> {
+    let path = find_pattern_dir(&state.get(), &name)
+        .map(|dir| dir.join("system.md"))
+        .filter(|path| path.exists())
+        .ok_or("Pattern content (system.md) not found.")?;
+
     fs::read_to_string(path).map_err(|e| e.to_string())
 }
@@ -0,0 +1,333 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::pipelines::{self, Branch, Classifier, PipelineStep};
+use crate::post_filter;
+
+// Pipelines stopped at a pause point, by pipeline_id. The sender carries the approved text;
+// dropping it cancels the pipeline.
+#[derive(Default)]
+pub struct PipelinePauses(Mutex<HashMap<String, oneshot::Sender<Option<String>>>>);
+
+#[derive(Serialize)]
+pub struct StepOutcome {
+    // "pattern", "branch", "parallel" (a fan-out branch) or "merge"
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    // The pattern run, or the classifier run of a classify branch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    // For branches: "then", "else" or the label the classifier picked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chosen: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PipelineResult {
+    pub pipeline_id: String,
+    pub steps: Vec<StepOutcome>,
+    pub output: String,
+}
+
+fn emit_step(app_handle: &AppHandle, pipeline_id: &str, status: &str, outcome: &StepOutcome) {
+    let _ = app_handle.emit(
+        "pipeline-progress",
+        json!({"pipeline_id": pipeline_id, "status": status, "step": outcome}),
+    );
+}
+
+pub fn step_request(base: &AIRequest, step: &PipelineStep, pattern: &str, input: &str, parent: Option<&String>) -> AIRequest {
+    let mut request = base.clone();
+    if let Some(vendor) = step.vendor.as_ref().filter(|v| **v != request.vendor) {
+        request.vendor = vendor.clone();
+        // The key belongs to the pipeline's vendor; run_recorded falls back to the stored one
+        request.api_key.clear();
+        request.cached_content = None;
+    }
+    if let Some(model) = &step.model {
+        request.model = model.clone();
+    }
+    if let Some(temperature) = step.temperature {
+        request.temperature = temperature;
+    }
+    // Persona, context and strategies chosen for the run apply to every step
+    let mut compose = base.compose.clone().unwrap_or_default();
+    compose.pattern = Some(pattern.to_string());
+    request.compose = Some(compose);
+    request.pattern = Some(pattern.to_string());
+    // A prefill is written for one pattern's output format, not every step's
+    request.assistant_prefill = None;
+    request.user_input = input.to_string();
+    request.run_id = Some(Uuid::new_v4().to_string());
+    request.parent_run_id = parent.cloned();
+    request.relation = parent.map(|_| "step".to_string());
+    request.dry_run = false;
+    request
+}
+
+// The label the reply names: an exact answer first, otherwise the label mentioned earliest
+fn choose_label(reply: &str, labels: &[String]) -> Option<String> {
+    let answer = reply.trim().trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    if let Some(label) = labels.iter().find(|l| l.to_lowercase() == answer) {
+        return Some(label.clone());
+    }
+    labels
+        .iter()
+        .filter_map(|label| {
+            let word = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(label))).ok()?;
+            word.find(reply).map(|m| (m.start(), label))
+        })
+        .min_by_key(|(start, _)| *start)
+        .map(|(_, label)| label.clone())
+}
+
+pub fn classifier_request(base: &AIRequest, classifier: &Classifier, labels: &[String], text: &str) -> AIRequest {
+    let mut request = base.clone();
+    if let Some(vendor) = classifier.vendor.as_ref().filter(|v| **v != request.vendor) {
+        request.vendor = vendor.clone();
+        request.api_key.clear();
+        request.cached_content = None;
+    }
+    if let Some(model) = &classifier.model {
+        request.model = model.clone();
+    }
+    let instructions = classifier.instructions.as_deref().map(|i| format!("\n\n{}", i.trim())).unwrap_or_default();
+    request.system_prompt = format!(
+        "Classify the user's text as exactly one of these labels: {}.{}\n\nReply with the label only.",
+        labels.join(", "),
+        instructions
+    );
+    request.compose = None;
+    request.pattern = None;
+    request.assistant_prefill = None;
+    request.thinking_level = None;
+    request.temperature = 0.0;
+    request.top_p = 1.0;
+    request.user_input = text.to_string();
+    request.relation = Some("classify".to_string());
+    request.dry_run = false;
+    request
+}
+
+async fn classify(
+    window: &Window,
+    base: &AIRequest,
+    classifier: &Classifier,
+    labels: &[String],
+    text: &str,
+    parent: Option<&String>,
+) -> Result<(Option<String>, String), String> {
+    let mut request = classifier_request(base, classifier, labels, text);
+    let run_id = Uuid::new_v4().to_string();
+    request.run_id = Some(run_id.clone());
+    request.parent_run_id = parent.cloned();
+
+    let reply = ai_client::run_recorded(window.app_handle(), Some(window.label().to_string()), request).await?;
+    Ok((choose_label(&reply, labels), run_id))
+}
+
+// Decides a branch on the current text; returns what was chosen, the steps to run and the
+// classifier run if there was one
+async fn decide(
+    window: &Window,
+    base: &AIRequest,
+    branch: Branch,
+    text: &str,
+    parent: Option<&String>,
+) -> Result<(String, Vec<PipelineStep>, Option<String>), String> {
+    if let Some(classifier) = &branch.classify {
+        let labels: Vec<String> = branch.cases.keys().cloned().collect();
+        let (label, run_id) = classify(window, base, classifier, &labels, text, parent).await?;
+        let mut cases = branch.cases;
+        return Ok(match label.and_then(|l| cases.remove(&l).map(|steps| (l, steps))) {
+            Some((label, steps)) => (label, steps, Some(run_id)),
+            None => ("else".to_string(), branch.otherwise, Some(run_id)),
+        });
+    }
+    let hit = match (&branch.contains, &branch.matches) {
+        (Some(needle), _) => text.to_lowercase().contains(&needle.to_lowercase()),
+        (_, Some(pattern)) => Regex::new(pattern).map_err(|e| e.to_string())?.is_match(text),
+        _ => false,
+    };
+    Ok(if hit {
+        ("then".to_string(), branch.then, None)
+    } else {
+        ("else".to_string(), branch.otherwise, None)
+    })
+}
+
+// Runs a fan-out's patterns concurrently, each streaming under its own run_id, and joins or
+// merges their outputs; returns the step's output and the merge run if there was one
+async fn run_fan_out(
+    window: &Window,
+    base: &AIRequest,
+    step: &PipelineStep,
+    input: &str,
+    parent: Option<&String>,
+    pipeline_id: &str,
+    steps: &mut Vec<StepOutcome>,
+) -> Result<(String, Option<String>), String> {
+    let app_handle = window.app_handle();
+    let Some(fan_out) = &step.fan_out else {
+        return Ok((input.to_string(), None));
+    };
+    let mut requests = Vec::new();
+    for branch in &fan_out.branches {
+        let Some(pattern) = branch.pattern.clone() else {
+            continue;
+        };
+        // Two branches may run one pattern on different models, so the model tells them apart
+        let heading = match &branch.model {
+            Some(model) => format!("{} ({})", pattern, model),
+            None => pattern.clone(),
+        };
+        let request = step_request(base, branch, &pattern, input, parent);
+        let outcome = StepOutcome { kind: "parallel", pattern: Some(pattern), run_id: request.run_id.clone(), chosen: None };
+        emit_step(app_handle, pipeline_id, "started", &outcome);
+        requests.push((heading, request, outcome, branch));
+    }
+
+    let runs = requests.iter().map(|(heading, request, outcome, branch)| async move {
+        let output = ai_client::run_recorded(app_handle, Some(window.label().to_string()), request.clone())
+            .await
+            .and_then(|output| post_filter::apply(&branch.filters, &output))
+            .map_err(|e| format!("Parallel step {} failed: {}", heading, e))?;
+        emit_step(app_handle, pipeline_id, "done", outcome);
+        Ok::<_, String>(format!("## {}\n\n{}", heading, output.trim()))
+    });
+    let combined = futures::future::try_join_all(runs).await?.join("\n\n");
+    steps.extend(requests.into_iter().map(|(_, _, outcome, _)| outcome));
+
+    let Some(merge_pattern) = &fan_out.merge_pattern else {
+        return Ok((combined, None));
+    };
+    let mut request = step_request(base, step, merge_pattern, &combined, parent);
+    request.relation = Some("merge".to_string());
+    let run_id = request.run_id.clone().unwrap_or_default();
+    let outcome = StepOutcome { kind: "merge", pattern: Some(merge_pattern.clone()), run_id: Some(run_id.clone()), chosen: None };
+    emit_step(app_handle, pipeline_id, "started", &outcome);
+    let output = ai_client::run_recorded(app_handle, Some(window.label().to_string()), request)
+        .await
+        .map_err(|e| format!("Merge step {} failed: {}", merge_pattern, e))?;
+    emit_step(app_handle, pipeline_id, "done", &outcome);
+    steps.push(outcome);
+    Ok((output, Some(run_id)))
+}
+
+// Parks the pipeline until resume_pipeline or cancel_pipeline is called for it; returns the
+// edited text, or None when the output was approved unchanged
+async fn await_approval(
+    app_handle: &AppHandle,
+    pipeline_id: &str,
+    run_id: Option<&str>,
+    output: &str,
+) -> Result<Option<String>, String> {
+    let (sender, receiver) = oneshot::channel();
+    app_handle.state::<PipelinePauses>().0.lock().unwrap().insert(pipeline_id.to_string(), sender);
+    let _ = app_handle.emit(
+        "awaiting-approval",
+        json!({"pipeline_id": pipeline_id, "run_id": run_id, "output": output}),
+    );
+    receiver.await.map_err(|_| "The pipeline was cancelled.".to_string())
+}
+
+// Runs a saved pipeline on `request.user_input`, with the request's vendor, model and
+// composition as defaults for every step. Each pattern step streams and is recorded as its
+// own run, linked to the step before it; pipeline-progress events carry each step's run_id
+// before it starts. `request.run_id`, when set, becomes the pipeline_id in those events.
+#[tauri::command]
+pub async fn run_pipeline(window: Window, name: String, request: AIRequest) -> Result<PipelineResult, String> {
+    let app_handle = window.app_handle();
+    let pipeline = pipelines::load(app_handle, &name)?;
+    let pipeline_id = request.run_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let mut pending: VecDeque<PipelineStep> = pipeline.steps.into();
+    let mut text = request.user_input.clone();
+    let mut parent: Option<String> = None;
+    let mut steps = Vec::new();
+    while let Some(step) = pending.pop_front() {
+        if let Some(branch) = step.branch {
+            let (chosen, next, run_id) = decide(&window, &request, branch, &text, parent.as_ref()).await?;
+            // The chosen steps run next, then whatever followed the branch
+            for step in next.into_iter().rev() {
+                pending.push_front(step);
+            }
+            let outcome = StepOutcome { kind: "branch", pattern: None, run_id, chosen: Some(chosen) };
+            emit_step(app_handle, &pipeline_id, "done", &outcome);
+            steps.push(outcome);
+            continue;
+        }
+        if step.fan_out.is_some() {
+            let (output, merge_run) =
+                run_fan_out(&window, &request, &step, &text, parent.as_ref(), &pipeline_id, &mut steps).await?;
+            text = post_filter::apply(&step.filters, &output).map_err(|e| format!("Step {}: {}", steps.len(), e))?;
+            if step.pause {
+                if let Some(edited) = await_approval(app_handle, &pipeline_id, merge_run.as_deref(), &text).await? {
+                    text = edited;
+                }
+            }
+            // Without a merge run the next step derives from the same run as the fan-out did
+            parent = merge_run.or(parent);
+            continue;
+        }
+        let Some(pattern) = step.pattern.clone() else {
+            continue;
+        };
+        let step_request = step_request(&request, &step, &pattern, &text, parent.as_ref());
+        let run_id = step_request.run_id.clone().unwrap_or_default();
+        let outcome = StepOutcome { kind: "pattern", pattern: Some(pattern.clone()), run_id: Some(run_id.clone()), chosen: None };
+        emit_step(app_handle, &pipeline_id, "started", &outcome);
+        text = ai_client::run_recorded(app_handle, Some(window.label().to_string()), step_request)
+            .await
+            .and_then(|output| post_filter::apply(&step.filters, &output))
+            .map_err(|e| format!("Step {} ({}) failed: {}", steps.len() + 1, pattern, e))?;
+        emit_step(app_handle, &pipeline_id, "done", &outcome);
+        steps.push(outcome);
+        if step.pause {
+            if let Some(edited) = await_approval(app_handle, &pipeline_id, Some(&run_id), &text).await? {
+                text = edited;
+            }
+        }
+        parent = Some(run_id);
+    }
+
+    Ok(PipelineResult { pipeline_id, steps, output: text })
+}
+
+// Continues a pipeline waiting at a pause point. `run_id` is its pipeline_id; `edited_text`
+// replaces the paused step's output as the next step's input, and None approves it as is.
+#[tauri::command]
+pub async fn resume_pipeline(
+    pauses: State<'_, PipelinePauses>,
+    run_id: String,
+    edited_text: Option<String>,
+) -> Result<(), String> {
+    let sender = pauses
+        .0
+        .lock()
+        .unwrap()
+        .remove(&run_id)
+        .ok_or_else(|| format!("Pipeline '{}' is not waiting for approval.", run_id))?;
+    sender
+        .send(edited_text)
+        .map_err(|_| "The pipeline is no longer running.".to_string())
+}
+
+// Stops a pipeline waiting at a pause point; run_pipeline returns an error
+#[tauri::command]
+pub async fn cancel_pipeline(pauses: State<'_, PipelinePauses>, run_id: String) -> Result<(), String> {
+    pauses
+        .0
+        .lock()
+        .unwrap()
+        .remove(&run_id)
+        .map(drop)
+        .ok_or_else(|| format!("Pipeline '{}' is not waiting for approval.", run_id))
+}
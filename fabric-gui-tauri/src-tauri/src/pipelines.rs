@@ -0,0 +1,371 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use crate::patterns::{check_pattern_name, find_pattern_dir};
+use crate::post_filter::{self, PostFilter};
+use crate::settings::SettingsState;
+
+// Bumped when a change to the format would make older versions misread a pipeline
+pub const PIPELINE_FORMAT_VERSION: u32 = 1;
+const MAX_STEPS: usize = 50;
+const MAX_BRANCH_DEPTH: usize = 4;
+const MAX_FAN_OUT: usize = 8;
+
+// Unknown fields are rejected so a typo in a hand-edited or shared file fails on import
+// instead of silently running a different pipeline
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    #[serde(default = "format_version")]
+    pub format_version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub steps: Vec<PipelineStep>,
+}
+
+// A pattern run, a branch or a fan-out. A step's output is the next step's input; unset
+// overrides use the vendor, model and temperature the pipeline is run with.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineStep {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<Branch>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan_out: Option<FanOut>,
+    // Applied to the step's output, so the next step gets only the fields it needs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<PostFilter>,
+    // Wait for the user to approve or edit this step's output before the pipeline goes on
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pause: bool,
+}
+
+// Picks the steps to run next from the current text, then the pipeline carries on after the
+// branch. Exactly one test is set: `contains` (case-insensitive) or `matches` (a regex) run
+// `then`, and `classify` asks a model for one of the `cases` labels. `else` runs when nothing
+// matches.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Branch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contains: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classify: Option<Classifier>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub then: Vec<PipelineStep>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cases: BTreeMap<String, Vec<PipelineStep>>,
+    #[serde(default, rename = "else", skip_serializing_if = "Vec::is_empty")]
+    pub otherwise: Vec<PipelineStep>,
+}
+
+// Pattern steps run concurrently on the same input. Their outputs are joined under a heading
+// each, and when `merge_pattern` is set that pattern turns them into the step's output, using
+// the fan-out step's own overrides.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FanOut {
+    pub branches: Vec<PipelineStep>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_pattern: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Classifier {
+    // What the labels mean, e.g. "bug: a defect report; feature: a request for something new"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct InvalidPipeline {
+    pub file: String,
+    pub error: String,
+}
+
+#[derive(Serialize)]
+pub struct PipelineList {
+    pub pipelines: Vec<Pipeline>,
+    // Files in the pipelines folder that don't validate, so a broken edit isn't just missing
+    pub invalid: Vec<InvalidPipeline>,
+}
+
+#[derive(Serialize)]
+pub struct PipelineImport {
+    pub name: String,
+    // Steps whose pattern isn't installed here; the pipeline is saved but can't run yet
+    pub missing_patterns: Vec<String>,
+}
+
+fn format_version() -> u32 {
+    PIPELINE_FORMAT_VERSION
+}
+
+fn pipelines_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_config_dir()
+        .map(|dir| dir.join("pipelines"))
+        .map_err(|e| e.to_string())
+}
+
+// Names become file names, so anything that could escape the pipelines folder is rejected
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == ' ');
+    if valid && name.trim() == name {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a valid pipeline name; use letters, digits, spaces, '_' or '-'.", name))
+    }
+}
+
+fn validate(pipeline: &Pipeline) -> Result<(), String> {
+    if pipeline.format_version > PIPELINE_FORMAT_VERSION {
+        return Err(format!(
+            "The pipeline uses format version {}; this version of the app reads up to {}.",
+            pipeline.format_version, PIPELINE_FORMAT_VERSION
+        ));
+    }
+    check_name(&pipeline.name)?;
+    if pipeline.steps.is_empty() {
+        return Err("A pipeline needs at least one step.".to_string());
+    }
+    let mut count = 0;
+    validate_steps(&pipeline.steps, "", 0, &mut count)?;
+    if count > MAX_STEPS {
+        return Err(format!("A pipeline can have at most {} steps, counting every branch.", MAX_STEPS));
+    }
+    Ok(())
+}
+
+// Step positions in errors read like "Step 3 > case bug > step 1"
+fn validate_steps(steps: &[PipelineStep], path: &str, depth: usize, count: &mut usize) -> Result<(), String> {
+    for (index, step) in steps.iter().enumerate() {
+        *count += 1;
+        let here = match path {
+            "" => format!("Step {}", index + 1),
+            _ => format!("{} > step {}", path, index + 1),
+        };
+        let at = |e: String| format!("{}: {}", here, e);
+        if step.vendor.is_some() && step.model.is_none() {
+            return Err(at("choose a model when overriding the vendor.".to_string()));
+        }
+        if let Some(t) = step.temperature.filter(|t| !(0.0..=2.0).contains(t)) {
+            return Err(at(format!("temperature {} is outside 0 to 2.", t)));
+        }
+        if step.branch.is_some() && !step.filters.is_empty() {
+            return Err(at("a branch has no output to filter; filter its steps.".to_string()));
+        }
+        for filter in &step.filters {
+            post_filter::check(filter).map_err(at)?;
+        }
+        match (&step.pattern, &step.branch, &step.fan_out) {
+            (Some(pattern), None, None) => check_pattern_name(pattern).map_err(at)?,
+            (None, Some(_), None) if step.pause => return Err(at("a branch can't pause; pause its steps.".to_string())),
+            (None, Some(branch), None) => {
+                if depth >= MAX_BRANCH_DEPTH {
+                    return Err(at(format!("branches can be nested at most {} deep.", MAX_BRANCH_DEPTH)));
+                }
+                validate_branch(branch, &here, depth + 1, count)?;
+            }
+            (None, None, Some(fan_out)) => validate_fan_out(fan_out, &here, count)?,
+            _ => return Err(at("set exactly one of pattern, branch or fan_out.".to_string())),
+        }
+    }
+    Ok(())
+}
+
+fn validate_fan_out(fan_out: &FanOut, path: &str, count: &mut usize) -> Result<(), String> {
+    let at = |e: String| format!("{}: {}", path, e);
+    if !(2..=MAX_FAN_OUT).contains(&fan_out.branches.len()) {
+        return Err(at(format!("a fan-out runs 2 to {} patterns.", MAX_FAN_OUT)));
+    }
+    validate_steps(&fan_out.branches, &format!("{} > fan-out", path), 0, count)?;
+    if fan_out.branches.iter().any(|b| b.pattern.is_none() || b.pause) {
+        return Err(at("fan-out branches are plain pattern steps without pauses.".to_string()));
+    }
+    if let Some(merge) = &fan_out.merge_pattern {
+        check_pattern_name(merge).map_err(at)?;
+    }
+    Ok(())
+}
+
+fn validate_branch(branch: &Branch, path: &str, depth: usize, count: &mut usize) -> Result<(), String> {
+    let at = |e: &str| format!("{}: {}", path, e);
+    let tests = [branch.contains.is_some(), branch.matches.is_some(), branch.classify.is_some()];
+    if tests.iter().filter(|&&t| t).count() != 1 {
+        return Err(at("a branch needs exactly one of contains, matches or classify."));
+    }
+    if let Some(pattern) = &branch.matches {
+        Regex::new(pattern).map_err(|e| at(&format!("invalid regex: {}", e)))?;
+    }
+    match &branch.classify {
+        Some(classifier) => {
+            if branch.cases.is_empty() || !branch.then.is_empty() {
+                return Err(at("a classify branch lists its steps under cases, one entry per label."));
+            }
+            if branch.cases.keys().any(|label| label.trim().is_empty()) {
+                return Err(at("case labels can't be empty."));
+            }
+            if classifier.vendor.is_some() && classifier.model.is_none() {
+                return Err(at("choose a model when overriding the classifier's vendor."));
+            }
+            for (label, steps) in &branch.cases {
+                validate_steps(steps, &format!("{} > case {}", path, label), depth, count)?;
+            }
+        }
+        None => {
+            if !branch.cases.is_empty() {
+                return Err(at("cases are only used with classify; put the steps under then."));
+            }
+            validate_steps(&branch.then, &format!("{} > then", path), depth, count)?;
+        }
+    }
+    validate_steps(&branch.otherwise, &format!("{} > else", path), depth, count)
+}
+
+fn parse(json: &str) -> Result<Pipeline, String> {
+    let pipeline: Pipeline = serde_json::from_str(json).map_err(|e| format!("Not a valid pipeline: {}", e))?;
+    validate(&pipeline)?;
+    Ok(pipeline)
+}
+
+fn read_pipeline(path: &Path) -> Result<Pipeline, String> {
+    parse(&fs::read_to_string(path).map_err(|e| e.to_string())?)
+}
+
+pub fn load(app_handle: &AppHandle, name: &str) -> Result<Pipeline, String> {
+    check_name(name)?;
+    let path = pipelines_dir(app_handle)?.join(format!("{}.json", name));
+    if !path.exists() {
+        return Err(format!("Pipeline '{}' does not exist.", name));
+    }
+    read_pipeline(&path)
+}
+
+fn write_pipeline(path: &Path, pipeline: &Pipeline) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(pipeline).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+// Every pattern a pipeline may run, in any branch
+pub fn patterns(steps: &[PipelineStep]) -> Vec<String> {
+    let mut found = Vec::new();
+    for step in steps {
+        found.extend(step.pattern.clone());
+        if let Some(branch) = &step.branch {
+            found.extend(patterns(&branch.then));
+            found.extend(branch.cases.values().flat_map(|steps| patterns(steps)));
+            found.extend(patterns(&branch.otherwise));
+        }
+        if let Some(fan_out) = &step.fan_out {
+            found.extend(patterns(&fan_out.branches));
+            found.extend(fan_out.merge_pattern.clone());
+        }
+    }
+    found
+}
+
+fn missing_patterns(app_handle: &AppHandle, pipeline: &Pipeline) -> Vec<String> {
+    let settings = app_handle.state::<SettingsState>().get();
+    let mut missing: Vec<String> = patterns(&pipeline.steps)
+        .into_iter()
+        .filter(|p| find_pattern_dir(&settings, p).is_none())
+        .collect();
+    missing.sort();
+    missing.dedup();
+    missing
+}
+
+#[tauri::command]
+pub async fn list_pipelines(app_handle: AppHandle) -> Result<PipelineList, String> {
+    let dir = pipelines_dir(&app_handle)?;
+    let mut list = PipelineList { pipelines: Vec::new(), invalid: Vec::new() };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(list);
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        match read_pipeline(&path) {
+            Ok(pipeline) => list.pipelines.push(pipeline),
+            Err(error) => list.invalid.push(InvalidPipeline {
+                file: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+                error,
+            }),
+        }
+    }
+    list.pipelines.sort_by_key(|p| p.name.to_lowercase());
+    Ok(list)
+}
+
+// Adds a pipeline or replaces the one with the same name
+#[tauri::command]
+pub async fn save_pipeline(app_handle: AppHandle, pipeline: Pipeline) -> Result<(), String> {
+    validate(&pipeline)?;
+    let pipeline = Pipeline { format_version: PIPELINE_FORMAT_VERSION, ..pipeline };
+    write_pipeline(&pipelines_dir(&app_handle)?.join(format!("{}.json", pipeline.name)), &pipeline)
+}
+
+#[tauri::command]
+pub async fn delete_pipeline(app_handle: AppHandle, name: String) -> Result<(), String> {
+    check_name(&name)?;
+    let path = pipelines_dir(&app_handle)?.join(format!("{}.json", name));
+    if !path.exists() {
+        return Err(format!("Pipeline '{}' does not exist.", name));
+    }
+    fs::remove_file(path).map_err(|e| e.to_string())
+}
+
+// Adds a shared pipeline file. An existing pipeline with the same name is only replaced with
+// `overwrite`, so importing can't silently clobber a local edit.
+#[tauri::command]
+pub async fn import_pipeline(
+    app_handle: AppHandle,
+    path: String,
+    overwrite: Option<bool>,
+) -> Result<PipelineImport, String> {
+    let pipeline = read_pipeline(Path::new(&path))?;
+    let target = pipelines_dir(&app_handle)?.join(format!("{}.json", pipeline.name));
+    if target.exists() && !overwrite.unwrap_or(false) {
+        return Err(format!("A pipeline named '{}' already exists.", pipeline.name));
+    }
+    write_pipeline(&target, &pipeline)?;
+    Ok(PipelineImport {
+        missing_patterns: missing_patterns(&app_handle, &pipeline),
+        name: pipeline.name,
+    })
+}
+
+// Writes a pipeline to `path` for sharing; the file is what import_pipeline reads
+#[tauri::command]
+pub async fn export_pipeline(app_handle: AppHandle, name: String, path: String) -> Result<(), String> {
+    let pipeline = load(&app_handle, &name)?;
+    write_pipeline(Path::new(&path), &pipeline)
+}
@@ -0,0 +1,173 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::http::{self, AuditedSend};
+use crate::settings::SettingsState;
+use crate::transcribe;
+
+// Progress is reported every this many downloaded bytes
+const DOWNLOAD_PROGRESS_STEP: u64 = 2 * 1024 * 1024;
+
+#[derive(Serialize, Clone)]
+pub struct PodcastEpisode {
+    pub title: String,
+    pub audio_url: String,
+    pub published: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PodcastResult {
+    pub episode: PodcastEpisode,
+    pub run_id: String,
+    pub transcript_chars: usize,
+    pub output: String,
+}
+
+fn emit_stage(app_handle: &AppHandle, run_id: &str, stage: &str, detail: serde_json::Value) {
+    let _ = app_handle.emit("podcast-progress", json!({"run_id": run_id, "stage": stage, "detail": detail}));
+}
+
+fn decode_xml_text(text: &str) -> String {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+        .unwrap_or(text);
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+// Feeds list episodes newest first, so the first <item> with an audio enclosure is the latest.
+// A full XML parser would be overkill for the three fields needed.
+fn latest_episode(feed: &str) -> Option<PodcastEpisode> {
+    let items = Regex::new(r"(?s)<item\b.*?</item>").unwrap();
+    let enclosure = Regex::new(r#"<enclosure\b[^>]*\burl\s*=\s*["']([^"']+)["']"#).unwrap();
+    let title = Regex::new(r"(?s)<title\b[^>]*>(.*?)</title>").unwrap();
+    let published = Regex::new(r"(?s)<pubDate>(.*?)</pubDate>").unwrap();
+
+    let episode = items.find_iter(feed).find_map(|m| {
+        let item = m.as_str();
+        let audio_url = decode_xml_text(enclosure.captures(item)?.get(1)?.as_str());
+        Some(PodcastEpisode {
+            title: title
+                .captures(item)
+                .map(|c| decode_xml_text(&c[1]))
+                .unwrap_or_else(|| "Untitled episode".to_string()),
+            audio_url,
+            published: published.captures(item).map(|c| decode_xml_text(&c[1])),
+        })
+    });
+    episode
+}
+
+async fn fetch_feed(feed_url: &str) -> Result<PodcastEpisode, String> {
+    let res = http::client_for(feed_url)?
+        .get(feed_url)
+        .timeout(Duration::from_secs(30))
+        .send_audited("podcast", None)
+        .await
+        .map_err(http::network_error)?;
+    if !res.status().is_success() {
+        return Err(format!("Could not download the feed ({})", res.status()));
+    }
+    let feed = res.text().await.map_err(|e| e.to_string())?;
+    latest_episode(&feed).ok_or_else(|| "The feed has no episode with an audio file.".to_string())
+}
+
+async fn download(app_handle: &AppHandle, run_id: &str, url: &str, target: &Path) -> Result<u64, String> {
+    let mut res = http::client_for(url)?
+        .get(url)
+        .send_audited("podcast", None)
+        .await
+        .map_err(http::network_error)?;
+    if !res.status().is_success() {
+        return Err(format!("Could not download the episode audio ({})", res.status()));
+    }
+    let total = res.content_length();
+
+    let mut file = File::create(target).map_err(|e| e.to_string())?;
+    let mut received: u64 = 0;
+    let mut next_report = DOWNLOAD_PROGRESS_STEP;
+    while let Some(chunk) = res.chunk().await.map_err(http::network_error)? {
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+        received += chunk.len() as u64;
+        if received >= next_report {
+            emit_stage(app_handle, run_id, "download", json!({"received": received, "total": total}));
+            next_report += DOWNLOAD_PROGRESS_STEP;
+        }
+    }
+    Ok(received)
+}
+
+// The audio file's extension helps whisper and the transcription API detect the format
+fn audio_extension(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    match path.rsplit('.').next().map(str::to_ascii_lowercase).as_deref() {
+        Some("m4a") => "m4a",
+        Some("ogg") => "ogg",
+        Some("wav") => "wav",
+        Some("aac") => "aac",
+        Some("opus") => "opus",
+        _ => "mp3",
+    }
+}
+
+// Fetches the feed, downloads the newest episode, transcribes it and runs the pattern on the
+// transcript. Each stage is announced with a podcast-progress event (feed, download,
+// transcribe, analyze); the pattern output streams like a normal run under `run_id`.
+#[tauri::command]
+pub async fn process_podcast_episode(
+    window: Window,
+    settings: State<'_, SettingsState>,
+    feed_url: String,
+    mut request: AIRequest,
+    engine: Option<String>,
+) -> Result<PodcastResult, String> {
+    let app_handle = window.app_handle();
+    let settings = settings.get();
+    let run_id = request.run_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    emit_stage(app_handle, &run_id, "feed", json!({"url": feed_url}));
+    let episode = fetch_feed(&feed_url).await?;
+
+    emit_stage(app_handle, &run_id, "download", json!({"title": episode.title, "url": episode.audio_url}));
+    let work_dir = std::env::temp_dir().join(format!("fabric-podcast-{}", run_id));
+    fs::create_dir_all(&work_dir).map_err(|e| e.to_string())?;
+    let audio = work_dir.join(format!("episode.{}", audio_extension(&episode.audio_url)));
+
+    let transcript = async {
+        download(app_handle, &run_id, &episode.audio_url, &audio).await?;
+        let engine = engine.as_deref().unwrap_or("local");
+        emit_stage(app_handle, &run_id, "transcribe", json!({"engine": engine}));
+        transcribe::transcribe_file(&settings, &audio, engine).await
+    }
+    .await;
+    // Episodes run to hundreds of megabytes; nothing is kept once the transcript exists
+    let _ = fs::remove_dir_all(&work_dir);
+    let transcript = transcript?;
+
+    emit_stage(app_handle, &run_id, "analyze", json!({"transcript_chars": transcript.chars().count()}));
+    request.user_input = format!("Podcast episode: {}\n\n{}", episode.title, transcript.trim());
+    request.run_id = Some(run_id.clone());
+    request.dry_run = false;
+    let output = ai_client::run_recorded(app_handle, Some(window.label().to_string()), request).await?;
+
+    emit_stage(app_handle, &run_id, "done", json!({}));
+    Ok(PodcastResult {
+        episode,
+        run_id,
+        transcript_chars: transcript.chars().count(),
+        output,
+    })
+}
@@ -0,0 +1,198 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Narrows a finished output to the part the next consumer needs. Filters apply in order, each
+// to the previous one's result.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PostFilter {
+    // Capture group `group` (default: the whole match) of the first match, or of every match
+    // with `all`, one per line
+    Regex {
+        pattern: String,
+        #[serde(default)]
+        group: Option<usize>,
+        #[serde(default)]
+        all: bool,
+    },
+    // A jq-style path into the JSON in the output: ".items[].title", "$.data[0].id",
+    // ".[\"odd key\"]". Strings come out as plain text, anything else as JSON.
+    JsonQuery { query: String },
+    // Keeps lines matching `include` and not matching `exclude`, then the first or last few
+    Lines {
+        #[serde(default)]
+        include: Option<String>,
+        #[serde(default)]
+        exclude: Option<String>,
+        #[serde(default)]
+        first: Option<usize>,
+        #[serde(default)]
+        last: Option<usize>,
+    },
+}
+
+enum Segment {
+    Key(String),
+    Index(i64),
+    All,
+}
+
+fn parse_query(query: &str) -> Result<Vec<Segment>, String> {
+    let invalid = || format!("'{}' is not a valid JSON query.", query);
+    let query = query.trim();
+    let mut rest = query.strip_prefix('$').unwrap_or(query);
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let inner = after[..end].trim();
+            segments.push(match inner {
+                "" | "*" => Segment::All,
+                _ if inner.starts_with('"') || inner.starts_with('\'') => {
+                    Segment::Key(inner.trim_matches(|c| c == '"' || c == '\'').to_string())
+                }
+                _ => Segment::Index(inner.parse().map_err(|_| invalid())?),
+            });
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let len = after.find(['.', '[']).unwrap_or(after.len());
+            match &after[..len] {
+                "" => {}
+                "*" => segments.push(Segment::All),
+                key => segments.push(Segment::Key(key.to_string())),
+            }
+            rest = &after[len..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+// The JSON value in an output: all of it, a fenced ```json block, or the span from the first
+// bracket to the last
+fn find_json(output: &str) -> Result<Value, String> {
+    let trimmed = output.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Ok(value);
+    }
+    let fence = Regex::new(r"(?s)```(?:json)?\s*\n(.*?)```").unwrap();
+    if let Some(value) = fence.captures(trimmed).and_then(|c| serde_json::from_str(&c[1]).ok()) {
+        return Ok(value);
+    }
+    let start = trimmed.find(['{', '[']);
+    let end = trimmed.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => {
+            serde_json::from_str(&trimmed[start..=end]).map_err(|e| format!("The output's JSON is not valid: {}", e))
+        }
+        _ => Err("The output contains no JSON.".to_string()),
+    }
+}
+
+fn json_query(output: &str, query: &str) -> Result<String, String> {
+    let mut values = vec![find_json(output)?];
+    for segment in parse_query(query)? {
+        values = values
+            .into_iter()
+            .flat_map(|value| match (&segment, value) {
+                (Segment::Key(key), Value::Object(mut map)) => map.remove(key).into_iter().collect(),
+                (Segment::Index(i), Value::Array(mut items)) => {
+                    let index = if *i < 0 { items.len() as i64 + i } else { *i };
+                    if (0..items.len() as i64).contains(&index) {
+                        vec![items.swap_remove(index as usize)]
+                    } else {
+                        Vec::new()
+                    }
+                }
+                (Segment::All, Value::Array(items)) => items,
+                (Segment::All, Value::Object(map)) => map.into_iter().map(|(_, v)| v).collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+    }
+    if values.is_empty() {
+        return Err(format!("The JSON query '{}' matched nothing.", query));
+    }
+    Ok(values
+        .iter()
+        .map(|v| match v {
+            Value::String(s) => s.clone(),
+            other => serde_json::to_string_pretty(other).unwrap_or_default(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn regex_capture(output: &str, pattern: &str, group: Option<usize>, all: bool) -> Result<String, String> {
+    let regex = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+    let group = group.unwrap_or(0);
+    let captured: Vec<&str> = regex
+        .captures_iter(output)
+        .filter_map(|c| c.get(group).map(|m| m.as_str()))
+        .take(if all { usize::MAX } else { 1 })
+        .collect();
+    if captured.is_empty() {
+        return Err(format!("The regex '{}' matched nothing.", pattern));
+    }
+    Ok(captured.join("\n"))
+}
+
+fn filter_lines(
+    output: &str,
+    include: Option<&str>,
+    exclude: Option<&str>,
+    first: Option<usize>,
+    last: Option<usize>,
+) -> Result<String, String> {
+    let compile = |p: &str| Regex::new(p).map_err(|e| format!("Invalid regex: {}", e));
+    let include = include.map(compile).transpose()?;
+    let exclude = exclude.map(compile).transpose()?;
+    let mut lines: Vec<&str> = output
+        .lines()
+        .filter(|l| include.as_ref().is_none_or(|r| r.is_match(l)))
+        .filter(|l| exclude.as_ref().is_none_or(|r| !r.is_match(l)))
+        .collect();
+    if let Some(first) = first {
+        lines.truncate(first);
+    }
+    if let Some(last) = last {
+        lines = lines.split_off(lines.len().saturating_sub(last));
+    }
+    Ok(lines.join("\n"))
+}
+
+// Catches bad regexes and queries when a filter is saved rather than after a long run
+pub fn check(filter: &PostFilter) -> Result<(), String> {
+    let compile = |p: &str| Regex::new(p).map(drop).map_err(|e| format!("Invalid regex: {}", e));
+    match filter {
+        PostFilter::Regex { pattern, .. } => compile(pattern),
+        PostFilter::JsonQuery { query } => parse_query(query).map(drop),
+        PostFilter::Lines { include, exclude, .. } => {
+            include.as_deref().map(compile).transpose()?;
+            exclude.as_deref().map(compile).transpose().map(drop)
+        }
+    }
+}
+
+pub fn apply(filters: &[PostFilter], output: &str) -> Result<String, String> {
+    let mut text = output.to_string();
+    for filter in filters {
+        text = match filter {
+            PostFilter::Regex { pattern, group, all } => regex_capture(&text, pattern, *group, *all)?,
+            PostFilter::JsonQuery { query } => json_query(&text, query)?,
+            PostFilter::Lines { include, exclude, first, last } => {
+                filter_lines(&text, include.as_deref(), exclude.as_deref(), *first, *last)?
+            }
+        };
+    }
+    Ok(text)
+}
+
+// For presets kept by the frontend: filters a finished output the same way pipeline steps do
+#[tauri::command]
+pub async fn apply_post_filters(text: String, filters: Vec<PostFilter>) -> Result<String, String> {
+    filters.iter().try_for_each(check)?;
+    apply(&filters, &text)
+}
@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::history::HistoryState;
+use crate::pattern_index::PatternIndex;
+use crate::patterns::{pattern_dirs, pattern_sources};
+use crate::settings::{Settings, SettingsState};
+use crate::snippets::SnippetStore;
+use crate::variables::VariableStore;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Serialize, Deserialize)]
+struct ProfilesFile {
+    active: String,
+}
+
+#[derive(Serialize)]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<String>,
+}
+
+pub struct ProfilePaths {
+    pub settings: PathBuf,
+    pub history: PathBuf,
+    pub variables: PathBuf,
+    pub snippets: PathBuf,
+}
+
+fn config_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle.path().app_config_dir().map_err(|e| e.to_string())
+}
+
+fn data_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle.path().app_data_dir().map_err(|e| e.to_string())
+}
+
+// The default profile keeps the pre-profiles file locations so existing installs need no migration
+pub fn profile_paths(app_handle: &AppHandle, name: &str) -> Result<ProfilePaths, String> {
+    let (config, data) = if name == DEFAULT_PROFILE {
+        (config_dir(app_handle)?, data_dir(app_handle)?)
+    } else {
+        (
+            config_dir(app_handle)?.join("profiles").join(name),
+            data_dir(app_handle)?.join("profiles").join(name),
+        )
+    };
+
+    Ok(ProfilePaths {
+        settings: config.join("settings.json"),
+        history: data.join("history.db"),
+        variables: config.join("variables.json"),
+        snippets: config.join("snippets.json"),
+    })
+}
+
+pub fn active_profile(app_handle: &AppHandle) -> String {
+    config_dir(app_handle)
+        .ok()
+        .and_then(|dir| fs::read_to_string(dir.join("profiles.json")).ok())
+        .and_then(|s| serde_json::from_str::<ProfilesFile>(&s).ok())
+        .map(|f| f.active)
+        .filter(|name| profile_exists(app_handle, name))
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+fn set_active_profile(app_handle: &AppHandle, name: &str) -> Result<(), String> {
+    let dir = config_dir(app_handle)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&ProfilesFile { active: name.to_string() })
+        .map_err(|e| e.to_string())?;
+    fs::write(dir.join("profiles.json"), json).map_err(|e| e.to_string())
+}
+
+fn profile_exists(app_handle: &AppHandle, name: &str) -> bool {
+    name == DEFAULT_PROFILE
+        || config_dir(app_handle)
+            .map(|dir| dir.join("profiles").join(name).is_dir())
+            .unwrap_or(false)
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 40
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err("Profile names may only contain letters, digits, '-' and '_' (max 40).".to_string())
+    }
+}
+
+#[tauri::command]
+pub async fn list_profiles(app_handle: AppHandle) -> Result<ProfileList, String> {
+    let mut profiles = vec![DEFAULT_PROFILE.to_string()];
+    if let Ok(entries) = fs::read_dir(config_dir(&app_handle)?.join("profiles")) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                profiles.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+    profiles[1..].sort();
+
+    Ok(ProfileList {
+        active: active_profile(&app_handle),
+        profiles,
+    })
+}
+
+#[tauri::command]
+pub async fn create_profile(
+    app_handle: AppHandle,
+    name: String,
+    copy_from: Option<String>,
+) -> Result<(), String> {
+    validate_name(&name)?;
+    if profile_exists(&app_handle, &name) {
+        return Err(format!("Profile '{}' already exists.", name));
+    }
+
+    let paths = profile_paths(&app_handle, &name)?;
+    let settings = match copy_from {
+        Some(source) => {
+            validate_name(&source)?;
+            if !profile_exists(&app_handle, &source) {
+                return Err(format!("Profile '{}' does not exist.", source));
+            }
+            let source_paths = profile_paths(&app_handle, &source)?;
+            fs::read_to_string(&source_paths.settings)
+                .ok()
+                .and_then(|s| serde_json::from_str::<Settings>(&s).ok())
+                .unwrap_or_default()
+        }
+        None => Settings::default(),
+    };
+
+    if let Some(parent) = paths.settings.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    fs::write(&paths.settings, json).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn switch_profile(
+    app_handle: AppHandle,
+    settings: State<'_, SettingsState>,
+    history: State<'_, HistoryState>,
+    variables: State<'_, VariableStore>,
+    snippets: State<'_, SnippetStore>,
+    pattern_index: State<'_, PatternIndex>,
+    name: String,
+) -> Result<Settings, String> {
+    validate_name(&name)?;
+    if !profile_exists(&app_handle, &name) {
+        return Err(format!("Profile '{}' does not exist.", name));
+    }
+
+    let paths = profile_paths(&app_handle, &name)?;
+    history.reopen(&paths.history)?;
+    let loaded = settings.reload(paths.settings);
+    variables.reload(paths.variables);
+    snippets.reload(paths.snippets);
+    // The profile may use another patterns folder
+    pattern_index.refresh(&pattern_sources(&loaded), pattern_dirs(&loaded))?;
+    set_active_profile(&app_handle, &name)?;
+
+    // Every window re-fetches patterns, settings and history when this fires
+    let _ = app_handle.emit("profile-changed", &name);
+    Ok(loaded)
+}
+
+#[tauri::command]
+pub async fn delete_profile(app_handle: AppHandle, name: String) -> Result<(), String> {
+    if name == DEFAULT_PROFILE {
+        return Err("The default profile cannot be deleted.".to_string());
+    }
+    if active_profile(&app_handle) == name {
+        return Err("Switch to another profile before deleting this one.".to_string());
+    }
+    validate_name(&name)?;
+
+    let paths = profile_paths(&app_handle, &name)?;
+    for dir in [paths.settings.parent(), paths.history.parent()].into_iter().flatten() {
+        if dir.exists() {
+            fs::remove_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use futures::future::join_all;
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::http::{self, AuditedSend};
+
+const CACHE_TTL: Duration = Duration::from_secs(300);
+const POLL_INTERVAL: Duration = Duration::from_secs(300);
+
+// OpenAI and Anthropic publish Atlassian Statuspage feeds; Google only has the Cloud incident feed
+const STATUSPAGE_FEEDS: [(&str, &str); 2] = [
+    ("openai", "https://status.openai.com/api/v2/summary.json"),
+    ("anthropic", "https://status.anthropic.com/api/v2/summary.json"),
+];
+const GOOGLE_INCIDENTS_FEED: &str = "https://status.cloud.google.com/incidents.json";
+
+#[derive(Serialize, Clone, PartialEq)]
+pub struct ProviderStatus {
+    pub vendor: String,
+    // Statuspage indicator: none, minor, major, critical, or unknown when the feed failed
+    pub indicator: String,
+    pub description: String,
+    pub incidents: Vec<String>,
+    pub checked_at: u64,
+}
+
+#[derive(Default)]
+pub struct ProviderStatusCache {
+    entries: Mutex<HashMap<String, (Instant, ProviderStatus)>>,
+}
+
+impl ProviderStatusCache {
+    fn fresh(&self) -> Option<Vec<ProviderStatus>> {
+        let entries = self.entries.lock().unwrap();
+        if entries.len() < STATUSPAGE_FEEDS.len() + 1 {
+            return None;
+        }
+        if entries.values().any(|(at, _)| at.elapsed() > CACHE_TTL) {
+            return None;
+        }
+        let mut statuses: Vec<ProviderStatus> = entries.values().map(|(_, s)| s.clone()).collect();
+        statuses.sort_by(|a, b| a.vendor.cmp(&b.vendor));
+        Some(statuses)
+    }
+
+    // Returns true when any provider's indicator changed since the last poll
+    fn store(&self, statuses: &[ProviderStatus]) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let mut changed = false;
+        for status in statuses {
+            let previous = entries.insert(status.vendor.clone(), (Instant::now(), status.clone()));
+            if previous.map(|(_, p)| p.indicator != status.indicator).unwrap_or(true) {
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn unknown(vendor: &str, error: String) -> ProviderStatus {
+    ProviderStatus {
+        vendor: vendor.to_string(),
+        indicator: "unknown".to_string(),
+        description: format!("Status feed unavailable: {}", error),
+        incidents: Vec::new(),
+        checked_at: now_secs(),
+    }
+}
+
+async fn fetch_json(url: &str) -> Result<serde_json::Value, String> {
+    let res = http::client_for(url)?.get(url)
+        .timeout(Duration::from_secs(10))
+        .send_audited("provider_status", None)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()));
+    }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+async fn fetch_statuspage(vendor: &str, url: &str) -> ProviderStatus {
+    let json = match fetch_json(url).await {
+        Ok(json) => json,
+        Err(e) => return unknown(vendor, e),
+    };
+
+    let status = &json["status"];
+    let incidents = json["incidents"]
+        .as_array()
+        .map(|list| {
+            list.iter()
+                .filter_map(|i| i["name"].as_str())
+                .map(|name| name.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    ProviderStatus {
+        vendor: vendor.to_string(),
+        indicator: status["indicator"].as_str().unwrap_or("unknown").to_string(),
+        description: status["description"].as_str().unwrap_or_default().to_string(),
+        incidents,
+        checked_at: now_secs(),
+    }
+}
+
+async fn fetch_google() -> ProviderStatus {
+    let json = match fetch_json(GOOGLE_INCIDENTS_FEED).await {
+        Ok(json) => json,
+        Err(e) => return unknown("google", e),
+    };
+
+    // Only open incidents that touch the Gemini / Vertex AI products are relevant here
+    let mut worst = "none";
+    let mut incidents = Vec::new();
+    for incident in json.as_array().into_iter().flatten() {
+        if !incident["end"].is_null() {
+            continue;
+        }
+        let affects_gemini = incident["affected_products"]
+            .as_array()
+            .map(|products| {
+                products.iter().any(|p| {
+                    let title = p["title"].as_str().unwrap_or_default();
+                    title.contains("Gemini") || title.contains("Vertex AI")
+                })
+            })
+            .unwrap_or(false);
+        if !affects_gemini {
+            continue;
+        }
+
+        incidents.push(incident["external_desc"].as_str().unwrap_or("Ongoing incident").to_string());
+        worst = match (incident["severity"].as_str(), worst) {
+            (Some("high"), _) => "major",
+            (_, "major") => "major",
+            _ => "minor",
+        };
+    }
+
+    let description = if incidents.is_empty() {
+        "All Systems Operational".to_string()
+    } else {
+        format!("{} ongoing incident(s) affecting Gemini", incidents.len())
+    };
+
+    ProviderStatus {
+        vendor: "google".to_string(),
+        indicator: worst.to_string(),
+        description,
+        incidents,
+        checked_at: now_secs(),
+    }
+}
+
+async fn poll_all() -> Vec<ProviderStatus> {
+    let statuspage = join_all(
+        STATUSPAGE_FEEDS
+            .iter()
+            .map(|(vendor, url)| fetch_statuspage(vendor, url)),
+    );
+    let (mut statuses, google) = futures::join!(statuspage, fetch_google());
+    statuses.push(google);
+    statuses.sort_by(|a, b| a.vendor.cmp(&b.vendor));
+    statuses
+}
+
+// Background poller so the UI gets a `provider-status` event as soon as an outage starts
+pub fn spawn_poller(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let statuses = poll_all().await;
+            let cache = app_handle.state::<ProviderStatusCache>();
+            if cache.store(&statuses) {
+                let _ = app_handle.emit("provider-status", &statuses);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_provider_status(
+    cache: State<'_, ProviderStatusCache>,
+    force_refresh: Option<bool>,
+) -> Result<Vec<ProviderStatus>, String> {
+    if !force_refresh.unwrap_or(false) {
+        if let Some(statuses) = cache.fresh() {
+            return Ok(statuses);
+        }
+    }
+
+    let statuses = poll_all().await;
+    cache.store(&statuses);
+    Ok(statuses)
+}
@@ -0,0 +1,494 @@
+use reqwest::Url;
+use ring::digest::{digest, SHA256};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::docs::estimate_tokens;
+use crate::history::{now_secs, HistoryState};
+use crate::settings::SettingsState;
+use crate::tray;
+use crate::validate;
+
+const DEFAULT_VENDOR_CONCURRENCY: usize = 2;
+
+// Declared lowest first so the derived ordering puts interactive runs ahead of batch items
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    #[default]
+    Batch,
+    Interactive,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    // A batch item held back by pause_batch
+    Paused,
+}
+
+#[derive(Serialize, Clone)]
+pub struct QueuedJob {
+    pub run_id: String,
+    pub priority: Priority,
+    pub status: JobStatus,
+    pub vendor: String,
+    pub model: String,
+    pub pattern: Option<String>,
+    pub enqueued_at: i64,
+}
+
+struct QueueEntry {
+    job: QueuedJob,
+    seq: u64,
+    request: AIRequest,
+    target: Option<String>,
+    // The spawned run, so abort_batch can stop it
+    task: Option<JoinHandle<()>>,
+}
+
+// What survives a restart; window targets don't, so resumed jobs broadcast their events
+#[derive(Serialize, Deserialize)]
+struct PersistedJob {
+    priority: Priority,
+    enqueued_at: i64,
+    request: AIRequest,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedQueue {
+    #[serde(default)]
+    batch_paused: bool,
+    jobs: Vec<PersistedJob>,
+}
+
+#[derive(Default)]
+struct QueueInner {
+    waiting: Vec<QueueEntry>,
+    running: Vec<QueueEntry>,
+    next_seq: u64,
+    // Batch items don't start while set; interactive runs are unaffected
+    batch_paused: bool,
+}
+
+// Runs compete for a bounded number of slots per vendor; interactive runs always start before
+// batch items, and jobs of equal priority start in the order they were enqueued.
+// Unfinished jobs are written to disk on every change so they resume after a restart.
+pub struct RunQueue {
+    path: PathBuf,
+    inner: Mutex<QueueInner>,
+}
+
+impl RunQueue {
+    // Jobs that were queued or running when the app last exited come back as queued, in the
+    // same order, and a paused batch stays paused
+    pub fn load(path: PathBuf) -> Self {
+        let text = fs::read_to_string(&path).unwrap_or_default();
+        // Older versions saved the bare job list
+        let persisted = serde_json::from_str::<PersistedQueue>(&text)
+            .or_else(|_| serde_json::from_str(&text).map(|jobs| PersistedQueue { batch_paused: false, jobs }))
+            .unwrap_or(PersistedQueue { batch_paused: false, jobs: Vec::new() });
+
+        let queue = Self {
+            path,
+            inner: Mutex::new(QueueInner { batch_paused: persisted.batch_paused, ..Default::default() }),
+        };
+        for job in persisted.jobs {
+            queue.push(job.request, job.priority, None, job.enqueued_at);
+        }
+        queue
+    }
+
+    pub fn enqueue(&self, request: AIRequest, priority: Priority, target: Option<String>) -> String {
+        self.push(request, priority, target, now_secs())
+    }
+
+    fn push(&self, mut request: AIRequest, priority: Priority, target: Option<String>, enqueued_at: i64) -> String {
+        let run_id = request.run_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+        request.run_id = Some(run_id.clone());
+
+        let mut inner = self.inner.lock().unwrap();
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+        inner.waiting.push(QueueEntry {
+            job: QueuedJob {
+                run_id: run_id.clone(),
+                priority,
+                status: JobStatus::Queued,
+                vendor: request.vendor.clone(),
+                model: request.model.clone(),
+                pattern: request.pattern.clone(),
+                enqueued_at,
+            },
+            seq,
+            request,
+            target,
+            task: None,
+        });
+        run_id
+    }
+
+    pub fn is_busy(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        !inner.waiting.is_empty() || !inner.running.is_empty()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let persisted = {
+            let inner = self.inner.lock().unwrap();
+            let jobs = inner
+                .running
+                .iter()
+                .chain(inner.waiting.iter())
+                .map(|e| {
+                    // Keys stay out of jobs.json; a resumed run falls back to the one in settings
+                    let mut request = e.request.clone();
+                    request.api_key.clear();
+                    PersistedJob { priority: e.job.priority, enqueued_at: e.job.enqueued_at, request }
+                })
+                .collect();
+            PersistedQueue { batch_paused: inner.batch_paused, jobs }
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(&persisted).map_err(|e| e.to_string())?;
+        fs::write(&self.path, json).map_err(|e| e.to_string())
+    }
+
+    pub fn snapshot(&self) -> Vec<QueuedJob> {
+        let inner = self.inner.lock().unwrap();
+        let mut waiting: Vec<&QueueEntry> = inner.waiting.iter().collect();
+        waiting.sort_by(|a, b| b.job.priority.cmp(&a.job.priority).then(a.seq.cmp(&b.seq)));
+
+        inner
+            .running
+            .iter()
+            .chain(waiting)
+            .map(|e| {
+                let mut job = e.job.clone();
+                if inner.batch_paused && job.status == JobStatus::Queued && job.priority == Priority::Batch {
+                    job.status = JobStatus::Paused;
+                }
+                job
+            })
+            .collect()
+    }
+
+    pub fn set_batch_paused(&self, paused: bool) {
+        self.inner.lock().unwrap().batch_paused = paused;
+    }
+
+    // Drops every waiting batch item and stops the running ones; returns the run IDs stopped
+    // mid-run and how many jobs were removed in all. The pause is lifted so the next batch
+    // starts normally.
+    fn abort_batch(&self) -> (Vec<String>, usize) {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.waiting.len() + inner.running.len();
+        inner.waiting.retain(|e| e.job.priority != Priority::Batch);
+        let mut stopped = Vec::new();
+        inner.running.retain(|e| {
+            if e.job.priority != Priority::Batch {
+                return true;
+            }
+            if let Some(task) = &e.task {
+                task.abort();
+            }
+            stopped.push(e.job.run_id.clone());
+            false
+        });
+        inner.batch_paused = false;
+        let removed = before - inner.waiting.len() - inner.running.len();
+        (stopped, removed)
+    }
+
+    fn attach(&self, run_id: &str, task: JoinHandle<()>) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(entry) = inner.running.iter_mut().find(|e| e.job.run_id == run_id) {
+            entry.task = Some(task);
+        }
+    }
+
+    pub fn cancel(&self, run_id: &str) -> Result<(), String> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.running.iter().any(|e| e.job.run_id == run_id) {
+            return Err("The run has already started and can no longer be removed from the queue.".to_string());
+        }
+        let before = inner.waiting.len();
+        inner.waiting.retain(|e| e.job.run_id != run_id);
+        if inner.waiting.len() == before {
+            return Err(format!("Run '{}' is not queued.", run_id));
+        }
+        Ok(())
+    }
+
+    // Moves every job that has a free vendor slot to running and returns what should be spawned
+    fn take_startable(&self, limit: usize) -> Vec<(String, AIRequest, Option<String>)> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut started = Vec::new();
+
+        loop {
+            let mut running_per_vendor: HashMap<String, usize> = HashMap::new();
+            for entry in &inner.running {
+                *running_per_vendor.entry(entry.job.vendor.clone()).or_default() += 1;
+            }
+
+            let batch_paused = inner.batch_paused;
+            let next = inner
+                .waiting
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| !(batch_paused && e.job.priority == Priority::Batch))
+                .filter(|(_, e)| running_per_vendor.get(&e.job.vendor).copied().unwrap_or(0) < limit)
+                .max_by(|(_, a), (_, b)| a.job.priority.cmp(&b.job.priority).then(b.seq.cmp(&a.seq)))
+                .map(|(i, _)| i);
+
+            match next {
+                Some(index) => {
+                    let mut entry = inner.waiting.remove(index);
+                    entry.job.status = JobStatus::Running;
+                    started.push((entry.job.run_id.clone(), entry.request.clone(), entry.target.clone()));
+                    inner.running.push(entry);
+                }
+                None => break,
+            }
+        }
+
+        started
+    }
+
+    fn finish(&self, run_id: &str) {
+        self.inner.lock().unwrap().running.retain(|e| e.job.run_id != run_id);
+    }
+}
+
+fn notify(app_handle: &AppHandle) {
+    let queue = app_handle.state::<RunQueue>();
+    let _ = queue.save();
+    let jobs = queue.snapshot();
+    tray::set_job_count(app_handle, jobs.len());
+    let _ = app_handle.emit("queue-changed", jobs);
+}
+
+// Starts whatever fits into the free vendor slots; called whenever a job is added or finishes
+pub fn dispatch(app_handle: &AppHandle) {
+    let limit = app_handle
+        .state::<SettingsState>()
+        .get()
+        .vendor_concurrency
+        .map(|n| n.max(1) as usize)
+        .unwrap_or(DEFAULT_VENDOR_CONCURRENCY);
+
+    let started = app_handle.state::<RunQueue>().take_startable(limit);
+    if started.is_empty() {
+        return;
+    }
+    notify(app_handle);
+
+    // Jobs run on the app's runtime rather than in a window, so they keep going while the
+    // main window is hidden to the tray
+    for (run_id, request, target) in started {
+        let handle = app_handle.clone();
+        let id = run_id.clone();
+        let task = tauri::async_runtime::spawn(async move {
+            // Failures are reported through the run's own ai-complete event
+            let _ = ai_client::run_recorded(&handle, target, request).await;
+            handle.state::<RunQueue>().finish(&id);
+            notify(&handle);
+            dispatch(&handle);
+        });
+        app_handle.state::<RunQueue>().attach(&run_id, task);
+    }
+}
+
+// Returns the run ID right away; output streams to the calling window once the job starts
+#[tauri::command]
+pub async fn enqueue_run(
+    window: Window,
+    queue: State<'_, RunQueue>,
+    request: AIRequest,
+    priority: Option<Priority>,
+) -> Result<String, String> {
+    if request.dry_run {
+        return Err("Dry runs don't call the vendor; use run_pattern instead of the queue.".to_string());
+    }
+    validate::check(window.app_handle(), &request)?;
+    let run_id = queue.enqueue(request, priority.unwrap_or_default(), Some(window.label().to_string()));
+    notify(window.app_handle());
+    dispatch(window.app_handle());
+    Ok(run_id)
+}
+
+#[tauri::command]
+pub async fn get_queue(queue: State<'_, RunQueue>) -> Result<Vec<QueuedJob>, String> {
+    Ok(queue.snapshot())
+}
+
+#[tauri::command]
+pub async fn cancel_queued(app_handle: AppHandle, queue: State<'_, RunQueue>, run_id: String) -> Result<(), String> {
+    queue.cancel(&run_id)?;
+    notify(&app_handle);
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct BatchItem {
+    pub request: AIRequest,
+    // The URL the input was fetched from, if any; two items from one URL are the same input
+    // even when the page changed slightly between fetches
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BatchItemResult {
+    // Where the item's output will be, or already is
+    pub run_id: String,
+    // "queued", "duplicate" (shares an earlier item's run) or "cached" (reuses a past run)
+    pub status: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct BatchSummary {
+    pub items: Vec<BatchItemResult>,
+    pub queued: usize,
+    pub duplicates: usize,
+    pub cached: usize,
+    // Estimated input tokens not sent because of duplicates and cached runs
+    pub saved_input_tokens: usize,
+}
+
+fn hex(data: &[u8]) -> String {
+    digest(&SHA256, data).as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// The input part of an item's identity: its source URL without fragment or trailing slash,
+// otherwise a hash of the text
+fn input_key(item: &BatchItem) -> String {
+    let url = item.source.as_deref().and_then(|s| Url::parse(s.trim()).ok());
+    match url {
+        Some(mut url) => {
+            url.set_fragment(None);
+            format!("url:{}", url.as_str().trim_end_matches('/'))
+        }
+        None => format!("text:{}", hex(item.request.user_input.trim().as_bytes())),
+    }
+}
+
+// The most recent successful run that sent exactly this prompt and input to this model
+fn cached_run(history: &HistoryState, request: &AIRequest) -> Option<String> {
+    history
+        .conn()
+        .query_row(
+            "SELECT id FROM runs WHERE success = 1 AND vendor = ?1 AND model = ?2 AND system_prompt = ?3 AND input = ?4
+             ORDER BY created_at DESC LIMIT 1",
+            params![request.vendor, request.model, request.system_prompt, request.user_input],
+            |row| row.get(0),
+        )
+        .optional()
+        .ok()
+        .flatten()
+}
+
+// Queues several runs as batch items. Items with the same input (same source URL or text) and
+// the same prompt, vendor and model run once; with `reuse_cached` (the default) an identical
+// successful run already in history is reused instead of queuing one at all.
+#[tauri::command]
+pub async fn enqueue_batch(
+    window: Window,
+    queue: State<'_, RunQueue>,
+    history: State<'_, HistoryState>,
+    items: Vec<BatchItem>,
+    reuse_cached: Option<bool>,
+) -> Result<BatchSummary, String> {
+    if items.is_empty() {
+        return Err("A batch needs at least one item.".to_string());
+    }
+    let app_handle = window.app_handle();
+    let settings = app_handle.state::<SettingsState>().get();
+    let reuse_cached = reuse_cached.unwrap_or(true);
+
+    // Everything is checked before anything is queued, so a bad item doesn't leave half a batch
+    let mut prepared = Vec::new();
+    for item in items {
+        if item.request.dry_run {
+            return Err("Dry runs don't call the vendor; use run_pattern instead of the queue.".to_string());
+        }
+        validate::check(app_handle, &item.request)?;
+        // Compared the way history records runs: composed prompt, expanded input
+        let mut composed = item.request.clone();
+        ai_client::apply_composition(app_handle, &settings, &mut composed)?;
+        let key = format!(
+            "{}\n{}\n{}\n{}",
+            composed.vendor,
+            composed.model,
+            hex(composed.system_prompt.as_bytes()),
+            input_key(&item)
+        );
+        prepared.push((key, item.request, composed));
+    }
+
+    let mut summary = BatchSummary { items: Vec::new(), queued: 0, duplicates: 0, cached: 0, saved_input_tokens: 0 };
+    let mut seen: HashMap<String, String> = HashMap::new();
+    for (key, request, composed) in prepared {
+        let tokens = estimate_tokens(&composed.system_prompt) + estimate_tokens(&composed.user_input);
+        let (run_id, status) = if let Some(run_id) = seen.get(&key) {
+            summary.duplicates += 1;
+            summary.saved_input_tokens += tokens;
+            (run_id.clone(), "duplicate")
+        } else if let Some(run_id) = reuse_cached.then(|| cached_run(&history, &composed)).flatten() {
+            summary.cached += 1;
+            summary.saved_input_tokens += tokens;
+            (run_id, "cached")
+        } else {
+            summary.queued += 1;
+            (queue.enqueue(request, Priority::Batch, Some(window.label().to_string())), "queued")
+        };
+        seen.insert(key, run_id.clone());
+        summary.items.push(BatchItemResult { run_id, status });
+    }
+    notify(app_handle);
+    dispatch(app_handle);
+    Ok(summary)
+}
+
+// Lets running batch items finish but starts no more until resume_batch, also across restarts
+#[tauri::command]
+pub async fn pause_batch(app_handle: AppHandle, queue: State<'_, RunQueue>) -> Result<(), String> {
+    queue.set_batch_paused(true);
+    notify(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn resume_batch(app_handle: AppHandle, queue: State<'_, RunQueue>) -> Result<(), String> {
+    queue.set_batch_paused(false);
+    notify(&app_handle);
+    dispatch(&app_handle);
+    Ok(())
+}
+
+// Removes every batch item from the queue, stopping those already running; interactive runs
+// carry on. Returns the number of jobs removed.
+#[tauri::command]
+pub async fn abort_batch(app_handle: AppHandle, queue: State<'_, RunQueue>) -> Result<usize, String> {
+    let (stopped, removed) = queue.abort_batch();
+    // A stopped run never reaches its own completion event or history record
+    for run_id in &stopped {
+        let _ = app_handle.emit("ai-complete", json!({"success": false, "error": "Aborted.", "run_id": run_id}));
+    }
+    notify(&app_handle);
+    dispatch(&app_handle);
+    Ok(removed)
+}
@@ -0,0 +1,1038 @@
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::ai_client::{self, AIRequest};
+use crate::chunking;
+use crate::embeddings;
+use crate::history::{now_secs, HistoryState};
+use crate::ingest;
+use crate::keyword_index::KeywordIndex;
+use crate::settings::{Settings, SettingsState};
+use crate::suggest;
+
+pub const DEFAULT_COLLECTION: &str = "default";
+const DEFAULT_TOP_K: usize = 5;
+// How often a watched folder is checked for added, changed and deleted files
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+// Only the start of a long input is embedded to find sources; it says what the input is about
+const QUERY_CHARS: usize = 6000;
+// Each retriever contributes this many candidates (or 4 × top_k) to merging and reranking
+const CANDIDATES: usize = 20;
+// Reciprocal rank fusion damping: a chunk scores 1 / (60 + rank) in each list it appears in
+const RRF_K: f32 = 60.0;
+// Passages shown to an LLM reranker are cut to this, to keep the ranking prompt small
+const RERANK_PASSAGE_CHARS: usize = 1500;
+const SUPPORTED_EXTENSIONS: &[&str] = &["pdf", "docx", "md", "markdown", "txt", "rst", "org", "srt", "vtt", "html", "htm"];
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS documents (
+    path TEXT PRIMARY KEY,
+    -- Modification time in Unix milliseconds; a file is embedded again when it changes
+    modified INTEGER NOT NULL,
+    indexed_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS chunks (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL REFERENCES documents(path) ON DELETE CASCADE,
+    page INTEGER,
+    heading TEXT,
+    text TEXT NOT NULL,
+    embedding BLOB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS chunks_path ON chunks(path);
+";
+
+// Retrieval for runs. Each collection (e.g. "work docs", "research papers") indexes one folder:
+// its documents are split with the chunking settings, embedded with the collection's model and
+// kept in rag/<collection>/index.db, with a BM25 keyword index beside it. A run with `rag` set
+// gets the best chunks of its collection as numbered sources, which the model cites as [1], [2]...
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RagSettings {
+    // The default collection's documents: PDF, Word, Markdown, text and subtitle files, in
+    // subfolders too
+    pub folder: Option<String>,
+    // The embedding model of collections created without one: "openai" (default), "google"
+    // or "lmstudio", and the vendor's small model when unset. LM Studio needs one named.
+    pub embedding_vendor: Option<String>,
+    pub embedding_model: Option<String>,
+    // Sources given to a run; 5 when unset
+    pub top_k: Option<usize>,
+    // Keep the index in step with the folder: new and changed files are embedded, deleted
+    // ones dropped, without rebuilding the rest
+    pub watch: bool,
+}
+
+// How a collection finds the sources of a run
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RetrievalSettings {
+    // "hybrid" (default) merges keyword (BM25) and vector matches by rank; "vector" or
+    // "keyword" use one alone
+    pub mode: Option<String>,
+    // Reorders the candidates before the top ones are kept: "llm" asks a chat model,
+    // "cross-encoder" a rerank API; off when unset
+    pub rerank: Option<String>,
+    // Any chat vendor for "llm" (the default vendor when unset), "jina" or "cohere" for
+    // "cross-encoder"
+    pub rerank_vendor: Option<String>,
+    // The vendor's small model when unset
+    pub rerank_model: Option<String>,
+}
+
+pub struct RagIndex {
+    conn: Mutex<Connection>,
+    keywords: KeywordIndex,
+    // Held while the index is being updated, so the watcher and commands never overlap
+    updating: tokio::sync::Mutex<()>,
+}
+
+// The collections in rag/, opened on first use
+pub struct RagCollections {
+    dir: PathBuf,
+    open: Mutex<HashMap<String, Arc<RagIndex>>>,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct IndexReport {
+    // Files embedded because they were new or had changed
+    pub embedded: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    pub chunks_embedded: usize,
+    // Files that could not be read, with the reason
+    pub failed: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct IndexStats {
+    pub name: String,
+    pub retrieval: RetrievalSettings,
+    pub folder: Option<String>,
+    pub documents: usize,
+    pub chunks: usize,
+    // Unix seconds of the last update, None before the first
+    pub last_updated: Option<i64>,
+    pub embedding_vendor: Option<String>,
+    pub embedding_model: Option<String>,
+    pub watching: bool,
+}
+
+#[derive(Serialize, Clone)]
+struct IndexProgress {
+    collection: String,
+    done: usize,
+    total: usize,
+    path: String,
+}
+
+// A retrieved chunk as the run saw it; `cited` is set once the answer refers to its marker
+#[derive(Serialize, Clone)]
+pub struct Citation {
+    pub marker: usize,
+    pub source: String,
+    // 1-based, for PDFs
+    pub page: Option<u32>,
+    pub heading: Option<String>,
+    pub text: String,
+    pub score: f32,
+    pub cited: bool,
+}
+
+struct Piece {
+    page: Option<u32>,
+    heading: Option<String>,
+    text: String,
+}
+
+fn to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn dot(a: &[f32], blob: &[u8]) -> f32 {
+    a.iter()
+        .zip(blob.chunks_exact(4))
+        .map(|(x, bytes)| x * f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .sum()
+}
+
+fn modified(path: &Path) -> i64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| SUPPORTED_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+// Hidden folders such as .git and .obsidian are skipped
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else if is_supported(&path) {
+            files.push(path);
+        }
+    }
+}
+
+// pdftotext ends each page with a form feed, so PDF chunks never span pages and know theirs
+fn pieces(settings: &Settings, path: &Path) -> Result<Vec<Piece>, String> {
+    let text = ingest::ingest(path)?;
+    let is_pdf = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+    let pages: Vec<(Option<u32>, &str)> = if is_pdf {
+        text.split('\u{c}').enumerate().map(|(i, page)| (Some(i as u32 + 1), page)).collect()
+    } else {
+        vec![(None, text.as_str())]
+    };
+    Ok(pages
+        .into_iter()
+        .flat_map(|(page, text)| {
+            chunking::chunk(text, &settings.chunking)
+                .into_iter()
+                .map(move |c| Piece { page, heading: c.heading, text: c.text })
+        })
+        .collect())
+}
+
+// The given vendor and model, falling back to the RAG settings' defaults
+fn embedding_model(settings: &Settings, vendor: Option<String>, model: Option<String>) -> Result<(String, String), String> {
+    let default_vendor = settings.rag.embedding_vendor.clone().unwrap_or_else(|| "openai".to_string());
+    let vendor = vendor.filter(|v| !v.trim().is_empty()).unwrap_or_else(|| default_vendor.clone());
+    // The default model only goes with the default vendor
+    let model = model
+        .or_else(|| settings.rag.embedding_model.clone().filter(|_| vendor == default_vendor))
+        .filter(|m| !m.trim().is_empty())
+        .or_else(|| embeddings::default_model(&vendor).map(str::to_string))
+        .ok_or_else(|| format!("Name the {} embedding model to use.", vendor))?;
+    if !["openai", "google", "lmstudio"].contains(&vendor.as_str()) {
+        return Err(format!("Embeddings are available from OpenAI, Google and LM Studio, not '{}'.", vendor));
+    }
+    Ok((vendor, model))
+}
+
+// Collection names become folder names
+fn check_collection_name(name: &str) -> Result<(), String> {
+    let valid = !name.trim().is_empty()
+        && name.trim() == name
+        && name.len() <= 40
+        && name.chars().all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err("Collection names may only contain letters, digits, spaces, '-' and '_' (max 40).".to_string())
+    }
+}
+
+impl RagCollections {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, open: Mutex::new(HashMap::new()) }
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.dir.join(name).join("index.db").is_file()
+    }
+
+    // The default collection is created on first use; others must have been created
+    fn get(&self, name: &str) -> Result<Arc<RagIndex>, String> {
+        check_collection_name(name)?;
+        let mut open = self.open.lock().unwrap();
+        if let Some(index) = open.get(name) {
+            return Ok(index.clone());
+        }
+        if name != DEFAULT_COLLECTION && !self.exists(name) {
+            return Err(format!("There is no RAG collection named '{}'.", name));
+        }
+        let index = Arc::new(RagIndex::open(&self.dir.join(name).join("index.db"))?);
+        open.insert(name.to_string(), index.clone());
+        Ok(index)
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|e| e.file_name().to_str().map(str::to_string))
+                    .filter(|name| check_collection_name(name).is_ok() && self.exists(name))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !names.iter().any(|n| n == DEFAULT_COLLECTION) {
+            names.push(DEFAULT_COLLECTION.to_string());
+        }
+        names.sort();
+        names
+    }
+}
+
+impl RagIndex {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "foreign_keys", "ON").map_err(|e| e.to_string())?;
+        conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+        let keywords = KeywordIndex::open(&path.with_file_name("keywords"))?;
+        let index = Self { conn: Mutex::new(conn), keywords, updating: tokio::sync::Mutex::new(()) };
+        index.sync_keywords()?;
+        Ok(index)
+    }
+
+    // Commits the keyword index, and rebuilds it from the chunks when the two disagree, e.g.
+    // for a collection indexed before keyword search or after an interrupted update
+    fn sync_keywords(&self) -> Result<(), String> {
+        self.keywords.commit()?;
+        if self.keywords.len() == self.count("chunks") as u64 {
+            return Ok(());
+        }
+        let rows: Vec<(i64, String, String)> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare("SELECT id, path, text FROM chunks ORDER BY path").map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+                .map_err(|e| e.to_string())?;
+            rows
+        };
+        self.keywords.clear()?;
+        for group in rows.chunk_by(|a, b| a.1 == b.1) {
+            let chunks: Vec<(i64, &str)> = group.iter().map(|(id, _, text)| (*id, text.as_str())).collect();
+            self.keywords.replace(&group[0].1, &chunks)?;
+        }
+        self.keywords.commit()
+    }
+
+    fn retrieval(&self) -> RetrievalSettings {
+        self.meta("retrieval").and_then(|r| serde_json::from_str(&r).ok()).unwrap_or_default()
+    }
+
+    // The collection's own model, or the settings' default before it has one
+    fn model(&self, settings: &Settings) -> Result<(String, String), String> {
+        embedding_model(settings, self.meta("embedding_vendor"), self.meta("embedding_model"))
+    }
+
+    // A changed model makes every stored vector useless, so the index is emptied
+    fn set_model(&self, vendor: &str, model: &str) -> Result<(), String> {
+        if self.meta("embedding_vendor").as_deref() != Some(vendor) || self.meta("embedding_model").as_deref() != Some(model) {
+            self.clear()?;
+            self.set_meta("embedding_vendor", vendor)?;
+            self.set_meta("embedding_model", model)?;
+        }
+        Ok(())
+    }
+
+    fn stats(&self, name: &str, settings: &Settings) -> IndexStats {
+        IndexStats {
+            name: name.to_string(),
+            retrieval: self.retrieval(),
+            folder: self.meta("folder"),
+            documents: self.count("documents"),
+            chunks: self.count("chunks"),
+            last_updated: self.meta("updated_at").and_then(|t| t.parse().ok()),
+            embedding_vendor: self.meta("embedding_vendor"),
+            embedding_model: self.meta("embedding_model"),
+            watching: settings.rag.watch && self.meta("folder").is_some(),
+        }
+    }
+
+    fn meta(&self, key: &str) -> Option<String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    fn set_meta(&self, key: &str, value: &str) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)", params![key, value])
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn count(&self, table: &str) -> usize {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get::<_, i64>(0))
+            .unwrap_or_default() as usize
+    }
+
+    // Indexed files by path, with the modification time they were embedded at
+    fn documents(&self) -> Result<HashMap<String, i64>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT path, modified FROM documents").map_err(|e| e.to_string())?;
+        let documents = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .and_then(|rows| rows.collect::<Result<HashMap<_, _>, _>>())
+            .map_err(|e| e.to_string())?;
+        Ok(documents)
+    }
+
+    fn remove(&self, path: &str) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM documents WHERE path = ?1", [path])
+            .map_err(|e| e.to_string())?;
+        self.keywords.remove(path);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        self.conn.lock().unwrap().execute("DELETE FROM documents", []).map_err(|e| e.to_string())?;
+        self.keywords.clear()?;
+        self.keywords.commit()
+    }
+
+    // Replaces the file's chunks in one transaction, so a failed update leaves the old ones
+    fn store(&self, path: &Path, modified: i64, pieces: &[Piece], vectors: &[Vec<f32>]) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let path_text = path.to_string_lossy();
+        tx.execute("DELETE FROM documents WHERE path = ?1", [&path_text]).map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO documents (path, modified, indexed_at) VALUES (?1, ?2, ?3)",
+            params![path_text, modified, now_secs()],
+        )
+        .map_err(|e| e.to_string())?;
+        let mut keyword_chunks = Vec::with_capacity(pieces.len());
+        for (piece, vector) in pieces.iter().zip(vectors) {
+            tx.execute(
+                "INSERT INTO chunks (path, page, heading, text, embedding) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![path_text, piece.page, piece.heading, piece.text, to_blob(vector)],
+            )
+            .map_err(|e| e.to_string())?;
+            keyword_chunks.push((tx.last_insert_rowid(), piece.text.as_str()));
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        self.keywords.replace(&path_text, &keyword_chunks)
+    }
+
+    // The `k` chunks closest to the query as (chunk ID, cosine similarity), best first
+    fn nearest(&self, query: &[f32], k: usize) -> Result<Vec<(i64, f32)>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut scored: Vec<(i64, f32)> = conn
+            .prepare("SELECT id, embedding FROM chunks")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, dot(query, &row.get::<_, Vec<u8>>(1)?))))?
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|e| e.to_string())?;
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+        Ok(scored)
+    }
+
+    // The chunks behind retrieval hits, in the same order; markers are set once the final
+    // order is known. A hit whose chunk was removed meanwhile is skipped.
+    fn chunks(&self, hits: &[(i64, f32)]) -> Result<Vec<Citation>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT path, page, heading, text FROM chunks WHERE id = ?1")
+            .map_err(|e| e.to_string())?;
+        let mut citations = Vec::with_capacity(hits.len());
+        for (id, score) in hits {
+            let citation = stmt
+                .query_row([id], |row| {
+                    Ok(Citation {
+                        marker: 0,
+                        source: row.get(0)?,
+                        page: row.get(1)?,
+                        heading: row.get(2)?,
+                        text: row.get(3)?,
+                        score: *score,
+                        cited: false,
+                    })
+                })
+                .optional()
+                .map_err(|e| e.to_string())?;
+            citations.extend(citation);
+        }
+        Ok(citations)
+    }
+}
+
+// Brings the collection in line with the folder. Only new and changed files are embedded
+// unless `full` is set or the folder differs from the collection's, which rebuilds it.
+async fn update(
+    app_handle: &AppHandle,
+    settings: &Settings,
+    name: &str,
+    index: &RagIndex,
+    folder: &str,
+    full: bool,
+) -> Result<IndexReport, String> {
+    if !Path::new(folder).is_dir() {
+        return Err(format!("Folder not found: {}", folder));
+    }
+    let (vendor, model) = index.model(settings)?;
+    let api_key = settings.api_key(&vendor);
+    let _updating = index.updating.lock().await;
+
+    if full || index.meta("folder").as_deref() != Some(folder) {
+        index.clear()?;
+        index.set_meta("folder", folder)?;
+    }
+    index.set_model(&vendor, &model)?;
+
+    let mut files = Vec::new();
+    collect_files(Path::new(folder), &mut files);
+    files.sort();
+    let mut indexed = index.documents()?;
+    let mut report = IndexReport::default();
+    let changed: Vec<(PathBuf, i64)> = files
+        .into_iter()
+        .filter_map(|path| {
+            let modified = modified(&path);
+            let unchanged = indexed.remove(path.to_string_lossy().as_ref()) == Some(modified);
+            report.unchanged += unchanged as usize;
+            (!unchanged).then_some((path, modified))
+        })
+        .collect();
+    // Whatever is left was deleted
+    for path in indexed.keys() {
+        index.remove(path)?;
+        report.removed += 1;
+    }
+
+    for (done, (path, modified)) in changed.iter().enumerate() {
+        let _ = app_handle.emit(
+            "rag-index-progress",
+            IndexProgress {
+                collection: name.to_string(),
+                done,
+                total: changed.len(),
+                path: path.to_string_lossy().to_string(),
+            },
+        );
+        let pieces = match pieces(settings, path) {
+            Ok(pieces) => pieces,
+            Err(e) => {
+                report.failed.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+        let texts: Vec<String> = pieces.iter().map(|p| p.text.clone()).collect();
+        // A vendor error fails every file the same way, so it ends the update
+        let vectors = embeddings::embed(&vendor, &model, api_key.as_deref(), &texts).await?;
+        index.store(path, *modified, &pieces, &vectors)?;
+        report.embedded += 1;
+        report.chunks_embedded += pieces.len();
+    }
+    index.sync_keywords()?;
+    index.set_meta("updated_at", &now_secs().to_string())?;
+    Ok(report)
+}
+
+fn collection_name(collection: Option<String>) -> String {
+    collection.unwrap_or_else(|| DEFAULT_COLLECTION.to_string())
+}
+
+// Indexes a folder into the collection (the default one when not given), embedding only what
+// changed since the last time. Without a folder the collection's own is used, or for the
+// default collection the one in the RAG settings.
+#[tauri::command]
+pub async fn index_rag_folder(
+    app_handle: AppHandle,
+    state: State<'_, SettingsState>,
+    collections: State<'_, RagCollections>,
+    collection: Option<String>,
+    folder: Option<String>,
+) -> Result<IndexReport, String> {
+    let settings = state.get();
+    let name = collection_name(collection);
+    let index = collections.get(&name)?;
+    let folder = folder
+        .or_else(|| index.meta("folder"))
+        .or_else(|| settings.rag.folder.clone().filter(|_| name == DEFAULT_COLLECTION))
+        .filter(|f| !f.trim().is_empty())
+        .ok_or("Choose a folder of documents to index.")?;
+    update(&app_handle, &settings, &name, &index, &folder, false).await
+}
+
+// Embeds every file of the collection's folder again, e.g. after changing the chunking settings
+#[tauri::command]
+pub async fn reindex(
+    app_handle: AppHandle,
+    state: State<'_, SettingsState>,
+    collections: State<'_, RagCollections>,
+    collection: Option<String>,
+) -> Result<IndexReport, String> {
+    let settings = state.get();
+    let name = collection_name(collection);
+    let index = collections.get(&name)?;
+    let folder = index
+        .meta("folder")
+        .ok_or("Nothing has been indexed yet. Index a folder of documents first.")?;
+    update(&app_handle, &settings, &name, &index, &folder, true).await
+}
+
+#[tauri::command]
+pub async fn get_index_stats(
+    state: State<'_, SettingsState>,
+    collections: State<'_, RagCollections>,
+    collection: Option<String>,
+) -> Result<IndexStats, String> {
+    let name = collection_name(collection);
+    Ok(collections.get(&name)?.stats(&name, &state.get()))
+}
+
+#[tauri::command]
+pub async fn list_rag_collections(
+    state: State<'_, SettingsState>,
+    collections: State<'_, RagCollections>,
+) -> Result<Vec<IndexStats>, String> {
+    let settings = state.get();
+    collections
+        .names()
+        .into_iter()
+        .map(|name| Ok(collections.get(&name)?.stats(&name, &settings)))
+        .collect()
+}
+
+// An empty collection; the embedding model defaults to the one in the RAG settings
+#[tauri::command]
+pub async fn create_rag_collection(
+    state: State<'_, SettingsState>,
+    collections: State<'_, RagCollections>,
+    name: String,
+    embedding_vendor: Option<String>,
+    embedding_model: Option<String>,
+) -> Result<IndexStats, String> {
+    let settings = state.get();
+    check_collection_name(&name)?;
+    if name == DEFAULT_COLLECTION || collections.exists(&name) {
+        return Err(format!("A RAG collection named '{}' already exists.", name));
+    }
+    let (vendor, model) = self::embedding_model(&settings, embedding_vendor, embedding_model)?;
+    let index = Arc::new(RagIndex::open(&collections.dir.join(&name).join("index.db"))?);
+    index.set_model(&vendor, &model)?;
+    collections.open.lock().unwrap().insert(name.clone(), index.clone());
+    Ok(index.stats(&name, &settings))
+}
+
+// Switching a collection to another embedding model empties it; index it again afterwards
+#[tauri::command]
+pub async fn configure_rag_collection(
+    state: State<'_, SettingsState>,
+    collections: State<'_, RagCollections>,
+    name: String,
+    embedding_vendor: String,
+    embedding_model: Option<String>,
+) -> Result<IndexStats, String> {
+    let settings = state.get();
+    let index = collections.get(&name)?;
+    let (vendor, model) = self::embedding_model(&settings, Some(embedding_vendor), embedding_model)?;
+    let _updating = index.updating.lock().await;
+    index.set_model(&vendor, &model)?;
+    Ok(index.stats(&name, &settings))
+}
+
+fn check_retrieval(retrieval: &RetrievalSettings) -> Result<(), String> {
+    if let Some(mode) = retrieval.mode.as_deref().filter(|m| !["hybrid", "vector", "keyword"].contains(m)) {
+        return Err(format!("Unknown retrieval mode '{}'. Use hybrid, vector or keyword.", mode));
+    }
+    let vendor = retrieval.rerank_vendor.as_deref();
+    match retrieval.rerank.as_deref() {
+        None | Some("llm") => Ok(()),
+        Some("cross-encoder") if vendor.is_none_or(|v| embeddings::default_rerank_model(v).is_some()) => Ok(()),
+        Some("cross-encoder") => Err("Cross-encoder reranking is available from jina and cohere.".to_string()),
+        Some(other) => Err(format!("Unknown reranker '{}'. Use llm or cross-encoder.", other)),
+    }
+}
+
+// How the collection retrieves the sources of a run; applies from the next run, without
+// indexing again
+#[tauri::command]
+pub async fn configure_rag_retrieval(
+    state: State<'_, SettingsState>,
+    collections: State<'_, RagCollections>,
+    name: String,
+    retrieval: RetrievalSettings,
+) -> Result<IndexStats, String> {
+    check_retrieval(&retrieval)?;
+    let index = collections.get(&name)?;
+    index.set_meta("retrieval", &serde_json::to_string(&retrieval).map_err(|e| e.to_string())?)?;
+    Ok(index.stats(&name, &state.get()))
+}
+
+#[tauri::command]
+pub async fn delete_rag_collection(collections: State<'_, RagCollections>, name: String) -> Result<(), String> {
+    if name == DEFAULT_COLLECTION {
+        return Err("The default collection cannot be deleted.".to_string());
+    }
+    let index = collections.get(&name)?;
+    // Waits for a running update, and closes the database before its folder goes
+    let updating = index.updating.lock().await;
+    collections.open.lock().unwrap().remove(&name);
+    drop(updating);
+    drop(index);
+    fs::remove_dir_all(collections.dir.join(&name)).map_err(|e| e.to_string())
+}
+
+// Checks the collections' folders for changes while `watch` is on
+pub fn spawn_watcher(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+            let settings = app_handle.state::<SettingsState>().get();
+            if !settings.rag.watch {
+                continue;
+            }
+            let collections = app_handle.state::<RagCollections>();
+            for name in collections.names() {
+                let Ok(index) = collections.get(&name) else {
+                    continue;
+                };
+                let Some(folder) = index.meta("folder") else {
+                    continue;
+                };
+                if index.updating.try_lock().is_err() {
+                    continue;
+                }
+                match update(&app_handle, &settings, &name, &index, &folder, false).await {
+                    Ok(report) if report.embedded + report.removed > 0 => {
+                        let _ = app_handle.emit("rag-index-updated", json!({"collection": name, "report": report}));
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Could not update the RAG collection '{}': {}", name, e),
+                }
+            }
+        }
+    });
+}
+
+// Merges ranked lists by reciprocal rank: chunks found by both retrievers rise to the top
+// without comparing BM25 scores to cosine similarities
+fn fuse(lists: &[&[(i64, f32)]]) -> Vec<(i64, f32)> {
+    let mut scores: HashMap<i64, f32> = HashMap::new();
+    for list in lists {
+        for (rank, (id, _)) in list.iter().enumerate() {
+            *scores.entry(*id).or_default() += 1.0 / (RRF_K + rank as f32 + 1.0);
+        }
+    }
+    let mut fused: Vec<(i64, f32)> = scores.into_iter().collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    fused
+}
+
+// The passage numbers (1-based) an LLM reranker listed, as indexes, in its order. Unknown and
+// repeated numbers are dropped.
+fn llm_order(response: &str, passages: usize) -> Vec<usize> {
+    let (Some(start), Some(end)) = (response.find('['), response.rfind(']')) else {
+        return Vec::new();
+    };
+    let numbers: Vec<serde_json::Value> = serde_json::from_str(response.get(start..=end).unwrap_or_default()).unwrap_or_default();
+    let mut order = Vec::new();
+    for n in numbers.iter().filter_map(serde_json::Value::as_u64).map(|n| n as usize) {
+        if (1..=passages).contains(&n) && !order.contains(&(n - 1)) {
+            order.push(n - 1);
+        }
+    }
+    order
+}
+
+async fn llm_rerank(settings: &Settings, retrieval: &RetrievalSettings, query: &str, candidates: &[Citation]) -> Result<Vec<usize>, String> {
+    let vendor = retrieval
+        .rerank_vendor
+        .clone()
+        .or_else(|| settings.default_vendor.clone())
+        .unwrap_or_else(|| "google".to_string());
+    let model = retrieval
+        .rerank_model
+        .clone()
+        .or_else(|| suggest::suggestion_model(&vendor).map(str::to_string))
+        .ok_or_else(|| format!("Name the {} model to rerank with.", vendor))?;
+    let passages: Vec<String> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("[{}] {}", i + 1, c.text.chars().take(RERANK_PASSAGE_CHARS).collect::<String>()))
+        .collect();
+    let request = AIRequest {
+        api_key: settings.api_key(&vendor).unwrap_or_default(),
+        vendor,
+        model,
+        system_prompt: "You rank passages by how useful they are for working on the user's text. Respond with only a \
+                        JSON array of the passage numbers, most useful first, leaving out passages that don't help."
+            .to_string(),
+        user_input: format!("Text:\n{}\n\nPassages:\n\n{}", query, passages.join("\n\n")),
+        temperature: 0.0,
+        top_p: 1.0,
+        ..Default::default()
+    };
+    Ok(llm_order(&ai_client::complete(&request).await?, candidates.len()))
+}
+
+// Reorders the candidates best first; cross-encoder scores replace the retrieval scores
+async fn rerank(settings: &Settings, retrieval: &RetrievalSettings, query: &str, candidates: Vec<Citation>) -> Result<Vec<Citation>, String> {
+    let order: Vec<(usize, Option<f32>)> = match retrieval.rerank.as_deref() {
+        Some("cross-encoder") => {
+            let vendor = retrieval.rerank_vendor.as_deref().unwrap_or("jina");
+            let model = retrieval
+                .rerank_model
+                .as_deref()
+                .or_else(|| embeddings::default_rerank_model(vendor))
+                .unwrap_or_default();
+            let texts: Vec<String> = candidates.iter().map(|c| c.text.clone()).collect();
+            embeddings::rerank(vendor, model, settings.api_key(vendor).as_deref(), query, &texts)
+                .await?
+                .into_iter()
+                .map(|(i, score)| (i, Some(score)))
+                .collect()
+        }
+        Some(_) => llm_rerank(settings, retrieval, query, &candidates).await?.into_iter().map(|i| (i, None)).collect(),
+        None => return Ok(candidates),
+    };
+    if order.is_empty() {
+        return Err("The reranker kept none of the candidates.".to_string());
+    }
+    let mut slots: Vec<Option<Citation>> = candidates.into_iter().map(Some).collect();
+    Ok(order
+        .into_iter()
+        .filter_map(|(i, score)| {
+            let mut citation = slots.get_mut(i)?.take()?;
+            citation.score = score.unwrap_or(citation.score);
+            Some(citation)
+        })
+        .collect())
+}
+
+// What each retrieval stage returned, as (chunk ID, score) best first, so the UI can show why
+// a run got the sources it did
+#[derive(Serialize, Clone, Default)]
+pub struct RetrievalTrace {
+    pub run_id: Option<String>,
+    pub collection: String,
+    pub retrieval: RetrievalSettings,
+    pub top_k: usize,
+    pub vector: Vec<(i64, f32)>,
+    pub keyword: Vec<(i64, f32)>,
+    pub fused: Vec<(i64, f32)>,
+    // Why the reranker's order wasn't used, when it failed
+    pub rerank_error: Option<String>,
+    // The sources kept, by file and final score
+    pub kept: Vec<(String, f32)>,
+}
+
+// The collection's best chunks for the query, best first, retrieved the way it's configured
+async fn retrieve(settings: &Settings, index: &RagIndex, query: &str, trace: &mut RetrievalTrace) -> Result<Vec<Citation>, String> {
+    let retrieval = index.retrieval();
+    let mode = retrieval.mode.as_deref().unwrap_or("hybrid");
+    let top_k = settings.rag.top_k.unwrap_or(DEFAULT_TOP_K).max(1);
+    let pool = CANDIDATES.max(top_k * 4);
+    trace.retrieval = retrieval.clone();
+    trace.top_k = top_k;
+
+    if mode != "keyword" {
+        let (vendor, model) = index.model(settings)?;
+        let vector = embeddings::embed(&vendor, &model, settings.api_key(&vendor).as_deref(), &[query.to_string()])
+            .await?
+            .remove(0);
+        trace.vector = index.nearest(&vector, pool)?;
+    }
+    if mode != "vector" {
+        trace.keyword = index.keywords.search(query, pool)?;
+    }
+    let mut ranked = match mode {
+        "vector" => trace.vector.clone(),
+        "keyword" => trace.keyword.clone(),
+        _ => {
+            trace.fused = fuse(&[&trace.vector, &trace.keyword]);
+            trace.fused.clone()
+        }
+    };
+
+    if retrieval.rerank.is_none() {
+        ranked.truncate(top_k);
+        return index.chunks(&ranked);
+    }
+    ranked.truncate(pool);
+    let candidates = index.chunks(&ranked)?;
+    let fallback = candidates.clone();
+    let mut citations = match rerank(settings, &retrieval, query, candidates).await {
+        Ok(reranked) => reranked,
+        // Reranking is a refinement; without it the merged order still stands
+        Err(e) => {
+            trace.rerank_error = Some(e);
+            fallback
+        }
+    };
+    citations.truncate(top_k);
+    Ok(citations)
+}
+
+// Puts the collection's best chunks for the input after it as numbered sources and asks the
+// model to cite them; returns the sources in marker order
+pub async fn augment(app_handle: &AppHandle, settings: &Settings, request: &mut AIRequest) -> Result<Vec<Citation>, String> {
+    let name = collection_name(request.rag_collection.clone());
+    let index = app_handle.state::<RagCollections>().get(&name)?;
+    if index.meta("folder").is_none() {
+        return Err(format!("The RAG collection '{}' is empty. Index a folder of documents into it first.", name));
+    }
+    let query: String = request.user_input.chars().take(QUERY_CHARS).collect();
+    let mut trace = RetrievalTrace { run_id: request.run_id.clone(), collection: name.clone(), ..Default::default() };
+    let retrieved = retrieve(settings, &index, &query, &mut trace).await;
+    if let Ok(citations) = &retrieved {
+        trace.kept = citations.iter().map(|c| (c.source.clone(), c.score)).collect();
+    }
+    let _ = app_handle.emit("rag-retrieval", &trace);
+    let mut citations = retrieved?;
+    if citations.is_empty() {
+        return Ok(citations);
+    }
+    for (i, citation) in citations.iter_mut().enumerate() {
+        citation.marker = i + 1;
+    }
+
+    let sources: Vec<String> = citations
+        .iter()
+        .map(|c| {
+            let page = c.page.map(|p| format!(", page {}", p)).unwrap_or_default();
+            format!("[{}] {}{}\n{}", c.marker, c.source, page, c.text)
+        })
+        .collect();
+    request.user_input = format!("{}\n\n---\nSources:\n\n{}", request.user_input, sources.join("\n\n"));
+    request.system_prompt.push_str(
+        "\n\nThe input is followed by numbered sources. Where your answer uses one, cite it with its number in square brackets, e.g. [2].",
+    );
+    Ok(citations)
+}
+
+// The source numbers cited in a text, from markers like [2], [1, 3] or [1][4]
+pub fn cited_markers(text: &str) -> BTreeSet<usize> {
+    let marker = Regex::new(r"\[(\d+(?:\s*,\s*\d+)*)\]").unwrap();
+    marker
+        .captures_iter(text)
+        .flat_map(|c| c[1].split(',').filter_map(|n| n.trim().parse().ok()).collect::<Vec<_>>())
+        .collect()
+}
+
+// Kept with the run in history, so get_run_citations works after a restart
+pub fn record_citations(history: &HistoryState, run_id: &str, citations: &[Citation], output: &str) -> Result<(), String> {
+    let cited = cited_markers(output);
+    let mut conn = history.conn();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for citation in citations {
+        tx.execute(
+            "INSERT OR REPLACE INTO run_citations (run_id, marker, source, page, heading, text, score, cited)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                run_id,
+                citation.marker as i64,
+                citation.source,
+                citation.page,
+                citation.heading,
+                citation.text,
+                citation.score,
+                cited.contains(&citation.marker)
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+// The sources a RAG run was given, in marker order, with whether the answer cited each
+#[tauri::command]
+pub async fn get_run_citations(history: State<'_, HistoryState>, run_id: String) -> Result<Vec<Citation>, String> {
+    let conn = history.conn();
+    let mut stmt = conn
+        .prepare(
+            "SELECT marker, source, page, heading, text, score, cited FROM run_citations
+             WHERE run_id = ?1 ORDER BY marker",
+        )
+        .map_err(|e| e.to_string())?;
+    let citations = stmt
+        .query_map([run_id], |row| {
+            Ok(Citation {
+                marker: row.get::<_, i64>(0)? as usize,
+                source: row.get(1)?,
+                page: row.get(2)?,
+                heading: row.get(3)?,
+                text: row.get(4)?,
+                score: row.get(5)?,
+                cited: row.get(6)?,
+            })
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| e.to_string())?;
+    Ok(citations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cited_markers_reads_single_grouped_and_adjacent_markers() {
+        let text = "Revenue grew [2]. Costs fell [1, 3][4] while [x] and [] are not markers, nor is [5";
+        assert_eq!(cited_markers(text), BTreeSet::from([1, 2, 3, 4]));
+        assert!(cited_markers("No sources here.").is_empty());
+    }
+
+    #[test]
+    fn dot_reads_vectors_back_from_blobs() {
+        let vector = [0.6, 0.8, 0.0];
+        assert!((dot(&vector, &to_blob(&vector)) - 1.0).abs() < 1e-6);
+        assert_eq!(dot(&[1.0, 0.0], &to_blob(&[0.0, 1.0])), 0.0);
+    }
+
+    #[test]
+    fn fuse_ranks_chunks_found_by_both_retrievers_first() {
+        let vector = [(1, 0.9), (2, 0.8), (3, 0.7)];
+        let keyword = [(3, 12.0), (4, 9.0)];
+        let ids: Vec<i64> = fuse(&[&vector, &keyword]).into_iter().map(|(id, _)| id).collect();
+        // 2 and 4 tie on rank and are ordered by ID
+        assert_eq!(ids, vec![3, 1, 2, 4]);
+        assert!(fuse(&[&[], &[]]).is_empty());
+    }
+
+    #[test]
+    fn llm_order_keeps_known_passages_once() {
+        assert_eq!(llm_order("Ranking: [3, 1, 3, 9, 0, 2]", 3), vec![2, 0, 1]);
+        assert!(llm_order("None of them help.", 3).is_empty());
+        assert!(llm_order("[\"a\"]", 3).is_empty());
+    }
+
+    #[test]
+    fn collection_names_must_be_plain_folder_names() {
+        for name in ["default", "work docs", "research_papers-2024"] {
+            assert!(check_collection_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", " padded", "../escape", "a/b", "dots.", &"x".repeat(41)] {
+            assert!(check_collection_name(name).is_err(), "{}", name);
+        }
+    }
+}
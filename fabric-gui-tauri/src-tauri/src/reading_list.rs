@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::history::now_secs;
+use crate::notify::{self, Publication};
+use crate::scrape;
+use crate::settings::SettingsState;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReadingItem {
+    pub url: String,
+    pub added_at: i64,
+    pub status: ItemStatus,
+    pub error: Option<String>,
+    pub run_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ReadingListFile {
+    items: Vec<ReadingItem>,
+    // The pattern run used last time, so the tray can process the list without the window
+    last_request: Option<AIRequest>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ReadingDigest {
+    pub path: String,
+    pub processed: usize,
+    pub failed: usize,
+}
+
+// Links collected during the day and processed in one go into a markdown digest
+pub struct ReadingList {
+    path: PathBuf,
+    inner: Mutex<ReadingListFile>,
+    processing: AtomicBool,
+}
+
+impl ReadingList {
+    pub fn load(path: PathBuf) -> Self {
+        let file = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            inner: Mutex::new(file),
+            processing: AtomicBool::new(false),
+        }
+    }
+
+    fn save(&self, file: &ReadingListFile) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(file).map_err(|e| e.to_string())?;
+        fs::write(&self.path, json).map_err(|e| e.to_string())
+    }
+
+    fn update<F: FnOnce(&mut ReadingListFile)>(&self, f: F) -> Result<Vec<ReadingItem>, String> {
+        let mut file = self.inner.lock().unwrap();
+        f(&mut file);
+        self.save(&file)?;
+        Ok(file.items.clone())
+    }
+
+    pub fn last_request(&self) -> Option<AIRequest> {
+        self.inner.lock().unwrap().last_request.clone()
+    }
+}
+
+fn notify(app_handle: &AppHandle, items: &[ReadingItem]) {
+    let _ = app_handle.emit("reading-list-changed", items);
+}
+
+fn digest_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("reading_digests").join(format!("digest-{}.md", now_secs())))
+        .map_err(|e| e.to_string())
+}
+
+// Scrapes each pending link, runs the pattern on it and writes every result into one digest.
+// Each run is recorded in history like any other; progress goes out as reading-list-progress.
+pub async fn process(app_handle: &AppHandle, mut template: AIRequest) -> Result<ReadingDigest, String> {
+    let list = app_handle.state::<ReadingList>();
+    if list.processing.swap(true, Ordering::SeqCst) {
+        return Err("The reading list is already being processed.".to_string());
+    }
+    let result = process_pending(app_handle, &list, &mut template).await;
+    list.processing.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn process_pending(app_handle: &AppHandle, list: &ReadingList, template: &mut AIRequest) -> Result<ReadingDigest, String> {
+    template.api_key.clear();
+    template.run_id = None;
+    template.dry_run = false;
+    template.acknowledged_stream = false;
+    list.update(|file| file.last_request = Some(template.clone()))?;
+
+    let pending: Vec<String> = list
+        .inner
+        .lock()
+        .unwrap()
+        .items
+        .iter()
+        .filter(|i| i.status != ItemStatus::Done)
+        .map(|i| i.url.clone())
+        .collect();
+    if pending.is_empty() {
+        return Err("The reading list has no unprocessed links.".to_string());
+    }
+
+    let jina_key = app_handle.state::<SettingsState>().get().api_key("jina");
+    let mut digest = format!("# Reading digest\n\n{} links\n", pending.len());
+    let (mut processed, mut failed) = (0, 0);
+
+    for (index, url) in pending.iter().enumerate() {
+        let _ = app_handle.emit(
+            "reading-list-progress",
+            json!({"url": url, "index": index, "total": pending.len()}),
+        );
+
+        let run_id = Uuid::new_v4().to_string();
+        let result = match scrape::scrape(url, jina_key.clone()).await {
+            Ok(page) => {
+                let mut request = template.clone();
+                request.user_input = page;
+                request.run_id = Some(run_id.clone());
+                ai_client::run_recorded(app_handle, None, request).await
+            }
+            Err(e) => Err(e),
+        };
+
+        match &result {
+            Ok(output) => {
+                processed += 1;
+                digest.push_str(&format!("\n---\n\n## {}\n\n{}\n", url, output.trim()));
+            }
+            Err(e) => {
+                failed += 1;
+                digest.push_str(&format!("\n---\n\n## {}\n\n_Failed: {}_\n", url, e));
+            }
+        }
+        let items = list.update(|file| {
+            if let Some(item) = file.items.iter_mut().find(|i| i.url == *url) {
+                item.status = if result.is_ok() { ItemStatus::Done } else { ItemStatus::Failed };
+                item.error = result.as_ref().err().cloned();
+                item.run_id = Some(run_id.clone());
+            }
+        })?;
+        notify(app_handle, &items);
+    }
+
+    let path = digest_path(app_handle)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, &digest).map_err(|e| e.to_string())?;
+    if app_handle.state::<SettingsState>().get().notify.publish_reading_digest {
+        let publication = Publication {
+            title: "Reading digest".to_string(),
+            pattern: template.pattern.clone(),
+            run_id: None,
+            output: digest,
+            created_at: now_secs(),
+            truncated: false,
+        };
+        notify::publish_unattended(app_handle, publication).await;
+    }
+
+    let summary = ReadingDigest {
+        path: path.to_string_lossy().to_string(),
+        processed,
+        failed,
+    };
+    let _ = app_handle.emit("reading-list-complete", summary.clone());
+    Ok(summary)
+}
+
+#[tauri::command]
+pub async fn add_to_reading_list(
+    app_handle: AppHandle,
+    list: State<'_, ReadingList>,
+    url: String,
+) -> Result<Vec<ReadingItem>, String> {
+    let url = url.trim().to_string();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!("'{}' is not a web link.", url));
+    }
+    let items = list.update(|file| {
+        if !file.items.iter().any(|i| i.url == url) {
+            file.items.push(ReadingItem {
+                url,
+                added_at: now_secs(),
+                status: ItemStatus::Pending,
+                error: None,
+                run_id: None,
+            });
+        }
+    })?;
+    notify(&app_handle, &items);
+    Ok(items)
+}
+
+#[tauri::command]
+pub async fn remove_from_reading_list(
+    app_handle: AppHandle,
+    list: State<'_, ReadingList>,
+    url: String,
+) -> Result<Vec<ReadingItem>, String> {
+    let items = list.update(|file| file.items.retain(|i| i.url != url))?;
+    notify(&app_handle, &items);
+    Ok(items)
+}
+
+#[tauri::command]
+pub async fn clear_processed_links(app_handle: AppHandle, list: State<'_, ReadingList>) -> Result<Vec<ReadingItem>, String> {
+    let items = list.update(|file| file.items.retain(|i| i.status != ItemStatus::Done))?;
+    notify(&app_handle, &items);
+    Ok(items)
+}
+
+#[tauri::command]
+pub async fn get_reading_list(list: State<'_, ReadingList>) -> Result<Vec<ReadingItem>, String> {
+    Ok(list.inner.lock().unwrap().items.clone())
+}
+
+// `request` is the pattern run applied to each page; its user_input is replaced by the page text
+#[tauri::command]
+pub async fn process_reading_list(app_handle: AppHandle, request: AIRequest) -> Result<ReadingDigest, String> {
+    process(&app_handle, request).await
+}
@@ -0,0 +1,68 @@
+use serde_json::json;
+use tauri::{Manager, State, Window};
+use uuid::Uuid;
+use crate::ai_client::{self, AIRequest};
+use crate::history::{HistoryEntry, HistoryState};
+
+// The columns plus request_json's other fields; runs recorded before request_json existed
+// are rebuilt from their columns alone
+pub fn request_from_entry(entry: &HistoryEntry) -> Result<AIRequest, String> {
+    let mut request = match &entry.request_json {
+        Some(stored) => serde_json::from_str(stored).map_err(|e| e.to_string())?,
+        None => json!({}),
+    };
+    let fields = request.as_object_mut().ok_or("The stored request is not a JSON object.")?;
+    fields.extend([
+        ("vendor".to_string(), json!(entry.vendor)),
+        ("model".to_string(), json!(entry.model)),
+        ("api_key".to_string(), json!("")),
+        ("system_prompt".to_string(), json!(entry.system_prompt)),
+        ("user_input".to_string(), json!(entry.input)),
+        ("temperature".to_string(), json!(entry.temperature)),
+        ("top_p".to_string(), json!(entry.top_p)),
+        ("thinking_level".to_string(), json!(entry.thinking_level)),
+        ("pattern".to_string(), json!(entry.pattern)),
+        ("parent_run_id".to_string(), json!(entry.parent_run_id)),
+        ("relation".to_string(), json!(entry.relation)),
+    ]);
+    serde_json::from_value(request).map_err(|e| e.to_string())
+}
+
+// Re-sends a recorded run unchanged, or against another vendor/model, as a new history entry
+// pointing back at the original. Pass `new_run_id` to filter events before the command returns.
+#[tauri::command]
+pub async fn replay_run(
+    window: Window,
+    history: State<'_, HistoryState>,
+    run_id: String,
+    vendor: Option<String>,
+    model: Option<String>,
+    new_run_id: Option<String>,
+) -> Result<String, String> {
+    let entry = history
+        .get(&run_id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))?;
+    let mut request = request_from_entry(&entry)?;
+
+    if let Some(vendor) = vendor.filter(|v| *v != request.vendor) {
+        if model.is_none() {
+            return Err("Choose a model when replaying against another vendor.".to_string());
+        }
+        request.vendor = vendor;
+        // Gemini context caches only exist on the vendor that created them
+        request.cached_content = None;
+    }
+    if let Some(model) = model {
+        request.model = model;
+    }
+
+    let replay_id = new_run_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    request.api_key.clear();
+    request.run_id = Some(replay_id.clone());
+    request.parent_run_id = Some(run_id);
+    request.relation = Some("replay".to_string());
+    request.dry_run = false;
+
+    ai_client::run_recorded(window.app_handle(), Some(window.label().to_string()), request).await?;
+    Ok(replay_id)
+}
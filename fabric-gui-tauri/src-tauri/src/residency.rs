@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{OnceLock, RwLock};
+use reqwest::Url;
+use crate::http;
+use crate::huggingface;
+use crate::vertex;
+
+const OPENAI_US_BASE_URL: &str = "https://api.openai.com";
+// Requires a project created with EU data residency
+const OPENAI_EU_BASE_URL: &str = "https://eu.api.openai.com";
+const BEDROCK_ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+
+// Where each vendor's requests are processed, and which runs must stay in the EU. Settings
+// are per profile, so a profile for GDPR-sensitive work can be EU-only as a whole.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ResidencySettings {
+    // "eu" sends OpenAI requests to eu.api.openai.com
+    pub openai_region: Option<String>,
+    // Sends Anthropic requests through Amazon Bedrock in this AWS region, e.g. "eu-central-1",
+    // with the Bedrock API key in api_keys "bedrock". The model must then be a Bedrock model
+    // or inference profile ID such as "eu.anthropic.claude-sonnet-4-20250514-v1:0".
+    pub anthropic_bedrock_region: Option<String>,
+    // Refuse every run whose route isn't pinned to the EU
+    pub eu_only: bool,
+    // Patterns that may only run on EU routes
+    pub eu_only_patterns: Vec<String>,
+}
+
+#[derive(Default)]
+struct ResidencyConfig {
+    settings: ResidencySettings,
+    bedrock_api_key: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct VendorRoute {
+    pub vendor: String,
+    // Where the requests go
+    pub host: String,
+    // Pinned to an EU region, or a server on this machine
+    pub eu: bool,
+}
+
+fn config() -> &'static RwLock<ResidencyConfig> {
+    static CONFIG: OnceLock<RwLock<ResidencyConfig>> = OnceLock::new();
+    CONFIG.get_or_init(RwLock::default)
+}
+
+pub fn configure(residency: &ResidencySettings, bedrock_api_key: Option<String>) {
+    *config().write().unwrap() = ResidencyConfig {
+        settings: residency.clone(),
+        bedrock_api_key: bedrock_api_key.filter(|k| !k.trim().is_empty()),
+    };
+}
+
+fn setting(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(|v| v.trim().to_lowercase()).filter(|v| !v.is_empty())
+}
+
+pub fn openai_base_url() -> &'static str {
+    match setting(&config().read().unwrap().settings.openai_region).as_deref() {
+        Some("eu") => OPENAI_EU_BASE_URL,
+        _ => OPENAI_US_BASE_URL,
+    }
+}
+
+pub fn bedrock_region() -> Option<String> {
+    setting(&config().read().unwrap().settings.anthropic_bedrock_region)
+}
+
+pub fn bedrock_base_url() -> Option<String> {
+    bedrock_region().map(|region| format!("https://bedrock-runtime.{}.amazonaws.com", region))
+}
+
+// None when Anthropic requests go to Anthropic itself
+pub fn bedrock_invoke_url(model: &str) -> Result<Option<String>, String> {
+    let Some(base) = bedrock_base_url() else {
+        return Ok(None);
+    };
+    if !model.contains("anthropic.") {
+        return Err(format!(
+            "Anthropic runs go through Amazon Bedrock, which doesn't know '{}'. Use a Bedrock model ID such as eu.anthropic.claude-sonnet-4-20250514-v1:0.",
+            model
+        ));
+    }
+    let mut url = Url::parse(&base).map_err(|_| "The Bedrock region is not valid.".to_string())?;
+    url.path_segments_mut()
+        .map_err(|_| "The Bedrock region is not valid.".to_string())?
+        .extend(["model", model, "invoke"]);
+    Ok(Some(url.to_string()))
+}
+
+pub fn bedrock_api_key() -> Result<String, String> {
+    config()
+        .read()
+        .unwrap()
+        .bedrock_api_key
+        .clone()
+        .ok_or_else(|| "Add your Amazon Bedrock API key in settings to run Anthropic models through Bedrock.".to_string())
+}
+
+// A Messages API body as Bedrock takes it: the model is in the URL and the version in the body
+pub fn bedrock_payload(mut payload: serde_json::Value) -> serde_json::Value {
+    if let Some(body) = payload.as_object_mut() {
+        body.remove("model");
+        body.remove("stream");
+        body.insert("anthropic_version".to_string(), BEDROCK_ANTHROPIC_VERSION.into());
+    }
+    payload
+}
+
+fn host(url: &str) -> String {
+    Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)).unwrap_or_default()
+}
+
+// Vendors that can be pinned; the others always run outside the EU as far as we can tell
+pub fn route(vendor: &str) -> VendorRoute {
+    let (host, eu) = match vendor {
+        "openai" => (host(openai_base_url()), openai_base_url() == OPENAI_EU_BASE_URL),
+        "anthropic" => match bedrock_region() {
+            Some(region) => (host(&bedrock_base_url().unwrap_or_default()), region.starts_with("eu-")),
+            None => ("api.anthropic.com".to_string(), false),
+        },
+        "vertex" => {
+            let host = host(&vertex::base_url());
+            let eu = host.starts_with("europe-");
+            (host, eu)
+        }
+        "lmstudio" => ("localhost".to_string(), true),
+        // A dedicated endpoint may be in the EU, but nothing here says so
+        "huggingface" => (host(&huggingface::base_url()), false),
+        vendor => (host(http::vendor_base_url(vendor)), false),
+    };
+    VendorRoute { vendor: vendor.to_string(), host, eu }
+}
+
+// Refuses a request bound for outside the EU when its profile or pattern is EU-only
+pub fn check(vendor: &str, pattern: Option<&str>) -> Result<(), String> {
+    let reason = {
+        let config = config().read().unwrap();
+        let residency = &config.settings;
+        if residency.eu_only {
+            "This profile is EU-only".to_string()
+        } else if let Some(pattern) = pattern.filter(|p| residency.eu_only_patterns.iter().any(|e| e == p)) {
+            format!("The pattern '{}' is EU-only", pattern)
+        } else {
+            return Ok(());
+        }
+    };
+    let route = route(vendor);
+    if route.eu {
+        return Ok(());
+    }
+    Err(format!(
+        "{}, but {} requests go to {}, which is not pinned to the EU. Choose an EU region for {} in the data residency settings, or another vendor.",
+        reason,
+        vendor,
+        if route.host.is_empty() { "an unknown endpoint" } else { &route.host },
+        vendor
+    ))
+}
+
+// The current route of every vendor, for the settings page
+#[tauri::command]
+pub async fn residency_routes() -> Result<Vec<VendorRoute>, String> {
+    Ok(["openai", "anthropic", "google", "vertex", "xai", "deepseek", "huggingface", "lmstudio"]
+        .into_iter()
+        .map(route)
+        .collect())
+}
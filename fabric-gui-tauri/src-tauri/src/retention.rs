@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use rusqlite::{params, Connection};
+use tauri::{AppHandle, Manager, State};
+use crate::history::{compact_large_outputs, now_secs, HistoryState};
+use crate::settings::SettingsState;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+const DELETE_BATCH: i64 = 100;
+
+// Runs that were filed into a collection are considered curated and are never pruned
+const PRUNABLE: &str = "id NOT IN (SELECT run_id FROM collection_runs)";
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    pub max_entries: Option<u64>,
+    pub max_age_days: Option<u64>,
+    pub max_db_mb: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct VacuumReport {
+    pub deleted: u64,
+    // Large outputs from older versions that were compressed
+    pub compacted: u64,
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+// Live data size, excluding pages freed by deletes that only VACUUM gives back. The audit log
+// is left out too: it can't be pruned, so counting it would let the size limit delete every run
+// once the log alone outgrows it.
+fn used_bytes(conn: &Connection) -> Result<u64, String> {
+    conn.query_row(
+        "SELECT (page_count - freelist_count) * page_size
+                - (SELECT COALESCE(SUM(pgsize), 0) FROM dbstat WHERE name IN ('audit_log', 'audit_log_at'))
+         FROM pragma_page_count(), pragma_freelist_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn file_bytes(conn: &Connection) -> Result<u64, String> {
+    conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+pub fn apply_policy(conn: &Connection, policy: &RetentionPolicy) -> Result<u64, String> {
+    let mut deleted = 0;
+
+    if let Some(days) = policy.max_age_days {
+        let cutoff = now_secs() - (days as i64) * 86_400;
+        deleted += conn
+            .execute(
+                &format!("DELETE FROM runs WHERE created_at < ?1 AND {}", PRUNABLE),
+                params![cutoff],
+            )
+            .map_err(|e| e.to_string())? as u64;
+    }
+
+    if let Some(max_entries) = policy.max_entries {
+        deleted += conn
+            .execute(
+                &format!(
+                    "DELETE FROM runs WHERE {} AND id NOT IN
+                     (SELECT id FROM runs ORDER BY created_at DESC LIMIT ?1)",
+                    PRUNABLE
+                ),
+                params![max_entries as i64],
+            )
+            .map_err(|e| e.to_string())? as u64;
+    }
+
+    if let Some(max_mb) = policy.max_db_mb {
+        let limit = max_mb * 1024 * 1024;
+        while used_bytes(conn)? > limit {
+            let removed = conn
+                .execute(
+                    &format!(
+                        "DELETE FROM runs WHERE id IN
+                         (SELECT id FROM runs WHERE {} ORDER BY created_at ASC LIMIT ?1)",
+                        PRUNABLE
+                    ),
+                    params![DELETE_BATCH],
+                )
+                .map_err(|e| e.to_string())? as u64;
+            if removed == 0 {
+                break;
+            }
+            deleted += removed;
+        }
+    }
+
+    Ok(deleted)
+}
+
+pub fn spawn_cleanup_task(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let policy = app_handle.state::<SettingsState>().get().history_retention;
+            let history = app_handle.state::<HistoryState>();
+            let _ = apply_policy(&history.conn(), &policy);
+            tokio::time::sleep(CLEANUP_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn vacuum_history(
+    history: State<'_, HistoryState>,
+    settings: State<'_, SettingsState>,
+) -> Result<VacuumReport, String> {
+    let policy = settings.get().history_retention;
+    let conn = history.conn();
+
+    let size_before = file_bytes(&conn)?;
+    let deleted = apply_policy(&conn, &policy)?;
+    let compacted = compact_large_outputs(&conn)?;
+    // Rebuild the FTS index too, since deletes leave its segments fragmented
+    conn.execute_batch("INSERT INTO runs_fts(runs_fts) VALUES ('optimize'); VACUUM;")
+        .map_err(|e| e.to_string())?;
+    let size_after = file_bytes(&conn)?;
+
+    Ok(VacuumReport {
+        deleted,
+        compacted,
+        size_before,
+        size_after,
+    })
+}
@@ -0,0 +1,171 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+use crate::patterns::{check_pattern_name, get_patterns_dir};
+use crate::settings::SettingsState;
+
+// Oldest revisions beyond this are pruned whenever a new one is taken
+const MAX_REVISIONS: usize = 50;
+const PATTERN_FILES: [&str; 2] = ["system.md", "user.md"];
+
+#[derive(Serialize)]
+pub struct PatternRevision {
+    // Milliseconds since the epoch; also the revision's directory name
+    pub id: String,
+    pub created_at: i64,
+    pub size: u64,
+}
+
+// Revisions live in the app data dir rather than next to the pattern, so Fabric doesn't
+// pick them up as patterns of their own
+fn revisions_dir(app_handle: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("pattern_revisions").join(name))
+        .map_err(|e| e.to_string())
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+fn read_files(dir: &Path) -> Vec<(&'static str, String)> {
+    PATTERN_FILES
+        .iter()
+        .filter_map(|file| fs::read_to_string(dir.join(file)).ok().map(|content| (*file, content)))
+        .collect()
+}
+
+fn sorted_revision_ids(dir: &Path) -> Vec<String> {
+    let mut ids: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().is_dir())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|id| id.parse::<u128>().is_ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    ids.sort_by_key(|id| std::cmp::Reverse(id.parse::<u128>().unwrap_or(0)));
+    ids
+}
+
+// Copies the pattern's current files into a new revision. Nothing is stored when the pattern
+// doesn't exist yet or is unchanged since the latest revision.
+pub fn snapshot(app_handle: &AppHandle, pattern_dir: &Path, name: &str) -> Result<(), String> {
+    let files = read_files(pattern_dir);
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let dir = revisions_dir(app_handle, name)?;
+    let ids = sorted_revision_ids(&dir);
+    if let Some(latest) = ids.first() {
+        if read_files(&dir.join(latest)) == files {
+            return Ok(());
+        }
+    }
+
+    let target = dir.join(now_millis().to_string());
+    fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+    for (file, content) in &files {
+        fs::write(target.join(file), content).map_err(|e| e.to_string())?;
+    }
+
+    for old in ids.iter().skip(MAX_REVISIONS - 1) {
+        let _ = fs::remove_dir_all(dir.join(old));
+    }
+    Ok(())
+}
+
+// Writes an edited pattern, keeping what was there before as a revision. `user` of None leaves
+// an existing user.md alone; an empty string removes it.
+#[tauri::command]
+pub async fn save_pattern(
+    app_handle: AppHandle,
+    state: State<'_, SettingsState>,
+    name: String,
+    system: String,
+    user: Option<String>,
+) -> Result<(), String> {
+    check_pattern_name(&name)?;
+    let dir = get_patterns_dir(&state.get()).join(&name);
+    snapshot(&app_handle, &dir, &name)?;
+
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    fs::write(dir.join("system.md"), system).map_err(|e| e.to_string())?;
+    match user.as_deref() {
+        Some("") => {
+            let _ = fs::remove_file(dir.join("user.md"));
+        }
+        Some(user) => fs::write(dir.join("user.md"), user).map_err(|e| e.to_string())?,
+        None => {}
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_pattern_revisions(app_handle: AppHandle, name: String) -> Result<Vec<PatternRevision>, String> {
+    check_pattern_name(&name)?;
+    let dir = revisions_dir(&app_handle, &name)?;
+    Ok(sorted_revision_ids(&dir)
+        .into_iter()
+        .map(|id| {
+            let size = PATTERN_FILES
+                .iter()
+                .filter_map(|file| fs::metadata(dir.join(&id).join(file)).ok())
+                .map(|m| m.len())
+                .sum();
+            PatternRevision {
+                created_at: (id.parse::<u128>().unwrap_or(0) / 1000) as i64,
+                id,
+                size,
+            }
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn get_pattern_revision(app_handle: AppHandle, name: String, id: String) -> Result<String, String> {
+    check_pattern_name(&name)?;
+    check_pattern_name(&id)?;
+    fs::read_to_string(revisions_dir(&app_handle, &name)?.join(&id).join("system.md"))
+        .map_err(|_| format!("Revision {} of '{}' not found.", id, name))
+}
+
+// The current version is saved as a revision first, so a restore can itself be undone
+#[tauri::command]
+pub async fn restore_pattern_revision(
+    app_handle: AppHandle,
+    state: State<'_, SettingsState>,
+    name: String,
+    id: String,
+) -> Result<(), String> {
+    check_pattern_name(&name)?;
+    check_pattern_name(&id)?;
+    let revision = revisions_dir(&app_handle, &name)?.join(&id);
+    let files = read_files(&revision);
+    if files.is_empty() {
+        return Err(format!("Revision {} of '{}' not found.", id, name));
+    }
+
+    let dir = get_patterns_dir(&state.get()).join(&name);
+    snapshot(&app_handle, &dir, &name)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    for file in PATTERN_FILES {
+        match files.iter().find(|(f, _)| *f == file) {
+            Some((_, content)) => fs::write(dir.join(file), content).map_err(|e| e.to_string())?,
+            None => {
+                let _ = fs::remove_file(dir.join(file));
+            }
+        }
+    }
+    Ok(())
+}
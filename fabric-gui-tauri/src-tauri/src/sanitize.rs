@@ -0,0 +1,278 @@
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+// Incrementally sanitizes streamed markdown so the webview can inject the rendered HTML as-is.
+// Raw HTML is stripped (script/style bodies included), dangerous link targets become "#",
+// and code is left untouched because the markdown renderer escapes it anyway.
+// Only text that can no longer turn into a tag or link is released; the rest waits for more chunks.
+pub struct StreamSanitizer {
+    pending: String,
+    at_line_start: bool,
+    in_fence: bool,
+    // Set while inside a <script> or <style> block whose closing tag hasn't arrived yet
+    skipping: Option<&'static str>,
+}
+
+impl Default for StreamSanitizer {
+    fn default() -> Self {
+        Self {
+            pending: String::new(),
+            at_line_start: true,
+            in_fence: false,
+            skipping: None,
+        }
+    }
+}
+
+impl StreamSanitizer {
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+        let mut out = String::new();
+
+        while let Some(pos) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=pos).collect();
+            out.push_str(&self.process(&line));
+            self.at_line_start = true;
+        }
+
+        let release = self.releasable();
+        if release > 0 {
+            let text: String = self.pending.drain(..release).collect();
+            out.push_str(&self.process(&text));
+            self.at_line_start = false;
+        }
+
+        out
+    }
+
+    // Releases whatever is still held back once the stream has ended
+    pub fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.pending);
+        let out = self.process(&rest);
+        *self = Self::default();
+        out
+    }
+
+    // How much of the pending partial line is safe to emit before the line is complete
+    fn releasable(&self) -> usize {
+        if self.skipping.is_some() {
+            return 0;
+        }
+        if self.at_line_start {
+            // Could still become a code fence
+            let trimmed = self.pending.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('`') || trimmed.starts_with('~') {
+                return 0;
+            }
+        }
+        if self.in_fence {
+            return self.pending.len();
+        }
+        self.pending.find(['<', '[', '`']).unwrap_or(self.pending.len())
+    }
+
+    fn process(&mut self, text: &str) -> String {
+        if self.at_line_start {
+            let trimmed = text.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                self.in_fence = !self.in_fence;
+                return text.to_string();
+            }
+        }
+        if self.in_fence {
+            return text.to_string();
+        }
+        self.sanitize_line(text)
+    }
+
+    fn sanitize_line(&mut self, line: &str) -> String {
+        let mut out = String::new();
+        let mut rest = line;
+        let line_start = self.at_line_start;
+
+        while !rest.is_empty() {
+            if let Some(tag) = self.skipping {
+                match find_ignore_case(rest, &format!("</{}", tag)) {
+                    Some(pos) => {
+                        let after = &rest[pos..];
+                        rest = after.find('>').map(|i| &after[i + 1..]).unwrap_or("");
+                        self.skipping = None;
+                    }
+                    None => {
+                        // Keep the line break so the surrounding markdown structure survives
+                        if rest.ends_with('\n') {
+                            out.push('\n');
+                        }
+                        return out;
+                    }
+                }
+                continue;
+            }
+
+            let backtick = rest.find('`');
+            let script = ["script", "style"]
+                .into_iter()
+                .filter_map(|tag| find_ignore_case(rest, &format!("<{}", tag)).map(|pos| (pos, tag)))
+                .min_by_key(|(pos, _)| *pos);
+
+            match (backtick, script) {
+                (Some(tick), script) if script.is_none_or(|(pos, _)| tick < pos) => {
+                    out.push_str(&clean_text(&rest[..tick], line_start && out.is_empty()));
+                    let (span, after) = split_code_span(&rest[tick..]);
+                    out.push_str(span);
+                    rest = after;
+                }
+                (_, Some((pos, tag))) => {
+                    out.push_str(&clean_text(&rest[..pos], line_start && out.is_empty()));
+                    self.skipping = Some(tag);
+                    rest = &rest[pos..];
+                }
+                _ => {
+                    out.push_str(&clean_text(rest, line_start && out.is_empty()));
+                    break;
+                }
+            }
+        }
+
+        out
+    }
+}
+
+// Sanitizes a complete document in one go, e.g. a translated output
+pub fn sanitize(text: &str) -> String {
+    let mut sanitizer = StreamSanitizer::default();
+    let mut out = sanitizer.push(text);
+    out.push_str(&sanitizer.finish());
+    out
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.to_ascii_lowercase().find(needle)
+}
+
+// Returns the inline code span at the start of `text` and what follows it.
+// An unmatched run of backticks is literal text.
+fn split_code_span(text: &str) -> (&str, &str) {
+    let ticks = text.len() - text.trim_start_matches('`').len();
+    let fence = &text[..ticks];
+    match text[ticks..].find(fence) {
+        Some(pos) => text.split_at(ticks + pos + ticks),
+        None => text.split_at(ticks),
+    }
+}
+
+fn link_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\]\(\s*(<[^>\n]*>|[^\s()]*(?:\([^\s()]*\)[^\s()]*)*)").unwrap())
+}
+
+fn reference_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^( {0,3}\[[^\]]+\]:\s*)(\S+)").unwrap())
+}
+
+fn html_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"<!--.*?-->|<([A-Za-z][A-Za-z0-9+.\-]*:[^\s<>]*)>|</?[A-Za-z][A-Za-z0-9\-]*(?:\s[^<>]*)?/?>|<[A-Za-z!/?]")
+            .unwrap()
+    })
+}
+
+fn clean_text(text: &str, line_start: bool) -> String {
+    let text = link_regex().replace_all(text, |caps: &Captures| {
+        if is_dangerous_url(&caps[1]) {
+            "](#".to_string()
+        } else {
+            caps[0].to_string()
+        }
+    });
+
+    let text = if line_start {
+        reference_regex().replace(&text, |caps: &Captures| {
+            if is_dangerous_url(&caps[2]) {
+                format!("{}#", &caps[1])
+            } else {
+                caps[0].to_string()
+            }
+        })
+    } else {
+        text
+    };
+
+    html_regex()
+        .replace_all(&text, |caps: &Captures| {
+            let matched = &caps[0];
+            if let Some(autolink) = caps.get(1) {
+                if is_dangerous_url(autolink.as_str()) {
+                    escape_html(matched)
+                } else {
+                    matched.to_string()
+                }
+            } else if matched.len() == 2 {
+                // A stray "<" followed by something tag-like, e.g. a tag split over lines
+                format!("&lt;{}", &matched[1..])
+            } else {
+                String::new()
+            }
+        })
+        .to_string()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Browsers ignore whitespace and control characters inside schemes and renderers decode
+// entities in link targets, so both are normalized before the scheme is checked
+fn is_dangerous_url(url: &str) -> bool {
+    let url = url.trim_start_matches('<').trim_end_matches('>');
+    let mut normalized = String::new();
+    let mut rest = url;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '&' {
+            if let Some(end) = rest.find(';') {
+                if let Some(decoded) = decode_entity(&rest[1..end]) {
+                    if !decoded.is_whitespace() {
+                        normalized.push(decoded.to_ascii_lowercase());
+                    }
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+        if !c.is_whitespace() && !c.is_control() {
+            normalized.push(c.to_ascii_lowercase());
+        }
+        rest = &rest[c.len_utf8()..];
+        if normalized.len() > 32 {
+            break;
+        }
+    }
+
+    if let Some(data) = normalized.strip_prefix("data:") {
+        return !["image/png", "image/gif", "image/jpeg", "image/webp"]
+            .iter()
+            .any(|mime| data.starts_with(mime));
+    }
+    ["javascript:", "vbscript:", "file:"]
+        .iter()
+        .any(|scheme| normalized.starts_with(scheme))
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    let lower = entity.to_ascii_lowercase();
+    let code = if let Some(hex) = lower.strip_prefix("#x") {
+        u32::from_str_radix(hex, 16).ok()?
+    } else if let Some(dec) = lower.strip_prefix('#') {
+        dec.parse().ok()?
+    } else {
+        return match lower.as_str() {
+            "colon" => Some(':'),
+            "tab" | "newline" => Some(' '),
+            _ => None,
+        };
+    };
+    char::from_u32(code)
+}
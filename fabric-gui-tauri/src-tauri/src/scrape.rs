@@ -0,0 +1,51 @@
+use tauri::State;
+use crate::cookies;
+use crate::http::{self, AuditedSend};
+use crate::i18n::tr_args;
+use crate::input::{self, LimitedText};
+use crate::media_cache;
+use crate::settings::SettingsState;
+
+const JINA_READER_URL: &str = "https://r.jina.ai/";
+
+// Same approach as the fabric CLI's --scrape_url: Jina's reader returns the page as markdown.
+// A key is optional and only raises the rate limit.
+pub async fn scrape(url: &str, api_key: Option<String>) -> Result<String, String> {
+    // Imported cookies for the page's domain are passed on so the reader sees the logged-in page
+    let cookie = cookies::header_for(url);
+    // The logged-in page is cached apart from the public one
+    let cache_key = media_cache::key(&[url, cookie.as_deref().unwrap_or_default()]);
+    if let Some(page) = media_cache::get("page", &cache_key) {
+        return Ok(page);
+    }
+
+    let mut request = http::client_for(JINA_READER_URL)?.get(format!("{}{}", JINA_READER_URL, url));
+    if let Some(key) = api_key {
+        request = request.bearer_auth(key);
+    }
+    if let Some(cookie) = cookie {
+        request = request.header("X-Set-Cookie", cookie);
+    }
+
+    let res = request
+        .send_audited("scrape", None)
+        .await
+        .map_err(http::network_error)?;
+    let status = res.status();
+    let text = res.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(tr_args(
+            "vendor-api-error",
+            &[("vendor", "Jina Reader"), ("status", &status.to_string()), ("details", &text.chars().take(300).collect::<String>())],
+        ));
+    }
+    media_cache::put("page", &cache_key, url, &text);
+    Ok(text)
+}
+
+#[tauri::command]
+pub async fn scrape_url(state: State<'_, SettingsState>, url: String) -> Result<LimitedText, String> {
+    let settings = state.get();
+    let page = scrape(&url, settings.api_key("jina")).await?;
+    Ok(input::limited(&settings, "scrape", page))
+}
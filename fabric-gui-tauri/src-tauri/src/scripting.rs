@@ -0,0 +1,305 @@
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+use crate::ai_client::AIRequest;
+use crate::settings::{Settings, SettingsState};
+
+const EVENTS: &[&str] = &["on_input", "on_chunk", "on_complete"];
+// Enough for any reasonable text handling while stopping runaway loops
+const MAX_OPERATIONS: u64 = 50_000_000;
+const MAX_STRING_BYTES: usize = 16 * 1024 * 1024;
+// The settings setting() can read; the others may hold secrets, such as proxy credentials or
+// tool servers' tokens, that a script could otherwise put into text sent to a vendor
+const VISIBLE_SETTINGS: &[&str] = &[
+    "default_vendor",
+    "default_model",
+    "translation_language",
+    "locale",
+    "sanitize_output",
+    "save_reasoning",
+    "resume_interrupted_streams",
+    "chunking",
+    "input_limits",
+];
+
+// Rhai scripts from the scripts folder that customize runs. A script defines any of
+// on_input(text, run), on_chunk(text, run) and on_complete(text, run) (the `run` parameter,
+// with pattern, vendor, model and run_id, may be left out); returning a string replaces the
+// text, returning nothing leaves it. Scripts run in file name order, each on the previous
+// one's result. Besides plain Rhai they can call setting("a.b") to read one of
+// VISIBLE_SETTINGS, and read_file, write_file and append_file on paths inside scripts/files.
+// print and debug output arrives as script-log events.
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<(String, AST)>,
+    run: Map,
+    app_handle: AppHandle,
+    run_id: String,
+}
+
+#[derive(Serialize, Clone)]
+struct ScriptLog {
+    run_id: Option<String>,
+    text: String,
+}
+
+#[derive(Serialize)]
+pub struct ScriptInfo {
+    pub name: String,
+    // The events the script handles
+    pub events: Vec<String>,
+    // Set when the script doesn't compile
+    pub error: Option<String>,
+}
+
+fn scripts_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("scripts"))
+        .map_err(|e| e.to_string())
+}
+
+// Only plain relative paths, so a script can't leave the files folder
+fn allowed_path(files: &Path, path: &str) -> Result<PathBuf, Box<EvalAltResult>> {
+    let relative = Path::new(path);
+    if path.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("'{}' is not a path inside the scripts' files folder.", path).into());
+    }
+    Ok(files.join(relative))
+}
+
+// Release builds have no console, so script output goes to the UI
+fn log(app_handle: &AppHandle, run_id: Option<&str>, text: &str) {
+    let _ = app_handle.emit("script-log", ScriptLog { run_id: run_id.map(str::to_string), text: text.to_string() });
+}
+
+fn visible_settings(settings: &Settings) -> Value {
+    let all = serde_json::to_value(settings).unwrap_or_default();
+    Value::Object(VISIBLE_SETTINGS.iter().map(|key| (key.to_string(), all[*key].clone())).collect())
+}
+
+fn setting_value(settings: &Value, path: &str) -> Dynamic {
+    let mut value = settings;
+    for key in path.split('.') {
+        value = &value[key];
+    }
+    match value {
+        Value::Null => Dynamic::UNIT,
+        Value::String(s) => s.clone().into(),
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64().unwrap_or_default().into(),
+        },
+        other => other.to_string().into(),
+    }
+}
+
+// A Rhai engine with no module imports, no eval, bounded work, and the restricted API
+fn engine(app_handle: &AppHandle, settings: &Settings, files: PathBuf, run_id: Option<&str>) -> Engine {
+    let (print_handle, print_run) = (app_handle.clone(), run_id.map(str::to_string));
+    let (debug_handle, debug_run) = (app_handle.clone(), run_id.map(str::to_string));
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_string_size(MAX_STRING_BYTES)
+        .set_max_call_levels(64)
+        .on_print(move |text| log(&print_handle, print_run.as_deref(), text))
+        .on_debug(move |text, _, _| log(&debug_handle, debug_run.as_deref(), text));
+
+    let visible = visible_settings(settings);
+    engine.register_fn("setting", move |path: &str| setting_value(&visible, path));
+
+    let dir = files.clone();
+    engine.register_fn("read_file", move |path: &str| -> Result<String, Box<EvalAltResult>> {
+        fs::read_to_string(allowed_path(&dir, path)?).map_err(|e| e.to_string().into())
+    });
+    let dir = files.clone();
+    engine.register_fn("write_file", move |path: &str, text: &str| -> Result<(), Box<EvalAltResult>> {
+        let path = allowed_path(&dir, path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, text).map_err(|e| e.to_string().into())
+    });
+    engine.register_fn("append_file", move |path: &str, text: &str| -> Result<(), Box<EvalAltResult>> {
+        let path = allowed_path(&files, path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|e| e.to_string().into())
+    });
+    engine
+}
+
+fn script_files(dir: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_str()?.strip_suffix(".rhai")?.to_string();
+            Some((name, e.path()))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn handlers(ast: &AST) -> Vec<String> {
+    ast.iter_functions()
+        .map(|f| f.name.to_string())
+        .filter(|name| EVENTS.contains(&name.as_str()))
+        .collect()
+}
+
+impl Scripts {
+    // The scripts for one run, or None when scripting is off or no script handles an event.
+    // A script that doesn't compile fails the run rather than being skipped silently.
+    pub fn load(app_handle: &AppHandle, settings: &Settings, request: &AIRequest, run_id: &str) -> Result<Option<Arc<Scripts>>, String> {
+        if !settings.scripts_enabled {
+            return Ok(None);
+        }
+        let dir = scripts_dir(app_handle)?;
+        let engine = engine(app_handle, settings, dir.join("files"), Some(run_id));
+        let mut scripts = Vec::new();
+        for (name, path) in script_files(&dir) {
+            let ast = engine
+                .compile_file(path)
+                .map_err(|e| format!("Script '{}' doesn't compile: {}", name, e))?;
+            if !handlers(&ast).is_empty() {
+                scripts.push((name, ast));
+            }
+        }
+        if scripts.is_empty() {
+            return Ok(None);
+        }
+        let mut run = Map::new();
+        run.insert("pattern".into(), request.pattern.clone().map(Dynamic::from).unwrap_or(Dynamic::UNIT));
+        run.insert("vendor".into(), request.vendor.clone().into());
+        run.insert("model".into(), request.model.clone().into());
+        run.insert("run_id".into(), run_id.to_string().into());
+        Ok(Some(Arc::new(Scripts { engine, scripts, run, app_handle: app_handle.clone(), run_id: run_id.to_string() })))
+    }
+
+    pub fn handles(&self, event: &str) -> bool {
+        self.scripts.iter().any(|(_, ast)| ast.iter_functions().any(|f| f.name == event))
+    }
+
+    pub fn run(&self, event: &str, text: String) -> Result<String, String> {
+        let mut text = text;
+        for (name, ast) in &self.scripts {
+            let Some(params) = ast.iter_functions().find(|f| f.name == event).map(|f| f.params.len()) else {
+                continue;
+            };
+            let mut scope = Scope::new();
+            let result = match params {
+                1 => self.engine.call_fn::<Dynamic>(&mut scope, ast, event, (text.clone(),)),
+                _ => self.engine.call_fn::<Dynamic>(&mut scope, ast, event, (text.clone(), self.run.clone())),
+            }
+            .map_err(|e| format!("Script '{}' failed in {}: {}", name, event, e))?;
+            if result.is_unit() {
+                continue;
+            }
+            text = result
+                .into_string()
+                .map_err(|kind| format!("Script '{}' returned a {} from {}; return a string or nothing.", name, kind, event))?;
+        }
+        Ok(text)
+    }
+
+    // Chunks can't fail the stream, so a failing on_chunk is logged and the chunk kept
+    pub fn chunk(&self, text: &str) -> String {
+        self.run("on_chunk", text.to_string()).unwrap_or_else(|e| {
+            log(&self.app_handle, Some(&self.run_id), &e);
+            text.to_string()
+        })
+    }
+}
+
+#[tauri::command]
+pub async fn list_scripts(app_handle: AppHandle) -> Result<Vec<ScriptInfo>, String> {
+    let settings = app_handle.state::<SettingsState>().get();
+    let dir = scripts_dir(&app_handle)?;
+    let engine = engine(&app_handle, &settings, dir.join("files"), None);
+    Ok(script_files(&dir)
+        .into_iter()
+        .map(|(name, path)| match engine.compile_file(path) {
+            Ok(ast) => ScriptInfo { name, events: handlers(&ast), error: None },
+            Err(e) => ScriptInfo { name, events: Vec::new(), error: Some(e.to_string()) },
+        })
+        .collect())
+}
+
+// Runs every enabled script's handler for `event` on a sample text, for trying scripts out
+#[tauri::command]
+pub async fn test_scripts(app_handle: AppHandle, event: String, text: String, request: Option<AIRequest>) -> Result<String, String> {
+    if !EVENTS.contains(&event.as_str()) {
+        return Err(format!("Unknown event '{}'. Use on_input, on_chunk or on_complete.", event));
+    }
+    let mut settings = app_handle.state::<SettingsState>().get();
+    settings.scripts_enabled = true;
+    let request = request.unwrap_or_default();
+    match Scripts::load(&app_handle, &settings, &request, "test")? {
+        Some(scripts) => scripts.run(&event, text),
+        None => Ok(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visible_settings_leave_out_keys_proxies_and_tool_servers() {
+        let mut settings = Settings::default();
+        settings.api_keys.insert("openai".to_string(), "sk-secret".to_string());
+        settings.default_vendor = Some("openai".to_string());
+        let visible = visible_settings(&settings);
+        assert_eq!(visible["default_vendor"], "openai");
+        for hidden in ["api_keys", "network", "mcp_servers", "notify", "zotero"] {
+            assert!(visible.get(hidden).is_none(), "{} is visible", hidden);
+        }
+        assert!(!visible.to_string().contains("sk-secret"));
+    }
+
+    #[test]
+    fn allowed_path_accepts_plain_relative_paths() {
+        let files = Path::new("files");
+        assert_eq!(allowed_path(files, "out/result.txt").unwrap(), files.join("out/result.txt"));
+    }
+
+    #[test]
+    fn allowed_path_rejects_parent_current_and_absolute_paths() {
+        let files = Path::new("files");
+        for path in ["", "..", "../secret", "out/../../secret", "./result.txt", "/etc/passwd"] {
+            assert!(allowed_path(files, path).is_err(), "{} was allowed", path);
+        }
+    }
+
+    #[test]
+    fn allowed_path_never_leaves_the_folder_with_drive_prefixes() {
+        // A prefix on Windows; elsewhere just an odd file name inside the folder
+        let files = Path::new("files");
+        for path in ["C:\\Windows\\win.ini", "C:/Windows/win.ini", "\\\\server\\share"] {
+            if let Ok(joined) = allowed_path(files, path) {
+                assert!(joined.starts_with(files), "{} became {}", path, joined.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::State;
+use crate::audit;
+use crate::chunking::ChunkOptions;
+use crate::emitter::Coalescing;
+use crate::export::ExportSettings;
+use crate::http::{self, NetworkSettings};
+use crate::huggingface;
+use crate::hooks::PostHook;
+use crate::i18n;
+use crate::input::InputLimits;
+use crate::mcp::McpServerConfig;
+use crate::media_cache;
+use crate::notify::NotifySettings;
+use crate::rag::RagSettings;
+use crate::vertex::{self, VertexSettings};
+use crate::residency::{self, ResidencySettings};
+use crate::retention::RetentionPolicy;
+use crate::zotero::ZoteroSettings;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub api_keys: HashMap<String, String>,
+    pub default_vendor: Option<String>,
+    pub default_model: Option<String>,
+    pub patterns_dir: Option<String>,
+    pub custom_patterns_dir: Option<String>,
+    // Where patterns installed from the community marketplace live; defaults to the app data dir
+    pub community_patterns_dir: Option<String>,
+    pub setup_completed: bool,
+    pub model_registry_url: Option<String>,
+    pub marketplace_index_url: Option<String>,
+    pub translation_language: Option<String>,
+    pub locale: Option<String>,
+    pub history_retention: RetentionPolicy,
+    // Strip raw HTML and dangerous links from streamed output before it reaches the webview
+    pub sanitize_output: bool,
+    // Maximum queued runs in flight per vendor; defaults to 2
+    pub vendor_concurrency: Option<u32>,
+    pub network: NetworkSettings,
+    pub vertex: VertexSettings,
+    // Dedicated Inference Endpoint or self-hosted TGI URL; unset uses the serverless Inference API
+    pub huggingface_endpoint: Option<String>,
+    // Keep the model's reasoning in history; it is always streamed but not stored by default
+    pub save_reasoning: bool,
+    // Streamed text is sent to the UI at most every this many milliseconds (default 30, 0 sends
+    // every delta as it arrives) or once this many bytes are waiting (default 4096)
+    pub stream_flush_ms: Option<u64>,
+    pub stream_flush_bytes: Option<usize>,
+    // Continue a response whose connection dropped mid-stream (e.g. after sleep) instead of failing
+    pub resume_interrupted_streams: bool,
+    // Program used for local speech-to-text; defaults to the openai-whisper CLI ("whisper")
+    pub whisper_command: Option<String>,
+    // Interpreter for Python sidecars such as diarization; defaults to py on Windows, python3 elsewhere
+    pub python_command: Option<String>,
+    // Documents folder and embedding model for retrieval-augmented runs
+    pub rag: RagSettings,
+    // Contact address Unpaywall requires for resolving DOIs to open-access PDFs
+    pub unpaywall_email: Option<String>,
+    // Render near-empty pages in a headless Chromium browser before giving up on extraction.
+    // Only works with the network firewall off, as the browser fetches pages by itself.
+    pub headless_fallback: bool,
+    // Browser executable for that; found automatically when unset
+    pub headless_browser: Option<String>,
+    // Jira site (e.g. https://acme.atlassian.net) and, for Jira Cloud, the account email the
+    // API token in api_keys "jira" belongs to
+    pub jira_url: Option<String>,
+    pub jira_email: Option<String>,
+    // Default splitting of inputs too long to send in one request
+    pub chunking: ChunkOptions,
+    // Notes folder and file naming for exported runs
+    pub export: ExportSettings,
+    pub zotero: ZoteroSettings,
+    // MQTT and Home Assistant channels runs can be published to
+    pub notify: NotifySettings,
+    // Shell commands presets can pipe their output to after a run
+    pub post_hooks: Vec<PostHook>,
+    // Model Context Protocol tool servers run as plugins
+    pub mcp_servers: Vec<McpServerConfig>,
+    // Run the Rhai scripts in the scripts folder on run events
+    pub scripts_enabled: bool,
+    // Also keep request bodies in the audit log, with stored API keys masked
+    pub audit_debug: bool,
+    // Regional endpoints and EU-only runs
+    pub residency: ResidencySettings,
+    // Largest text fetched files, pages and transcripts are cut to
+    pub input_limits: InputLimits,
+    // Hours fetched transcripts and pages, PDF text and transcriptions are reused for; 0 turns
+    // the media cache off. A week when unset.
+    pub media_cache_ttl_hours: Option<u64>,
+}
+
+const DEFAULT_STREAM_FLUSH_MS: u64 = 30;
+const DEFAULT_STREAM_FLUSH_BYTES: usize = 4096;
+
+impl Settings {
+    pub fn stream_coalescing(&self) -> Option<Coalescing> {
+        let interval = self.stream_flush_ms.unwrap_or(DEFAULT_STREAM_FLUSH_MS);
+        (interval > 0).then(|| Coalescing {
+            interval: Duration::from_millis(interval),
+            max_bytes: self.stream_flush_bytes.unwrap_or(DEFAULT_STREAM_FLUSH_BYTES).max(1),
+        })
+    }
+
+    pub fn api_key(&self, vendor: &str) -> Option<String> {
+        self.api_keys
+            .get(vendor)
+            .filter(|k| !k.trim().is_empty())
+            .cloned()
+    }
+}
+
+pub struct SettingsState {
+    path: Mutex<PathBuf>,
+    inner: Mutex<Settings>,
+}
+
+// A missing or corrupt file falls back to defaults rather than blocking startup
+fn read_from_disk(path: &PathBuf) -> Settings {
+    let settings: Settings = fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    i18n::set_locale(settings.locale.as_deref());
+    http::configure(&settings.network);
+    audit::configure(settings.audit_debug, &settings.api_keys);
+    residency::configure(&settings.residency, settings.api_key("bedrock"));
+    vertex::configure(&settings.vertex);
+    huggingface::configure(settings.huggingface_endpoint.as_deref());
+    media_cache::configure(settings.media_cache_ttl_hours);
+    settings
+}
+
+impl SettingsState {
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            inner: Mutex::new(read_from_disk(&path)),
+            path: Mutex::new(path),
+        }
+    }
+
+    // Points the state at another profile's settings file
+    pub fn reload(&self, path: PathBuf) -> Settings {
+        let settings = read_from_disk(&path);
+        *self.path.lock().unwrap() = path;
+        *self.inner.lock().unwrap() = settings.clone();
+        settings
+    }
+
+    pub fn get(&self) -> Settings {
+        self.inner.lock().unwrap().clone()
+    }
+
+    pub fn update<F: FnOnce(&mut Settings)>(&self, f: F) -> Result<Settings, String> {
+        let mut settings = self.inner.lock().unwrap();
+        f(&mut settings);
+        i18n::set_locale(settings.locale.as_deref());
+        http::configure(&settings.network);
+        audit::configure(settings.audit_debug, &settings.api_keys);
+        residency::configure(&settings.residency, settings.api_key("bedrock"));
+        vertex::configure(&settings.vertex);
+        huggingface::configure(settings.huggingface_endpoint.as_deref());
+        media_cache::configure(settings.media_cache_ttl_hours);
+        save_to_disk(&self.path.lock().unwrap(), &settings)?;
+        Ok(settings.clone())
+    }
+}
+
+fn save_to_disk(path: &PathBuf, settings: &Settings) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_settings(state: State<'_, SettingsState>) -> Result<Settings, String> {
+    Ok(state.get())
+}
+
+#[tauri::command]
+pub async fn save_settings(state: State<'_, SettingsState>, settings: Settings) -> Result<Settings, String> {
+    state.update(|current| *current = settings)
+}
@@ -0,0 +1,239 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use home::home_dir;
+use tauri::State;
+use crate::health::probe;
+use crate::http;
+use crate::i18n::tr;
+use crate::patterns::get_patterns_dir;
+use crate::settings::SettingsState;
+
+// Maps GUI vendor ids to the variable names used in the fabric CLI's .env
+const VENDOR_ENV_KEYS: [(&str, &str); 5] = [
+    ("google", "GEMINI_API_KEY"),
+    ("openai", "OPENAI_API_KEY"),
+    ("anthropic", "ANTHROPIC_API_KEY"),
+    ("xai", "GROK_API_KEY"),
+    ("deepseek", "DEEPSEEK_API_KEY"),
+];
+
+#[derive(Serialize)]
+pub struct FabricInstall {
+    pub cli_path: Option<String>,
+    pub cli_version: Option<String>,
+    pub config_dir: Option<String>,
+    pub env_file_found: bool,
+    pub patterns_dir: Option<String>,
+    pub pattern_count: usize,
+    pub configured_vendors: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportSummary {
+    pub imported_keys: Vec<String>,
+    pub patterns_dir: Option<String>,
+    pub custom_patterns_dir: Option<String>,
+    pub default_vendor: Option<String>,
+    pub default_model: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct VendorPing {
+    pub vendor: String,
+    pub ok: bool,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct SetupReadiness {
+    pub patterns_found: bool,
+    pub pattern_count: usize,
+    pub configured_vendors: Vec<String>,
+    pub setup_completed: bool,
+    pub ready: bool,
+}
+
+pub fn fabric_config_dir() -> Option<PathBuf> {
+    home_dir().map(|p| p.join(".config").join("fabric"))
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    let candidates = if cfg!(windows) {
+        vec![format!("{}.exe", name), name.to_string()]
+    } else {
+        vec![name.to_string()]
+    };
+
+    for dir in std::env::split_paths(&paths) {
+        for candidate in &candidates {
+            let path = dir.join(candidate);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+fn read_env_file(path: &PathBuf) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    if let Ok(content) = fs::read_to_string(path) {
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().trim_matches('"').trim_matches('\'');
+                if !value.is_empty() {
+                    values.insert(key.trim().to_string(), value.to_string());
+                }
+            }
+        }
+    }
+    values
+}
+
+fn count_patterns(dir: &PathBuf) -> usize {
+    fs::read_dir(dir)
+        .map(|entries| entries.flatten().filter(|e| e.path().is_dir()).count())
+        .unwrap_or(0)
+}
+
+#[tauri::command]
+pub async fn detect_fabric_install() -> Result<FabricInstall, String> {
+    let cli_path = find_in_path("fabric");
+    let cli_version = cli_path.as_ref().and_then(|path| {
+        Command::new(path)
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    });
+
+    let config_dir = fabric_config_dir().filter(|p| p.exists());
+    let env_path = config_dir.as_ref().map(|p| p.join(".env"));
+    let env_file_found = env_path.as_ref().map(|p| p.exists()).unwrap_or(false);
+    let env = env_path.map(|p| read_env_file(&p)).unwrap_or_default();
+
+    let patterns_dir = config_dir
+        .as_ref()
+        .map(|p| p.join("patterns"))
+        .filter(|p| p.exists());
+    let pattern_count = patterns_dir.as_ref().map(count_patterns).unwrap_or(0);
+
+    let configured_vendors = VENDOR_ENV_KEYS
+        .iter()
+        .filter(|(_, env_key)| env.contains_key(*env_key))
+        .map(|(vendor, _)| vendor.to_string())
+        .collect();
+
+    Ok(FabricInstall {
+        cli_path: cli_path.map(|p| p.to_string_lossy().to_string()),
+        cli_version,
+        config_dir: config_dir.map(|p| p.to_string_lossy().to_string()),
+        env_file_found,
+        patterns_dir: patterns_dir.map(|p| p.to_string_lossy().to_string()),
+        pattern_count,
+        configured_vendors,
+    })
+}
+
+#[tauri::command]
+pub async fn import_fabric_config(state: State<'_, SettingsState>) -> Result<ImportSummary, String> {
+    let config_dir = fabric_config_dir()
+        .filter(|p| p.exists())
+        .ok_or_else(|| tr("setup-no-fabric-config"))?;
+
+    let env = read_env_file(&config_dir.join(".env"));
+
+    let mut imported_keys = Vec::new();
+    let patterns_dir = Some(config_dir.join("patterns"))
+        .filter(|p| p.exists())
+        .map(|p| p.to_string_lossy().to_string());
+    let custom_patterns_dir = env.get("CUSTOM_PATTERNS_DIRECTORY").cloned();
+    let default_vendor = env.get("DEFAULT_VENDOR").map(|v| match v.to_lowercase().as_str() {
+        "gemini" => "google".to_string(),
+        other => other.to_string(),
+    });
+    let default_model = env.get("DEFAULT_MODEL").cloned();
+
+    state.update(|settings| {
+        for (vendor, env_key) in VENDOR_ENV_KEYS {
+            if let Some(key) = env.get(env_key) {
+                settings.api_keys.insert(vendor.to_string(), key.clone());
+                imported_keys.push(vendor.to_string());
+            }
+        }
+        if patterns_dir.is_some() {
+            settings.patterns_dir = patterns_dir.clone();
+        }
+        if custom_patterns_dir.is_some() {
+            settings.custom_patterns_dir = custom_patterns_dir.clone();
+        }
+        if default_vendor.is_some() {
+            settings.default_vendor = default_vendor.clone();
+        }
+        if default_model.is_some() {
+            settings.default_model = default_model.clone();
+        }
+    })?;
+
+    Ok(ImportSummary {
+        imported_keys,
+        patterns_dir,
+        custom_patterns_dir,
+        default_vendor,
+        default_model,
+    })
+}
+
+#[tauri::command]
+pub async fn test_configured_vendors(state: State<'_, SettingsState>) -> Result<Vec<VendorPing>, String> {
+    let settings = state.get();
+    let mut results = Vec::new();
+
+    for (vendor, _) in VENDOR_ENV_KEYS {
+        if let Some(key) = settings.api_key(vendor) {
+            let report = probe(&http::vendor_client(vendor)?, vendor, &key, None).await;
+            results.push(VendorPing {
+                vendor: vendor.to_string(),
+                ok: report.ok,
+                message: report.message,
+            });
+        }
+    }
+
+    Ok(results)
+}
+
+#[tauri::command]
+pub async fn get_setup_status(state: State<'_, SettingsState>) -> Result<SetupReadiness, String> {
+    let settings = state.get();
+    let patterns_dir = get_patterns_dir(&settings);
+    let pattern_count = count_patterns(&patterns_dir);
+    let configured_vendors: Vec<String> = VENDOR_ENV_KEYS
+        .iter()
+        .filter(|(vendor, _)| settings.api_key(vendor).is_some())
+        .map(|(vendor, _)| vendor.to_string())
+        .collect();
+
+    Ok(SetupReadiness {
+        patterns_found: pattern_count > 0,
+        pattern_count,
+        ready: pattern_count > 0 && !configured_vendors.is_empty(),
+        configured_vendors,
+        setup_completed: settings.setup_completed,
+    })
+}
+
+#[tauri::command]
+pub async fn complete_setup(state: State<'_, SettingsState>) -> Result<(), String> {
+    state.update(|settings| settings.setup_completed = true)?;
+    Ok(())
+}
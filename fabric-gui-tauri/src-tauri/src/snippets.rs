@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::State;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Snippet {
+    // Typed in the input as-is, e.g. "/audience"
+    pub trigger: String,
+    pub content: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+// Reusable blocks of text (audience descriptions, style guides...) inserted into the input by
+// typing their trigger; stored per profile
+pub struct SnippetStore {
+    path: Mutex<PathBuf>,
+    inner: Mutex<Vec<Snippet>>,
+}
+
+fn read_from_disk(path: &PathBuf) -> Vec<Snippet> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn is_trigger_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+impl SnippetStore {
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            inner: Mutex::new(read_from_disk(&path)),
+            path: Mutex::new(path),
+        }
+    }
+
+    // Points the store at another profile's snippets file
+    pub fn reload(&self, path: PathBuf) {
+        *self.inner.lock().unwrap() = read_from_disk(&path);
+        *self.path.lock().unwrap() = path;
+    }
+
+    fn save(&self, snippets: &[Snippet]) -> Result<(), String> {
+        let path = self.path.lock().unwrap().clone();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(snippets).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    // A trigger only expands as a word of its own, so paths and URLs containing the same
+    // text (https://example.com/audience) are left alone
+    pub fn expand(&self, text: &str) -> String {
+        let snippets = self.inner.lock().unwrap();
+        if snippets.is_empty() || !text.contains('/') {
+            return text.to_string();
+        }
+
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+        let mut at_word_start = true;
+        while let Some(start) = rest.find('/') {
+            let before = &rest[..start];
+            let word_start = before.chars().last().map(char::is_whitespace).unwrap_or(at_word_start);
+            expanded.push_str(before);
+
+            let after = &rest[start + 1..];
+            let len = after.find(|c: char| !is_trigger_char(c)).unwrap_or(after.len());
+            let trigger = &rest[start..start + 1 + len];
+            match snippets.iter().find(|s| s.trigger == trigger).filter(|_| word_start && len > 0) {
+                Some(snippet) => expanded.push_str(&snippet.content),
+                None => expanded.push_str(trigger),
+            }
+            at_word_start = false;
+            rest = &after[len..];
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+fn validate_trigger(trigger: &str) -> Result<(), String> {
+    let valid = trigger.len() > 1 && trigger.starts_with('/') && trigger[1..].chars().all(is_trigger_char);
+    if valid {
+        Ok(())
+    } else {
+        Err("Snippet triggers start with '/' followed by letters, digits, '_' or '-'.".to_string())
+    }
+}
+
+// Adds a snippet or replaces the one with the same trigger
+#[tauri::command]
+pub async fn save_snippet(store: State<'_, SnippetStore>, snippet: Snippet) -> Result<(), String> {
+    validate_trigger(&snippet.trigger)?;
+    let mut snippets = store.inner.lock().unwrap();
+    match snippets.iter_mut().find(|s| s.trigger == snippet.trigger) {
+        Some(existing) => *existing = snippet,
+        None => snippets.push(snippet),
+    }
+    snippets.sort_by(|a, b| a.trigger.cmp(&b.trigger));
+    store.save(&snippets)
+}
+
+#[tauri::command]
+pub async fn delete_snippet(store: State<'_, SnippetStore>, trigger: String) -> Result<(), String> {
+    let mut snippets = store.inner.lock().unwrap();
+    let before = snippets.len();
+    snippets.retain(|s| s.trigger != trigger);
+    if snippets.len() == before {
+        return Err(format!("Snippet '{}' does not exist.", trigger));
+    }
+    store.save(&snippets)
+}
+
+#[tauri::command]
+pub async fn list_snippets(store: State<'_, SnippetStore>) -> Result<Vec<Snippet>, String> {
+    Ok(store.inner.lock().unwrap().clone())
+}
+
+// Shows the input as it will be sent, for a preview next to the editor
+#[tauri::command]
+pub async fn expand_snippets(store: State<'_, SnippetStore>, text: String) -> Result<String, String> {
+    Ok(store.expand(&text))
+}
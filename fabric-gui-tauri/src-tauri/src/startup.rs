@@ -0,0 +1,92 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+// How long each part of startup took, so a slow start can be traced to its cause. Setup steps
+// block the window; background tasks announce themselves with a startup-ready event when done.
+pub struct StartupMetrics {
+    started: Instant,
+    steps: Mutex<Vec<StartupStep>>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct StartupStep {
+    pub name: String,
+    // Milliseconds since the process started
+    pub started_ms: u64,
+    pub duration_ms: u64,
+    pub background: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct StartupReport {
+    pub steps: Vec<StartupStep>,
+    // When the last blocking setup step finished
+    pub setup_ms: u64,
+    // Background tasks that haven't finished yet
+    pub pending: Vec<String>,
+    pub uptime_ms: u64,
+}
+
+// Background tasks reported by get_startup_metrics until they finish
+const BACKGROUND_TASKS: &[&str] = &["pattern_index", "model_registry"];
+
+// Created first thing in main, so times include Tauri's own start
+impl Default for StartupMetrics {
+    fn default() -> Self {
+        Self { started: Instant::now(), steps: Mutex::default() }
+    }
+}
+
+impl StartupMetrics {
+    fn since_start(&self, at: Instant) -> u64 {
+        at.duration_since(self.started).as_millis() as u64
+    }
+
+    fn record(&self, name: &str, started: Instant, background: bool, error: Option<String>) {
+        self.steps.lock().unwrap().push(StartupStep {
+            name: name.to_string(),
+            started_ms: self.since_start(started),
+            duration_ms: started.elapsed().as_millis() as u64,
+            background,
+            error,
+        });
+    }
+
+    // Times one blocking setup step
+    pub fn measure<T>(&self, name: &str, step: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = step();
+        self.record(name, started, false, None);
+        result
+    }
+}
+
+// Records a finished background task and tells the window its data is ready
+pub fn ready(app_handle: &AppHandle, name: &str, started: Instant, error: Option<String>) {
+    let metrics = app_handle.state::<StartupMetrics>();
+    metrics.record(name, started, true, error.clone());
+    let _ = app_handle.emit(
+        "startup-ready",
+        serde_json::json!({"task": name, "elapsed_ms": metrics.since_start(Instant::now()), "error": error}),
+    );
+}
+
+#[tauri::command]
+pub async fn get_startup_metrics(metrics: State<'_, StartupMetrics>) -> Result<StartupReport, String> {
+    let steps = metrics.steps.lock().unwrap().clone();
+    let setup_ms = steps
+        .iter()
+        .filter(|s| !s.background)
+        .map(|s| s.started_ms + s.duration_ms)
+        .max()
+        .unwrap_or_default();
+    let pending = BACKGROUND_TASKS
+        .iter()
+        .filter(|task| !steps.iter().any(|s| s.background && s.name == **task))
+        .map(|task| task.to_string())
+        .collect();
+    Ok(StartupReport { steps, setup_ms, pending, uptime_ms: metrics.since_start(Instant::now()) })
+}
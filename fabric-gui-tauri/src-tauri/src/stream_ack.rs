@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::State;
+use tokio::sync::Notify;
+
+// More unacknowledged events than this pauses reading from the vendor
+const MAX_UNACKED_EVENTS: u64 = 64;
+// A window that stops acknowledging (closed, reloaded) must not stall the run for good
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Flow control for one run in acknowledged streaming mode. The frontend reports how many
+// stream events it has processed; while it lags too far behind, the vendor loop stops
+// reading the HTTP body, so TCP pushes back instead of chunks piling up in memory.
+#[derive(Default)]
+pub struct StreamWindow {
+    sent: AtomicU64,
+    acked: AtomicU64,
+    notify: Notify,
+    // Set after an ack timeout; the rest of the run streams without waiting
+    abandoned: AtomicBool,
+}
+
+impl StreamWindow {
+    pub fn record_sent(&self) {
+        self.sent.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn ack(&self, received: u64) {
+        self.acked.fetch_max(received, Ordering::SeqCst);
+        self.notify.notify_one();
+    }
+
+    fn has_capacity(&self) -> bool {
+        self.abandoned.load(Ordering::SeqCst)
+            || self.sent.load(Ordering::SeqCst).saturating_sub(self.acked.load(Ordering::SeqCst)) <= MAX_UNACKED_EVENTS
+    }
+
+    pub async fn wait_for_capacity(&self) {
+        while !self.has_capacity() {
+            // notify_one stores a permit, so an ack arriving between the check and here isn't lost
+            if tokio::time::timeout(ACK_TIMEOUT, self.notify.notified()).await.is_err() {
+                self.abandoned.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+type Windows = Arc<Mutex<HashMap<String, Arc<StreamWindow>>>>;
+
+#[derive(Default)]
+pub struct StreamAcks {
+    runs: Windows,
+}
+
+// Keeps a run's window registered for acks; dropping it unregisters the run, also when the
+// run's future is dropped halfway because the run was aborted or a sibling run failed
+pub struct AckRegistration {
+    runs: Windows,
+    run_id: String,
+    window: Arc<StreamWindow>,
+}
+
+impl AckRegistration {
+    pub fn window(&self) -> Arc<StreamWindow> {
+        self.window.clone()
+    }
+}
+
+impl Drop for AckRegistration {
+    fn drop(&mut self) {
+        let mut runs = self.runs.lock().unwrap();
+        // A later run registered under the same ID keeps its own window
+        if runs.get(&self.run_id).is_some_and(|w| Arc::ptr_eq(w, &self.window)) {
+            runs.remove(&self.run_id);
+        }
+    }
+}
+
+impl StreamAcks {
+    pub fn register(&self, run_id: &str) -> AckRegistration {
+        let window = Arc::new(StreamWindow::default());
+        self.runs.lock().unwrap().insert(run_id.to_string(), window.clone());
+        AckRegistration { runs: self.runs.clone(), run_id: run_id.to_string(), window }
+    }
+}
+
+// `received` is the total number of ai-chunk and ai-thinking events the frontend has
+// handled for the run; acks for finished runs are ignored
+#[tauri::command]
+pub async fn ack_stream(acks: State<'_, StreamAcks>, run_id: String, received: u64) -> Result<(), String> {
+    if let Some(window) = acks.runs.lock().unwrap().get(&run_id) {
+        window.ack(received);
+    }
+    Ok(())
+}
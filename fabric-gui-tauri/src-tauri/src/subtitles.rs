@@ -0,0 +1,112 @@
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use crate::ingest;
+use crate::transcribe::Segment;
+
+// Common subtitle guidance: lines of at most ~42 characters, two lines per cue
+const MAX_LINE_CHARS: usize = 42;
+
+// "00:01:02,345" for SRT, "00:01:02.345" for VTT
+fn cue_time(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+// Breaks a cue's text into two balanced lines when it is too long for one
+fn wrap(text: &str) -> String {
+    if text.chars().count() <= MAX_LINE_CHARS {
+        return text.to_string();
+    }
+    let middle = text.len() / 2;
+    let split = text
+        .match_indices(' ')
+        .map(|(i, _)| i)
+        .min_by_key(|i| i.abs_diff(middle));
+    match split {
+        Some(i) => format!("{}\n{}", &text[..i], &text[i + 1..]),
+        None => text.to_string(),
+    }
+}
+
+// Fits the pattern output to the original cues. Output that kept one line per cue (with or
+// without the "[hh:mm:ss]" prefixes the transcript had) maps line for line; anything else
+// is spread over the cues in proportion to how much text each originally held.
+fn fit(cues: &[Segment], output: &str) -> Vec<String> {
+    let prefix = Regex::new(r"^\s*(?:\[\d{1,2}:\d{2}(?::\d{2})?\]|\d{1,2}:\d{2}(?::\d{2})?\s*[-–:])\s*").unwrap();
+    let lines: Vec<String> = output
+        .lines()
+        .map(|l| prefix.replace(l, "").trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect();
+    if lines.len() == cues.len() {
+        return lines;
+    }
+
+    let words: Vec<&str> = lines.iter().flat_map(|l| l.split_whitespace()).collect();
+    let weights: Vec<usize> = cues.iter().map(|c| c.text.split_whitespace().count().max(1)).collect();
+    let total: usize = weights.iter().sum();
+
+    let mut fitted = Vec::with_capacity(cues.len());
+    let mut taken = 0;
+    let mut weight_so_far = 0;
+    for weight in weights {
+        weight_so_far += weight;
+        let until = (words.len() * weight_so_far).div_ceil(total).min(words.len());
+        fitted.push(words[taken..until].join(" "));
+        taken = until;
+    }
+    fitted
+}
+
+fn render(cues: &[Segment], texts: &[String], vtt: bool) -> String {
+    let mut file = if vtt { "WEBVTT\n\n".to_string() } else { String::new() };
+    let separator = if vtt { '.' } else { ',' };
+    let mut number = 0;
+    for (cue, text) in cues.iter().zip(texts) {
+        if text.is_empty() {
+            continue;
+        }
+        number += 1;
+        if !vtt {
+            file.push_str(&format!("{}\n", number));
+        }
+        file.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            cue_time(cue.start, separator),
+            cue_time(cue.end, separator),
+            wrap(text)
+        ));
+    }
+    file
+}
+
+// Writes pattern output (a translation or cleanup of a subtitle transcript) back into
+// subtitles with the source file's timing. The format follows the target's extension.
+#[tauri::command]
+pub async fn export_subtitles(source_path: String, output: String, target_path: String) -> Result<usize, String> {
+    let source = fs::read_to_string(&source_path).map_err(|e| format!("Could not read {}: {}", source_path, e))?;
+    let cues = ingest::parse_subtitles(&source);
+    if cues.is_empty() {
+        return Err("The source file has no subtitle cues to take timing from.".to_string());
+    }
+    let vtt = match Path::new(&target_path).extension().map(|e| e.to_string_lossy().to_lowercase()).as_deref() {
+        Some("vtt") => true,
+        Some("srt") => false,
+        _ => return Err("Save subtitles as .srt or .vtt.".to_string()),
+    };
+    if output.trim().is_empty() {
+        return Err("There is no output to write.".to_string());
+    }
+
+    let texts = fit(&cues, &output);
+    fs::write(&target_path, render(&cues, &texts, vtt)).map_err(|e| e.to_string())?;
+    Ok(texts.iter().filter(|t| !t.is_empty()).count())
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tauri::State;
+use crate::ai_client::{self, AIRequest};
+use crate::patterns::{pattern_dirs, pattern_sources};
+use crate::settings::{Settings, SettingsState};
+
+const SUGGESTION_COUNT: usize = 3;
+// How many keyword matches the model gets to choose from
+const CANDIDATE_COUNT: usize = 25;
+const MAX_INPUT_CHARS: usize = 4000;
+
+#[derive(Serialize)]
+pub struct PatternSuggestion {
+    pub name: String,
+    pub description: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SuggestionResult {
+    pub suggestions: Vec<PatternSuggestion>,
+    // "model" when a model ranked the candidates, "keyword" for the offline fallback
+    pub source: &'static str,
+}
+
+#[derive(Deserialize)]
+struct ModelPick {
+    name: String,
+    reason: Option<String>,
+}
+
+// A small, cheap model per vendor is plenty for picking from a short list
+pub fn suggestion_model(vendor: &str) -> Option<&'static str> {
+    match vendor {
+        "google" | "vertex" => Some("gemini-2.5-flash"),
+        "openai" => Some("gpt-4o-mini"),
+        "anthropic" => Some("claude-3-5-haiku-latest"),
+        "xai" => Some("grok-3-mini"),
+        "deepseek" => Some("deepseek-chat"),
+        _ => None,
+    }
+}
+
+// Prefers the one-line summaries in pattern_explanations.md and falls back to the
+// first paragraph of each pattern's system.md
+fn pattern_descriptions(settings: &Settings) -> Vec<(String, String)> {
+    let mut explanations = HashMap::new();
+    for dir in pattern_sources(settings) {
+        if let Ok(content) = fs::read_to_string(dir.join("pattern_explanations.md")) {
+            for line in content.lines() {
+                let Some(rest) = line.split_once(". **").map(|(_, rest)| rest) else { continue };
+                if let Some((name, description)) = rest.split_once("**:") {
+                    explanations.entry(name.to_string()).or_insert_with(|| description.trim().to_string());
+                }
+            }
+        }
+    }
+
+    pattern_dirs(settings)
+        .into_iter()
+        .map(|(name, dir)| {
+            let description = explanations
+                .get(&name)
+                .cloned()
+                .unwrap_or_else(|| first_paragraph(&dir.join("system.md")));
+            (name, description)
+        })
+        .collect()
+}
+
+fn first_paragraph(path: &Path) -> String {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    content
+        .split("\n\n")
+        .map(str::trim)
+        .find(|p| !p.is_empty() && !p.starts_with('#'))
+        .map(|p| p.chars().take(300).collect())
+        .unwrap_or_default()
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.len() > 2)
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+// BM25 over pattern names and descriptions
+fn keyword_rank(input: &str, patterns: &[(String, String)]) -> Vec<usize> {
+    const K1: f64 = 1.2;
+    const B: f64 = 0.75;
+
+    let query: HashSet<String> = tokenize(input).into_iter().collect();
+    let docs: Vec<Vec<String>> = patterns
+        .iter()
+        .map(|(name, description)| tokenize(&format!("{} {}", name.replace('_', " "), description)))
+        .collect();
+    let avg_len = docs.iter().map(Vec::len).sum::<usize>() as f64 / docs.len().max(1) as f64;
+
+    let mut doc_freq: HashMap<&str, usize> = HashMap::new();
+    for doc in &docs {
+        for term in doc.iter().collect::<HashSet<_>>() {
+            *doc_freq.entry(term.as_str()).or_default() += 1;
+        }
+    }
+
+    let n = docs.len() as f64;
+    let mut scored: Vec<(usize, f64)> = docs
+        .iter()
+        .enumerate()
+        .map(|(i, doc)| {
+            let score = query
+                .iter()
+                .map(|term| {
+                    let tf = doc.iter().filter(|t| *t == term).count() as f64;
+                    if tf == 0.0 {
+                        return 0.0;
+                    }
+                    let df = doc_freq.get(term.as_str()).copied().unwrap_or(0) as f64;
+                    let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                    idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * doc.len() as f64 / avg_len))
+                })
+                .sum();
+            (i, score)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+async fn model_rank(
+    vendor: &str,
+    api_key: String,
+    input: &str,
+    candidates: &[&(String, String)],
+) -> Result<Vec<ModelPick>, String> {
+    let model = suggestion_model(vendor).ok_or_else(|| "No suggestion model for this vendor.".to_string())?;
+    let list = candidates
+        .iter()
+        .map(|(name, description)| format!("- {}: {}", name, description))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let request = AIRequest {
+        vendor: vendor.to_string(),
+        model: model.to_string(),
+        api_key,
+        system_prompt: format!(
+            "You recommend Fabric patterns. From the list below, choose the {} patterns that are most \
+             useful to run on the user's content. Respond with only a JSON array of objects with \
+             \"name\" and \"reason\" (one short sentence) fields, best match first.\n\nPatterns:\n{}",
+            SUGGESTION_COUNT, list
+        ),
+        user_input: input.chars().take(MAX_INPUT_CHARS).collect(),
+        temperature: 0.0,
+        top_p: 1.0,
+        ..Default::default()
+    };
+
+    let response = ai_client::complete(&request).await?;
+    let start = response.find('[').ok_or_else(|| "Model returned no JSON array.".to_string())?;
+    let end = response.rfind(']').ok_or_else(|| "Model returned no JSON array.".to_string())?;
+    serde_json::from_str(&response[start..=end]).map_err(|e| e.to_string())
+}
+
+// Keyword matching narrows ~200 patterns to a shortlist; a cheap model picks the best three
+// when a key for the default vendor is available, otherwise the keyword ranking is used as-is
+#[tauri::command]
+pub async fn suggest_patterns(state: State<'_, SettingsState>, input_text: String) -> Result<SuggestionResult, String> {
+    let settings = state.get();
+    let patterns = pattern_descriptions(&settings);
+    if patterns.is_empty() {
+        return Err("Fabric patterns directory not found. Please install Fabric first.".to_string());
+    }
+
+    let ranked = keyword_rank(&input_text, &patterns);
+    let candidates: Vec<&(String, String)> = if ranked.is_empty() {
+        patterns.iter().take(CANDIDATE_COUNT).collect()
+    } else {
+        ranked.iter().take(CANDIDATE_COUNT).map(|&i| &patterns[i]).collect()
+    };
+
+    let vendor = settings.default_vendor.clone().unwrap_or_else(|| "google".to_string());
+    if let Some(api_key) = settings.api_key(&vendor) {
+        if let Ok(picks) = model_rank(&vendor, api_key, &input_text, &candidates).await {
+            let suggestions: Vec<PatternSuggestion> = picks
+                .into_iter()
+                .filter_map(|pick| {
+                    let (name, description) = patterns.iter().find(|(name, _)| *name == pick.name)?;
+                    Some(PatternSuggestion {
+                        name: name.clone(),
+                        description: description.clone(),
+                        reason: pick.reason,
+                    })
+                })
+                .take(SUGGESTION_COUNT)
+                .collect();
+            if !suggestions.is_empty() {
+                return Ok(SuggestionResult { suggestions, source: "model" });
+            }
+        }
+    }
+
+    Ok(SuggestionResult {
+        suggestions: ranked
+            .into_iter()
+            .take(SUGGESTION_COUNT)
+            .map(|i| PatternSuggestion {
+                name: patterns[i].0.clone(),
+                description: patterns[i].1.clone(),
+                reason: None,
+            })
+            .collect(),
+        source: "keyword",
+    })
+}
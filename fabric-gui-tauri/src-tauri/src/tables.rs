@@ -0,0 +1,175 @@
+use regex::Regex;
+use rust_xlsxwriter::{Format, Workbook};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use tauri::State;
+use crate::history::HistoryState;
+
+// Excel's limit on worksheet names
+const MAX_SHEET_NAME: usize = 31;
+
+struct Table {
+    // The closest heading above the table, used to name its sheet
+    title: Option<String>,
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+#[derive(Serialize)]
+pub struct TableExport {
+    pub tables: usize,
+    pub files: Vec<String>,
+}
+
+fn split_row(line: &str) -> Vec<String> {
+    let inner = line.trim().trim_start_matches('|');
+    let inner = inner.strip_suffix('|').filter(|_| !inner.ends_with("\\|")).unwrap_or(inner);
+    // An escaped \| is part of the cell
+    inner
+        .replace("\\|", "\u{0}")
+        .split('|')
+        .map(|cell| plain(&cell.replace('\u{0}', "|")))
+        .collect()
+}
+
+// Cell text without markdown emphasis, code ticks or link targets
+fn plain(cell: &str) -> String {
+    let link = Regex::new(r"\[([^\]]*)\]\([^)]*\)").unwrap();
+    let text = link.replace_all(cell.trim(), "$1").to_string();
+    let text = text.replace("<br>", "\n").replace("<br/>", "\n");
+    let emphasis = Regex::new(r"(\*\*|__|`)(.*?)(\*\*|__|`)").unwrap();
+    emphasis.replace_all(&text, "$2").trim().to_string()
+}
+
+fn parse_tables(output: &str) -> Vec<Table> {
+    let rule = Regex::new(r"^\s*\|?\s*:?-{3,}:?\s*(\|\s*:?-{3,}:?\s*)*\|?\s*$").unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    let mut tables = Vec::new();
+    let mut title = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        if let Some(heading) = line.strip_prefix('#') {
+            title = Some(heading.trim_start_matches('#').trim().to_string());
+        }
+        // A table is a row containing pipes followed by a |---|---| rule
+        if line.contains('|') && lines.get(i + 1).is_some_and(|next| rule.is_match(next)) {
+            let header = split_row(line);
+            let mut rows = Vec::new();
+            i += 2;
+            while i < lines.len() && lines[i].contains('|') && !lines[i].trim().is_empty() {
+                let mut row = split_row(lines[i]);
+                row.resize(header.len(), String::new());
+                rows.push(row);
+                i += 1;
+            }
+            tables.push(Table { title: title.take(), header, rows });
+            continue;
+        }
+        i += 1;
+    }
+    tables
+}
+
+fn write_csv(table: &Table, path: &Path) -> Result<(), String> {
+    let mut writer = csv::Writer::from_path(path).map_err(|e| e.to_string())?;
+    writer.write_record(&table.header).map_err(|e| e.to_string())?;
+    for row in &table.rows {
+        writer.write_record(row).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+// "1,234.5", "42%" and "$10" stay text in markdown; plain numbers become numeric cells
+fn number(cell: &str) -> Option<f64> {
+    let value = cell.trim();
+    if value.is_empty() || value.starts_with('+') || (value.starts_with('0') && value.len() > 1 && !value.starts_with("0.")) {
+        return None;
+    }
+    value.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+fn sheet_name(table: &Table, index: usize, used: &mut HashSet<String>) -> String {
+    let base: String = table
+        .title
+        .as_deref()
+        .unwrap_or("")
+        .chars()
+        .filter(|c| !"[]:*?/\\".contains(*c))
+        .take(MAX_SHEET_NAME - 4)
+        .collect::<String>()
+        .trim()
+        .to_string();
+    let base = if base.is_empty() { format!("Table {}", index + 1) } else { base };
+    let mut name = base.clone();
+    let mut n = 2;
+    while !used.insert(name.to_lowercase()) {
+        name = format!("{} {}", base, n);
+        n += 1;
+    }
+    name
+}
+
+fn write_xlsx(tables: &[Table], path: &Path) -> Result<(), String> {
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let mut used = HashSet::new();
+    for (index, table) in tables.iter().enumerate() {
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(sheet_name(table, index, &mut used)).map_err(|e| e.to_string())?;
+        for (col, cell) in table.header.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, cell, &bold).map_err(|e| e.to_string())?;
+        }
+        for (r, row) in table.rows.iter().enumerate() {
+            for (col, cell) in row.iter().enumerate() {
+                let (row, col) = (r as u32 + 1, col as u16);
+                match number(cell) {
+                    Some(n) => sheet.write_number(row, col, n),
+                    None => sheet.write_string(row, col, cell),
+                }
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        sheet.autofit();
+    }
+    workbook.save(path).map_err(|e| e.to_string())
+}
+
+// Writes the markdown tables of a run's output to a spreadsheet. An .xlsx `path` gets one
+// sheet per table; a .csv path gets the first table, and further tables go next to it as
+// name-2.csv, name-3.csv, ...
+#[tauri::command]
+pub async fn export_tables(
+    history: State<'_, HistoryState>,
+    run_id: String,
+    path: String,
+) -> Result<TableExport, String> {
+    let entry = history
+        .get(&run_id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))?;
+    let tables = parse_tables(&entry.output);
+    if tables.is_empty() {
+        return Err("The output has no markdown tables.".to_string());
+    }
+
+    let target = Path::new(&path);
+    let files = match target.extension().map(|e| e.to_string_lossy().to_lowercase()).as_deref() {
+        Some("xlsx") => {
+            write_xlsx(&tables, target)?;
+            vec![path.clone()]
+        }
+        Some("csv") => {
+            let stem = target.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let mut files = Vec::new();
+            for (index, table) in tables.iter().enumerate() {
+                let file = if index == 0 { target.to_path_buf() } else { target.with_file_name(format!("{}-{}.csv", stem, index + 1)) };
+                write_csv(table, &file)?;
+                files.push(file.to_string_lossy().to_string());
+            }
+            files
+        }
+        _ => return Err("Save tables as .csv or .xlsx.".to_string()),
+    };
+    Ok(TableExport { tables: tables.len(), files })
+}
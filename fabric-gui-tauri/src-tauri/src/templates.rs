@@ -0,0 +1,180 @@
+use chrono::{Local, TimeZone};
+use handlebars::{no_escape, Handlebars};
+use pulldown_cmark::{html, Options, Parser};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, State};
+use crate::history::{HistoryEntry, HistoryState};
+
+// Shipped layouts; a user template with the same name replaces one, and deleting it brings the
+// original back
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("note.md", include_str!("../resources/templates/note.md.hbs")),
+    ("report.html", include_str!("../resources/templates/report.html.hbs")),
+    ("email.txt", include_str!("../resources/templates/email.txt.hbs")),
+];
+
+#[derive(Serialize)]
+pub struct TemplateInfo {
+    pub name: String,
+    pub builtin: bool,
+    // A built-in template the user has edited
+    pub customized: bool,
+}
+
+fn templates_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("templates"))
+        .map_err(|e| e.to_string())
+}
+
+// Names are file names with the output format as extension, e.g. "weekly.md" or "skin.html"
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.contains("..")
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a valid template name.", name))
+    }
+}
+
+fn template_source(app_handle: &AppHandle, name: &str) -> Result<String, String> {
+    check_name(name)?;
+    let path = templates_dir(app_handle)?.join(format!("{}.hbs", name));
+    if path.exists() {
+        return fs::read_to_string(path).map_err(|e| e.to_string());
+    }
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, source)| source.to_string())
+        .ok_or_else(|| format!("Template '{}' does not exist.", name))
+}
+
+// Only HTML templates escape values; markdown and text ones take the output as it is
+fn engine(name: &str) -> Handlebars<'static> {
+    let mut engine = Handlebars::new();
+    if !name.ends_with(".html") && !name.ends_with(".htm") {
+        engine.register_escape_fn(no_escape);
+    }
+    engine
+}
+
+// The first H1 of the output, else the pattern name
+pub fn title(entry: &HistoryEntry) -> String {
+    entry
+        .output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("# "))
+        .map(|t| t.trim().trim_matches('*').trim().to_string())
+        .filter(|t| !t.is_empty())
+        .or_else(|| entry.pattern.clone())
+        .unwrap_or_else(|| "Fabric output".to_string())
+}
+
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut out = String::new();
+    html::push_html(&mut out, Parser::new_ext(markdown, Options::all()));
+    out
+}
+
+// What templates can use: {{title}}, {{output}}, {{{output_html}}}, {{date}}, {{time}},
+// {{pattern}}, {{vendor}}, {{model}}, {{input}}, {{id}}, {{duration_ms}}
+fn context(entry: &HistoryEntry) -> Value {
+    let created = Local.timestamp_opt(entry.created_at, 0).single().unwrap_or_else(Local::now);
+    json!({
+        "id": entry.id,
+        "title": title(entry),
+        "output": entry.output,
+        "output_html": markdown_to_html(&entry.output),
+        "input": entry.input,
+        "pattern": entry.pattern,
+        "vendor": entry.vendor,
+        "model": entry.model,
+        "date": created.format("%Y-%m-%d").to_string(),
+        "time": created.format("%H:%M").to_string(),
+        "datetime": created.to_rfc3339(),
+        "duration_ms": entry.duration_ms,
+        "success": entry.success,
+    })
+}
+
+pub fn render(app_handle: &AppHandle, template: &str, entry: &HistoryEntry) -> Result<String, String> {
+    let source = template_source(app_handle, template)?;
+    engine(template)
+        .render_template(&source, &context(entry))
+        .map_err(|e| format!("Rendering '{}' failed: {}", template, e))
+}
+
+#[tauri::command]
+pub async fn list_templates(app_handle: AppHandle) -> Result<Vec<TemplateInfo>, String> {
+    let dir = templates_dir(&app_handle)?;
+    let mut templates: Vec<TemplateInfo> = BUILTIN_TEMPLATES
+        .iter()
+        .map(|(name, _)| TemplateInfo {
+            name: name.to_string(),
+            builtin: true,
+            customized: dir.join(format!("{}.hbs", name)).exists(),
+        })
+        .collect();
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for name in entries.flatten().filter_map(|e| e.file_name().to_str()?.strip_suffix(".hbs").map(str::to_string)) {
+            if !templates.iter().any(|t| t.name == name) {
+                templates.push(TemplateInfo { name, builtin: false, customized: false });
+            }
+        }
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+#[tauri::command]
+pub async fn get_template(app_handle: AppHandle, name: String) -> Result<String, String> {
+    template_source(&app_handle, &name)
+}
+
+// Saves a template after checking it compiles, so a typo shows up in the editor rather than
+// at export time
+#[tauri::command]
+pub async fn save_template(app_handle: AppHandle, name: String, source: String) -> Result<(), String> {
+    check_name(&name)?;
+    engine(&name)
+        .register_template_string(&name, &source)
+        .map_err(|e| format!("The template doesn't compile: {}", e))?;
+    let dir = templates_dir(&app_handle)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    fs::write(dir.join(format!("{}.hbs", name)), source).map_err(|e| e.to_string())
+}
+
+// Deletes a user template; for an edited built-in this restores the shipped version
+#[tauri::command]
+pub async fn delete_template(app_handle: AppHandle, name: String) -> Result<(), String> {
+    check_name(&name)?;
+    let path = templates_dir(&app_handle)?.join(format!("{}.hbs", name));
+    if !path.exists() {
+        return Err(format!("Template '{}' has no saved version to delete.", name));
+    }
+    fs::remove_file(path).map_err(|e| e.to_string())
+}
+
+// Renders a recorded run through a Handlebars template, e.g. a markdown note, an HTML report
+// or an email body
+#[tauri::command]
+pub async fn render_output(
+    app_handle: AppHandle,
+    history: State<'_, HistoryState>,
+    template: String,
+    run_id: String,
+) -> Result<String, String> {
+    let entry = history
+        .get(&run_id)?
+        .ok_or_else(|| format!("History entry '{}' not found.", run_id))?;
+    render(&app_handle, &template, &entry)
+}
@@ -0,0 +1,277 @@
+use regex::Regex;
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+use tauri::State;
+use crate::http::{self, AuditedSend};
+use crate::input::{self, TruncationInfo};
+use crate::settings::{Settings, SettingsState};
+
+const X_API: &str = "https://api.twitter.com/2";
+const X_SYNDICATION_URL: &str = "https://cdn.syndication.twimg.com/tweet-result";
+// Tweets followed up the reply chain when no X API token is set
+const MAX_THREAD_TWEETS: usize = 50;
+const DEFAULT_REDDIT_COMMENTS: usize = 30;
+// Replies below this depth are dropped; deep subthreads rarely add to a summary
+const MAX_REPLY_DEPTH: usize = 3;
+
+#[derive(Serialize)]
+pub struct Thread {
+    // "x" or "reddit"
+    pub platform: String,
+    pub url: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    // Posts followed by comments as plain markdown
+    pub text: String,
+    // Set by fetch_thread when the text was cut to the scrape input limit
+    pub truncation: Option<TruncationInfo>,
+}
+
+fn x_status_id(url: &str) -> Option<String> {
+    let status = Regex::new(r"^https?://(?:www\.|mobile\.)?(?:twitter|x)\.com/[^/]+/status(?:es)?/(\d+)").unwrap();
+    status.captures(url.trim()).map(|c| c[1].to_string())
+}
+
+fn reddit_post_id(url: &str) -> Option<String> {
+    let post = Regex::new(r"^https?://(?:(?:www\.|old\.|new\.|np\.)?reddit\.com/(?:r/[^/]+/)?comments/|redd\.it/)([a-z0-9]+)").unwrap();
+    post.captures(url.trim()).map(|c| c[1].to_string())
+}
+
+pub fn is_thread_url(url: &str) -> bool {
+    x_status_id(url).is_some() || reddit_post_id(url).is_some()
+}
+
+async fn get_json(url: &str, bearer: Option<&str>) -> Result<Value, String> {
+    // Reddit throttles requests without a descriptive user agent
+    let mut request = http::client_for(url)?
+        .get(url)
+        .header("User-Agent", format!("fabric-gui/{}", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(30));
+    if let Some(token) = bearer {
+        request = request.bearer_auth(token);
+    }
+    let res = request.send_audited("threads", None).await.map_err(http::network_error)?;
+    let status = res.status();
+    if status.as_u16() == 404 {
+        return Err("The post was not found; it may be deleted or private.".to_string());
+    }
+    if !status.is_success() {
+        return Err(format!("Could not load {} ({})", url, status));
+    }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+// The token the embed widgets send along with a tweet id
+fn syndication_token(id: &str) -> String {
+    let value = id.parse::<f64>().unwrap_or(0.0) / 1e15 * std::f64::consts::PI;
+    let digit = |d: u32| std::char::from_digit(d, 36).unwrap();
+    let mut whole = value.trunc() as u64;
+    let mut token = String::new();
+    while whole > 0 {
+        token.insert(0, digit((whole % 36) as u32));
+        whole /= 36;
+    }
+    let mut fraction = value.fract();
+    for _ in 0..12 {
+        if fraction == 0.0 {
+            break;
+        }
+        fraction *= 36.0;
+        token.push(digit(fraction.trunc() as u32));
+        fraction = fraction.fract();
+    }
+    token.replace('0', "")
+}
+
+// Without API access only the reply chain above a tweet is public, so the thread is
+// rebuilt from its last tweet upwards while the author stays the same
+async fn x_thread_public(id: &str) -> Result<(String, Vec<String>), String> {
+    let mut tweets = Vec::new();
+    let mut author: Option<String> = None;
+    let mut next = Some(id.to_string());
+    while let Some(id) = next.take() {
+        if tweets.len() >= MAX_THREAD_TWEETS {
+            break;
+        }
+        let url = format!("{}?id={}&token={}", X_SYNDICATION_URL, id, syndication_token(&id));
+        let tweet = match get_json(&url, None).await {
+            Ok(tweet) => tweet,
+            Err(e) if tweets.is_empty() => return Err(e),
+            Err(_) => break,
+        };
+        let screen_name = tweet["user"]["screen_name"].as_str().unwrap_or("").to_string();
+        if author.as_ref().is_some_and(|a| *a != screen_name) {
+            break;
+        }
+        author.get_or_insert(screen_name);
+        if let Some(text) = tweet["text"].as_str() {
+            tweets.insert(0, text.to_string());
+        }
+        next = tweet["parent"]["id_str"]
+            .as_str()
+            .or_else(|| tweet["in_reply_to_status_id_str"].as_str())
+            .map(|s| s.to_string());
+    }
+    Ok((author.unwrap_or_default(), tweets))
+}
+
+fn tweet_text(tweet: &Value) -> String {
+    // Long posts carry their full text in note_tweet; `text` is truncated
+    tweet["note_tweet"]["text"].as_str().or_else(|| tweet["text"].as_str()).unwrap_or("").to_string()
+}
+
+// With an API token the whole conversation is searched for the author's own posts; the
+// recent search endpoint only covers the last seven days, older threads fall back to the
+// reply chain
+async fn x_thread_api(id: &str, token: &str) -> Result<(String, Vec<String>), String> {
+    let url = format!(
+        "{}/tweets/{}?tweet.fields=conversation_id,created_at,note_tweet&expansions=author_id&user.fields=username",
+        X_API, id
+    );
+    let tweet = get_json(&url, Some(token)).await?;
+    let username = tweet["includes"]["users"][0]["username"].as_str().unwrap_or("").to_string();
+    let conversation = tweet["data"]["conversation_id"].as_str().unwrap_or(id).to_string();
+
+    let query = format!("conversation_id:{} from:{}", conversation, username);
+    let mut search = Url::parse(&format!("{}/tweets/search/recent", X_API)).unwrap();
+    search
+        .query_pairs_mut()
+        .append_pair("query", &query)
+        .append_pair("max_results", "100")
+        .append_pair("tweet.fields", "created_at,note_tweet");
+    let replies = get_json(search.as_str(), Some(token)).await?;
+    let mut posts: Vec<(u64, String)> = replies["data"]
+        .as_array()
+        .map(|data| data.iter().map(|t| (t["id"].as_str().and_then(|i| i.parse().ok()).unwrap_or(0), tweet_text(t))).collect())
+        .unwrap_or_default();
+    if posts.is_empty() {
+        return x_thread_public(id).await;
+    }
+
+    // The first post of the conversation isn't a reply, so search doesn't return it
+    if !posts.iter().any(|(i, _)| i.to_string() == conversation) {
+        let url = format!("{}/tweets/{}?tweet.fields=note_tweet", X_API, conversation);
+        let root = get_json(&url, Some(token)).await?;
+        posts.push((conversation.parse().unwrap_or(0), tweet_text(&root["data"])));
+    }
+    posts.sort_by_key(|(i, _)| *i);
+    Ok((username, posts.into_iter().map(|(_, text)| text).collect()))
+}
+
+async fn fetch_x(settings: &Settings, url: &str, id: &str) -> Result<Thread, String> {
+    let (author, tweets) = match settings.api_key("x") {
+        Some(token) => x_thread_api(id, &token).await?,
+        None => x_thread_public(id).await?,
+    };
+    let count = tweets.len();
+    let body = tweets
+        .iter()
+        .enumerate()
+        .map(|(i, t)| if count > 1 { format!("{}/{} {}", i + 1, count, t) } else { t.clone() })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok(Thread {
+        platform: "x".to_string(),
+        url: url.to_string(),
+        title: None,
+        text: format!("Thread by @{}\n\n{}", author, body),
+        author: Some(author),
+        truncation: None,
+    })
+}
+
+fn reddit_comments(listing: &Value, depth: usize, remaining: &mut usize, output: &mut Vec<String>) {
+    let mut comments: Vec<&Value> = listing["data"]["children"]
+        .as_array()
+        .map(|c| c.iter().filter(|c| c["kind"] == "t1").map(|c| &c["data"]).collect())
+        .unwrap_or_default();
+    comments.sort_by_key(|c| std::cmp::Reverse(c["score"].as_i64().unwrap_or(0)));
+    for comment in comments {
+        if *remaining == 0 {
+            return;
+        }
+        let body = comment["body"].as_str().unwrap_or("");
+        if body.is_empty() || body == "[deleted]" || body == "[removed]" {
+            continue;
+        }
+        *remaining -= 1;
+        let indent = "  ".repeat(depth);
+        let body = body.trim().replace('\n', &format!("\n{}  ", indent));
+        output.push(format!(
+            "{}- u/{} ({} points): {}",
+            indent,
+            comment["author"].as_str().unwrap_or("[deleted]"),
+            comment["score"].as_i64().unwrap_or(0),
+            body
+        ));
+        if depth + 1 < MAX_REPLY_DEPTH {
+            reddit_comments(&comment["replies"], depth + 1, remaining, output);
+        }
+    }
+}
+
+async fn fetch_reddit(url: &str, id: &str, max_comments: usize) -> Result<Thread, String> {
+    let api = format!("https://www.reddit.com/comments/{}.json?sort=top&raw_json=1&limit={}", id, max_comments.max(1) * 2);
+    let json = get_json(&api, None).await?;
+    let post = &json[0]["data"]["children"][0]["data"];
+    let title = post["title"].as_str().map(|t| t.to_string());
+    let author = post["author"].as_str().map(|a| a.to_string());
+
+    let mut text = format!(
+        "# {}\n\nr/{} · u/{} · {} points\n",
+        title.as_deref().unwrap_or(""),
+        post["subreddit"].as_str().unwrap_or(""),
+        author.as_deref().unwrap_or("[deleted]"),
+        post["score"].as_i64().unwrap_or(0)
+    );
+    if let Some(body) = post["selftext"].as_str().filter(|b| !b.trim().is_empty()) {
+        text.push_str(&format!("\n{}\n", body.trim()));
+    }
+    if post["is_self"] == false {
+        if let Some(link) = post["url"].as_str() {
+            text.push_str(&format!("\nLink: {}\n", link));
+        }
+    }
+
+    let mut comments = Vec::new();
+    let mut remaining = max_comments;
+    reddit_comments(&json[1], 0, &mut remaining, &mut comments);
+    if !comments.is_empty() {
+        text.push_str(&format!("\n## Top comments\n\n{}\n", comments.join("\n")));
+    }
+    Ok(Thread {
+        platform: "reddit".to_string(),
+        url: url.to_string(),
+        title,
+        author,
+        text,
+        truncation: None,
+    })
+}
+
+pub async fn fetch(settings: &Settings, url: &str, max_comments: Option<usize>) -> Result<Thread, String> {
+    if let Some(id) = x_status_id(url) {
+        return fetch_x(settings, url, &id).await;
+    }
+    if let Some(id) = reddit_post_id(url) {
+        return fetch_reddit(url, &id, max_comments.unwrap_or(DEFAULT_REDDIT_COMMENTS)).await;
+    }
+    Err(format!("'{}' is not an X post or Reddit thread link.", url))
+}
+
+// Rebuilds an X thread (the author's own posts in order) or a Reddit post with its top
+// comments. X uses the API token saved under api_keys "x" when present and otherwise
+// follows the public reply chain, so pass the thread's last post in that case.
+#[tauri::command]
+pub async fn fetch_thread(
+    state: State<'_, SettingsState>,
+    url: String,
+    max_comments: Option<usize>,
+) -> Result<Thread, String> {
+    let settings = state.get();
+    let thread = fetch(&settings, &url, max_comments).await?;
+    let (text, truncation) = input::limit(&settings, "scrape", thread.text);
+    Ok(Thread { text, truncation, ..thread })
+}
@@ -0,0 +1,272 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tauri::State;
+use crate::http::{self, AuditedSend};
+use crate::settings::{Settings, SettingsState};
+
+const LINEAR_API: &str = "https://api.linear.app/graphql";
+
+const LINEAR_ISSUE_QUERY: &str = "query($id: String!) {
+  issue(id: $id) {
+    id identifier title description url priorityLabel
+    state { name } assignee { name } creator { name }
+    labels { nodes { name } }
+    comments(first: 100) { nodes { body createdAt user { name } } }
+  }
+}";
+
+const LINEAR_COMMENT_MUTATION: &str = "mutation($issueId: String!, $body: String!) {
+  commentCreate(input: { issueId: $issueId, body: $body }) { success comment { url } }
+}";
+
+#[derive(Serialize)]
+pub struct Ticket {
+    // "jira" or "linear"
+    pub tracker: String,
+    pub key: String,
+    pub title: String,
+    pub status: Option<String>,
+    pub url: String,
+    pub comments: usize,
+    // Description and comment history as markdown for a pattern
+    pub text: String,
+}
+
+// Jira browse links, Linear issue links, or a bare key such as "ENG-123"; a bare key goes
+// to whichever tracker is configured unless `tracker` says otherwise
+fn resolve(settings: &Settings, reference: &str, tracker: Option<&str>) -> Result<(String, String), String> {
+    let reference = reference.trim();
+    let linear = Regex::new(r"^https?://linear\.app/[^/]+/issue/([A-Za-z0-9]+-\d+)").unwrap();
+    let jira = Regex::new(r"^https?://[^/]+/(?:browse/|.*[?&]selectedIssue=)([A-Z][A-Z0-9_]+-\d+)").unwrap();
+    let key = Regex::new(r"^[A-Za-z][A-Za-z0-9_]*-\d+$").unwrap();
+
+    if let Some(c) = linear.captures(reference) {
+        return Ok(("linear".to_string(), c[1].to_uppercase()));
+    }
+    if let Some(c) = jira.captures(reference) {
+        return Ok(("jira".to_string(), c[1].to_string()));
+    }
+    if !key.is_match(reference) {
+        return Err(format!("'{}' is not a Jira or Linear ticket key or link.", reference));
+    }
+    let tracker = match tracker {
+        Some(t) => t.to_string(),
+        None => match (jira_configured(settings), settings.api_key("linear").is_some()) {
+            (true, false) => "jira".to_string(),
+            (false, true) => "linear".to_string(),
+            (false, false) => return Err("Connect Jira or Linear in settings to import tickets.".to_string()),
+            (true, true) => return Err(format!("Both Jira and Linear are connected; choose which one {} is from.", reference)),
+        },
+    };
+    Ok((tracker, reference.to_uppercase()))
+}
+
+fn jira_configured(settings: &Settings) -> bool {
+    settings.jira_url.as_ref().is_some_and(|u| !u.trim().is_empty()) && settings.api_key("jira").is_some()
+}
+
+// Jira Cloud takes the account email and an API token as basic auth; Server and Data
+// Center take a personal access token as a bearer token, used when no email is set
+fn jira_request(settings: &Settings, method: reqwest::Method, path: &str) -> Result<reqwest::RequestBuilder, String> {
+    if !jira_configured(settings) {
+        return Err("Set jira_url and a Jira API token in settings first.".to_string());
+    }
+    let base = settings.jira_url.as_deref().unwrap_or("").trim().trim_end_matches('/');
+    let url = format!("{}{}", base, path);
+    let token = settings.api_key("jira").unwrap_or_default();
+    let request = http::client_for(&url)?.request(method, &url).timeout(Duration::from_secs(30));
+    Ok(match settings.jira_email.as_ref().filter(|e| !e.trim().is_empty()) {
+        Some(email) => request.basic_auth(email, Some(token)),
+        None => request.bearer_auth(token),
+    })
+}
+
+async fn send_json(request: reqwest::RequestBuilder, tracker: &str) -> Result<Value, String> {
+    let res = request.send_audited("tickets", None).await.map_err(http::network_error)?;
+    let status = res.status();
+    if status.as_u16() == 401 || status.as_u16() == 403 {
+        return Err(format!("{} rejected the API token ({}).", tracker, status));
+    }
+    if status.as_u16() == 404 {
+        return Err(format!("{} has no such ticket, or the token can't see it.", tracker));
+    }
+    if !status.is_success() {
+        let text = res.text().await.unwrap_or_default();
+        return Err(format!("{} API error ({}): {}", tracker, status, &text.chars().take(300).collect::<String>()));
+    }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+fn date(timestamp: &str) -> &str {
+    &timestamp[..timestamp.len().min(10)]
+}
+
+// API v2 returns descriptions and comments as wiki markup, which models read fine;
+// v3 would return Atlassian Document Format trees
+async fn fetch_jira(settings: &Settings, key: &str) -> Result<Ticket, String> {
+    let path = format!("/rest/api/2/issue/{}?fields=summary,description,status,issuetype,priority,assignee,reporter,labels,comment", key);
+    let issue = send_json(jira_request(settings, reqwest::Method::GET, &path)?, "Jira").await?;
+    let fields = &issue["fields"];
+    let title = fields["summary"].as_str().unwrap_or("").to_string();
+    let status = fields["status"]["name"].as_str().map(|s| s.to_string());
+
+    let mut text = format!(
+        "# {}: {}\n\n{} · {} · priority {} · reported by {} · assigned to {}",
+        key,
+        title,
+        fields["issuetype"]["name"].as_str().unwrap_or("Issue"),
+        status.as_deref().unwrap_or("unknown status"),
+        fields["priority"]["name"].as_str().unwrap_or("none"),
+        fields["reporter"]["displayName"].as_str().unwrap_or("unknown"),
+        fields["assignee"]["displayName"].as_str().unwrap_or("nobody")
+    );
+    let labels: Vec<&str> = fields["labels"].as_array().into_iter().flatten().filter_map(|l| l.as_str()).collect();
+    if !labels.is_empty() {
+        text.push_str(&format!(" · labels: {}", labels.join(", ")));
+    }
+    text.push_str(&format!("\n\n## Description\n\n{}\n", fields["description"].as_str().unwrap_or("(no description)").trim()));
+
+    let comments = fields["comment"]["comments"].as_array().cloned().unwrap_or_default();
+    if !comments.is_empty() {
+        text.push_str("\n## Comments\n");
+        for c in &comments {
+            text.push_str(&format!(
+                "\n**{}** ({}):\n{}\n",
+                c["author"]["displayName"].as_str().unwrap_or("unknown"),
+                date(c["created"].as_str().unwrap_or("")),
+                c["body"].as_str().unwrap_or("").trim()
+            ));
+        }
+    }
+
+    Ok(Ticket {
+        tracker: "jira".to_string(),
+        url: format!("{}/browse/{}", settings.jira_url.as_deref().unwrap_or("").trim().trim_end_matches('/'), key),
+        key: key.to_string(),
+        title,
+        status,
+        comments: comments.len(),
+        text,
+    })
+}
+
+async fn linear(settings: &Settings, query: &str, variables: Value) -> Result<Value, String> {
+    let token = settings.api_key("linear").ok_or("Set a Linear API key in settings first.")?;
+    // Personal API keys go in the header as-is, without a Bearer prefix
+    let request = http::client_for(LINEAR_API)?
+        .post(LINEAR_API)
+        .header("Authorization", token)
+        .timeout(Duration::from_secs(30))
+        .json(&json!({"query": query, "variables": variables}));
+    let res = send_json(request, "Linear").await?;
+    if let Some(message) = res["errors"][0]["message"].as_str() {
+        return Err(format!("Linear: {}", message));
+    }
+    Ok(res["data"].clone())
+}
+
+async fn fetch_linear(settings: &Settings, key: &str) -> Result<(Ticket, String), String> {
+    let data = linear(settings, LINEAR_ISSUE_QUERY, json!({"id": key})).await?;
+    let issue = &data["issue"];
+    if issue.is_null() {
+        return Err(format!("Linear has no issue {}.", key));
+    }
+    let title = issue["title"].as_str().unwrap_or("").to_string();
+    let status = issue["state"]["name"].as_str().map(|s| s.to_string());
+
+    let mut text = format!(
+        "# {}: {}\n\n{} · priority {} · created by {} · assigned to {}",
+        key,
+        title,
+        status.as_deref().unwrap_or("unknown status"),
+        issue["priorityLabel"].as_str().unwrap_or("none"),
+        issue["creator"]["name"].as_str().unwrap_or("unknown"),
+        issue["assignee"]["name"].as_str().unwrap_or("nobody")
+    );
+    let labels: Vec<&str> = issue["labels"]["nodes"].as_array().into_iter().flatten().filter_map(|l| l["name"].as_str()).collect();
+    if !labels.is_empty() {
+        text.push_str(&format!(" · labels: {}", labels.join(", ")));
+    }
+    text.push_str(&format!("\n\n## Description\n\n{}\n", issue["description"].as_str().unwrap_or("(no description)").trim()));
+
+    // Linear lists comments newest first
+    let mut comments = issue["comments"]["nodes"].as_array().cloned().unwrap_or_default();
+    comments.sort_by(|a, b| a["createdAt"].as_str().cmp(&b["createdAt"].as_str()));
+    if !comments.is_empty() {
+        text.push_str("\n## Comments\n");
+        for c in &comments {
+            text.push_str(&format!(
+                "\n**{}** ({}):\n{}\n",
+                c["user"]["name"].as_str().unwrap_or("unknown"),
+                date(c["createdAt"].as_str().unwrap_or("")),
+                c["body"].as_str().unwrap_or("").trim()
+            ));
+        }
+    }
+
+    let ticket = Ticket {
+        tracker: "linear".to_string(),
+        key: issue["identifier"].as_str().unwrap_or(key).to_string(),
+        url: issue["url"].as_str().unwrap_or("").to_string(),
+        title,
+        status,
+        comments: comments.len(),
+        text,
+    };
+    Ok((ticket, issue["id"].as_str().unwrap_or("").to_string()))
+}
+
+// Imports a Jira or Linear ticket with its description and comment history as pattern input
+#[tauri::command]
+pub async fn fetch_ticket(
+    state: State<'_, SettingsState>,
+    reference: String,
+    tracker: Option<String>,
+) -> Result<Ticket, String> {
+    let settings = state.get();
+    let (tracker, key) = resolve(&settings, &reference, tracker.as_deref())?;
+    match tracker.as_str() {
+        "jira" => fetch_jira(&settings, &key).await,
+        "linear" => Ok(fetch_linear(&settings, &key).await?.0),
+        other => Err(format!("Unknown tracker '{}'. Use jira or linear.", other)),
+    }
+}
+
+// Posts a pattern's output back to the ticket as a comment; returns the comment's link
+// where the tracker provides one
+#[tauri::command]
+pub async fn post_ticket_comment(
+    state: State<'_, SettingsState>,
+    reference: String,
+    body: String,
+    tracker: Option<String>,
+) -> Result<Option<String>, String> {
+    if body.trim().is_empty() {
+        return Err("The comment is empty.".to_string());
+    }
+    let settings = state.get();
+    let (tracker, key) = resolve(&settings, &reference, tracker.as_deref())?;
+    match tracker.as_str() {
+        "jira" => {
+            let path = format!("/rest/api/2/issue/{}/comment", key);
+            let request = jira_request(&settings, reqwest::Method::POST, &path)?.json(&json!({"body": body}));
+            let comment = send_json(request, "Jira").await?;
+            let base = settings.jira_url.as_deref().unwrap_or("").trim().trim_end_matches('/');
+            Ok(comment["id"]
+                .as_str()
+                .map(|id| format!("{}/browse/{}?focusedCommentId={}", base, key, id)))
+        }
+        "linear" => {
+            // The mutation needs the issue's UUID rather than its identifier
+            let (_, issue_id) = fetch_linear(&settings, &key).await?;
+            let data = linear(&settings, LINEAR_COMMENT_MUTATION, json!({"issueId": issue_id, "body": body})).await?;
+            if data["commentCreate"]["success"] != true {
+                return Err("Linear did not accept the comment.".to_string());
+            }
+            Ok(data["commentCreate"]["comment"]["url"].as_str().map(|u| u.to_string()))
+        }
+        other => Err(format!("Unknown tracker '{}'. Use jira or linear.", other)),
+    }
+}
@@ -0,0 +1,155 @@
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+use uuid::Uuid;
+use crate::http::{self, AuditedSend};
+use crate::media_cache;
+use crate::residency;
+use crate::settings::Settings;
+
+// The transcription API rejects larger uploads
+pub const OPENAI_MAX_AUDIO_BYTES: u64 = 25 * 1024 * 1024;
+// Local whisper runs at about real time on a CPU, so this allows for long recordings
+const LOCAL_TIMEOUT: Duration = Duration::from_secs(3 * 60 * 60);
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "m4a", "wav", "ogg", "opus", "flac", "aac", "webm", "mp4", "mkv", "mov"];
+
+// Recordings (audio, or video with an audio track) as opposed to text transcripts
+pub fn is_audio(path: &Path) -> bool {
+    path.extension()
+        .map(|e| AUDIO_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Segment {
+    // Seconds from the start of the recording
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    // Set when the transcript was diarized
+    #[serde(default)]
+    pub speaker: Option<String>,
+}
+
+// Whisper's JSON output and OpenAI's verbose_json share this shape
+#[derive(Deserialize)]
+struct WhisperJson {
+    segments: Vec<Segment>,
+}
+
+// "local" runs the openai-whisper CLI (or a compatible one set in settings), "openai" uploads
+// to the OpenAI transcription API
+pub async fn transcribe_file(settings: &Settings, path: &Path, engine: &str) -> Result<String, String> {
+    Ok(segments_text(&transcribe_segments(settings, path, engine).await?))
+}
+
+pub async fn transcribe_segments(settings: &Settings, path: &Path, engine: &str) -> Result<Vec<Segment>, String> {
+    // Keyed by the recording itself and by what transcribes it
+    let cache_key = media_cache::file_key(path, &[engine, settings.whisper_command.as_deref().unwrap_or_default()]).ok();
+    let cached = cache_key.as_deref().and_then(|key| media_cache::get("transcription", key));
+    if let Some(segments) = cached.and_then(|json| serde_json::from_str(&json).ok()) {
+        return Ok(segments);
+    }
+
+    let json = match engine {
+        "local" => transcribe_local(settings, path).await?,
+        "openai" => transcribe_openai(settings, path).await?,
+        other => return Err(format!("Unknown transcription engine '{}'. Use 'local' or 'openai'.", other)),
+    };
+    let parsed: WhisperJson = serde_json::from_str(&json).map_err(|e| format!("Unexpected transcription output: {}", e))?;
+    let segments: Vec<Segment> = parsed
+        .segments
+        .into_iter()
+        .map(|s| Segment { text: s.text.trim().to_string(), ..s })
+        .filter(|s| !s.text.is_empty())
+        .collect();
+    if let (Some(key), Ok(json)) = (&cache_key, serde_json::to_string(&segments)) {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        media_cache::put("transcription", key, &name, &json);
+    }
+    Ok(segments)
+}
+
+// One line per segment, or with speakers one paragraph per turn ("Speaker 1: ...")
+pub fn segments_text(segments: &[Segment]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut last_speaker: Option<&str> = None;
+    for segment in segments {
+        match segment.speaker.as_deref() {
+            Some(speaker) if last_speaker == Some(speaker) => {
+                let last = lines.last_mut().unwrap();
+                last.push(' ');
+                last.push_str(&segment.text);
+            }
+            Some(speaker) => lines.push(format!("{}: {}", speaker, segment.text)),
+            None => lines.push(segment.text.clone()),
+        }
+        last_speaker = segment.speaker.as_deref();
+    }
+    let separator = if last_speaker.is_some() { "\n\n" } else { "\n" };
+    lines.join(separator)
+}
+
+// Whisper writes <stem>.json into the output directory. That is a fresh temporary one, so a
+// file of the same name next to the recording is left alone.
+async fn transcribe_local(settings: &Settings, path: &Path) -> Result<String, String> {
+    let program = settings.whisper_command.as_deref().unwrap_or("whisper");
+    let out_dir = std::env::temp_dir().join(format!("fabric-whisper-{}", Uuid::new_v4()));
+    fs::create_dir_all(&out_dir).map_err(|e| e.to_string())?;
+    let child = Command::new(program)
+        .arg(path)
+        .arg("--output_format")
+        .arg("json")
+        .arg("--output_dir")
+        .arg(&out_dir)
+        .kill_on_drop(true)
+        .output();
+    let result = match tokio::time::timeout(LOCAL_TIMEOUT, child).await {
+        Err(_) => Err("Local transcription did not finish in time.".to_string()),
+        Ok(Err(_)) => Err(format!("Local transcription requires '{}' (openai-whisper) on the PATH.", program)),
+        Ok(Ok(output)) if !output.status.success() => Err(String::from_utf8_lossy(&output.stderr).to_string()),
+        Ok(Ok(_)) => {
+            let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            fs::read_to_string(out_dir.join(format!("{}.json", stem))).map_err(|e| e.to_string())
+        }
+    };
+    let _ = fs::remove_dir_all(&out_dir);
+    result
+}
+
+async fn transcribe_openai(settings: &Settings, path: &Path) -> Result<String, String> {
+    let api_key = settings
+        .api_key("openai")
+        .ok_or("Cloud transcription uses OpenAI; add an OpenAI API key in settings.")?;
+    let size = fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size > OPENAI_MAX_AUDIO_BYTES {
+        return Err("The audio is larger than OpenAI's 25 MB limit; transcribe it locally instead.".to_string());
+    }
+
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "audio.mp3".to_string());
+    let audio = Part::bytes(fs::read(path).map_err(|e| e.to_string())?).file_name(file_name);
+    let form = Form::new()
+        .text("model", "whisper-1")
+        .text("response_format", "verbose_json")
+        .part("file", audio);
+
+    residency::check("openai", None)?;
+    let url = format!("{}/v1/audio/transcriptions", residency::openai_base_url());
+    let res = http::client_for(&url)?
+        .post(&url)
+        .bearer_auth(api_key)
+        .multipart(form)
+        .timeout(Duration::from_secs(600))
+        .send_audited("transcription", None)
+        .await
+        .map_err(http::network_error)?;
+    let status = res.status();
+    let text = res.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("Transcription failed ({}): {}", status, &text.chars().take(300).collect::<String>()));
+    }
+    Ok(text)
+}
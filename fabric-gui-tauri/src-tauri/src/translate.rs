@@ -0,0 +1,30 @@
+use crate::ai_client::{self, AIRequest};
+use crate::i18n::tr_args;
+
+fn translation_prompt(target_language: &str) -> String {
+    format!(
+        "You are a professional translator. Translate the user's text into {lang}. \
+Preserve Markdown formatting, code blocks, URLs, and proper nouns exactly. \
+If the text is already in {lang}, return it unchanged. \
+Output only the translation, with no preamble or commentary.",
+        lang = target_language
+    )
+}
+
+// Runs as a secondary call on the same vendor/model as the pattern run it belongs to
+pub async fn translate_text(base: &AIRequest, text: &str, target_language: &str) -> Result<String, String> {
+    let request = AIRequest {
+        system_prompt: translation_prompt(target_language),
+        user_input: text.to_string(),
+        temperature: 0.2,
+        top_p: 1.0,
+        thinking_level: None,
+        translate_input: false,
+        translate_output: false,
+        ..base.clone()
+    };
+
+    ai_client::complete(&request)
+        .await
+        .map_err(|e| tr_args("translation-failed", &[("error", &e)]))
+}
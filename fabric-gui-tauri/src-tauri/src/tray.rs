@@ -0,0 +1,63 @@
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Manager, Window};
+use crate::queue::RunQueue;
+use crate::reading_list::{self, ReadingList};
+
+const TRAY_ID: &str = "main";
+
+pub fn create(app_handle: &AppHandle) -> tauri::Result<()> {
+    let show = MenuItem::with_id(app_handle, "show", "Show Fabric", true, None::<&str>)?;
+    let reading = MenuItem::with_id(app_handle, "reading_list", "Process reading list", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app_handle, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(app_handle, &[&show, &reading, &quit])?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Fabric")
+        .menu(&menu)
+        .on_menu_event(|app_handle, event| match event.id.as_ref() {
+            "show" => show_main(app_handle),
+            // Reuses the pattern of the last processing; the first time it has to be chosen in the window
+            "reading_list" => match app_handle.state::<ReadingList>().last_request() {
+                Some(request) => {
+                    let app_handle = app_handle.clone();
+                    tauri::async_runtime::spawn(async move {
+                        // Results and failures are reported through reading-list events
+                        let _ = reading_list::process(&app_handle, request).await;
+                    });
+                }
+                None => show_main(app_handle),
+            },
+            // Unfinished jobs are already on disk and resume on the next start
+            "quit" => app_handle.exit(0),
+            _ => {}
+        });
+    if let Some(icon) = app_handle.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app_handle)?;
+    Ok(())
+}
+
+fn show_main(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+pub fn set_job_count(app_handle: &AppHandle, count: usize) {
+    if let Some(tray) = app_handle.tray_by_id(TRAY_ID) {
+        let tooltip = match count {
+            0 => "Fabric".to_string(),
+            1 => "Fabric — 1 job in progress".to_string(),
+            n => format!("Fabric — {} jobs in progress", n),
+        };
+        let _ = tray.set_tooltip(Some(tooltip));
+    }
+}
+
+// Closing the main window while jobs are pending hides it to the tray instead of quitting
+pub fn keeps_running(window: &Window) -> bool {
+    window.label() == "main" && window.state::<RunQueue>().is_busy()
+}
@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Url};
+use tauri_plugin_updater::UpdaterExt;
+use serde_json::json;
+use crate::http::{self, AuditedSend};
+
+const RELEASES_API: &str = "https://api.github.com/repos/coolman1984/Fabric/releases";
+const STABLE_ENDPOINT: &str = "https://github.com/coolman1984/Fabric/releases/latest/download/latest.json";
+const BETA_ENDPOINT: &str = "https://github.com/coolman1984/Fabric/releases/download/gui-beta/latest.json";
+
+#[derive(Serialize)]
+pub struct UpdateInfo {
+    pub available: bool,
+    pub channel: String,
+    pub current_version: String,
+    pub version: Option<String>,
+    pub date: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReleaseNotes {
+    pub version: String,
+    pub name: String,
+    pub published_at: String,
+    pub prerelease: bool,
+    pub notes: String,
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    name: Option<String>,
+    published_at: Option<String>,
+    prerelease: bool,
+    body: Option<String>,
+}
+
+fn channel_endpoint(channel: &str) -> Result<Url, String> {
+    let endpoint = match channel {
+        "stable" => STABLE_ENDPOINT,
+        "beta" => BETA_ENDPOINT,
+        _ => return Err(format!("Unknown release channel '{}'. Use 'stable' or 'beta'.", channel)),
+    };
+    Url::parse(endpoint).map_err(|e| e.to_string())
+}
+
+// Updates are only installed when their signature checks out against plugins.updater.pubkey;
+// builds made without a release signing key have none and can't update themselves
+fn has_update_key(app_handle: &AppHandle) -> bool {
+    app_handle
+        .config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|key| key.as_str())
+        .is_some_and(|key| !key.trim().is_empty())
+}
+
+async fn find_update(app_handle: &AppHandle, channel: &str) -> Result<Option<tauri_plugin_updater::Update>, String> {
+    if !has_update_key(app_handle) {
+        return Err("This build has no update signing key, so updates can't be verified. Download new versions from the releases page.".to_string());
+    }
+    let endpoint = channel_endpoint(channel)?;
+    let updater = app_handle
+        .updater_builder()
+        .endpoints(vec![endpoint])
+        .map_err(|e| e.to_string())?
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    updater.check().await.map_err(|e| format!("Update check failed: {}", e))
+}
+
+#[tauri::command]
+pub async fn check_for_updates(app_handle: AppHandle, channel: String) -> Result<UpdateInfo, String> {
+    let current_version = app_handle.package_info().version.to_string();
+
+    match find_update(&app_handle, &channel).await? {
+        Some(update) => Ok(UpdateInfo {
+            available: true,
+            channel,
+            current_version,
+            version: Some(update.version.clone()),
+            date: update.date.map(|d| d.to_string()),
+            notes: update.body.clone(),
+        }),
+        None => Ok(UpdateInfo {
+            available: false,
+            channel,
+            current_version,
+            version: None,
+            date: None,
+            notes: None,
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn install_update(app_handle: AppHandle, channel: String) -> Result<(), String> {
+    let update = find_update(&app_handle, &channel)
+        .await?
+        .ok_or_else(|| "Already running the latest version.".to_string())?;
+
+    // Report download progress so the UI can show a progress bar
+    let mut downloaded: usize = 0;
+    let progress_handle = app_handle.clone();
+    update
+        .download_and_install(
+            |chunk_length, content_length| {
+                downloaded += chunk_length;
+                let _ = progress_handle.emit("update-progress", json!({
+                    "downloaded": downloaded,
+                    "total": content_length
+                }));
+            },
+            || {},
+        )
+        .await
+        .map_err(|e| format!("Update install failed: {}", e))?;
+
+    app_handle.restart();
+}
+
+#[tauri::command]
+pub async fn get_changelog(channel: String, limit: Option<usize>) -> Result<Vec<ReleaseNotes>, String> {
+    let include_prereleases = match channel.as_str() {
+        "stable" => false,
+        "beta" => true,
+        _ => return Err(format!("Unknown release channel '{}'. Use 'stable' or 'beta'.", channel)),
+    };
+
+    let client = http::client_for(RELEASES_API)?;
+    let res = client.get(RELEASES_API)
+        .header("User-Agent", "fabric-gui-tauri")
+        .header("Accept", "application/vnd.github+json")
+        .send_audited("update_check", None)
+        .await
+        .map_err(http::network_error)?;
+
+    let status = res.status();
+    if !status.is_success() {
+        return Err(format!("Could not fetch changelog ({})", status));
+    }
+
+    let releases: Vec<GithubRelease> = res.json().await.map_err(|e| e.to_string())?;
+
+    Ok(releases
+        .into_iter()
+        .filter(|r| include_prereleases || !r.prerelease)
+        .take(limit.unwrap_or(10))
+        .map(|r| ReleaseNotes {
+            name: r.name.unwrap_or_else(|| r.tag_name.clone()),
+            version: r.tag_name,
+            published_at: r.published_at.unwrap_or_default(),
+            prerelease: r.prerelease,
+            notes: r.body.unwrap_or_default(),
+        })
+        .collect())
+}
@@ -0,0 +1,117 @@
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+use crate::ai_client::AIRequest;
+use crate::models::{ModelCapabilities, ModelRegistryState};
+
+#[derive(Serialize)]
+pub struct ValidationIssue {
+    // The AIRequest field to change
+    pub field: &'static str,
+    pub message: String,
+}
+
+fn issue(field: &'static str, message: String) -> ValidationIssue {
+    ValidationIssue { field, message }
+}
+
+// Vertex AI serves the same Gemini models as the Gemini API
+fn registry_vendor(vendor: &str) -> &str {
+    match vendor {
+        "vertex" => "google",
+        vendor => vendor,
+    }
+}
+
+// Catches what providers would otherwise reject with a bare 400. Range checks always apply;
+// model-specific checks only when the model is in the capability registry.
+pub fn issues(request: &AIRequest, model: Option<&ModelCapabilities>) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let vendor = registry_vendor(&request.vendor);
+
+    let max_temperature = if vendor == "anthropic" { 1.0 } else { 2.0 };
+    if !(0.0..=max_temperature).contains(&request.temperature) {
+        issues.push(issue(
+            "temperature",
+            format!("Temperature must be between 0 and {} for this vendor.", max_temperature),
+        ));
+    }
+    if !(0.0..=1.0).contains(&request.top_p) {
+        issues.push(issue("top_p", "Top P must be between 0 and 1.".to_string()));
+    }
+
+    let thinking = request.thinking_level.is_some_and(|level| level > 0);
+    if thinking && vendor != "google" && vendor != "anthropic" {
+        issues.push(issue(
+            "thinking_level",
+            "The thinking level only applies to Gemini and Claude models; turn it off for this vendor.".to_string(),
+        ));
+    }
+    if thinking && vendor == "anthropic" && request.assistant_prefill.as_deref().is_some_and(|p| !p.is_empty()) {
+        issues.push(issue(
+            "assistant_prefill",
+            "Claude can't combine extended thinking with a response prefill; remove one of them.".to_string(),
+        ));
+    }
+
+    if request.max_tokens == Some(0) {
+        issues.push(issue("max_tokens", "The maximum output length must be at least 1 token.".to_string()));
+    }
+
+    let Some(model) = model else {
+        return issues;
+    };
+
+    if model.vendor != vendor {
+        issues.push(issue(
+            "model",
+            format!("{} is a {} model; switch the vendor or pick a {} model.", model.id, model.vendor, request.vendor),
+        ));
+        // The remaining checks describe a model the request isn't really for
+        return issues;
+    }
+
+    // o-series reasoning models only accept the default sampling settings
+    if !model.supports_temperature && (request.temperature != 1.0 || request.top_p != 1.0) {
+        issues.push(issue(
+            "temperature",
+            format!("{} doesn't support custom sampling; set temperature and top P to 1.", model.id),
+        ));
+    }
+
+    if thinking && !model.supports_thinking {
+        issues.push(issue(
+            "thinking_level",
+            format!("{} has no thinking mode; turn the thinking level off or choose a thinking model.", model.id),
+        ));
+    }
+
+    if let Some(max_tokens) = request.max_tokens.filter(|&n| u64::from(n) > model.max_output_tokens) {
+        issues.push(issue(
+            "max_tokens",
+            format!(
+                "{} can produce at most {} output tokens, but {} were requested.",
+                model.id, model.max_output_tokens, max_tokens
+            ),
+        ));
+    }
+
+    issues
+}
+
+pub fn check(app_handle: &AppHandle, request: &AIRequest) -> Result<(), String> {
+    let model = app_handle.state::<ModelRegistryState>().find(&request.model);
+    let issues = issues(request, model.as_ref());
+    if issues.is_empty() {
+        return Ok(());
+    }
+    Err(issues.into_iter().map(|i| i.message).collect::<Vec<_>>().join(" "))
+}
+
+// Lets the UI flag settings next to the offending control before anything is sent
+#[tauri::command]
+pub async fn validate_request(
+    registry: State<'_, ModelRegistryState>,
+    request: AIRequest,
+) -> Result<Vec<ValidationIssue>, String> {
+    Ok(issues(&request, registry.find(&request.model).as_ref()))
+}
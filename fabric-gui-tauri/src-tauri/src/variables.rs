@@ -0,0 +1,120 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::State;
+
+const PREFIX: &str = "{{var:";
+
+#[derive(Serialize)]
+pub struct Variable {
+    pub name: String,
+    pub value: String,
+}
+
+// Named values referenced as {{var:name}} in patterns and input, stored per profile
+pub struct VariableStore {
+    path: Mutex<PathBuf>,
+    inner: Mutex<BTreeMap<String, String>>,
+}
+
+fn read_from_disk(path: &PathBuf) -> BTreeMap<String, String> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+impl VariableStore {
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            inner: Mutex::new(read_from_disk(&path)),
+            path: Mutex::new(path),
+        }
+    }
+
+    // Points the store at another profile's variables file
+    pub fn reload(&self, path: PathBuf) {
+        *self.inner.lock().unwrap() = read_from_disk(&path);
+        *self.path.lock().unwrap() = path;
+    }
+
+    fn save(&self, variables: &BTreeMap<String, String>) -> Result<(), String> {
+        let path = self.path.lock().unwrap().clone();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(variables).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    // Replaces every {{var:name}} with its value. Unknown names are left as written so the
+    // mistake is visible in the output rather than silently dropped.
+    pub fn expand(&self, text: &str) -> String {
+        if !text.contains(PREFIX) {
+            return text.to_string();
+        }
+        let variables = self.inner.lock().unwrap();
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(PREFIX) {
+            expanded.push_str(&rest[..start]);
+            let after = &rest[start + PREFIX.len()..];
+            let Some(end) = after.find("}}") else {
+                rest = &rest[start..];
+                break;
+            };
+            match variables.get(after[..end].trim()) {
+                Some(value) => expanded.push_str(value),
+                None => expanded.push_str(&rest[start..start + PREFIX.len() + end + 2]),
+            }
+            rest = &after[end + 2..];
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err("Variable names may only contain letters, digits, '_', '-' and '.'.".to_string())
+    }
+}
+
+#[tauri::command]
+pub async fn set_variable(store: State<'_, VariableStore>, name: String, value: String) -> Result<(), String> {
+    validate_name(&name)?;
+    let mut variables = store.inner.lock().unwrap();
+    variables.insert(name, value);
+    store.save(&variables)
+}
+
+#[tauri::command]
+pub async fn delete_variable(store: State<'_, VariableStore>, name: String) -> Result<(), String> {
+    let mut variables = store.inner.lock().unwrap();
+    if variables.remove(&name).is_none() {
+        return Err(format!("Variable '{}' does not exist.", name));
+    }
+    store.save(&variables)
+}
+
+#[tauri::command]
+pub async fn list_variables(store: State<'_, VariableStore>) -> Result<Vec<Variable>, String> {
+    Ok(store
+        .inner
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, value)| Variable {
+            name: name.clone(),
+            value: value.clone(),
+        })
+        .collect())
+}
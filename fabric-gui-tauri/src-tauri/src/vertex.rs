@@ -0,0 +1,289 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use home::home_dir;
+use reqwest::Url;
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use crate::history::now_secs;
+use crate::http::{self, AuditedSend};
+
+const DEFAULT_LOCATION: &str = "us-central1";
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+// Tokens are refreshed this long before Google says they expire
+const EXPIRY_MARGIN: Duration = Duration::from_secs(120);
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct VertexSettings {
+    // Falls back to the project in the credentials file
+    pub project_id: Option<String>,
+    // Region such as "europe-west4", or "global"; defaults to us-central1
+    pub location: Option<String>,
+    // Service-account JSON; without it GOOGLE_APPLICATION_CREDENTIALS and then the
+    // gcloud application default credentials are used
+    pub credentials_path: Option<String>,
+}
+
+// The fields of a service-account key and of a `gcloud auth application-default login` file
+#[derive(Deserialize)]
+struct CredentialsFile {
+    #[serde(rename = "type")]
+    kind: String,
+    project_id: Option<String>,
+    quota_project_id: Option<String>,
+    client_email: Option<String>,
+    private_key: Option<String>,
+    token_uri: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
+}
+
+#[derive(Serialize)]
+pub struct VertexAuthStatus {
+    pub credentials_path: String,
+    // "service_account" or "authorized_user"
+    pub credential_type: String,
+    pub project_id: String,
+    pub location: String,
+    pub endpoint: String,
+    pub token_expires_in_secs: u64,
+}
+
+fn settings() -> &'static RwLock<VertexSettings> {
+    static SETTINGS: OnceLock<RwLock<VertexSettings>> = OnceLock::new();
+    SETTINGS.get_or_init(|| RwLock::new(VertexSettings::default()))
+}
+
+fn token_cache() -> &'static Mutex<Option<CachedToken>> {
+    static CACHE: OnceLock<Mutex<Option<CachedToken>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(None))
+}
+
+// Called whenever settings load or change; a different project or key invalidates the token
+pub fn configure(vertex: &VertexSettings) {
+    *settings().write().unwrap() = vertex.clone();
+    *token_cache().lock().unwrap() = None;
+}
+
+fn location() -> String {
+    settings()
+        .read()
+        .unwrap()
+        .location
+        .clone()
+        .filter(|l| !l.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_LOCATION.to_string())
+}
+
+// The global location has no regional host prefix
+pub fn base_url() -> String {
+    match location().as_str() {
+        "global" => "https://aiplatform.googleapis.com".to_string(),
+        location => format!("https://{}-aiplatform.googleapis.com", location),
+    }
+}
+
+fn credentials_path() -> Result<PathBuf, String> {
+    if let Some(path) = settings().read().unwrap().credentials_path.clone().filter(|p| !p.trim().is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    if let Some(path) = std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS") {
+        return Ok(PathBuf::from(path));
+    }
+
+    let adc = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("gcloud"))
+    } else {
+        home_dir().map(|dir| dir.join(".config").join("gcloud"))
+    }
+    .map(|dir| dir.join("application_default_credentials.json"));
+
+    adc.filter(|path| path.exists()).ok_or_else(|| {
+        "No Google Cloud credentials found. Choose a service-account JSON file in the Vertex AI settings \
+         or run `gcloud auth application-default login`."
+            .to_string()
+    })
+}
+
+fn read_credentials() -> Result<(PathBuf, CredentialsFile), String> {
+    let path = credentials_path()?;
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Could not read credentials file {}: {}", path.display(), e))?;
+    let credentials = serde_json::from_str(&content)
+        .map_err(|e| format!("Credentials file {} is not valid: {}", path.display(), e))?;
+    Ok((path, credentials))
+}
+
+fn project_id(credentials: &CredentialsFile) -> Result<String, String> {
+    settings()
+        .read()
+        .unwrap()
+        .project_id
+        .clone()
+        .filter(|p| !p.trim().is_empty())
+        .or_else(|| credentials.project_id.clone())
+        .or_else(|| credentials.quota_project_id.clone())
+        .ok_or_else(|| "No Google Cloud project set. Enter a project ID in the Vertex AI settings.".to_string())
+}
+
+pub fn credentials_available() -> bool {
+    credentials_path().is_ok()
+}
+
+pub fn model_url(model: &str, method: &str) -> Result<String, String> {
+    let (_, credentials) = read_credentials()?;
+    Ok(format!(
+        "{}/v1/projects/{}/locations/{}/publishers/google/models/{}:{}",
+        base_url(),
+        project_id(&credentials)?,
+        location(),
+        model,
+        method
+    ))
+}
+
+// Service accounts exchange a self-signed JWT for an access token (RFC 7523)
+fn signed_assertion(credentials: &CredentialsFile, token_uri: &str) -> Result<String, String> {
+    let email = credentials.client_email.as_deref().ok_or("The service-account file has no client_email.")?;
+    let pem = credentials.private_key.as_deref().ok_or("The service-account file has no private_key.")?;
+
+    let der_base64: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+    let der = base64::engine::general_purpose::STANDARD
+        .decode(der_base64.trim())
+        .map_err(|e| format!("The service-account private key is not valid: {}", e))?;
+    let key = RsaKeyPair::from_pkcs8(&der).map_err(|e| format!("The service-account private key is not valid: {}", e))?;
+
+    let issued_at = now_secs();
+    let header = URL_SAFE_NO_PAD.encode(json!({"alg": "RS256", "typ": "JWT"}).to_string());
+    let claims = URL_SAFE_NO_PAD.encode(
+        json!({
+            "iss": email,
+            "scope": CLOUD_PLATFORM_SCOPE,
+            "aud": token_uri,
+            "iat": issued_at,
+            "exp": issued_at + 3600,
+        })
+        .to_string(),
+    );
+    let message = format!("{}.{}", header, claims);
+
+    let mut signature = vec![0; key.public().modulus_len()];
+    key.sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), message.as_bytes(), &mut signature)
+        .map_err(|_| "Could not sign the token request.".to_string())?;
+    Ok(format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+pub fn form_body(params: &[(&str, &str)]) -> String {
+    // Url does the form encoding; only its query string is used
+    let mut url = Url::parse("http://localhost/").expect("static URL");
+    url.query_pairs_mut().extend_pairs(params);
+    url.query().unwrap_or_default().to_string()
+}
+
+async fn fetch_token(credentials: &CredentialsFile) -> Result<TokenResponse, String> {
+    let token_uri = credentials.token_uri.as_deref().unwrap_or(DEFAULT_TOKEN_URI);
+    let body = match credentials.kind.as_str() {
+        "service_account" => {
+            let assertion = signed_assertion(credentials, token_uri)?;
+            form_body(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &assertion),
+            ])
+        }
+        "authorized_user" => form_body(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", credentials.client_id.as_deref().unwrap_or_default()),
+            ("client_secret", credentials.client_secret.as_deref().unwrap_or_default()),
+            ("refresh_token", credentials.refresh_token.as_deref().unwrap_or_default()),
+        ]),
+        other => return Err(format!("Unsupported Google credentials type '{}'.", other)),
+    };
+
+    let res = http::client_for(token_uri)?
+        .post(token_uri)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send_audited("vertex_auth", None)
+        .await
+        .map_err(http::network_error)?;
+
+    let status = res.status();
+    if !status.is_success() {
+        let details = res.text().await.unwrap_or_default();
+        return Err(format!(
+            "Google rejected the Vertex AI credentials ({}): {}",
+            status,
+            &details.chars().take(300).collect::<String>()
+        ));
+    }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+// Cached until shortly before expiry, so runs don't each pay for a token exchange
+pub async fn access_token() -> Result<String, String> {
+    if let Some(cached) = token_cache().lock().unwrap().as_ref() {
+        if cached.expires_at > Instant::now() + EXPIRY_MARGIN {
+            return Ok(cached.token.clone());
+        }
+    }
+
+    let (_, credentials) = read_credentials()?;
+    let response = fetch_token(&credentials).await?;
+    *token_cache().lock().unwrap() = Some(CachedToken {
+        token: response.access_token.clone(),
+        expires_at: Instant::now() + Duration::from_secs(response.expires_in),
+    });
+    Ok(response.access_token)
+}
+
+// A token in the request's key field (e.g. from `gcloud auth print-access-token`) wins
+pub async fn bearer_token(api_key: &str) -> Result<String, String> {
+    if !api_key.trim().is_empty() {
+        return Ok(api_key.trim().to_string());
+    }
+    access_token().await
+}
+
+#[tauri::command]
+pub async fn check_vertex_auth() -> Result<VertexAuthStatus, String> {
+    let (path, credentials) = read_credentials()?;
+    let project_id = project_id(&credentials)?;
+    // Always exchange a fresh token so a revoked key shows up here rather than in the next run
+    *token_cache().lock().unwrap() = None;
+    access_token().await?;
+
+    let expires_in = token_cache()
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|t| t.expires_at.saturating_duration_since(Instant::now()).as_secs())
+        .unwrap_or(0);
+
+    Ok(VertexAuthStatus {
+        credentials_path: path.display().to_string(),
+        credential_type: credentials.kind,
+        project_id,
+        location: location(),
+        endpoint: base_url(),
+        token_expires_in_secs: expires_in,
+    })
+}
@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use tauri::{AppHandle, Manager, State};
+use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+// Roughly a few seconds of work; a plugin that loops forever runs out instead of hanging the app
+const FUEL_LIMIT: u64 = 5_000_000_000;
+const MEMORY_LIMIT_BYTES: usize = 256 * 1024 * 1024;
+const STAGES: &[&str] = &["preprocess", "postprocess"];
+
+// WebAssembly input cleaners and output transformers from the plugins folder. A plugin is a
+// core module with no imports, so it can't reach files, the network or the clock. It exports
+// its `memory`, `alloc(len: i32) -> i32`, and `preprocess` and/or `postprocess`, which take the
+// (ptr, len) of UTF-8 text and return the result's location as (ptr << 32) | len. A plugin
+// fails a run by trapping.
+#[derive(Default)]
+pub struct WasmPlugins {
+    // Set up on first use; there's no reason to pay for the compiler at startup
+    engine: OnceLock<Engine>,
+    // Compiled modules by name, with the file's modification time to notice edits
+    modules: Mutex<HashMap<String, (SystemTime, Module)>>,
+}
+
+#[derive(Serialize)]
+pub struct WasmPlugin {
+    pub name: String,
+    pub preprocess: bool,
+    pub postprocess: bool,
+    // Set when the file isn't a valid plugin
+    pub error: Option<String>,
+}
+
+fn plugins_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("plugins"))
+        .map_err(|e| e.to_string())
+}
+
+impl WasmPlugins {
+    fn engine(&self) -> &Engine {
+        self.engine.get_or_init(|| {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            Engine::new(&config).expect("default wasmtime config")
+        })
+    }
+
+    fn module(&self, app_handle: &AppHandle, name: &str) -> Result<Module, String> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_".contains(c)) {
+            return Err(format!("'{}' is not a valid plugin name.", name));
+        }
+        let path = plugins_dir(app_handle)?.join(format!("{}.wasm", name));
+        let modified = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map_err(|_| format!("There is no plugin named '{}'.", name))?;
+        if let Some((at, module)) = self.modules.lock().unwrap().get(name) {
+            if *at == modified {
+                return Ok(module.clone());
+            }
+        }
+        let module = Module::from_file(self.engine(), &path).map_err(|e| format!("Plugin '{}' is not valid WebAssembly: {}", name, e))?;
+        if module.imports().next().is_some() {
+            return Err(format!("Plugin '{}' imports functions; plugins must be self-contained.", name));
+        }
+        for export in ["memory", "alloc"] {
+            if module.get_export(export).is_none() {
+                return Err(format!("Plugin '{}' does not export '{}'.", name, export));
+            }
+        }
+        self.modules.lock().unwrap().insert(name.to_string(), (modified, module.clone()));
+        Ok(module)
+    }
+}
+
+// Runs one stage in a fresh instance with its own memory and fuel
+fn call(engine: &Engine, module: &Module, stage: &str, text: &str) -> Result<String, String> {
+    let len = i32::try_from(text.len()).map_err(|_| "The text is too long for a plugin.".to_string())?;
+    let mut store: Store<StoreLimits> = Store::new(engine, StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT_BYTES).build());
+    store.limiter(|limits| limits);
+    store.set_fuel(FUEL_LIMIT).map_err(|e| e.to_string())?;
+    let failed = |e: wasmtime::Error| match e.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => "The plugin ran too long and was stopped.".to_string(),
+        _ => format!("The plugin failed: {}", e),
+    };
+
+    let instance = Instance::new(&mut store, module, &[]).map_err(failed)?;
+    let memory = instance.get_memory(&mut store, "memory").ok_or("The plugin's 'memory' is not a memory.")?;
+    let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc").map_err(|e| e.to_string())?;
+    let run = instance
+        .get_typed_func::<(i32, i32), i64>(&mut store, stage)
+        .map_err(|_| format!("The plugin has no '{}' stage.", stage))?;
+
+    let ptr = alloc.call(&mut store, len).map_err(failed)?;
+    memory
+        .write(&mut store, ptr as u32 as usize, text.as_bytes())
+        .map_err(|_| "The plugin allocated memory outside its bounds.".to_string())?;
+    let packed = run.call(&mut store, (ptr, len)).map_err(failed)?;
+    let (out_ptr, out_len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+    // Checked before anything is allocated, so a bogus length can't make the app allocate 4 GB
+    let end = out_ptr
+        .checked_add(out_len)
+        .filter(|end| *end <= memory.data_size(&store))
+        .ok_or("The plugin returned a result outside its memory.")?;
+    let out = memory.data(&store)[out_ptr..end].to_vec();
+    String::from_utf8(out).map_err(|_| "The plugin returned text that is not UTF-8.".to_string())
+}
+
+#[tauri::command]
+pub async fn list_wasm_plugins(app_handle: AppHandle, plugins: State<'_, WasmPlugins>) -> Result<Vec<WasmPlugin>, String> {
+    let Ok(entries) = fs::read_dir(plugins_dir(&app_handle)?) else {
+        return Ok(Vec::new());
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|e| e.file_name().to_str()?.strip_suffix(".wasm").map(str::to_string))
+        .collect();
+    names.sort();
+    Ok(names
+        .into_iter()
+        .map(|name| match plugins.module(&app_handle, &name) {
+            Ok(module) => WasmPlugin {
+                preprocess: module.get_export("preprocess").is_some(),
+                postprocess: module.get_export("postprocess").is_some(),
+                name,
+                error: None,
+            },
+            Err(error) => WasmPlugin { name, preprocess: false, postprocess: false, error: Some(error) },
+        })
+        .collect())
+}
+
+// Runs a plugin's "preprocess" stage on an input before it's sent, or its "postprocess" stage
+// on a finished output
+#[tauri::command]
+pub async fn run_wasm_plugin(
+    app_handle: AppHandle,
+    plugins: State<'_, WasmPlugins>,
+    name: String,
+    stage: String,
+    text: String,
+) -> Result<String, String> {
+    if !STAGES.contains(&stage.as_str()) {
+        return Err(format!("Unknown plugin stage '{}'. Use preprocess or postprocess.", stage));
+    }
+    let module = plugins.module(&app_handle, &name)?;
+    let engine = plugins.engine().clone();
+    tauri::async_runtime::spawn_blocking(move || call(&engine, &module, &stage, &text))
+        .await
+        .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,19 @@
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+
+// Session windows load the same frontend as the main window; the capability file
+// matches their `session-*` labels so they can invoke commands and receive events
+#[tauri::command]
+pub async fn open_new_session_window(app_handle: AppHandle) -> Result<String, String> {
+    let label = (1..)
+        .map(|n| format!("session-{}", n))
+        .find(|label| app_handle.get_webview_window(label).is_none())
+        .unwrap_or_default();
+
+    WebviewWindowBuilder::new(&app_handle, &label, WebviewUrl::App("index.html".into()))
+        .title(format!("Fabric — {}", label.replace('-', " ")))
+        .inner_size(1000.0, 700.0)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    Ok(label)
+}
@@ -1,14 +1,55 @@
-use tauri::Manager;
+use tauri::{Manager, State};
 use tauri_plugin_shell::ShellExt;
 use std::process::Command;
 use std::path::PathBuf;
+use serde_json::Value;
+use crate::input;
+use crate::media_cache;
+use crate::settings::SettingsState;
 
+// The script's JSON ({"transcript", "video_id"} or {"error"}), with the transcript cut to the
+// transcript input limit and a "truncation" entry saying whether it was
 #[tauri::command]
 pub async fn get_youtube_transcript(
     app_handle: tauri::AppHandle,
+    state: State<'_, SettingsState>,
     url: String,
     include_timestamps: bool,
 ) -> Result<String, String> {
+    let output = fetch_transcript(app_handle, url, include_timestamps).await?;
+    let mut parsed: Value = serde_json::from_str(&output).map_err(|e| format!("Unexpected transcript output: {}", e))?;
+    if let Some(transcript) = parsed.get("transcript").and_then(Value::as_str) {
+        let (transcript, truncation) = input::limit(&state.get(), "transcript", transcript.to_string());
+        parsed["transcript"] = transcript.into();
+        parsed["truncation"] = serde_json::to_value(truncation).map_err(|e| e.to_string())?;
+    }
+    Ok(parsed.to_string())
+}
+
+// Just the transcript text, for use as input
+pub async fn transcript(app_handle: tauri::AppHandle, url: String, include_timestamps: bool) -> Result<String, String> {
+    let output = fetch_transcript(app_handle, url, include_timestamps).await?;
+    let parsed: Value = serde_json::from_str(&output).map_err(|e| format!("Unexpected transcript output: {}", e))?;
+    if let Some(error) = parsed.get("error").and_then(Value::as_str) {
+        return Err(error.to_string());
+    }
+    parsed
+        .get("transcript")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "The transcript script returned no transcript.".to_string())
+}
+
+async fn fetch_transcript(
+    app_handle: tauri::AppHandle,
+    url: String,
+    include_timestamps: bool,
+) -> Result<String, String> {
+    let cache_key = media_cache::key(&[&url, &include_timestamps.to_string()]);
+    if let Some(transcript) = media_cache::get("youtube", &cache_key) {
+        return Ok(transcript);
+    }
+
     let python_script = app_handle
         .path()
         .resource_dir()
@@ -40,7 +81,7 @@ pub async fn get_youtube_transcript(
     }
 
     let mut cmd = Command::new("py");
-    cmd.arg("-3").arg(script_path).arg("--url").arg(url);
+    cmd.arg("-3").arg(script_path).arg("--url").arg(&url);
     
     if include_timestamps {
         cmd.arg("--timestamps");
@@ -49,7 +90,9 @@ pub async fn get_youtube_transcript(
     let output = cmd.output().map_err(|e| e.to_string())?;
 
     if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        let transcript = String::from_utf8_lossy(&output.stdout).to_string();
+        media_cache::put("youtube", &cache_key, &url, &transcript);
+        Ok(transcript)
     } else {
         Err(String::from_utf8_lossy(&output.stderr).to_string())
     }
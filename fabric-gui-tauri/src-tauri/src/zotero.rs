@@ -0,0 +1,305 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::time::Duration;
+use tauri::State;
+use crate::http::{self, AuditedSend};
+use crate::paper;
+use crate::settings::{Settings, SettingsState};
+use crate::templates;
+
+const WEB_API: &str = "https://api.zotero.org";
+// Zotero 7's local API (Settings > Advanced > "Allow other applications to communicate with
+// Zotero"); read-only, so notes always go through the web API
+const LOCAL_API: &str = "http://localhost:23119/api";
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ZoteroSettings {
+    // The numeric user id from zotero.org/settings/keys, or a group id; the key is api_keys "zotero"
+    pub library_id: Option<String>,
+    // "user" (default) or "group"
+    pub library_type: Option<String>,
+    // Read items and PDFs from the running Zotero app instead of zotero.org
+    pub local_api: bool,
+}
+
+#[derive(Serialize)]
+pub struct ZoteroCollection {
+    pub key: String,
+    pub name: String,
+    pub parent: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ZoteroItem {
+    pub key: String,
+    pub item_type: String,
+    pub title: String,
+    pub creators: Vec<String>,
+    pub date: Option<String>,
+    pub doi: Option<String>,
+    pub has_pdf: bool,
+}
+
+#[derive(Serialize)]
+pub struct ZoteroPaper {
+    pub key: String,
+    pub title: String,
+    pub sections: Vec<String>,
+    // False when the item has no readable PDF and the text is the metadata and abstract only
+    pub full_text: bool,
+    // Metadata, then the paper as markdown with a heading per detected section
+    pub text: String,
+}
+
+fn base_url(settings: &ZoteroSettings, local: bool) -> Result<String, String> {
+    let group = match settings.library_type.as_deref().unwrap_or("user") {
+        "user" => false,
+        "group" => true,
+        other => return Err(format!("Unknown Zotero library type '{}'. Use user or group.", other)),
+    };
+    let id = settings
+        .library_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()));
+    let library = match (group, id) {
+        // The local API calls the signed-in user 0
+        (false, _) if local => "users/0".to_string(),
+        (false, Some(id)) => format!("users/{}", id),
+        (true, Some(id)) => format!("groups/{}", id),
+        _ => return Err("Set the Zotero library id in settings first.".to_string()),
+    };
+    Ok(format!("{}/{}", if local { LOCAL_API } else { WEB_API }, library))
+}
+
+fn request(settings: &Settings, method: reqwest::Method, url: &str, local: bool) -> Result<reqwest::RequestBuilder, String> {
+    let mut request = http::client_for(url)?
+        .request(method, url)
+        .header("Zotero-API-Version", "3")
+        .timeout(Duration::from_secs(60));
+    if !local {
+        let key = settings.api_key("zotero").ok_or("Add a Zotero API key in settings first.")?;
+        request = request.header("Zotero-API-Key", key);
+    }
+    Ok(request)
+}
+
+async fn send(request: reqwest::RequestBuilder, local: bool) -> Result<reqwest::Response, String> {
+    let res = request.send_audited("zotero", None).await.map_err(|e| {
+        if local {
+            "Could not reach Zotero; open it and allow other applications to communicate with it.".to_string()
+        } else {
+            http::network_error(e)
+        }
+    })?;
+    let status = res.status();
+    if status.as_u16() == 403 {
+        return Err("Zotero rejected the API key, or it has no access to this library.".to_string());
+    }
+    if status.as_u16() == 404 {
+        return Err("Zotero has no such item or collection.".to_string());
+    }
+    if !status.is_success() {
+        let text = res.text().await.unwrap_or_default();
+        return Err(format!("Zotero API error ({}): {}", status, &text.chars().take(300).collect::<String>()));
+    }
+    Ok(res)
+}
+
+async fn get_json(settings: &Settings, path: &str, query: &[(&str, String)]) -> Result<Value, String> {
+    let local = settings.zotero.local_api;
+    let mut url = Url::parse(&format!("{}{}", base_url(&settings.zotero, local)?, path)).map_err(|e| e.to_string())?;
+    url.query_pairs_mut().append_pair("format", "json");
+    for (name, value) in query {
+        url.query_pairs_mut().append_pair(name, value);
+    }
+    send(request(settings, reqwest::Method::GET, url.as_str(), local)?, local)
+        .await?
+        .json()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Keys are eight characters from Zotero's alphabet, which also keeps them safe in a URL path
+fn check_key(key: &str) -> Result<&str, String> {
+    let key = key.trim();
+    if key.len() == 8 && key.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Ok(key)
+    } else {
+        Err(format!("'{}' is not a Zotero item or collection key.", key))
+    }
+}
+
+fn text_field(data: &Value, key: &str) -> Option<String> {
+    data[key].as_str().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+fn creators(data: &Value) -> Vec<String> {
+    data["creators"]
+        .as_array()
+        .map(|list| {
+            list.iter()
+                .map(|c| match text_field(c, "name") {
+                    Some(name) => name,
+                    None => format!(
+                        "{} {}",
+                        text_field(c, "firstName").unwrap_or_default(),
+                        text_field(c, "lastName").unwrap_or_default()
+                    )
+                    .trim()
+                    .to_string(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn item(value: &Value) -> ZoteroItem {
+    let data = &value["data"];
+    ZoteroItem {
+        key: text_field(value, "key").unwrap_or_default(),
+        item_type: text_field(data, "itemType").unwrap_or_default(),
+        title: text_field(data, "title").unwrap_or_default(),
+        creators: creators(data),
+        date: text_field(data, "date"),
+        doi: text_field(data, "DOI"),
+        has_pdf: value["links"]["attachment"]["attachmentType"] == "application/pdf",
+    }
+}
+
+#[tauri::command]
+pub async fn list_zotero_collections(state: State<'_, SettingsState>) -> Result<Vec<ZoteroCollection>, String> {
+    let settings = state.get();
+    let collections = get_json(&settings, "/collections", &[("limit", "100".to_string())]).await?;
+    Ok(collections
+        .as_array()
+        .map(|list| {
+            list.iter()
+                .map(|c| ZoteroCollection {
+                    key: text_field(c, "key").unwrap_or_default(),
+                    name: text_field(&c["data"], "name").unwrap_or_default(),
+                    parent: text_field(&c["data"], "parentCollection"),
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+// Top-level items (papers, books, ...) of a collection or of the whole library, optionally
+// narrowed by a title/creator/year search
+#[tauri::command]
+pub async fn list_zotero_items(
+    state: State<'_, SettingsState>,
+    collection: Option<String>,
+    search: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<ZoteroItem>, String> {
+    let settings = state.get();
+    let path = match collection.as_deref().filter(|c| !c.trim().is_empty()) {
+        Some(key) => format!("/collections/{}/items/top", check_key(key)?),
+        None => "/items/top".to_string(),
+    };
+    let mut query = vec![("limit", limit.unwrap_or(DEFAULT_LIMIT).clamp(1, 100).to_string())];
+    if let Some(search) = search.filter(|s| !s.trim().is_empty()) {
+        query.push(("q", search));
+    }
+    let items = get_json(&settings, &path, &query).await?;
+    Ok(items.as_array().map(|list| list.iter().map(item).collect()).unwrap_or_default())
+}
+
+// The PDF's bytes: the web API serves stored files directly, the local API points at the file
+// on disk
+async fn pdf_bytes(settings: &Settings, attachment_key: &str) -> Result<Vec<u8>, String> {
+    let local = settings.zotero.local_api;
+    let base = base_url(&settings.zotero, local)?;
+    if local {
+        let url = format!("{}/items/{}/file/view/url", base, attachment_key);
+        let file_url = send(request(settings, reqwest::Method::GET, &url, true)?, true)
+            .await?
+            .text()
+            .await
+            .map_err(|e| e.to_string())?;
+        let path = Url::parse(file_url.trim())
+            .ok()
+            .and_then(|u| u.to_file_path().ok())
+            .ok_or("Zotero did not return a local file for the PDF.")?;
+        return fs::read(path).map_err(|e| e.to_string());
+    }
+    let url = format!("{}/items/{}/file", base, attachment_key);
+    let res = send(request(settings, reqwest::Method::GET, &url, false)?, false).await?;
+    Ok(res.bytes().await.map_err(|e| e.to_string())?.to_vec())
+}
+
+// Pulls an item's metadata and the text of its PDF as pattern input. Items whose PDF is only
+// linked elsewhere, or missing, come back with the abstract alone.
+#[tauri::command]
+pub async fn fetch_zotero_item(
+    state: State<'_, SettingsState>,
+    key: String,
+    include_references: Option<bool>,
+) -> Result<ZoteroPaper, String> {
+    let settings = state.get();
+    let key = check_key(&key)?;
+    let value = get_json(&settings, &format!("/items/{}", key), &[]).await?;
+    let data = &value["data"];
+    let title = text_field(data, "title").unwrap_or_else(|| "Untitled".to_string());
+
+    let mut text = format!("# {}\n\n", title);
+    let authors = creators(data);
+    if !authors.is_empty() {
+        text.push_str(&format!("Authors: {}\n", authors.join(", ")));
+    }
+    for (label, field) in [("Published", "date"), ("In", "publicationTitle"), ("DOI", "DOI"), ("URL", "url")] {
+        if let Some(value) = text_field(data, field) {
+            text.push_str(&format!("{}: {}\n", label, value));
+        }
+    }
+    if let Some(abstract_note) = text_field(data, "abstractNote") {
+        text.push_str(&format!("\n## Abstract\n\n{}\n", abstract_note));
+    }
+
+    let attachment = value["links"]["attachment"]
+        .as_object()
+        .filter(|a| a["attachmentType"] == "application/pdf")
+        .and_then(|a| a["href"].as_str())
+        .and_then(|href| href.rsplit('/').next())
+        .map(str::to_string);
+    let mut sections = Vec::new();
+    let mut full_text = false;
+    if let Some(attachment) = attachment {
+        let bytes = pdf_bytes(&settings, &attachment).await?;
+        let (body, found) = paper::structure(&paper::pdf_bytes_text(&bytes)?, include_references.unwrap_or(false));
+        text.push_str(&format!("\n{}\n", body));
+        sections = found;
+        full_text = true;
+    }
+    Ok(ZoteroPaper { key: key.to_string(), title, sections, full_text, text: text.trim().to_string() })
+}
+
+// Attaches a pattern's output to the item as a child note tagged "fabric"; always through the
+// web API, as the local one can't write
+#[tauri::command]
+pub async fn attach_zotero_note(state: State<'_, SettingsState>, key: String, text: String) -> Result<String, String> {
+    if text.trim().is_empty() {
+        return Err("The note is empty.".to_string());
+    }
+    let settings = state.get();
+    let key = check_key(&key)?;
+    let url = format!("{}/items", base_url(&settings.zotero, false)?);
+    let note = json!([{
+        "itemType": "note",
+        "parentItem": key,
+        "note": templates::markdown_to_html(&text),
+        "tags": [{"tag": "fabric"}],
+    }]);
+    let res = send(request(&settings, reqwest::Method::POST, &url, false)?.json(&note), false).await?;
+    let body: Value = res.json().await.map_err(|e| e.to_string())?;
+    if let Some(failed) = body["failed"]["0"].as_object() {
+        return Err(format!("Zotero did not save the note: {}", failed.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error")));
+    }
+    Ok(body["successful"]["0"]["key"].as_str().unwrap_or_default().to_string())
+}